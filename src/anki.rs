@@ -0,0 +1,123 @@
+use std::fmt::Write as _;
+
+use crate::markup::escape;
+use crate::morse::{text_to_code, Charset};
+use crate::profile::{CharacterStats, Stats};
+
+// ---------- Anki decks ------------------------------------------------------
+// `cwgen stats export-anki DIR` turns what is missed most into flashcards for
+// Anki, so review carries on between sessions: each card plays a missed
+// character or word and shows it, with its code, on the back. Anki imports
+// the notes from a tab separated text file whose header names the deck and
+// note type; the sounds are WAV files next to it, which go in Anki's media
+// folder (Tools → Check Media shows where) before importing. Characters
+// come from everything that scores copy (see profile::MISSES_FROM), words
+// from copy quizzes and copying behind.
+pub const NOTES_FILE: &str = "cards.txt";
+
+/// Something missed, to review.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Card {
+    pub text: String,
+    pub sent: u64,
+    pub missed: u64,
+}
+
+impl Card {
+    /// Its sound's file name: "cwgen-v.wav", "cwgen-word-cq.wav"; characters
+    /// a file name can't hold are spelled out by code point.
+    pub fn media_name(&self) -> String {
+        let kind = if self.text.chars().count() == 1 { "" } else { "word-" };
+        let name: String = self
+            .text
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c.to_ascii_lowercase().to_string(),
+                false => format!("u{:x}", c as u32),
+            })
+            .collect();
+        format!("cwgen-{}{}.wav", kind, name)
+    }
+
+    fn error_rate(&self) -> f64 {
+        self.missed as f64 / self.sent.max(1) as f64
+    }
+}
+
+/// Up to `limit` of the characters and words missed, the most often missed
+/// (for how often each was sent) first.
+pub fn missed(stats: &Stats, limit: usize) -> Vec<Card> {
+    let card = |(text, counts): (&String, &CharacterStats)| Card { text: text.clone(), sent: counts.sent, missed: counts.missed };
+    let mut cards: Vec<Card> = stats
+        .characters
+        .iter()
+        .chain(&stats.words)
+        .map(card)
+        .filter(|card| card.missed > 0 && !card.text.trim().is_empty())
+        .collect();
+    cards.sort_by(|a, b| {
+        b.error_rate().total_cmp(&a.error_rate()).then(b.missed.cmp(&a.missed)).then(a.text.cmp(&b.text))
+    });
+    cards.truncate(limit);
+    cards
+}
+
+/// The notes for Anki to import into `deck`: the sound on the front, the
+/// text and its code on the back.
+pub fn notes(cards: &[Card], deck: &str, charset: Charset) -> String {
+    let mut notes = format!("#separator:tab\n#html:true\n#notetype:Basic\n#deck:{}\n#tags column:3\n", field(deck));
+    for card in cards {
+        let code = text_to_code(&card.text, charset).unwrap_or_default();
+        let kind = if card.text.chars().count() == 1 { "character" } else { "word" };
+        let _ = writeln!(
+            notes,
+            "[sound:{}]\t{}<br>{}\tcwgen {}",
+            card.media_name(),
+            escape(&field(&card.text)),
+            escape(&field(&code)),
+            kind,
+        );
+    }
+    notes
+}
+
+// No tabs or line breaks inside a field
+fn field(text: &str) -> String {
+    text.replace(['\t', '\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards() -> Vec<Card> {
+        let counts = |sent, missed| CharacterStats { sent, missed };
+        let mut stats = Stats::default();
+        stats.characters.insert("V".into(), counts(10, 4));
+        stats.characters.insert("E".into(), counts(50, 0));
+        stats.characters.insert("/".into(), counts(5, 1));
+        stats.words.insert("CQ".into(), counts(4, 2));
+        stats.words.insert("QTH".into(), counts(8, 2));
+        missed(&stats, 3)
+    }
+
+    #[test]
+    fn test_most_missed_first() {
+        let texts: Vec<String> = cards().into_iter().map(|card| card.text).collect();
+        assert_eq!(texts, ["CQ", "V", "QTH"]);
+    }
+
+    #[test]
+    fn test_media_name() {
+        assert_eq!(Card { text: "/".into(), sent: 5, missed: 1 }.media_name(), "cwgen-u2f.wav");
+        assert_eq!(Card { text: "CQ".into(), sent: 4, missed: 2 }.media_name(), "cwgen-word-cq.wav");
+    }
+
+    #[test]
+    fn test_anki_notes() {
+        let notes = notes(&cards(), "CW\tmissed", Charset::International);
+        assert!(notes.starts_with("#separator:tab\n#html:true\n#notetype:Basic\n#deck:CW missed\n#tags column:3\n"), "{}", notes);
+        assert!(notes.contains("[sound:cwgen-v.wav]\tV<br>...-\tcwgen character\n"), "{}", notes);
+        assert!(notes.ends_with("[sound:cwgen-word-qth.wav]\tQTH<br>--.- - ....\tcwgen word\n"), "{}", notes);
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use id3::frame::ExtendedText;
+use id3::{ErrorKind, Tag, TagLike, Version};
+
+use crate::audio::{MorseAudio, ToneShape};
+use crate::conditions::Conditions;
+use crate::morse::Timing;
+
+// ---------- Answer keys -----------------------------------------------------
+// Self-study files shouldn't give the answer away before you've copied them.
+// The answer either follows the practice text at a slow speed after a long
+// pause, or is kept scrambled in the file's ID3 chunk until `cwgen answer`
+// reveals it. The scrambling only keeps the text from showing up in a tag
+// editor or `strings`; it isn't meant to stand up to anyone trying.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum AnswerKey {
+    /// Send the text again at a slow speed at the end of the file
+    Trailer,
+    /// Hide the text in the file, to be shown by `cwgen answer FILE`
+    Hidden,
+}
+
+const TRAILER_WPM: u32 = 10;
+const TRAILER_PAUSE_SECS: f64 = 5.0;
+const FRAME_DESCRIPTION: &str = "CWGEN ANSWER";
+const SCRAMBLE_KEY: &[u8] = b"cwgen answer key";
+
+/// A pause, then "ANS" and `text` at a slow, clean speed.
+pub fn trailer(text: &str, tone: u32, tone_shape: ToneShape, sample_rate: u32) -> Vec<f32> {
+    let answer = format!("ANS {}", text.split_whitespace().collect::<Vec<_>>().join(" "));
+    let timing = Timing::new(TRAILER_WPM, 0);
+    let audio = MorseAudio::new_with_sample_rate(sample_rate, &answer, timing, tone, Conditions::default(), tone_shape, None);
+    let mut samples = vec![0.0; (TRAILER_PAUSE_SECS * sample_rate as f64) as usize];
+    samples.extend_from_slice(audio.get_samples());
+    samples
+}
+
+fn scramble(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().zip(SCRAMBLE_KEY.iter().cycle()).map(|(b, k)| b ^ k).collect()
+}
+
+/// Whether an answer can go into `path` the way `key` says, to be checked
+/// before the file is written: only WAV files carry a hidden one.
+pub fn check(key: Option<AnswerKey>, path: &str) -> Result<()> {
+    if let (Some(AnswerKey::Hidden), true) = (key, crate::opus::is_opus(path)) {
+        anyhow::bail!("a hidden answer needs a WAV file; use --answer-key trailer for Opus");
+    }
+    Ok(())
+}
+
+/// Add `text` to the file's tags, scrambled. Other tags are kept.
+pub fn hide(path: &str, text: &str) -> Result<()> {
+    check(Some(AnswerKey::Hidden), path)?;
+    let mut tag = read_tag(path)?.unwrap_or_default();
+    let value: String = scramble(text.trim().as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+    tag.add_frame(ExtendedText { description: FRAME_DESCRIPTION.into(), value });
+    tag.write_to_path(path, Version::Id3v24).with_context(|| format!("writing answer to {}", path))
+}
+
+/// The hidden answer in `path`, if it has one.
+pub fn reveal(path: &str) -> Result<Option<String>> {
+    let Some(tag) = read_tag(path)? else { return Ok(None) };
+    let Some(frame) = tag.extended_texts().find(|t| t.description == FRAME_DESCRIPTION) else { return Ok(None) };
+    let bytes: Vec<u8> = (0..frame.value.len() / 2)
+        .map(|i| u8::from_str_radix(&frame.value[2 * i..2 * i + 2], 16))
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("damaged answer in {}", path))?;
+    Ok(Some(String::from_utf8_lossy(&scramble(&bytes)).into_owned()))
+}
+
+fn read_tag(path: &str) -> Result<Option<Tag>> {
+    match Tag::read_from_path(path) {
+        Ok(tag) => Ok(Some(tag)),
+        Err(e) if matches!(e.kind, ErrorKind::NoTag) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("reading tags of {}", path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::write_wav;
+
+    #[test]
+    fn test_hidden_answer() {
+        let path = std::env::temp_dir().join(format!("cwgen-answer-{}.wav", std::process::id()));
+        let path = path.to_string_lossy();
+        write_wav(&path, 8000, &[0.0; 800]).unwrap();
+        assert_eq!(reveal(&path).unwrap(), None);
+        hide(&path, "KQZXV TRMPL\n").unwrap();
+
+        let raw = std::fs::read(&*path).unwrap();
+        let answer = reveal(&path).unwrap();
+        std::fs::remove_file(&*path).unwrap();
+        assert_eq!(answer.as_deref(), Some("KQZXV TRMPL"));
+        assert!(!raw.windows(5).any(|w| w == b"KQZXV"));
+    }
+
+    #[test]
+    fn test_hidden_answer_needs_wav() {
+        assert!(check(Some(AnswerKey::Hidden), "lesson.opus").is_err());
+        assert!(check(Some(AnswerKey::Hidden), "lesson.wav").is_ok());
+        assert!(check(Some(AnswerKey::Trailer), "lesson.opus").is_ok());
+    }
+
+    #[test]
+    fn test_trailer() {
+        // Five seconds of pause, then the slow answer
+        let samples = trailer("E", 700, ToneShape::Sine, 8000);
+        assert!(samples[..40000].iter().all(|s| *s == 0.0));
+        assert!(samples[40000..].iter().any(|s| *s != 0.0));
+    }
+}
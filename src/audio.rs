@@ -1,12 +1,28 @@
 use anyhow::Result;
-use hound::{WavSpec, WavWriter};
 use rand::Rng;
-use rodio::{source::Source, OutputStream, Sink};
-use std::time::Duration;
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::buffer::SamplesBuffer;
+use rodio::{source::Source, OutputStream, OutputStreamHandle, Sink};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-use crate::morse::{Timing, MorseError};
+use crate::conditions::Conditions;
+use crate::eq::{self, equalize};
+use crate::events::{self, Event};
+use crate::loudness::{limit, report, Limiting, Normalize};
+use crate::morse::{key_timeline, random_callsign, sign_starts, signs, text_to_code, Envelope, Timing, MorseError};
+use crate::opus;
+use crate::preamble::{self, Preamble, PreambleKind};
+use crate::record::Recorder;
+use crate::replay::{self, keep, History};
+use crate::tags::Tags;
+use crate::wav::WavFile;
 
 // ---------- Tone Generator -------------------------------------------------
+#[derive(Clone)]
 pub struct ToneGenerator {
     sample_rate: u32,
     base_frequency: f64,
@@ -17,7 +33,7 @@ pub struct ToneGenerator {
     symbol_start_time: f64,
 }
 
-#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
 pub enum ToneShape {
     Sine,
     Square,
@@ -37,6 +53,12 @@ impl ToneGenerator {
         }
     }
     
+    /// Retune without a phase jump (for sweeps).
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.base_frequency = frequency;
+        self.current_frequency = frequency;
+    }
+
     pub fn start_symbol(&mut self, sample_time: f64) {
         if self.drift_percentage.is_some() {
             self.symbol_start_time = sample_time;
@@ -113,6 +135,17 @@ impl SsbNoise {
         }
     }
 
+    // Noise `snr_db` below a steady tone: the RMS of the noise at amplitude
+    // 1.0 (NOISE_RMS, measured) against the RMS of the S9 signal
+    fn with_snr(snr_db: f32) -> Self {
+        const NOISE_RMS: f32 = 0.126;
+        let signal_rms = SIGNAL_AMPLITUDE / std::f32::consts::SQRT_2;
+        SsbNoise {
+            amplitude: signal_rms / NOISE_RMS / 10f32.powf(snr_db / 20.0),
+            ..SsbNoise::new(0)
+        }
+    }
+
     fn next(&mut self, sample_rate: u32) -> f32 {
         // 1. wide-band white
         let white = rand::rng().random_range(-1.0f32..1.0);
@@ -131,24 +164,327 @@ impl SsbNoise {
     }
 }
 
+// ---------- QRN static crashes ---------------------------------------------
+// Impulsive atmospheric noise: short bursts of low-passed white noise that
+// arrive at random and decay quickly, like lightning crashes on 80 m.
+struct StaticCrashes {
+    rate: f64,
+    amplitude: f32,
+    envelope: f32,
+    decay: f32,
+    lp: f32,
+}
+
+impl StaticCrashes {
+    fn new(qrn_level: u8) -> Self {
+        StaticCrashes {
+            rate: qrn_level as f64 * 0.6,          // crashes per second
+            amplitude: qrn_level as f32 * 0.15,
+            envelope: 0.0,
+            decay: 0.0,
+            lp: 0.0,
+        }
+    }
+
+    fn next(&mut self, sample_rate: u32) -> f32 {
+        if self.rate == 0.0 {
+            return 0.0;
+        }
+        let mut rng = rand::rng();
+        if rng.random_bool((self.rate / sample_rate as f64).min(1.0)) {
+            self.envelope = self.amplitude * rng.random_range(0.3..1.0);
+            let decay_secs = rng.random_range(0.005..0.04);
+            self.decay = (-1.0 / (sample_rate as f64 * decay_secs)).exp() as f32;
+        }
+        let white = rng.random_range(-1.0f32..1.0);
+        self.lp += (white - self.lp) * 0.3;
+        let sample = self.lp * self.envelope;
+        self.envelope *= self.decay;
+        sample
+    }
+}
+
+// ---------- QSB fading -----------------------------------------------------
+// Slow signal-strength fading: two incommensurate sinusoids so the pattern
+// doesn't repeat audibly. Depth scales with the QSB level.
+struct Fading {
+    depth: f64,
+    rates: [f64; 2],
+    phases: [f64; 2],
+}
+
+impl Fading {
+    fn new(qsb_level: u8, rate: Option<f32>) -> Self {
+        let mut rng = rand::rng();
+        let rates = match rate {
+            Some(hz) => [hz as f64, hz as f64 * 2.7],
+            None => [rng.random_range(0.05..0.15), rng.random_range(0.15..0.4)],
+        };
+        Fading {
+            depth: qsb_level as f64 / 10.0,
+            rates,
+            phases: [rng.random_range(0.0..std::f64::consts::TAU), rng.random_range(0.0..std::f64::consts::TAU)],
+        }
+    }
+
+    fn gain(&mut self, sample_rate: u32, severity: f32) -> f32 {
+        if self.depth == 0.0 {
+            return 1.0;
+        }
+        let mut level = 0.0;
+        for (phase, rate) in self.phases.iter_mut().zip(self.rates) {
+            *phase += std::f64::consts::TAU * rate / sample_rate as f64;
+            level += 0.5 - 0.5 * phase.cos();
+        }
+        (1.0 - self.depth * severity as f64 * level / 2.0) as f32
+    }
+}
+
+// ---------- Condition automation -------------------------------------------
+// Tracks session time and re-evaluates the scenario trend every block of
+// samples, so noise and fading drift slowly instead of staying constant.
+struct Severity {
+    conditions: Conditions,
+    sample_rate: u32,
+    sample: u64,
+    value: f32,
+}
+
+impl Severity {
+    const BLOCK: u64 = 1024;
+
+    fn new(conditions: Conditions, sample_rate: u32) -> Self {
+        Severity { conditions, sample_rate, sample: 0, value: conditions.severity(0.0) as f32 }
+    }
+
+    fn next(&mut self) -> f32 {
+        if self.sample.is_multiple_of(Self::BLOCK) {
+            let elapsed = self.sample as f64 / self.sample_rate as f64;
+            self.value = self.conditions.severity(elapsed) as f32;
+        }
+        self.sample += 1;
+        self.value
+    }
+}
+
+// ---------- Interfering stations -------------------------------------------
+// Another operator somewhere near our frequency: sends CQs, calls and
+// exchanges at its own speed, pitch and strength, then goes idle for a while.
+// How long it stays idle depends on the pileup density. With drift, its
+// frequency wanders too, a slow random walk (up to DRIFT_HZ_PER_SEC a second
+// per drift level) that carries it towards our signal, across it and away
+// again, bouncing back at MAX_OFFSET_HZ. The same stations, each keeping one
+// callsign, fill the band that `cwgen tune` tunes across; there they can
+// also be scripted, sending only what they are given.
+const DRIFT_HZ_PER_SEC: f64 = 4.0;
+const MAX_OFFSET_HZ: f64 = 700.0;
+// How often a drifting interferer is retuned
+const RETUNE_SECS: f64 = 0.02;
+
+pub struct Interferer {
+    tone: ToneGenerator,
+    centre: f64,
+    offset: f64,
+    /// Added to the frequency to give the pitch heard (see `tune`)
+    shift: f64,
+    call: Option<String>,
+    /// Calls and works on its own, rather than waiting to be given something
+    /// to send
+    auto: bool,
+    /// Hz a second, and its limit
+    speed: f64,
+    max_speed: f64,
+    retune_in: usize,
+    amplitude: f32,
+    wpm: u32,
+    pileup: u8,
+    sample_rate: u32,
+    elements: VecDeque<(bool, usize)>,
+    envelope: f32,
+}
+
+impl Interferer {
+    fn new(base_tone: u32, pileup: u8, drift: u8, sample_rate: u32) -> Self {
+        let mut rng = rand::rng();
+        let offset = rng.random_range(80..600) as i32 * if rng.random_bool(0.5) { 1 } else { -1 };
+        let frequency = (base_tone as i32 + offset).max(200) as u32;
+        let max_speed = drift.min(9) as f64 * DRIFT_HZ_PER_SEC;
+        let mut interferer = Interferer {
+            tone: ToneGenerator::new(frequency, sample_rate, ToneShape::Sine, None),
+            centre: base_tone as f64,
+            offset: frequency as f64 - base_tone as f64,
+            shift: 0.0,
+            call: None,
+            auto: true,
+            speed: rng.random_range(-1.0..=1.0) * max_speed,
+            max_speed,
+            retune_in: 0,
+            amplitude: rng.random_range(0.04..0.2),
+            wpm: rng.random_range(14..=32),
+            pileup,
+            sample_rate,
+            elements: VecDeque::new(),
+            envelope: 0.0,
+        };
+        interferer.queue_idle();
+        interferer
+    }
+
+    /// A station on `frequency` that always signs the same, random, call.
+    pub fn station(frequency: f64, pileup: u8, drift: u8, sample_rate: u32) -> Self {
+        let mut rng = rand::rng();
+        let mut station = Interferer::new(frequency as u32, pileup, drift, sample_rate);
+        station.centre = frequency;
+        station.offset = 0.0;
+        station.amplitude = rng.random_range(0.1..0.5);
+        station.call = Some(random_callsign(&mut rng));
+        station.tune(0.0);
+        station
+    }
+
+    /// A station on `frequency` that keeps quiet until given something to
+    /// send.
+    pub fn scripted(frequency: f64, wpm: u32, amplitude: f32, sample_rate: u32) -> Self {
+        let mut station = Interferer::station(frequency, 0, 0, sample_rate);
+        station.auto = false;
+        station.elements.clear();
+        station.wpm = wpm;
+        station.amplitude = amplitude;
+        station
+    }
+
+    /// Send `text` once whatever is queued has gone.
+    pub fn send(&mut self, text: &str) {
+        for element in key_timeline(text, &Timing::new(self.wpm, 0)) {
+            let len = (element.duration.as_secs_f64() * self.sample_rate as f64) as usize;
+            self.elements.push_back((element.key_down, len));
+        }
+    }
+
+    /// Wait `secs` before sending anything more.
+    pub fn pause(&mut self, secs: f64) {
+        self.elements.push_back((false, (secs * self.sample_rate as f64) as usize));
+    }
+
+    /// Whether it has sent everything it was given.
+    pub fn idle(&self) -> bool {
+        self.elements.iter().all(|(_, remaining)| *remaining == 0)
+    }
+
+    /// Where it is now, drift and all.
+    pub fn frequency(&self) -> f64 {
+        self.centre + self.offset
+    }
+
+    pub fn call(&self) -> Option<&str> {
+        self.call.as_deref()
+    }
+
+    pub fn wpm(&self) -> u32 {
+        self.wpm
+    }
+
+    /// Hear it `shift` Hz higher (lower if negative), as a receiver's BFO
+    /// does.
+    pub fn tune(&mut self, shift: f64) {
+        self.shift = shift;
+        self.tone.set_frequency((self.frequency() + shift).max(200.0));
+    }
+
+    fn queue_idle(&mut self) {
+        let busy = self.pileup.min(9) as f64 / 9.0;
+        let idle_secs = rand::rng().random_range(0.3..8.0) * (1.0 - busy) + 0.2;
+        self.elements.push_back((false, (idle_secs * self.sample_rate as f64) as usize));
+    }
+
+    fn queue_message(&mut self) {
+        let mut rng = rand::rng();
+        let call = self.call.clone().unwrap_or_else(|| random_callsign(&mut rng));
+        let text = match rng.random_range(0..4) {
+            0 => format!("CQ CQ DE {} {} K", call, call),
+            1 => format!("{} 5NN", call),
+            2 => format!("TU {}", call),
+            _ => call,
+        };
+        self.send(&text);
+    }
+
+    // One step of the walk: the speed changes a little at a time, so the
+    // drift is smooth rather than jittery
+    fn wander(&mut self) {
+        let mut rng = rand::rng();
+        self.speed = (self.speed + rng.random_range(-0.1..=0.1) * self.max_speed).clamp(-self.max_speed, self.max_speed);
+        self.offset += self.speed * RETUNE_SECS;
+        if self.offset.abs() > MAX_OFFSET_HZ {
+            self.offset = MAX_OFFSET_HZ.copysign(self.offset);
+            self.speed = -self.speed;
+        }
+        self.tone.set_frequency((self.frequency() + self.shift).max(200.0));
+        self.retune_in = (RETUNE_SECS * self.sample_rate as f64) as usize;
+    }
+
+    pub fn next(&mut self) -> f32 {
+        if self.max_speed > 0.0 {
+            if self.retune_in == 0 {
+                self.wander();
+            }
+            self.retune_in -= 1;
+        }
+        let key_down = loop {
+            match self.elements.front_mut() {
+                Some((_, 0)) => {
+                    self.elements.pop_front();
+                }
+                Some((key_down, remaining)) => {
+                    *remaining -= 1;
+                    break *key_down;
+                }
+                None if self.auto => {
+                    self.queue_message();
+                    self.queue_idle();
+                }
+                None => break false,
+            }
+        };
+        // ~4 ms one-pole ramp keeps the keying click-free
+        let target = if key_down { 1.0 } else { 0.0 };
+        self.envelope += (target - self.envelope) * (250.0 / self.sample_rate as f32).min(1.0);
+        self.tone.next_sample(0.0) * self.amplitude * self.envelope
+    }
+}
+
 // ---------- Continuous noise source ----------------------------------------
 // Infinite QRM source for use as a separate sink running across an entire
 // practice session, so the noise floor never drops between words.
 pub struct NoiseSource {
     noise: SsbNoise,
+    crashes: StaticCrashes,
+    interferers: Vec<Interferer>,
+    severity: Severity,
     sample_rate: u32,
 }
 
 impl NoiseSource {
-    pub fn new(qrm: u8, sample_rate: u32) -> Self {
-        Self { noise: SsbNoise::new(qrm), sample_rate }
+    pub fn new(conditions: Conditions, tone: u32, sample_rate: u32) -> Self {
+        Self {
+            noise: conditions.snr.map_or_else(|| SsbNoise::new(conditions.qrm), SsbNoise::with_snr),
+            crashes: StaticCrashes::new(conditions.qrn),
+            interferers: (0..conditions.interferers)
+                .map(|_| Interferer::new(tone, conditions.pileup, conditions.interferer_drift, sample_rate))
+                .collect(),
+            severity: Severity::new(conditions, sample_rate),
+            sample_rate,
+        }
     }
 }
 
 impl Iterator for NoiseSource {
     type Item = f32;
     fn next(&mut self) -> Option<f32> {
-        Some(self.noise.next(self.sample_rate))
+        let qrm: f32 = self.interferers.iter_mut().map(Interferer::next).sum();
+        let severity = self.severity.next();
+        let noise = self.noise.next(self.sample_rate) + self.crashes.next(self.sample_rate);
+        Some(noise * severity + qrm)
     }
 }
 
@@ -159,148 +495,417 @@ impl Source for NoiseSource {
     fn total_duration(&self) -> Option<Duration> { None }
 }
 
-// ---------- Audio generator ------------------------------------------------
-pub struct MorseAudio {
-    samples: Vec<f32>,
-    pos: usize,
+// ---------- Keyed sidetone --------------------------------------------------
+// A tone keyed live, for paddles: whatever is playing follows the key as
+// soon as the next buffer goes out, instead of waiting for a rendered
+// character to be queued. Each edge is ramped over SIDETONE_RAMP so keying
+// doesn't click.
+const SIDETONE_RAMP: Duration = Duration::from_millis(5);
+const SIDETONE_LEVEL: f32 = 0.25;
+
+pub struct Sidetone {
+    key: Arc<AtomicBool>,
+    phase: f32,
+    step: f32,
+    envelope: f32,
+    ramp: f32,
     sample_rate: u32,
 }
 
-impl MorseAudio {
-    pub fn new_with_sample_rate(
-        sample_rate: u32,
-        text: &str,
-        timing: Timing,
-        tone: u32,
-        qrm: u8,
-        tone_shape: ToneShape,
-        drift_percentage: Option<u8>,
-    ) -> Self {
-        Self::build(sample_rate, text, timing, tone, qrm, tone_shape, drift_percentage, true)
+impl Sidetone {
+    /// A sidetone at `tone` Hz, and the key that sounds it.
+    pub fn new(tone: u32, sample_rate: u32) -> (Self, Arc<AtomicBool>) {
+        let key = Arc::new(AtomicBool::new(false));
+        let sidetone = Sidetone {
+            key: key.clone(),
+            phase: 0.0,
+            step: std::f32::consts::TAU * tone as f32 / sample_rate as f32,
+            envelope: 0.0,
+            ramp: 1.0 / (SIDETONE_RAMP.as_secs_f32() * sample_rate as f32),
+            sample_rate,
+        };
+        (sidetone, key)
+    }
+}
+
+impl Iterator for Sidetone {
+    type Item = f32;
+    fn next(&mut self) -> Option<f32> {
+        self.envelope = match self.key.load(Ordering::Relaxed) {
+            true => (self.envelope + self.ramp).min(1.0),
+            false => (self.envelope - self.ramp).max(0.0),
+        };
+        self.phase = (self.phase + self.step) % std::f32::consts::TAU;
+        Some(self.phase.sin() * self.envelope * SIDETONE_LEVEL)
+    }
+}
+
+impl Source for Sidetone {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { 1 }
+    fn sample_rate(&self) -> u32 { self.sample_rate }
+    fn total_duration(&self) -> Option<Duration> { None }
+}
+
+// ---------- Band simulation -------------------------------------------------
+// Everything the channel does to the keyed tone: fading on the signal itself
+// plus the QRM/QRN bed and interfering stations underneath it.
+struct Band {
+    background: Option<NoiseSource>,
+    fading: Fading,
+    severity: Severity,
+}
+
+impl Band {
+    fn new(conditions: Conditions, tone: u32, sample_rate: u32, include_noise: bool) -> Self {
+        Band {
+            background: include_noise.then(|| NoiseSource::new(conditions, tone, sample_rate)),
+            fading: Fading::new(conditions.qsb, conditions.qsb_rate),
+            severity: Severity::new(conditions, sample_rate),
+        }
+    }
+
+    fn noise(&mut self) -> f32 {
+        self.background.as_mut().and_then(Iterator::next).unwrap_or(0.0)
+    }
+
+    // The fading gain on the signal now, and the noise under it
+    fn mark(&mut self, sample_rate: u32) -> (f32, f32) {
+        let severity = self.severity.next();
+        (self.fading.gain(sample_rate, severity), self.noise())
+    }
+
+    fn space(&mut self, sample_rate: u32) -> f32 {
+        // Keep the fading clock running through gaps
+        let severity = self.severity.next();
+        self.fading.gain(sample_rate, severity);
+        self.noise()
+    }
+}
+
+// Random scale factor in [1 - amount, 1 + amount)
+fn randomize(rng: &mut impl Rng, amount: f64) -> f64 {
+    if amount == 0.0 {
+        1.0
+    } else {
+        1.0 + rng.random_range(-amount..amount)
     }
+}
+
+// ---------- Audio generator ------------------------------------------------
+// `MorseSynth` makes each sample as it is asked for, so playback starts at
+// once and a long text takes no more memory than a short one. Only the plan,
+// one entry per element and space, is worked out up front, jitter and word
+// speed wobble included, which also tells how long the whole will be.
+// `MorseAudio` is the same sound rendered into a buffer, for writing to
+// files and for everything that works on the samples afterwards.
+
+// Morse signal amplitude (S9 level)
+const SIGNAL_AMPLITUDE: f32 = 0.25;
+
+// A stretch of key down or key up, in samples
+#[derive(Debug, Clone, Copy)]
+enum Segment {
+    Mark { len: usize, first: bool },
+    Space(usize),
+}
+
+impl Segment {
+    fn len(&self) -> usize {
+        match *self {
+            Segment::Mark { len, .. } | Segment::Space(len) => len,
+        }
+    }
+}
+
+// The segments `text` is sent as
+fn plan(text: &str, timing: &Timing, conditions: Conditions, sample_rate: u32) -> Vec<Segment> {
+    let mut rng = rand::rng();
+    let mut segments = Vec::new();
+    let samples = |duration: Duration| (sample_rate as f64 * duration.as_secs_f64()) as usize;
+
+    // Sloppy-fist impairments: per-element jitter and per-word speed wobble
+    let jitter = conditions.jitter.min(50) as f64 / 100.0;
+    let speed_variance = conditions.speed_variance.min(50) as f64 / 100.0;
+    let mut word_scale = randomize(&mut rng, speed_variance);
+    let mut is_first_symbol = true;
+
+    for (sign, code) in signs(text, timing.charset) {
+        if sign == " " {
+            // Word space (the preceding character space is already in place),
+            // at the pace of the word it ends
+            segments.push(Segment::Space(samples((timing.wrd - timing.chr).mul_f64(word_scale))));
+            word_scale = randomize(&mut rng, speed_variance);
+        } else if let Some(code) = code {
+            for sym in code.chars() {
+                let Some((key_down, dur)) = timing.element(sym) else { continue };
+                let dur = dur.mul_f64(word_scale * randomize(&mut rng, jitter));
+                if !key_down {
+                    // The space inside an American character
+                    segments.push(Segment::Space(samples(dur)));
+                    continue;
+                }
+                segments.push(Segment::Mark { len: samples(dur), first: is_first_symbol });
+                is_first_symbol = false;
+
+                // Symbol space
+                let sym = timing.sym.mul_f64(word_scale * randomize(&mut rng, jitter));
+                segments.push(Segment::Space(samples(sym)));
+            }
+
+            // Character space
+            let off = (sample_rate as f64 * (timing.chr - timing.sym).as_secs_f64() * word_scale) as usize;
+            segments.push(Segment::Space(off));
+        }
+    }
+    segments
+}
+
+/// Morse audio made as it is played.
+pub struct MorseSynth {
+    segments: std::vec::IntoIter<Segment>,
+    // The segment being made, and how far into it
+    current: Option<Segment>,
+    at: usize,
+    tone_generator: ToneGenerator,
+    // Binaural: the right ear's tone, and its sample for the frame whose
+    // left sample was just given out
+    right: Option<ToneGenerator>,
+    pending: Option<f32>,
+    band: Band,
+    envelope: Envelope,
+    attack: usize,
+    release: usize,
+    sample_time: f64,
+    sample_rate: u32,
+    // Frames still to come
+    remaining: usize,
+    // Band noise to pad with, and how long before the text starts, in frames
+    pad: usize,
+    lead: usize,
+}
 
+impl MorseSynth {
     pub fn new(
+        sample_rate: u32,
         text: &str,
         timing: Timing,
         tone: u32,
-        qrm: u8,
+        conditions: Conditions,
         tone_shape: ToneShape,
         drift_percentage: Option<u8>,
     ) -> Self {
-        // Use 44100 Hz for high-quality audio playback
-        Self::new_with_sample_rate(44100, text, timing, tone, qrm, tone_shape, drift_percentage)
+        Self::build(sample_rate, text, timing, tone, conditions, tone_shape, drift_percentage, true)
     }
 
-    // Signal-only buffer: morse tone with envelope, silence in gaps. Intended
-    // to be mixed against a separate continuous NoiseSource.
-    pub fn new_signal_only(
+    /// The tone alone, silent between elements, for mixing against a
+    /// separate NoiseSource: only fading and timing impairments apply.
+    pub fn signal_only(
         sample_rate: u32,
         text: &str,
         timing: Timing,
         tone: u32,
+        conditions: Conditions,
         tone_shape: ToneShape,
         drift_percentage: Option<u8>,
     ) -> Self {
-        Self::build(sample_rate, text, timing, tone, 0, tone_shape, drift_percentage, false)
+        Self::build(sample_rate, text, timing, tone, conditions, tone_shape, drift_percentage, false)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn build(
         sample_rate: u32,
         text: &str,
         timing: Timing,
         tone: u32,
-        qrm: u8,
+        conditions: Conditions,
         tone_shape: ToneShape,
         drift_percentage: Option<u8>,
         include_noise: bool,
     ) -> Self {
-        let mut tone_generator = ToneGenerator::new(tone, sample_rate, tone_shape, drift_percentage);
-        let mut samples = Vec::new();
-        let mut noise = SsbNoise::new(qrm);
-
-        let attack_dur  = timing.sym.mul_f32(0.15);
-        let release_dur = timing.sym.mul_f32(0.25);
-
-        // Morse signal amplitude (S9 level)
-        let signal_amplitude = 0.25;
+        let segments = plan(text, &timing, conditions, sample_rate);
+        let remaining = segments.iter().map(Segment::len).sum();
+        let (attack, release) = timing.envelope.edges(timing.sym, sample_rate);
+        let noisy = include_noise && (conditions.qrm > 0 || conditions.snr.is_some() || conditions.qrn > 0 || conditions.interferers > 0);
+        MorseSynth {
+            segments: segments.into_iter(),
+            current: None,
+            at: 0,
+            tone_generator: ToneGenerator::new(tone, sample_rate, tone_shape, drift_percentage),
+            right: None,
+            pending: None,
+            band: Band::new(conditions, tone, sample_rate, include_noise),
+            envelope: timing.envelope,
+            attack,
+            release,
+            sample_time: 0.0,
+            sample_rate,
+            remaining,
+            pad: match noisy {
+                true => (conditions.noise_pad_ms as u64 * sample_rate as u64 / 1000) as usize,
+                false => 0,
+            },
+            lead: 0,
+        }
+    }
 
-        let mut sample_time = 0.0;
-        let mut is_first_symbol = true;
+    /// With the band's noise running on either side of the text for the
+    /// conditions' `noise_pad_ms`, so the signal doesn't start exactly as
+    /// the clip does. The lead-in varies between half and all of that,
+    /// since a start that can be timed gives the first character away.
+    pub fn padded(self) -> Self {
+        if self.pad == 0 {
+            return self;
+        }
+        let (lead, pad) = (rand::rng().random_range(self.pad / 2..=self.pad), self.pad);
+        self.around(vec![Segment::Space(lead)], vec![Segment::Space(pad)])
+    }
 
-        let gap_sample = |noise: &mut SsbNoise, sample_rate: u32| -> f32 {
-            if include_noise { noise.next(sample_rate) } else { 0.0 }
+    /// With `preamble` sent first, cleanly at `timing`'s speed, and a pause
+    /// after it.
+    pub fn preamble(self, preamble: Option<Preamble>, timing: &Timing) -> Self {
+        let Some(preamble) = preamble else { return self };
+        let frames = |length: Duration| (length.as_secs_f64() * self.sample_rate as f64) as usize;
+        let mut segments = match preamble.kind {
+            PreambleKind::Carrier => vec![Segment::Mark { len: frames(preamble.length), first: true }],
+            PreambleKind::Vvv => {
+                let length = |text| plan(text, timing, Conditions::default(), self.sample_rate).iter().map(Segment::len).sum::<usize>();
+                let group = length("VVV VVV") - length("VVV");
+                let groups = preamble.groups(Duration::from_secs_f64(group as f64 / self.sample_rate as f64));
+                plan(&vec!["VVV"; groups].join(" "), timing, Conditions::default(), self.sample_rate)
+            }
         };
+        segments.push(Segment::Space(frames(preamble::GAP)));
+        self.around(segments, Vec::new())
+    }
 
-        // Build tone track - noise should be continuous throughout
-        for ch in text.chars() {
-            let up = ch.to_ascii_uppercase();
-            if let Some(code) = crate::morse::MORSE.get(&up) {
-                for sym in code.chars() {
-                    let dur = match sym {
-                        '.' => timing.dot,
-                        '-' => timing.dash,
-                        _ => continue
-                    };
-
-                    let len = (sample_rate as f64 * dur.as_secs_f64()) as usize;
-                    let attack  = (sample_rate as f64 * attack_dur.as_secs_f64()) as usize;
-                    let release = (sample_rate as f64 * release_dur.as_secs_f64()) as usize;
-
-                    // Start new symbol - reset frequency for drift and phase for continuity
-                    tone_generator.start_symbol(sample_time);
-
-                    // Generate tone with envelope (plus optional noise bed)
-                    for i in 0..len {
-                        let mut amp = 1.0;
-                        if i < attack {
-                            amp = i as f32 / attack as f32;
-                        }
-                        if i >= len - release {
-                            amp = (len - i) as f32 / release as f32;
-                        }
+    // With `before` ahead of what is still to come and `after` behind it
+    fn around(mut self, before: Vec<Segment>, after: Vec<Segment>) -> Self {
+        let (ahead, behind) = (before.iter().map(Segment::len).sum::<usize>(), after.iter().map(Segment::len).sum::<usize>());
+        let segments = before.into_iter().chain(std::mem::take(&mut self.segments)).chain(after);
+        self.segments = segments.collect::<Vec<_>>().into_iter();
+        self.remaining += ahead + behind;
+        self.lead += ahead;
+        self
+    }
 
-                        // Extra gentle start for the very first symbol to prevent any click
-                        if is_first_symbol && i == 0 {
-                            amp *= 0.1;
-                        }
+    /// How long the noise runs before the text starts.
+    pub fn lead(&self) -> Duration {
+        Duration::from_secs_f64(self.lead as f64 / self.sample_rate as f64)
+    }
 
-                        let tone_sample = tone_generator.next_sample(sample_time) * signal_amplitude * amp;
-                        let noise_sample = if include_noise { noise.next(sample_rate) } else { 0.0 };
-                        samples.push(tone_sample + noise_sample);
-                        sample_time += 1.0 / sample_rate as f64;
-                    }
+    /// Stereo, with the tone `offset_hz` apart between the ears: half of it
+    /// below in the left ear and half above in the right. Each ear hears a
+    /// steady note; together they beat at the offset.
+    pub fn binaural(mut self, offset_hz: Option<u32>) -> Self {
+        if let Some(offset) = offset_hz.filter(|&offset| offset > 0) {
+            let tone = self.tone_generator.base_frequency;
+            let mut right = self.tone_generator.clone();
+            self.tone_generator.set_frequency(tone - offset as f64 / 2.0);
+            right.set_frequency(tone + offset as f64 / 2.0);
+            self.right = Some(right);
+        }
+        self
+    }
 
-                    is_first_symbol = false;
+    // The envelope's gain `i` samples into a mark `len` long
+    fn gain(&self, i: usize, len: usize, first: bool) -> f32 {
+        let mut amp = 1.0;
+        if i < self.attack {
+            amp = self.envelope.gain(i as f32 / self.attack as f32);
+        }
+        if i >= len.saturating_sub(self.release) {
+            amp = self.envelope.gain((len - i) as f32 / self.release as f32);
+        }
+        // Extra gentle start for the very first symbol to prevent any click
+        if first && i == 0 {
+            amp *= 0.1;
+        }
+        amp
+    }
+}
+
+impl Iterator for MorseSynth {
+    type Item = f32;
 
-                    // Symbol space
-                    let off = (sample_rate as f64 * timing.sym.as_secs_f64()) as usize;
-                    for _ in 0..off {
-                        samples.push(gap_sample(&mut noise, sample_rate));
-                        sample_time += 1.0 / sample_rate as f64;
+    fn next(&mut self) -> Option<f32> {
+        if let Some(sample) = self.pending.take() {
+            return Some(sample);
+        }
+        loop {
+            let sample = match self.current {
+                None => {
+                    let segment = self.segments.next()?;
+                    if let Segment::Mark { .. } = segment {
+                        // New symbol - reset frequency for drift and phase for continuity
+                        self.tone_generator.start_symbol(self.sample_time);
+                        if let Some(right) = &mut self.right {
+                            right.start_symbol(self.sample_time);
+                        }
                     }
+                    (self.current, self.at) = (Some(segment), 0);
+                    continue;
                 }
-
-                // Character space
-                let off = (sample_rate as f64 * (timing.chr - timing.sym).as_secs_f64()) as usize;
-                for _ in 0..off {
-                    samples.push(gap_sample(&mut noise, sample_rate));
-                    sample_time += 1.0 / sample_rate as f64;
+                Some(Segment::Mark { len, first }) if self.at < len => {
+                    let amp = self.gain(self.at, len, first) * self.envelope.ripple(self.sample_time) * SIGNAL_AMPLITUDE;
+                    let (fade, noise) = self.band.mark(self.sample_rate);
+                    let time = self.sample_time;
+                    self.pending = self.right.as_mut().map(|right| right.next_sample(time) * amp * fade + noise);
+                    self.tone_generator.next_sample(time) * amp * fade + noise
                 }
-            } else if up == ' ' {
-                // Word space
-                let off = (sample_rate as f64 * (timing.wrd - timing.chr).as_secs_f64()) as usize;
-                for _ in 0..off {
-                    samples.push(gap_sample(&mut noise, sample_rate));
-                    sample_time += 1.0 / sample_rate as f64;
+                Some(Segment::Space(len)) if self.at < len => {
+                    let noise = self.band.space(self.sample_rate);
+                    self.pending = self.right.is_some().then_some(noise);
+                    noise
                 }
-            }
+                Some(_) => {
+                    self.current = None;
+                    continue;
+                }
+            };
+            self.at += 1;
+            self.remaining -= 1;
+            self.sample_time += 1.0 / self.sample_rate as f64;
+            return Some(sample);
         }
+    }
 
-        MorseAudio {
-            samples,
-            pos: 0,
-            sample_rate,
-        }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.remaining * self.channels() as usize + self.pending.is_some() as usize;
+        (left, Some(left))
+    }
+}
+
+impl Source for MorseSynth {
+    fn current_frame_len(&self) -> Option<usize> { None }
+
+    fn channels(&self) -> u16 { 1 + self.right.is_some() as u16 }
+
+    fn sample_rate(&self) -> u32 { self.sample_rate }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f64(self.remaining as f64 / self.sample_rate as f64))
+    }
+}
+
+pub struct MorseAudio {
+    samples: Vec<f32>,
+    pos: usize,
+    sample_rate: u32,
+}
+
+impl MorseAudio {
+    pub fn new_with_sample_rate(
+        sample_rate: u32,
+        text: &str,
+        timing: Timing,
+        tone: u32,
+        conditions: Conditions,
+        tone_shape: ToneShape,
+        drift_percentage: Option<u8>,
+    ) -> Self {
+        let synth = MorseSynth::new(sample_rate, text, timing, tone, conditions, tone_shape, drift_percentage);
+        MorseAudio { samples: synth.collect(), pos: 0, sample_rate }
     }
 
     pub fn get_samples(&self) -> &[f32] {
@@ -336,57 +941,546 @@ impl Source for MorseAudio {
     }
 }
 
+// ---------- Audio host -----------------------------------------------------
+/// Where sound goes to and comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum AudioHost {
+    /// The system default (ALSA, CoreAudio, WASAPI)
+    #[default]
+    Default,
+    /// A JACK server (or PipeWire's JACK interface), for routing into other
+    /// audio software; needs the `jack` feature
+    Jack,
+}
+
+static HOST: OnceLock<AudioHost> = OnceLock::new();
+
+/// Choose the host for the rest of the run; call before anything plays.
+pub fn set_host(host: AudioHost) {
+    let _ = HOST.set(host);
+}
+
+pub fn host() -> Result<rodio::cpal::Host> {
+    match HOST.get().copied().unwrap_or_default() {
+        AudioHost::Default => Ok(rodio::cpal::default_host()),
+        #[cfg(feature = "jack")]
+        AudioHost::Jack => rodio::cpal::host_from_id(rodio::cpal::HostId::Jack)
+            .map_err(|e| MorseError::AudioDeviceError(format!("JACK: {}", e)).into()),
+        #[cfg(not(feature = "jack"))]
+        AudioHost::Jack => anyhow::bail!("this cwgen was built without JACK; rebuild with `cargo build --release --features jack`"),
+    }
+}
+
+/// A rodio stream on the chosen host's default output.
+pub fn output_stream() -> Result<(OutputStream, OutputStreamHandle)> {
+    let device = host()?
+        .default_output_device()
+        .ok_or_else(|| MorseError::AudioDeviceError("no output device".into()))?;
+    Ok(OutputStream::try_from_device(&device).map_err(|e| MorseError::AudioDeviceError(e.to_string()))?)
+}
+
+/// How what is played reaches the listener, set up once for the run and
+/// handed to everything that plays: the listening EQ (see eq.rs) and the
+/// `--replay` history (see replay.rs).
+#[derive(Debug, Clone, Default)]
+pub struct Speaker {
+    pub eq: Vec<eq::Band>,
+    pub history: Option<History>,
+}
+
 // ---------- Audio playback helper ------------------------------------------
+// Used when the output device doesn't report a preferred rate
+const FALLBACK_PLAYBACK_RATE: u32 = 44100;
+
+/// The default output device's own sample rate. Audio rendered at this rate
+/// plays without rodio converting it on the way out.
+pub fn playback_rate() -> u32 {
+    static RATE: OnceLock<u32> = OnceLock::new();
+    *RATE.get_or_init(|| {
+        host()
+            .ok()
+            .and_then(|host| host.default_output_device())
+            .and_then(|device| device.default_output_config().ok())
+            .map(|config| config.sample_rate().0)
+            .unwrap_or(FALLBACK_PLAYBACK_RATE)
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn play_audio(
     text: &str, 
     timing: Timing, 
     tone: u32, 
-    qrm: u8,
+    conditions: Conditions,
     tone_shape: ToneShape,
     drift_percentage: Option<u8>,
+    recorder: Option<&Recorder>,
+    speaker: &Speaker,
 ) -> Result<()> {
-    let (_stream, handle) = OutputStream::try_default()
-        .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+    play(text, timing, tone, conditions, tone_shape, drift_percentage, None, recorder, speaker, false)
+}
+
+/// Play `text` after `preamble`, if given, and with `caption` printing the
+/// morse for each character as it is heard.
+#[allow(clippy::too_many_arguments)]
+pub fn play_text(
+    text: &str,
+    timing: Timing,
+    tone: u32,
+    conditions: Conditions,
+    tone_shape: ToneShape,
+    drift_percentage: Option<u8>,
+    preamble: Option<Preamble>,
+    speaker: &Speaker,
+    caption: bool,
+) -> Result<()> {
+    play(text, timing, tone, conditions, tone_shape, drift_percentage, preamble, None, speaker, caption)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play(
+    text: &str,
+    timing: Timing,
+    tone: u32,
+    conditions: Conditions,
+    tone_shape: ToneShape,
+    drift_percentage: Option<u8>,
+    preamble: Option<Preamble>,
+    recorder: Option<&Recorder>,
+    speaker: &Speaker,
+    caption: bool,
+) -> Result<()> {
+    let (_stream, handle) = output_stream()?;
     
     let sink = Sink::try_new(&handle)
         .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
     
-    sink.append(MorseAudio::new(text, timing, tone, qrm, tone_shape, drift_percentage));
+    let sample_rate = playback_rate();
+    let synth = MorseSynth::new(sample_rate, text, timing, tone, conditions, tone_shape, drift_percentage)
+        .preamble(preamble, &timing)
+        .padded()
+        .binaural(timing.binaural);
+    let lead = synth.lead();
+    let total = synth.size_hint().0;
+    let duration_s = synth.total_duration().unwrap_or_default().as_secs_f64();
+    let channels = synth.channels();
+    let (audio, limited) = Limiting::new(synth, sample_rate, channels);
+    match recorder {
+        Some(recorder) => sink.append(equalize(keep(recorder.tap(audio), speaker.history.as_ref()), &speaker.eq)),
+        None => sink.append(equalize(keep(audio, speaker.history.as_ref()), &speaker.eq)),
+    }
+    events::emit(Event::PlaybackStarted { text, duration_s });
+    let started = Instant::now() + lead;
+    // Keys are read for replays while the text plays, in raw mode, so the
+    // caption's line is only ended once that is over
+    std::thread::scope(|scope| {
+        if caption {
+            scope.spawn(|| print_as_heard(text, &timing, started, speaker.history.as_ref()));
+        }
+        scope.spawn(|| events::chars_as_heard(text, &timing, started, speaker.history.as_ref()));
+        replay::wait(&handle, &[&sink], speaker)
+    })?;
+    if caption {
+        println!();
+    }
+    let limited = *limited.lock().unwrap();
+    if limited.clipped > 0 {
+        eprintln!("Warning: {}", report(&limited, total));
+    }
+    Ok(())
+}
+
+// The morse for each character (or prosign) of `text` as it is heard,
+// playback having begun at `started` with replays from `history`, with a
+// slash between words
+fn print_as_heard(text: &str, timing: &Timing, started: Instant, history: Option<&History>) {
+    let clock = replay::Clock::start(history);
+    let mut out = std::io::stdout();
+    let mut in_word = false;
+    for (sign, offset) in sign_starts(text, timing) {
+        let space = sign.chars().all(char::is_whitespace);
+        let code = match sign {
+            _ if space && in_word => "/".to_string(),
+            _ if space => continue,
+            sign => match text_to_code(sign, timing.charset) {
+                Ok(code) if !code.is_empty() => code,
+                _ => continue,
+            },
+        };
+        in_word = !space;
+        if !clock.sleep_until(started + offset) {
+            return;
+        }
+        let _ = write!(out, "{} ", code);
+        let _ = out.flush();
+    }
+}
+
+/// Play an already rendered mono buffer and wait for it to finish.
+pub fn play_samples(samples: Vec<f32>, sample_rate: u32, speaker: &Speaker) -> Result<()> {
+    play_mix(samples, 1, sample_rate, None, speaker)
+}
+
+/// Play an already rendered buffer of `channels` interleaved, into
+/// `recorder` too if there is one, and wait for it to finish.
+pub fn play_mix(
+    mut samples: Vec<f32>,
+    channels: u16,
+    sample_rate: u32,
+    recorder: Option<&Recorder>,
+    speaker: &Speaker,
+) -> Result<()> {
+    if let Some(result) = limit(&mut samples, sample_rate) {
+        eprintln!("Warning: {}", report(&result, samples.len()));
+    }
+    let (_stream, handle) = output_stream()?;
+    let sink = Sink::try_new(&handle)
+        .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+    let buffer = SamplesBuffer::new(channels, sample_rate, samples);
+    match recorder {
+        Some(recorder) => sink.append(equalize(keep(recorder.tap(buffer), speaker.history.as_ref()), &speaker.eq)),
+        None => sink.append(equalize(keep(buffer, speaker.history.as_ref()), &speaker.eq)),
+    }
+    replay::wait(&handle, &[&sink], speaker)?;
+    Ok(())
+}
+
+/// Play a mono buffer as it is, without the listening EQ: keying for a
+/// radio, or a test signal.
+pub fn play_signal(mut samples: Vec<f32>, sample_rate: u32) -> Result<()> {
+    if let Some(result) = limit(&mut samples, sample_rate) {
+        eprintln!("Warning: {}", report(&result, samples.len()));
+    }
+    let (_stream, handle) = output_stream()?;
+    let sink = Sink::try_new(&handle)
+        .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+    sink.append(SamplesBuffer::new(1, sample_rate, samples));
     sink.sleep_until_end();
-    
     Ok(())
 }
 
+/// Play separate left and right channels and wait for them to finish.
+pub fn play_stereo(left: &[f32], right: &[f32], sample_rate: u32) -> Result<()> {
+    let (_stream, handle) = output_stream()?;
+    let sink = Sink::try_new(&handle)
+        .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+    sink.append(SamplesBuffer::new(2, sample_rate, interleave(left, right)));
+    sink.sleep_until_end();
+    Ok(())
+}
+
+/// Left and right channels as stereo frames, the shorter padded with silence.
+pub fn interleave(left: &[f32], right: &[f32]) -> Vec<f32> {
+    let frames = left.len().max(right.len());
+    (0..frames)
+        .flat_map(|i| [left.get(i).copied().unwrap_or(0.0), right.get(i).copied().unwrap_or(0.0)])
+        .collect()
+}
+
 // ---------- WAV file output ------------------------------------------------
+// Default rate for written files: small, and plenty for a tone under 3 kHz
+pub const FILE_SAMPLE_RATE: u32 = 8000;
+
+/// Sample format of written WAV files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum WavFormat {
+    /// 16-bit PCM with TPDF dither
+    #[default]
+    #[value(name = "16")]
+    Int16,
+    /// 24-bit PCM
+    #[value(name = "24")]
+    Int24,
+    /// 32-bit float
+    #[value(name = "float")]
+    Float32,
+}
+
+/// How rendered audio is written to a file.
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub format: WavFormat,
+    pub normalize: Option<Normalize>,
+    /// 2 writes the mono render to both channels, for players that refuse
+    /// mono files
+    pub channels: u16,
+    pub tags: Tags,
+    /// For .opus files
+    pub opus_bitrate_kbps: u32,
+}
+
+impl Default for Export {
+    fn default() -> Self {
+        Export {
+            format: WavFormat::default(),
+            normalize: None,
+            channels: 1,
+            tags: Tags::default(),
+            opus_bitrate_kbps: opus::DEFAULT_BITRATE_KBPS,
+        }
+    }
+}
+
+impl Export {
+    /// Whether `filename` can be written this way.
+    pub fn check(&self, filename: &str) -> Result<()> {
+        if opus::is_opus(filename) && self.channels != 1 {
+            anyhow::bail!("{}: Opus files are mono; leave out --channels or write a WAV file", filename);
+        }
+        Ok(())
+    }
+
+    pub fn write(&self, filename: &str, sample_rate: u32, mut samples: Vec<f32>) -> Result<()> {
+        self.check(filename)?;
+        if let Some(normalize) = self.normalize {
+            normalize.apply(&mut samples, sample_rate);
+        }
+        if opus::is_opus(filename) {
+            return opus::write(filename, sample_rate, &samples, self.opus_bitrate_kbps, &self.tags);
+        }
+        write_wav_as(filename, sample_rate, &samples, self.format, self.channels)?;
+        if !self.tags.is_empty() {
+            self.tags.write_to_wav(filename)?;
+        }
+        Ok(())
+    }
+
+    /// Write `samples` as they are made, without holding them all. Normalizing
+    /// measures the whole render and Opus encodes it in one go, so for those
+    /// they are gathered first. A stereo source is written as stereo,
+    /// whatever `channels` says.
+    pub fn write_stream(&self, filename: &str, sample_rate: u32, samples: impl Source<Item = f32>) -> Result<()> {
+        self.check(filename)?;
+        match (samples.channels(), self.normalize) {
+            (1, normalize) if normalize.is_some() || opus::is_opus(filename) => self.write(filename, sample_rate, samples.collect()),
+            (1, _) => self.stream(filename, sample_rate, samples, 1),
+            _ if opus::is_opus(filename) => anyhow::bail!("{}: Opus files are mono; write stereo to a WAV file", filename),
+            (channels, Some(normalize)) => {
+                let mut frames: Vec<f32> = samples.collect();
+                normalize.apply_stereo(&mut frames, sample_rate);
+                self.stream(filename, sample_rate, frames.into_iter(), channels)
+            }
+            (channels, None) => self.stream(filename, sample_rate, samples, channels),
+        }
+    }
+
+    // Interleaved `samples` of `channels` channels through the limiter to a
+    // WAV file, mono repeated on each of the file's channels
+    fn stream(&self, filename: &str, sample_rate: u32, samples: impl Iterator<Item = f32>, channels: u16) -> Result<()> {
+        let (file_channels, repeat) = match channels {
+            1 => (self.channels, self.channels),
+            _ => (channels, 1),
+        };
+        let mut writer = WavFile::create(filename, sample_rate, self.format, file_channels)?;
+        let (samples, limited) = Limiting::new(samples, sample_rate, channels);
+        let mut rng = rand::rng();
+        let mut total = 0;
+        for sample in samples {
+            writer.write(sample, repeat, &mut rng)?;
+            total += 1;
+        }
+        writer.finish()?;
+        let limited = *limited.lock().unwrap();
+        if limited.clipped > 0 {
+            eprintln!("Warning: {}: {}", filename, report(&limited, total));
+        }
+        if !self.tags.is_empty() {
+            self.tags.write_to_wav(filename)?;
+        }
+        Ok(())
+    }
+
+    /// Separate left and right channels, whatever `channels` says. Nothing is
+    /// normalized, since the two usually carry signals at set levels.
+    pub fn write_stereo(&self, filename: &str, sample_rate: u32, left: &[f32], right: &[f32]) -> Result<()> {
+        if opus::is_opus(filename) {
+            anyhow::bail!("{}: Opus files are mono; write the two channels to a WAV file", filename);
+        }
+        write_tracks(filename, sample_rate, &[left, right], self.format, 2)?;
+        if !self.tags.is_empty() {
+            self.tags.write_to_wav(filename)?;
+        }
+        Ok(())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn save_audio_to_wav(
     text: &str,
     timing: Timing,
     tone: u32,
-    qrm: u8,
+    conditions: Conditions,
     tone_shape: ToneShape,
     drift_percentage: Option<u8>,
+    preamble: Option<Preamble>,
     filename: &str,
+    sample_rate: u32,
+    export: Export,
 ) -> Result<()> {
-    // Use 8000 Hz for smaller WAV files - adequate for morse code
-    let morse_audio = MorseAudio::new_with_sample_rate(8000, text, timing, tone, qrm, tone_shape, drift_percentage);
-    let samples = morse_audio.get_samples();
-    
-    let spec = WavSpec {
-        channels: 1,
-        sample_rate: morse_audio.sample_rate,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
+    let synth = MorseSynth::new(sample_rate, text, timing, tone, conditions, tone_shape, drift_percentage)
+        .preamble(preamble, &timing)
+        .padded()
+        .binaural(timing.binaural);
+    export.write_stream(filename, sample_rate, synth)
+}
+
+// 16-bit mono PCM
+pub fn write_wav(filename: &str, sample_rate: u32, samples: &[f32]) -> Result<()> {
+    write_wav_as(filename, sample_rate, samples, WavFormat::Int16, 1)
+}
+
+// Mono `samples`, repeated on every channel of each frame
+fn write_wav_as(filename: &str, sample_rate: u32, samples: &[f32], format: WavFormat, channels: u16) -> Result<()> {
+    write_tracks(filename, sample_rate, &[samples], format, channels)
+}
+
+// One track repeated on every channel, or one track per channel
+fn write_tracks(filename: &str, sample_rate: u32, tracks: &[&[f32]], format: WavFormat, channels: u16) -> Result<()> {
+    let mut writer = WavFile::create(filename, sample_rate, format, channels)?;
+
+    // Anything past full scale goes through the limiter instead of clipping
+    let tracks: Vec<Vec<f32>> = tracks
+        .iter()
+        .map(|samples| {
+            let mut samples = samples.to_vec();
+            if samples.iter().any(|s| s.abs() > 1.0) {
+                if let Some(result) = limit(&mut samples, sample_rate) {
+                    eprintln!("Warning: {}: {}", filename, report(&result, samples.len()));
+                }
+            }
+            samples
+        })
+        .collect();
+    let (repeat, length) = match tracks.len() {
+        1 => (channels, tracks[0].len()),
+        _ => (1, tracks.iter().map(Vec::len).max().unwrap_or(0)),
     };
     
-    let mut writer = WavWriter::create(filename, spec)?;
-    
-    for &sample in samples {
-        // Convert f32 sample in range [-1.0, 1.0] to i16
-        let scaled = (sample * i16::MAX as f32).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-        writer.write_sample(scaled)?;
+    let mut rng = rand::rng();
+    for i in 0..length {
+        for track in &tracks {
+            writer.write(track.get(i).copied().unwrap_or(0.0), repeat, &mut rng)?;
+        }
     }
     
-    writer.finalize()?;
-    Ok(())
+    writer.finish()
 }
 
+// Triangular (TPDF) dither of ±1 LSB before rounding: quantization error
+// becomes a steady, signal-independent hiss instead of distortion that
+// follows the signal, which is what makes quiet noise beds sound gritty
+pub(crate) fn dither_to_i16(sample: f32, rng: &mut impl Rng) -> i16 {
+    let dither = rng.random::<f32>() - rng.random::<f32>();
+    (sample * i16::MAX as f32 + dither).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dither_linearizes_quiet_signal() {
+        // A level of a quarter LSB rounds to zero without dither; with it,
+        // the average of the output still lands on a quarter LSB
+        let mut rng = rand::rng();
+        let quarter = 0.25 / i16::MAX as f32;
+        let sum: i64 = (0..100_000).map(|_| dither_to_i16(quarter, &mut rng) as i64).sum();
+        assert!((sum as f64 / 100_000.0 - 0.25).abs() < 0.02);
+        assert_eq!(dither_to_i16(1.5, &mut rng), i16::MAX);
+    }
+
+    #[test]
+    fn test_interferer_drift() {
+        let rate = 8000;
+        let mut steady = Interferer::new(700, 5, 0, rate);
+        let start = steady.tone.current_frequency;
+        for _ in 0..rate * 10 {
+            steady.next();
+        }
+        assert_eq!(steady.tone.current_frequency, start);
+
+        // A minute of the fastest drift moves it, never too fast nor too far
+        let mut drifting = Interferer::new(700, 5, 9, rate);
+        let mut last = drifting.tone.current_frequency;
+        let (mut lowest, mut highest) = (last, last);
+        for _ in 0..rate * 60 {
+            drifting.next();
+            let frequency = drifting.tone.current_frequency;
+            assert!((frequency - last).abs() <= 9.0 * DRIFT_HZ_PER_SEC * RETUNE_SECS + 1e-9);
+            assert!((frequency - 700.0).abs() <= MAX_OFFSET_HZ);
+            (lowest, highest, last) = (lowest.min(frequency), highest.max(frequency), frequency);
+        }
+        assert!(highest - lowest > 20.0, "{}-{}", lowest, highest);
+    }
+
+    // Two PARIS at 60 ms a unit, ending on a character space rather than a
+    // word space
+    const PARIS_TWICE: usize = 8000 * (100 - 4) * 60 / 1000;
+
+    #[test]
+    fn test_synth_streams() {
+        // Known length before a sample is made, and just as long once made
+        let synth = MorseSynth::new(8000, "PARIS PARIS", Timing::new(20, 0), 700, Conditions::default(), ToneShape::Sine, None);
+        let (length, duration) = (synth.size_hint().0, synth.total_duration().unwrap());
+        assert_eq!(length, PARIS_TWICE);
+        assert_eq!(duration, Duration::from_millis((100 - 4) * 60));
+        let samples: Vec<f32> = synth.collect();
+        assert_eq!(samples.len(), length);
+        assert!(samples.iter().any(|s| s.abs() > 0.2));
+    }
+
+    #[test]
+    fn test_binaural_synth() {
+        // Twice the samples over the same time, the left ear's tone 10 Hz
+        // under the right's through a second of dah
+        let synth = MorseSynth::new(8000, "TT", Timing::new(1, 0), 700, Conditions::default(), ToneShape::Sine, None).binaural(Some(10));
+        assert_eq!((synth.channels(), synth.size_hint().0), (2, 2 * synth.total_duration().unwrap().as_millis() as usize * 8));
+        let frames: Vec<f32> = synth.collect();
+        let crossings = |channel: usize| {
+            let ear: Vec<f32> = frames.iter().skip(channel).step_by(2).copied().collect();
+            ear[1000..9000].windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count() as i64
+        };
+        assert!(((crossings(1) - crossings(0)) - 20).abs() <= 2, "{} {}", crossings(0), crossings(1));
+    }
+
+    #[test]
+    fn test_padded_synth() {
+        // A lead-in of noise half to all of the pad, the whole pad after,
+        // and nothing for a clean band
+        let (timing, length) = (Timing::new(20, 0), PARIS_TWICE);
+        let noisy = Conditions { qrm: 3, noise_pad_ms: 500, ..Conditions::default() };
+        let synth = MorseSynth::new(8000, "PARIS PARIS", timing, 700, noisy, ToneShape::Sine, None).padded();
+        assert!((250..=500).contains(&synth.lead().as_millis()), "{:?}", synth.lead());
+        assert_eq!(synth.size_hint().0, length + (synth.lead().as_secs_f64() * 8000.0).round() as usize + 4000);
+        let clean = Conditions { noise_pad_ms: 500, ..Conditions::default() };
+        assert_eq!(MorseSynth::new(8000, "PARIS PARIS", timing, 700, clean, ToneShape::Sine, None).padded().size_hint().0, length);
+    }
+
+    #[test]
+    fn test_speed_wobble_stretches_word_space() {
+        let timing = Timing::new(20, 0);
+        let wobbly = Conditions { speed_variance: 50, ..Conditions::default() };
+        let segments = plan("E E", &timing, wobbly, 8000);
+        let (Segment::Mark { len: dot, .. }, Segment::Space(word)) = (segments[0], segments[3]) else { panic!("{:?}", segments) };
+        assert!((word as f64 / 1920.0 - dot as f64 / 480.0).abs() < 0.01, "{} {}", dot, word);
+    }
+
+    #[test]
+    fn test_export_streams() {
+        // Written as it comes, limiter and all, the file matches one written
+        // from the whole render
+        let samples: Vec<f32> = (0..8000).map(|i| 1.5 * (i as f32 * 0.3).sin()).collect();
+        let export = Export { format: WavFormat::Float32, ..Export::default() };
+        let path = |name: &str| std::env::temp_dir().join(format!("cwgen-export-{}-{}.wav", name, std::process::id()));
+        let (whole, streamed) = (path("whole"), path("streamed"));
+        export.write(whole.to_str().unwrap(), 8000, samples.clone()).unwrap();
+        export.write_stream(streamed.to_str().unwrap(), 8000, SamplesBuffer::new(1, 8000, samples)).unwrap();
+        let (whole_bytes, streamed_bytes) = (std::fs::read(&whole).unwrap(), std::fs::read(&streamed).unwrap());
+        let _ = (std::fs::remove_file(&whole), std::fs::remove_file(&streamed));
+        assert_eq!(whole_bytes.len(), streamed_bytes.len());
+        let floats = |bytes: &[u8]| bytes.chunks(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect::<Vec<_>>();
+        let (a, b) = (floats(&whole_bytes[80..]), floats(&streamed_bytes[80..]));
+        assert!(a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-4));
+        assert!(b.iter().all(|s| s.abs() <= 1.0));
+    }
+}
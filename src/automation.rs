@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+
+use crate::morse::MorseError;
+
+// ---------- Automation files ------------------------------------------------
+// `--automation FILE` changes speed, pitch and noise over the course of a
+// render, the way a mixer automates a fader: the file lists points in time
+// (or at word counts) with the values there, and in between each value moves
+// in a straight line, so a lesson can start at 15 WPM on a quiet band and end
+// at 25 WPM with the signal 3 dB over the noise half an hour later:
+//
+//     # at    settings
+//     0:00    wpm=15 snr=20 tone=600
+//     20:00   wpm=22
+//     30:00   wpm=25 snr=3 tone=650
+//
+// Noise is `snr` in dB, or `qrm` in the 0-9 steps of --qrm; a file uses one
+// or the other.
+//
+// Points are `M:SS` or `H:MM:SS` (or plain seconds), or `word N` to count
+// words of text instead; one file uses one or the other. A setting holds its
+// first value before its first point and its last after its last, and one
+// that no point mentions is left to the command line. The text is rendered
+// STEP_WORDS words at a time, each with the values where it starts.
+const STEP_WORDS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Position {
+    Seconds,
+    Words,
+}
+
+#[derive(Debug, Default)]
+struct Lane {
+    points: Vec<(f64, f64)>,
+}
+
+impl Lane {
+    fn value(&self, at: f64) -> Option<f64> {
+        let after = self.points.iter().position(|&(x, _)| x > at);
+        match after {
+            _ if self.points.is_empty() => None,
+            Some(0) => Some(self.points[0].1),
+            None => self.points.last().map(|&(_, y)| y),
+            Some(i) => {
+                let ((x0, y0), (x1, y1)) = (self.points[i - 1], self.points[i]);
+                Some(y0 + (y1 - y0) * (at - x0) / (x1 - x0))
+            }
+        }
+    }
+}
+
+pub struct Automation {
+    position: Position,
+    wpm: Lane,
+    tone: Lane,
+    qrm: Lane,
+    snr: Lane,
+}
+
+/// The automated values at one point of a render; `None` where the file
+/// doesn't set them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Settings {
+    pub wpm: Option<u32>,
+    pub tone: Option<u32>,
+    pub qrm: Option<u8>,
+    pub snr: Option<f32>,
+}
+
+impl Automation {
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading automation {}", path))?;
+        Ok(Self::parse(&text).map_err(|e| MorseError::InvalidAutomation(format!("{}: {}", path, e)))?)
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut automation: Option<Automation> = None;
+        let mut last = f64::MIN;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let Some(first) = fields.next() else { continue };
+            let error = |message: String| format!("line {}: {}", number + 1, message);
+            let (position, at) = match first {
+                "word" => {
+                    let count = fields.next().ok_or_else(|| error("`word` needs a word count".into()))?;
+                    let count = count.parse::<u32>().map_err(|_| error(format!("{} isn't a word count", count)))?;
+                    (Position::Words, count as f64)
+                }
+                time => (Position::Seconds, parse_time(time).ok_or_else(|| error(format!("{} isn't a time", time)))?),
+            };
+            let automation = automation.get_or_insert_with(|| Automation {
+                position,
+                wpm: Lane::default(),
+                tone: Lane::default(),
+                qrm: Lane::default(),
+                snr: Lane::default(),
+            });
+            if position != automation.position {
+                return Err(error("points are all times or all word counts, not both".into()));
+            }
+            if at <= last {
+                return Err(error("points must go forward".into()));
+            }
+            last = at;
+            for field in fields {
+                let (name, value) = field.split_once('=').ok_or_else(|| error(format!("{} isn't NAME=VALUE", field)))?;
+                let value: f64 = value.parse().map_err(|_| error(format!("{} isn't a number", value)))?;
+                let (lane, range) = match name {
+                    "wpm" => (&mut automation.wpm, 1.0..=100.0),
+                    "tone" => (&mut automation.tone, 100.0..=3000.0),
+                    "qrm" => (&mut automation.qrm, 0.0..=9.0),
+                    "snr" => (&mut automation.snr, -20.0..=40.0),
+                    _ => return Err(error(format!("unknown setting {} (wpm, tone, snr or qrm)", name))),
+                };
+                if !range.contains(&value) {
+                    return Err(error(format!("{} = {} (must be {}-{})", name, value, range.start(), range.end())));
+                }
+                lane.points.push((at, value));
+            }
+        }
+        let automation = automation.ok_or("no points")?;
+        if !automation.qrm.points.is_empty() && !automation.snr.points.is_empty() {
+            return Err("set the noise with snr or qrm, not both".into());
+        }
+        Ok(automation)
+    }
+
+    /// The settings `seconds` into the render, `words` words into the text.
+    pub fn at(&self, seconds: f64, words: usize) -> Settings {
+        let at = match self.position {
+            Position::Seconds => seconds,
+            Position::Words => words as f64,
+        };
+        Settings {
+            wpm: self.wpm.value(at).map(|wpm| wpm.round() as u32),
+            tone: self.tone.value(at).map(|tone| tone.round() as u32),
+            qrm: self.qrm.value(at).map(|qrm| qrm.round() as u8),
+            snr: self.snr.value(at).map(|snr| snr as f32),
+        }
+    }
+
+    /// Render `text` a step at a time with `render`, which is given each
+    /// step's words, the time it starts at and the settings there.
+    pub fn render(&self, text: &str, sample_rate: u32, mut render: impl FnMut(&str, f64, Settings) -> Vec<f32>) -> Vec<f32> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let steps = words.len().div_ceil(STEP_WORDS);
+        let mut samples = Vec::new();
+        for (i, step) in words.chunks(STEP_WORDS).enumerate() {
+            let start = samples.len() as f64 / sample_rate as f64;
+            let step = step.join(" ") + if i + 1 < steps { " " } else { "" };
+            samples.extend(render(&step, start, self.at(start, i * STEP_WORDS)));
+        }
+        samples
+    }
+}
+
+/// [H:]M:SS, or seconds.
+pub fn parse_time(time: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in time.split(':') {
+        let part: f64 = part.parse().ok().filter(|&part: &f64| part >= 0.0)?;
+        seconds = seconds * 60.0 + part;
+    }
+    Some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_automation() {
+        let automation = Automation::parse("# faster and noisier\n0:00 wpm=15 qrm=1\n10:00 wpm=25\n30:00 qrm=7\n").unwrap();
+        assert_eq!(automation.at(0.0, 0), Settings { wpm: Some(15), qrm: Some(1), ..Settings::default() });
+        assert_eq!(automation.at(300.0, 0).wpm, Some(20));
+        assert_eq!(automation.at(900.0, 0), Settings { wpm: Some(25), qrm: Some(4), ..Settings::default() });
+        assert_eq!(automation.at(3600.0, 0).qrm, Some(7));
+    }
+
+    #[test]
+    fn test_automation_by_word() {
+        let words = Automation::parse("word 0 tone=500\nword 100 tone=700").unwrap();
+        assert_eq!(words.at(1000.0, 50).tone, Some(600));
+    }
+
+    #[test]
+    fn test_render_steps() {
+        // Each step is given the settings where it starts
+        let words = Automation::parse("word 0 tone=500\nword 100 tone=700").unwrap();
+        let mut seen = Vec::new();
+        let samples = words.render(&"CQ ".repeat(12), 10, |step, start, settings| {
+            seen.push((step.to_string(), start, settings.tone));
+            vec![0.0; 20]
+        });
+        assert_eq!(samples.len(), 60);
+        assert_eq!(seen[1], ("CQ CQ CQ CQ CQ ".into(), 2.0, Some(510)));
+        assert_eq!(seen[2].0, "CQ CQ");
+    }
+
+    #[test]
+    fn test_automation_snr() {
+        let snr = Automation::parse("0:00 snr=20\n1:00 snr=0").unwrap();
+        assert_eq!(snr.at(45.0, 0).snr, Some(5.0));
+    }
+
+    #[test]
+    fn test_invalid_automation() {
+        assert!(Automation::parse("1:00 wpm=20\n0:30 wpm=10").is_err());
+        assert!(Automation::parse("0:00 wpm=20\nword 10 wpm=10").is_err());
+        assert!(Automation::parse("0:00 snr=3 qrm=2").is_err());
+        assert!(Automation::parse("0:00 snr=60").is_err());
+        assert!(Automation::parse("0:00 qrm=12").is_err());
+        assert!(Automation::parse("# nothing").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::Result;
+use chrono::Local;
+use std::time::Duration;
+
+use crate::morse::{key_timeline, Timing};
+
+// ---------- Station identification ------------------------------------------
+// Amateur licences require the station's callsign at the end of each
+// transmission and at set intervals during a long one. Rather than rely on
+// the message containing it, the ID is added to whatever is sent: at the end
+// unless the text already ends with the call, and between words wherever
+// the next word would run past the interval since the last ID. The timer
+// restarts with each transmission, since each one ends with an ID.
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum IdRule {
+    /// United States (FCC §97.119): every 10 minutes
+    Fcc,
+    /// United Kingdom (Ofcom): every 15 minutes
+    Ofcom,
+    /// Canada (ISED RBR-4): every 30 minutes
+    Ised,
+}
+
+impl IdRule {
+    pub fn interval(self) -> Duration {
+        let minutes = match self {
+            IdRule::Fcc => 10,
+            IdRule::Ofcom => 15,
+            IdRule::Ised => 30,
+        };
+        Duration::from_secs(minutes * 60)
+    }
+}
+
+pub struct StationId {
+    pub call: String,
+    pub interval: Duration,
+}
+
+impl StationId {
+    pub fn new(call: &str, rule: IdRule, interval_minutes: Option<u64>) -> Self {
+        let interval = interval_minutes.map_or(rule.interval(), |minutes| Duration::from_secs(minutes * 60));
+        StationId { call: call.to_uppercase(), interval }
+    }
+
+    /// `text` with the ID added wherever it is due.
+    pub fn apply(&self, text: &str, timing: &Timing) -> String {
+        let ident = format!("DE {}", self.call);
+        let id_length = length(&ident, timing);
+        let mut words: Vec<&str> = Vec::new();
+        let mut since_id = Duration::ZERO;
+        for word in text.split_whitespace() {
+            let word_length = length(word, timing);
+            if !words.is_empty() && since_id + word_length + id_length > self.interval {
+                words.push(&ident);
+                since_id = Duration::ZERO;
+            }
+            words.push(word);
+            since_id += word_length;
+        }
+        if !words.last().is_some_and(|last| last.eq_ignore_ascii_case(&self.call)) {
+            words.push(&ident);
+        }
+        words.join(" ")
+    }
+}
+
+// Keyed time of a word and the gap after it
+fn length(word: &str, timing: &Timing) -> Duration {
+    key_timeline(&format!("{} ", word), timing).iter().map(|e| e.duration).sum()
+}
+
+// ---------- Beacon ----------------------------------------------------------
+/// Send `message`, identified, `count` times (or until stopped), `every`
+/// apart.
+pub fn run(
+    message: &str,
+    id: &StationId,
+    timing: &Timing,
+    every: Duration,
+    count: Option<u32>,
+    mut send: impl FnMut(&str) -> Result<()>,
+) -> Result<()> {
+    let text = id.apply(message, timing);
+    let mut sent = 0;
+    loop {
+        println!("{} {}", Local::now().format("%H:%M:%S"), text);
+        send(&text)?;
+        sent += 1;
+        if count.is_some_and(|count| sent >= count) {
+            return Ok(());
+        }
+        std::thread::sleep(every);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_station_id() {
+        let timing = Timing::new(20, 0);
+        let id = StationId::new("w1aw", IdRule::Fcc, None);
+        assert_eq!(id.apply("VVV TEST", &timing), "VVV TEST DE W1AW");
+        assert_eq!(id.apply("VVV DE W1AW", &timing), "VVV DE W1AW");
+    }
+
+    #[test]
+    fn test_station_id_interval() {
+        // About 3 s a word at 20 WPM, so a one-minute interval fits a few
+        // words before each ID
+        let timing = Timing::new(20, 0);
+        let id = StationId::new("W1AW", IdRule::Fcc, Some(1));
+        let text = id.apply(&"PARIS ".repeat(60), &timing);
+        let sections: Vec<&str> = text.split("DE W1AW").collect();
+        assert!(sections.len() > 2);
+        assert!(sections.iter().all(|section| length(section.trim(), &timing) <= Duration::from_secs(60)));
+        assert!(text.ends_with("DE W1AW"));
+    }
+}
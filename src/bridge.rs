@@ -0,0 +1,85 @@
+use anyhow::{bail, Context, Result};
+use std::process::Command;
+
+// ---------- Voice chat bridge -----------------------------------------------
+// Club nets already meet on Mumble (or Discord, Jitsi, ...), so rather than
+// speak their protocols cwgen plays into a virtual audio device that the
+// voice chat client takes as its microphone. With PulseAudio or PipeWire
+// that is a null sink, created for the session (and removed after it) if it
+// doesn't already exist; its monitor is the microphone. Everything played,
+// in any mode, goes there instead of the speakers.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct BridgeArgs {
+    /// Play into a virtual audio device NAME (created if needed) for voice
+    /// chat such as Mumble to use as its microphone
+    #[arg(long, value_name = "NAME")]
+    pub voice_chat: Option<String>,
+}
+
+/// The virtual device playback is routed to while this is held.
+pub struct VirtualDevice {
+    // Set when the sink was created here, to be unloaded again
+    module: Option<String>,
+}
+
+impl VirtualDevice {
+    pub fn open(args: &BridgeArgs) -> Result<Option<Self>> {
+        let Some(name) = &args.voice_chat else { return Ok(None) };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) {
+            bail!("--voice-chat {:?}: use letters, digits, '-', '_' and '.' only", name);
+        }
+        let sinks = pactl(&["list", "short", "sinks"])?;
+        let module = if has_sink(&sinks, name) {
+            None
+        } else {
+            let sink_name = format!("sink_name={}", name);
+            let description = format!("sink_properties=device.description={}", name);
+            Some(pactl(&["load-module", "module-null-sink", &sink_name, &description])?.trim().to_string())
+        };
+        // The ALSA pulse plugin (and so the default device) plays wherever
+        // this points
+        std::env::set_var("PULSE_SINK", name);
+        println!("Playing into \"{}\": choose \"Monitor of {}\" as the microphone in your voice chat", name, name);
+        Ok(Some(VirtualDevice { module }))
+    }
+}
+
+impl Drop for VirtualDevice {
+    fn drop(&mut self) {
+        if let Some(module) = &self.module {
+            if let Err(e) = pactl(&["unload-module", module]) {
+                eprintln!("Warning: couldn't remove the virtual device: {}", e);
+            }
+        }
+    }
+}
+
+fn pactl(args: &[&str]) -> Result<String> {
+    let output = Command::new("pactl")
+        .args(args)
+        .output()
+        .context("running pactl: --voice-chat needs PulseAudio or PipeWire (elsewhere, make a virtual cable such as VB-CABLE or BlackHole the default output)")?;
+    if !output.status.success() {
+        bail!("pactl {}: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// `pactl list short sinks`: index, name, driver, format, state
+fn has_sink(list: &str, name: &str) -> bool {
+    list.lines().any(|line| line.split('\t').nth(1) == Some(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_sink() {
+        let list = "47\talsa_output.pci-0000_00_1f.3.analog-stereo\tPipeWire\ts32le 2ch 48000Hz\tSUSPENDED\n\
+                    112\tcwgen-net\tPipeWire\tfloat32le 2ch 48000Hz\tIDLE\n";
+        assert!(has_sink(list, "cwgen-net"));
+        assert!(!has_sink(list, "cwgen"));
+        assert!(!has_sink("", "cwgen-net"));
+    }
+}
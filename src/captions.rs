@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::morse::{char_starts, Timing};
+use crate::replay::{self, History};
+
+// ---------- Live captions ---------------------------------------------------
+// `--captions FILE` keeps FILE holding what has just been sent, rewritten as
+// each character is heard, for anyone streaming CW practice or a club net:
+// OBS's Text source, with "Read from file" ticked, shows it as an overlay in
+// step with the audio. The file holds the last LINE_CHARS or so, whole words
+// dropping off the front, and empties after CLEAR_AFTER without anything
+// sent, so an old line doesn't hang about over a quiet stream. Each update
+// goes to a temporary file that is renamed over it, so OBS never reads half
+// a line.
+const LINE_CHARS: usize = 40;
+const CLEAR_AFTER: Duration = Duration::from_secs(8);
+
+struct Captions {
+    path: PathBuf,
+    line: String,
+    last: Instant,
+}
+
+impl Captions {
+    fn write(&self) {
+        let temporary = self.path.with_extension("tmp");
+        let written = std::fs::write(&temporary, &self.line).and_then(|()| std::fs::rename(&temporary, &self.path));
+        if let Err(e) = written {
+            eprintln!("Warning: --captions: {}", e);
+        }
+    }
+}
+
+static CAPTIONS: OnceLock<Mutex<Captions>> = OnceLock::new();
+
+/// Caption into `path`, starting it empty.
+pub fn open(path: &str) -> Result<()> {
+    let captions = Captions { path: PathBuf::from(path), line: String::new(), last: Instant::now() };
+    std::fs::write(path, "").with_context(|| format!("writing {}", path))?;
+    if CAPTIONS.set(Mutex::new(captions)).is_ok() {
+        std::thread::spawn(|| loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let mut captions = CAPTIONS.get().unwrap().lock().unwrap();
+            if !captions.line.is_empty() && captions.last.elapsed() >= CLEAR_AFTER {
+                captions.line.clear();
+                captions.write();
+            }
+        });
+    }
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    CAPTIONS.get().is_some()
+}
+
+/// `ch` has just been heard.
+pub fn heard(ch: char) {
+    let Some(captions) = CAPTIONS.get() else { return };
+    let mut captions = captions.lock().unwrap();
+    captions.last = Instant::now();
+    append(&mut captions.line, ch);
+    captions.write();
+}
+
+/// Caption each character of `text` as it is heard, playback having begun
+/// at `started` with replays from `history`.
+pub fn as_heard(text: &str, timing: &Timing, started: Instant, history: Option<&History>) {
+    let clock = replay::Clock::start(history);
+    for (ch, offset) in char_starts(text, timing) {
+        if !clock.sleep_until(started + offset) {
+            return;
+        }
+        heard(ch);
+    }
+}
+
+// Add `ch` to the line, one space between words, and drop words off the
+// front once it is too long
+fn append(line: &mut String, ch: char) {
+    if ch.is_whitespace() {
+        if !line.is_empty() && !line.ends_with(' ') {
+            line.push(' ');
+        }
+        return;
+    }
+    line.push(ch.to_ascii_uppercase());
+    while line.chars().count() > LINE_CHARS {
+        match line.find(' ') {
+            Some(space) => line.replace_range(..=space, ""),
+            // One word longer than the line: keep its end
+            None => {
+                line.remove(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caption_line() {
+        let mut line = String::new();
+        for ch in " cq  cq\nde w1aw".chars() {
+            append(&mut line, ch);
+        }
+        assert_eq!(line, "CQ CQ DE W1AW");
+        for ch in " PSE QRS ES QSL VIA BURO TNX FER QSO 73".chars() {
+            append(&mut line, ch);
+        }
+        assert_eq!(line, "PSE QRS ES QSL VIA BURO TNX FER QSO 73");
+        for ch in " TU".chars() {
+            append(&mut line, ch);
+        }
+        assert_eq!(line, "QRS ES QSL VIA BURO TNX FER QSO 73 TU");
+    }
+}
@@ -0,0 +1,207 @@
+use crossterm::cursor::{MoveDown, MoveToColumn, MoveUp};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::queue;
+use std::io::Write;
+
+use crate::history::History;
+
+// ---------- Compose mode ----------------------------------------------------
+// With `--compose`, interactive mode edits a whole line before it is keyed:
+// the usual cursor keys and Backspace/Delete, Home/End (or Ctrl-A/Ctrl-E),
+// Ctrl-U to clear, and ↑/↓ to bring back a line from the history to edit.
+// Enter sends the line. The editor stays on the bottom row of the terminal
+// while the echo of what is being keyed (and a partner's) runs on the row
+// above it, so the next line can be written while the last one is sent.
+#[derive(Default)]
+pub struct LineEditor {
+    text: Vec<char>,
+    cursor: usize,
+}
+
+pub enum Action {
+    Send(String),
+    Edited,
+    Ignored,
+}
+
+impl LineEditor {
+    pub fn key(&mut self, key: KeyEvent, history: &mut History) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Enter => {
+                self.cursor = 0;
+                return Action::Send(self.text.drain(..).collect());
+            }
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.text.len(),
+            KeyCode::Char('u') if ctrl => self.set(""),
+            KeyCode::Char(_) if ctrl => return Action::Ignored,
+            KeyCode::Char(c) => {
+                self.text.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.text.len() => {
+                self.text.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.text.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Up => {
+                if let Some(line) = history.older() {
+                    self.set(line);
+                }
+            }
+            KeyCode::Down => {
+                self.set(history.newer().unwrap_or_default());
+            }
+            _ => return Action::Ignored,
+        }
+        Action::Edited
+    }
+
+    fn set(&mut self, text: &str) {
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+    }
+
+    // The part of the line that fits in `width` columns, and where the
+    // cursor is within it
+    fn view(&self, width: usize) -> (String, usize) {
+        let start = self.cursor.saturating_sub(width.saturating_sub(1));
+        let shown = self.text[start..].iter().take(width).collect();
+        (shown, self.cursor - start)
+    }
+}
+
+// ---------- Screen ----------------------------------------------------------
+// Everything interactive mode prints goes through here. Without the editor
+// it's written as it comes; with it, the echo goes on the row above the
+// editor and messages on rows of their own, with the editor redrawn after.
+pub struct Screen {
+    editor: Option<LineEditor>,
+    // Columns used on the echo row
+    column: usize,
+}
+
+const PROMPT: &str = "> ";
+
+impl Screen {
+    pub fn new(compose: bool) -> Self {
+        let screen = Screen { editor: compose.then(LineEditor::default), column: 0 };
+        screen.draw();
+        screen
+    }
+
+    pub fn editor(&mut self) -> Option<&mut LineEditor> {
+        self.editor.as_mut()
+    }
+
+    /// Show what was just heard, `columns` wide.
+    pub fn echo(&mut self, text: &str, columns: usize) {
+        if self.editor.is_none() {
+            print!("{}", text);
+            flush();
+            return;
+        }
+        if self.column + columns >= width() {
+            self.newline();
+        }
+        let mut out = std::io::stdout();
+        let _ = queue!(out, MoveUp(1), MoveToColumn(self.column as u16));
+        print!("{}", text);
+        let _ = queue!(out, MoveDown(1));
+        self.column += columns;
+        self.draw();
+    }
+
+    /// End the echo row.
+    pub fn newline(&mut self) {
+        if self.editor.is_some() {
+            let _ = queue!(std::io::stdout(), MoveToColumn(0), Clear(ClearType::CurrentLine));
+            self.column = 0;
+        }
+        print!("\r\n");
+        self.draw();
+    }
+
+    /// Print `text` on a line of its own.
+    pub fn message(&mut self, text: &str) {
+        if self.editor.is_none() {
+            print!("\r\n{}\r\n", text);
+            flush();
+            return;
+        }
+        if self.column > 0 {
+            self.newline();
+        }
+        let mut out = std::io::stdout();
+        let _ = queue!(out, MoveUp(1), MoveToColumn(0), Clear(ClearType::CurrentLine));
+        print!("{}", text);
+        let _ = queue!(out, MoveDown(1));
+        self.newline();
+    }
+
+    /// Redraw the editor, if there is one.
+    pub fn draw(&self) {
+        if let Some(editor) = &self.editor {
+            let (shown, at) = editor.view(width().saturating_sub(PROMPT.len() + 1));
+            let _ = queue!(std::io::stdout(), MoveToColumn(0), Clear(ClearType::CurrentLine));
+            print!("{}{}", PROMPT, shown);
+            let _ = queue!(std::io::stdout(), MoveToColumn((PROMPT.len() + at) as u16));
+        }
+        flush();
+    }
+}
+
+fn width() -> usize {
+    terminal::size().map_or(80, |(columns, _)| columns as usize)
+}
+
+fn flush() {
+    let _ = std::io::stdout().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(editor: &mut LineEditor, history: &mut History, code: KeyCode) -> Action {
+        editor.key(KeyEvent::from(code), history)
+    }
+
+    #[test]
+    fn test_line_editor() {
+        let mut history = History::open(None).unwrap();
+        let mut editor = LineEditor::default();
+        for c in "CQ W1AW".chars() {
+            press(&mut editor, &mut history, KeyCode::Char(c));
+        }
+        for _ in 0..4 {
+            press(&mut editor, &mut history, KeyCode::Left);
+        }
+        for c in "DE ".chars() {
+            press(&mut editor, &mut history, KeyCode::Char(c));
+        }
+        press(&mut editor, &mut history, KeyCode::End);
+        press(&mut editor, &mut history, KeyCode::Backspace);
+        assert_eq!(editor.text.iter().collect::<String>(), "CQ DE W1A");
+        assert_eq!(editor.view(4), ("W1A".to_string(), 3));
+    }
+
+    #[test]
+    fn test_history_recall() {
+        let mut history = History::open(None).unwrap();
+        history.push("CQ DE W1AW").unwrap();
+        let mut editor = LineEditor::default();
+        press(&mut editor, &mut history, KeyCode::Char('X'));
+        press(&mut editor, &mut history, KeyCode::Up);
+        assert!(matches!(press(&mut editor, &mut history, KeyCode::Enter), Action::Send(line) if line == "CQ DE W1AW"));
+        assert!(editor.text.is_empty());
+    }
+}
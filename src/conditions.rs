@@ -0,0 +1,286 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::morse::MorseError;
+
+// ---------- Band conditions ------------------------------------------------
+// Every impairment the audio engine knows how to simulate, bundled so it can
+// be threaded through playback, practice and file output as a single value.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Conditions {
+    /// Background QRM: S0 (no noise) … S9 (extreme)
+    pub qrm: u8,
+    /// Background noise as a signal-to-noise ratio in dB, in place of `qrm`
+    pub snr: Option<f32>,
+    /// Atmospheric static crashes (0-9)
+    pub qrn: u8,
+    /// Fading depth (0-9)
+    pub qsb: u8,
+    /// Random element-length jitter in percent (0-50)
+    pub jitter: u8,
+    /// Random per-word speed variation in percent (0-50)
+    pub speed_variance: u8,
+    /// Fading rate in Hz; randomized when unset
+    pub qsb_rate: Option<f32>,
+    /// Other stations keying inside the passband (0-9)
+    pub interferers: u8,
+    /// How busy the interferers are, from mostly idle (0) to wall-to-wall (9)
+    pub pileup: u8,
+    /// How fast the interferers wander in frequency, from fixed (0) to
+    /// sweeping across the passband in a minute or two (9)
+    pub interferer_drift: u8,
+    /// How noise and fading evolve over a session
+    pub trend: Trend,
+    /// Minutes the trend takes to run its course (or one wander cycle)
+    pub trend_minutes: f32,
+    /// Band noise before the first element and after the last, in ms
+    pub noise_pad_ms: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trend {
+    /// Conditions stay put
+    #[default]
+    Steady,
+    /// Band opens up: starts at full severity and eases off
+    Improving,
+    /// Band closes down: starts mild and worsens to full severity
+    Degrading,
+    /// Slowly swings between mild and full severity
+    Wandering,
+}
+
+impl Conditions {
+    /// Multiplier (0.3-1.0) applied to noise and fading `elapsed` seconds
+    /// into a session. The configured levels are the worst the band gets.
+    pub fn severity(&self, elapsed: f64) -> f64 {
+        const MILD: f64 = 0.3;
+        let period = self.trend_minutes.max(0.1) as f64 * 60.0;
+        let progress = (elapsed / period).clamp(0.0, 1.0);
+        // Smoothstep so changes start and settle gently
+        let eased = progress * progress * (3.0 - 2.0 * progress);
+        match self.trend {
+            Trend::Steady => 1.0,
+            Trend::Improving => 1.0 - (1.0 - MILD) * eased,
+            Trend::Degrading => MILD + (1.0 - MILD) * eased,
+            Trend::Wandering => {
+                let phase = std::f64::consts::TAU * elapsed / period;
+                MILD + (1.0 - MILD) * (0.5 + 0.5 * phase.cos())
+            }
+        }
+    }
+
+    /// Snapshot of these conditions with the trend applied at `elapsed` seconds.
+    pub fn at(&self, elapsed: f64) -> Conditions {
+        let severity = self.severity(elapsed);
+        let scale = |level: u8| (level as f64 * severity).round() as u8;
+        Conditions {
+            qrm: scale(self.qrm),
+            qrn: scale(self.qrn),
+            qsb: scale(self.qsb),
+            trend: Trend::Steady,
+            ..*self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ConditionsPreset {
+    /// Quiet band, machine-perfect sending
+    Easy,
+    /// An ordinary evening on 40 m
+    Typical,
+    /// Summer static, deep fades and a sloppy fist
+    Rough,
+    /// Contest weekend on a dead band
+    Brutal,
+}
+
+impl ConditionsPreset {
+    pub fn conditions(self) -> Conditions {
+        match self {
+            ConditionsPreset::Easy => Conditions {
+                qrm: 1, qrn: 0, qsb: 0, jitter: 0, speed_variance: 0,
+                ..Conditions::default()
+            },
+            ConditionsPreset::Typical => Conditions {
+                qrm: 3, qrn: 2, qsb: 3, jitter: 5, speed_variance: 5,
+                interferers: 1, pileup: 3, ..Conditions::default()
+            },
+            ConditionsPreset::Rough => Conditions {
+                qrm: 5, qrn: 5, qsb: 6, jitter: 12, speed_variance: 10,
+                interferers: 2, pileup: 5, interferer_drift: 2, ..Conditions::default()
+            },
+            ConditionsPreset::Brutal => Conditions {
+                qrm: 7, qrn: 8, qsb: 8, jitter: 20, speed_variance: 20,
+                interferers: 4, pileup: 8, interferer_drift: 5, ..Conditions::default()
+            },
+        }
+    }
+}
+
+// ---------- Scenario files -------------------------------------------------
+// A shareable TOML description of a band environment, e.g.
+//
+//     name = "160 m winter night"
+//     [noise]
+//     qrm = 2
+//     qrn = 4
+//     [fading]
+//     depth = 7
+//     rate_hz = 0.05
+//     [interferers]
+//     count = 1
+//     pileup = 2
+//     drift = 3
+//     [sending]
+//     jitter = 5
+//     [trend]
+//     direction = "degrading"
+//     minutes = 30
+pub const MAX_NOISE_PAD_MS: u32 = 10_000;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub noise: ScenarioNoise,
+    pub fading: ScenarioFading,
+    pub interferers: ScenarioInterferers,
+    pub sending: ScenarioSending,
+    pub trend: ScenarioTrend,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScenarioNoise {
+    pub qrm: u8,
+    pub qrn: u8,
+    pub pad_ms: u32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScenarioFading {
+    pub depth: u8,
+    pub rate_hz: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScenarioInterferers {
+    pub count: u8,
+    pub pileup: u8,
+    pub drift: u8,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScenarioSending {
+    pub jitter: u8,
+    pub speed_variance: u8,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScenarioTrend {
+    pub direction: Trend,
+    pub minutes: Option<f32>,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading scenario {}", path))?;
+        let scenario: Scenario = toml::from_str(&text)
+            .map_err(|e| MorseError::InvalidScenario(format!("{}: {}", path, e)))?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<(), MorseError> {
+        let checks = [
+            ("noise.qrm", self.noise.qrm, 9),
+            ("noise.qrn", self.noise.qrn, 9),
+            ("fading.depth", self.fading.depth, 9),
+            ("interferers.count", self.interferers.count, 9),
+            ("interferers.pileup", self.interferers.pileup, 9),
+            ("interferers.drift", self.interferers.drift, 9),
+            ("sending.jitter", self.sending.jitter, 50),
+            ("sending.speed_variance", self.sending.speed_variance, 50),
+        ];
+        for (field, value, max) in checks {
+            if value > max {
+                return Err(MorseError::InvalidScenario(
+                    format!("{} = {} (must be 0-{})", field, value, max),
+                ));
+            }
+        }
+        if self.noise.pad_ms > MAX_NOISE_PAD_MS {
+            return Err(MorseError::InvalidScenario(
+                format!("noise.pad_ms = {} (must be 0-{})", self.noise.pad_ms, MAX_NOISE_PAD_MS),
+            ));
+        }
+        if let Some(rate) = self.fading.rate_hz {
+            if !(0.0..=5.0).contains(&rate) {
+                return Err(MorseError::InvalidScenario(
+                    format!("fading.rate_hz = {} (must be 0-5)", rate),
+                ));
+            }
+        }
+        if let Some(minutes) = self.trend.minutes {
+            if minutes <= 0.0 {
+                return Err(MorseError::InvalidScenario(
+                    format!("trend.minutes = {} (must be positive)", minutes),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn conditions(&self) -> Conditions {
+        Conditions {
+            qrm: self.noise.qrm,
+            snr: None,
+            qrn: self.noise.qrn,
+            qsb: self.fading.depth,
+            jitter: self.sending.jitter,
+            speed_variance: self.sending.speed_variance,
+            qsb_rate: self.fading.rate_hz,
+            interferers: self.interferers.count,
+            pileup: self.interferers.pileup,
+            interferer_drift: self.interferers.drift,
+            trend: self.trend.direction,
+            trend_minutes: self.trend.minutes.unwrap_or(30.0),
+            noise_pad_ms: self.noise.pad_ms,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steady_severity() {
+        let conditions = Conditions { qrm: 8, trend_minutes: 10.0, ..Conditions::default() };
+        assert_eq!(conditions.severity(300.0), 1.0);
+    }
+
+    #[test]
+    fn test_degrading_severity() {
+        let conditions = Conditions { qrm: 8, trend_minutes: 10.0, trend: Trend::Degrading, ..Conditions::default() };
+        assert!((conditions.severity(0.0) - 0.3).abs() < 1e-9);
+        assert!((conditions.severity(600.0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_improving_qrm() {
+        let conditions = Conditions { qrm: 8, trend_minutes: 10.0, trend: Trend::Improving, ..Conditions::default() };
+        assert_eq!(conditions.at(0.0).qrm, 8);
+        assert_eq!(conditions.at(3600.0).qrm, 2);
+    }
+}
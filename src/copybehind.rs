@@ -0,0 +1,249 @@
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode};
+use crossterm::style::Stylize;
+use crossterm::cursor::MoveToColumn;
+use crossterm::queue;
+use crossterm::terminal::{self, Clear, ClearType};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rodio::Sink;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::audio::{output_stream, playback_rate, MorseAudio, Speaker, ToneShape};
+use crate::eq::equalize;
+use crate::conditions::Conditions;
+use crate::events;
+use crate::exam::{copied_as, copied_characters};
+use crate::morse::{MorseError, Timing};
+use crate::record::Recorder;
+use crate::replay::{self, keep};
+
+// ---------- Copying behind --------------------------------------------------
+// Experienced operators copy a word or two behind what they are hearing. With
+// `--copy-behind N` practice words are sent back to back, without waiting, and
+// each one is typed while the word N later is being sent: with N = 1, word 1
+// is typed while word 2 sounds. Space or Enter ends an answer, which is
+// scored against the oldest word not yet answered. A word that falls further
+// behind than that, unanswered, counts as missed.
+const KEY_POLL: Duration = Duration::from_millis(10);
+
+/// A word that has been scored, and what was typed for it.
+pub struct Score {
+    pub word: String,
+    pub answer: Option<String>,
+    pub marks: Vec<(char, bool)>,
+}
+
+impl Score {
+    pub fn correct(&self) -> bool {
+        self.answer.as_deref().is_some_and(|answer| answer.trim().eq_ignore_ascii_case(&self.word))
+    }
+}
+
+/// The words sent but not yet answered, and the running score.
+pub struct Window {
+    depth: usize,
+    pending: VecDeque<String>,
+    /// Characters copied, of those sent
+    pub copied: usize,
+    pub sent: usize,
+    pub scored: usize,
+    pub correct: usize,
+    /// Every character scored, and what was copied in its place
+    pub copied_as: Vec<(char, Option<char>)>,
+    /// Every word scored, and whether it was right
+    pub words: Vec<(String, bool)>,
+}
+
+impl Window {
+    pub fn new(depth: usize) -> Self {
+        Window { depth, pending: VecDeque::new(), copied: 0, sent: 0, scored: 0, correct: 0, copied_as: Vec::new(), words: Vec::new() }
+    }
+
+    /// `word` has started sending. Returns the words that have now fallen
+    /// too far behind, scored as missed.
+    pub fn send(&mut self, word: &str) -> Vec<Score> {
+        self.pending.push_back(word.to_string());
+        let mut missed = Vec::new();
+        while self.pending.len() > self.depth + 1 {
+            let word = self.pending.pop_front().unwrap();
+            missed.push(self.score(word, None));
+        }
+        missed
+    }
+
+    /// Score `copy` against the oldest word not yet answered.
+    pub fn answer(&mut self, copy: &str) -> Option<Score> {
+        let word = self.pending.pop_front()?;
+        Some(self.score(word, Some(copy.to_string())))
+    }
+
+    fn score(&mut self, word: String, answer: Option<String>) -> Score {
+        let marks = copied_characters(&word, answer.as_deref().unwrap_or_default());
+        self.copied_as.extend(copied_as(&word, answer.as_deref().unwrap_or_default()));
+        let score = Score { word, answer, marks };
+        self.copied += score.marks.iter().filter(|(_, copied)| *copied).count();
+        self.sent += score.marks.len();
+        self.scored += 1;
+        self.correct += score.correct() as usize;
+        self.words.push((score.word.clone(), score.correct()));
+        score
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.sent == 0 { 0.0 } else { 100.0 * self.copied as f64 / self.sent as f64 }
+    }
+}
+
+/// Send practice words continuously, scoring answers typed `depth` words
+/// behind, recording what is played to `record` if given. Returns the
+/// score.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    rng: &mut impl Rng,
+    depth: usize,
+    timing: Timing,
+    tone: u32,
+    mut content: Vec<String>,
+    conditions: Conditions,
+    tone_shape: ToneShape,
+    record: Option<&str>,
+    speaker: &Speaker,
+) -> Result<Window> {
+    if content.is_empty() {
+        anyhow::bail!("nothing to practice: the text has no usable words");
+    }
+    content.shuffle(rng);
+    let sample_rate = playback_rate();
+    let recorder = record.map(|_| Recorder::new(sample_rate));
+    let (_stream, handle) = output_stream()?;
+    let sink = Sink::try_new(&handle).map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+
+    let replays = if speaker.history.is_some() { ", Tab to replay the last few seconds" } else { "" };
+    println!("Copy behind – type each word while the one {} later is sent; Space or Enter ends it{}, Esc to quit:\n", depth, replays);
+
+    let mut window = Window::new(depth);
+    let mut words = content.iter().cycle();
+    let mut typed = String::new();
+    let session_start = Instant::now();
+
+    terminal::enable_raw_mode()?;
+    let result = (|| {
+    loop {
+        if sink.empty() {
+            let word = words.next().unwrap();
+            for missed in window.send(word) {
+                show(&window, &missed, &mut typed);
+            }
+            // The trailing space keeps a word gap between words
+            let audio = MorseAudio::new_with_sample_rate(
+                sample_rate,
+                &format!("{} ", word),
+                timing,
+                tone,
+                conditions.at(session_start.elapsed().as_secs_f64()),
+                tone_shape,
+                None,
+            );
+            let duration_s = audio.get_samples().len() as f64 / sample_rate as f64;
+            match &recorder {
+                Some(recorder) => sink.append(equalize(keep(recorder.tap(audio), speaker.history.as_ref()), &speaker.eq)),
+                None => sink.append(equalize(keep(audio, speaker.history.as_ref()), &speaker.eq)),
+            }
+            events::emit(events::Event::PlaybackStarted { text: word, duration_s });
+        }
+        if !event::poll(KEY_POLL)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Esc => break,
+                code if code == replay::KEY && speaker.history.is_some() => replay::replay(&handle, &[&sink], speaker)?,
+                KeyCode::Char(' ') | KeyCode::Enter if !typed.is_empty() => {
+                    if let Some(score) = window.answer(&typed) {
+                        show(&window, &score, &mut typed);
+                    }
+                }
+                KeyCode::Char(' ') | KeyCode::Enter => {}
+                KeyCode::Backspace if !typed.is_empty() => {
+                    typed.pop();
+                    print!("\x08 \x08");
+                }
+                KeyCode::Char(c) => {
+                    typed.push(c.to_ascii_uppercase());
+                    print!("{}", c.to_ascii_uppercase());
+                }
+                _ => {}
+            }
+            let _ = std::io::stdout().flush();
+        }
+    }
+    Ok(())
+    })();
+    terminal::disable_raw_mode()?;
+    sink.stop();
+    println!(
+        "\n{}/{} words right, {:.0}% of characters copied",
+        window.correct, window.scored, window.percent(),
+    );
+    if let (Some(recorder), Some(path)) = (&recorder, record) {
+        recorder.save(path)?;
+        println!("Session recorded to: {}", path);
+    }
+    result.map(|()| window)
+}
+
+// Finish the line being typed with the word's mark (or a missed word on a
+// line of its own), keeping what has been typed towards the next answer
+fn show(window: &Window, score: &Score, typed: &mut String) {
+    let mark = match (&score.answer, score.correct()) {
+        (Some(_), true) => "✓".green().to_string(),
+        (Some(_), false) => format!("{} {}", "✗".red(), score.word),
+        (None, _) => format!("{} {} (missed)", "✗".red(), score.word),
+    };
+    match &score.answer {
+        Some(_) => {
+            print!(" {}\r\n", mark);
+            typed.clear();
+        }
+        None => {
+            let _ = queue!(std::io::stdout(), MoveToColumn(0), Clear(ClearType::CurrentLine));
+            print!("{}\r\n{}", mark, typed);
+        }
+    }
+    let _ = std::io::stdout().flush();
+    events::emit(events::Event::ItemScored {
+        item: window.scored,
+        expected: &score.word,
+        answer: score.answer.as_deref().unwrap_or_default(),
+        correct: score.correct(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_behind_window() {
+        let mut window = Window::new(1);
+        assert!(window.send("CQ").is_empty());
+        assert!(window.send("DE").is_empty());
+        // Typed while DE sounds: CQ
+        assert!(window.answer("cq").unwrap().correct());
+        assert!(window.send("W1AW").is_empty());
+        // DE wasn't answered while W1AW sounded
+        let missed = window.send("K");
+        assert_eq!(missed.len(), 1);
+        assert_eq!(missed[0].word, "DE");
+        assert!(!missed[0].correct());
+        let late = window.answer("W1A").unwrap();
+        assert_eq!(late.word, "W1AW");
+        assert!(!late.correct());
+
+        assert_eq!((window.scored, window.correct), (3, 1));
+        assert_eq!((window.copied, window.sent), (5, 8));
+    }
+}
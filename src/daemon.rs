@@ -0,0 +1,429 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Local, Timelike};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::morse::{key_timeline, Timing};
+use crate::mqtt::{self, Subscriber};
+
+// ---------- Alert daemon ----------------------------------------------------
+// Runs until stopped, sending whatever arrives as CW: each line written to a
+// named pipe, and each message published to the MQTT topics subscribed to.
+// A topic can have its own speed and pitch, so a doorbell and a washing
+// machine sound different before a single character has been read. Sources
+// run on threads of their own and hand messages over a channel.
+//
+// Messages wait in a queue, most urgent first and otherwise in the order
+// they came. With `--preempt` a more urgent one cuts off whatever is being
+// sent, which goes back in the queue from the word it had reached and
+// carries on once the queue is clear of anything more urgent.
+#[derive(clap::Args, Debug, Clone)]
+pub struct DaemonArgs {
+    /// Named pipe to read messages from, one per line (created if missing)
+    #[arg(long, value_name = "PATH")]
+    pub fifo: Option<PathBuf>,
+
+    /// MQTT broker to subscribe to (HOST or HOST:PORT)
+    #[arg(long, value_name = "HOST", requires = "topic")]
+    pub mqtt: Option<String>,
+
+    /// Topic to subscribe to, with its own speed and pitch if given, e.g.
+    /// home/door@25/900 (repeatable)
+    #[arg(long, value_name = "FILTER[@WPM[/HZ]]", requires = "mqtt")]
+    pub topic: Vec<TopicSpec>,
+
+    /// Send the time (HHMM) every MIN minutes, on the hour and at multiples
+    /// of MIN past it
+    #[arg(long, value_name = "MIN", value_parser = clap::value_parser!(u32).range(1..=1440))]
+    pub clock: Option<u32>,
+
+    /// Text file to send once, as practice, whenever nothing else is waiting
+    #[arg(long, value_name = "FILE")]
+    pub practice: Option<PathBuf>,
+
+    /// Priority of a source: fifo, clock, practice or a topic filter, e.g.
+    /// home/alarm=9 (repeatable; higher goes first)
+    #[arg(long, value_name = "SOURCE=N", value_parser = parse_priority)]
+    pub priority: Vec<(String, u8)>,
+
+    /// Let a higher-priority message interrupt the one being sent, which
+    /// resumes afterwards
+    #[arg(long)]
+    pub preempt: bool,
+}
+
+// Unless --priority says otherwise: practice fills the quiet, the clock
+// comes before it, and alerts before both
+const PRACTICE_PRIORITY: u8 = 0;
+const CLOCK_PRIORITY: u8 = 1;
+const ALERT_PRIORITY: u8 = 5;
+
+fn parse_priority(s: &str) -> Result<(String, u8), String> {
+    let (source, priority) = s.rsplit_once('=').ok_or("expected SOURCE=N")?;
+    let priority = priority.parse().map_err(|_| format!("priority must be 0-255, not {:?}", priority))?;
+    Ok((source.to_string(), priority))
+}
+
+impl DaemonArgs {
+    // Exact names first, then the first topic filter matching
+    fn priority_of(&self, source: &str, default: u8) -> u8 {
+        let exact = self.priority.iter().find(|(name, _)| name == source);
+        let matching = || self.priority.iter().find(|(filter, _)| mqtt::topic_matches(filter, source));
+        exact.or_else(matching).map_or(default, |(_, priority)| *priority)
+    }
+}
+
+/// A topic filter and how its messages sound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicSpec {
+    pub filter: String,
+    pub wpm: Option<u32>,
+    pub tone: Option<u32>,
+}
+
+impl FromStr for TopicSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (filter, sound) = match s.split_once('@') {
+            Some((filter, sound)) => (filter, Some(sound)),
+            None => (s, None),
+        };
+        if filter.is_empty() {
+            return Err("empty topic filter".into());
+        }
+        let number = |value: &str, range: std::ops::RangeInclusive<u32>, what: &str| {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|n| range.contains(n))
+                .ok_or_else(|| format!("{} must be {}-{}, not {:?}", what, range.start(), range.end(), value))
+        };
+        let (wpm, tone) = match sound.map(|sound| sound.split_once('/').unwrap_or((sound, ""))) {
+            None => (None, None),
+            Some((wpm, "")) => (Some(number(wpm, 1..=100, "WPM")?), None),
+            Some((wpm, tone)) => (Some(number(wpm, 1..=100, "WPM")?), Some(number(tone, 100..=3000, "tone")?)),
+        };
+        Ok(TopicSpec { filter: filter.to_string(), wpm, tone })
+    }
+}
+
+/// A message to send, and how.
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub text: String,
+    pub timing: Timing,
+    pub tone: u32,
+    /// "fifo", "clock", "practice" or the MQTT topic it came from
+    pub source: String,
+    pub priority: u8,
+    // Order of arrival, kept by a resumed remainder
+    arrived: u64,
+}
+
+impl Alert {
+    fn new(text: String, timing: Timing, tone: u32, source: String) -> Self {
+        Alert { text, timing, tone, source, priority: ALERT_PRIORITY, arrived: 0 }
+    }
+
+    // What is left after `elapsed`, from the start of the word then being
+    // sent
+    fn remainder(&self, elapsed: Duration) -> Option<Alert> {
+        let words: Vec<&str> = self.text.split_whitespace().collect();
+        let mut end = Duration::ZERO;
+        let reached = words.iter().position(|word| {
+            end += key_timeline(&format!("{} ", word), &self.timing).iter().map(|e| e.duration).sum::<Duration>();
+            end > elapsed
+        })?;
+        Some(Alert { text: words[reached..].join(" "), ..self.clone() })
+    }
+}
+
+// Most urgent first, then first come
+impl Ord for Alert {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority).then(other.arrived.cmp(&self.arrived))
+    }
+}
+
+impl PartialOrd for Alert {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Alert {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Alert {}
+
+/// Where alerts go: started, and cut off if something more urgent comes.
+pub trait Transmitter {
+    /// Start sending `alert`, returning how long it will take.
+    fn start(&mut self, alert: &Alert) -> Result<Duration>;
+    /// Stop whatever is being sent.
+    fn stop(&mut self);
+}
+
+// A broker that drops the connection is tried again after this
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// Start the sources and send alerts as they come, until every source has
+/// stopped and the queue is empty.
+pub fn run(args: &DaemonArgs, timing: Timing, tone: u32, transmitter: &mut impl Transmitter) -> Result<()> {
+    if args.fifo.is_none() && args.mqtt.is_none() && args.clock.is_none() && args.practice.is_none() {
+        bail!("nothing to send: give --fifo, --mqtt with --topic, --clock or --practice");
+    }
+    let (sender, alerts) = mpsc::channel();
+    if let Some(path) = &args.fifo {
+        let path = fifo(path)?;
+        let priority = args.priority_of("fifo", ALERT_PRIORITY);
+        let sender = sender.clone();
+        thread::spawn(move || read_fifo(&path, timing, tone, priority, sender));
+    }
+    if let Some(broker) = &args.mqtt {
+        let (broker, topics) = (broker.clone(), args.topic.clone());
+        let args = args.clone();
+        let sender = sender.clone();
+        thread::spawn(move || subscribe(&broker, &topics, timing, tone, &args, sender));
+    }
+    if let Some(minutes) = args.clock {
+        let priority = args.priority_of("clock", CLOCK_PRIORITY);
+        let sender = sender.clone();
+        thread::spawn(move || clock(minutes, timing, tone, priority, sender));
+    }
+    if let Some(path) = &args.practice {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let priority = args.priority_of("practice", PRACTICE_PRIORITY);
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let _ = sender.send(Alert { priority, ..Alert::new(text, timing, tone, "practice".into()) });
+    }
+    drop(sender);
+
+    let mut queue = BinaryHeap::new();
+    let mut arrivals = 0;
+    let mut sending: Option<(Alert, Instant, Duration)> = None;
+    let mut sources_open = true;
+    loop {
+        // Wait for the next alert, or until the one being sent is done
+        let arrived = match &sending {
+            Some((_, started, length)) if sources_open => {
+                match alerts.recv_timeout(length.saturating_sub(started.elapsed())) {
+                    Ok(alert) => Some(alert),
+                    Err(RecvTimeoutError::Timeout) => {
+                        sending = None;
+                        None
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        sources_open = false;
+                        None
+                    }
+                }
+            }
+            Some((_, started, length)) => {
+                thread::sleep(length.saturating_sub(started.elapsed()));
+                sending = None;
+                None
+            }
+            None if queue.is_empty() => match alerts.recv() {
+                Ok(alert) => Some(alert),
+                Err(_) => return Ok(()),
+            },
+            None => None,
+        };
+
+        if let Some(mut alert) = arrived {
+            arrivals += 1;
+            alert.arrived = arrivals;
+            if let Some((current, started, _)) = &sending {
+                if args.preempt && alert.priority > current.priority {
+                    transmitter.stop();
+                    if let Some(rest) = current.remainder(started.elapsed()) {
+                        println!("{} [{}] interrupted, resuming later", Local::now().format("%H:%M:%S"), current.source);
+                        queue.push(rest);
+                    }
+                    sending = None;
+                }
+            }
+            queue.push(alert);
+        }
+        if sending.is_none() {
+            if let Some(alert) = queue.pop() {
+                println!("{} [{}] {}", Local::now().format("%H:%M:%S"), alert.source, alert.text);
+                // One message failing (a keying limit, say) shouldn't stop the rest
+                match transmitter.start(&alert) {
+                    Ok(length) => sending = Some((alert, Instant::now(), length)),
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+        }
+    }
+}
+
+// Creates the pipe if it isn't there
+#[cfg(unix)]
+fn fifo(path: &Path) -> Result<PathBuf> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if !path.exists() {
+        let status = std::process::Command::new("mkfifo").arg(path).status().context("running mkfifo")?;
+        if !status.success() {
+            bail!("couldn't create the named pipe {}", path.display());
+        }
+    } else if !std::fs::metadata(path)?.file_type().is_fifo() {
+        bail!("{} isn't a named pipe", path.display());
+    }
+    Ok(path.to_path_buf())
+}
+
+#[cfg(not(unix))]
+fn fifo(_path: &Path) -> Result<PathBuf> {
+    bail!("--fifo needs a Unix named pipe; use --mqtt instead")
+}
+
+// A pipe reaches end-of-file each time its writer closes it; opening it
+// again waits for the next one
+fn read_fifo(path: &Path, timing: Timing, tone: u32, priority: u8, alerts: Sender<Alert>) {
+    let source = path.display().to_string();
+    loop {
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Error: {}: {}", source, e);
+                return;
+            }
+        };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            let text = line.trim();
+            if !text.is_empty() {
+                let alert = Alert { priority, ..Alert::new(text.to_string(), timing, tone, "fifo".into()) };
+                if alerts.send(alert).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn subscribe(broker: &str, topics: &[TopicSpec], timing: Timing, tone: u32, args: &DaemonArgs, alerts: Sender<Alert>) {
+    let filters: Vec<&str> = topics.iter().map(|t| t.filter.as_str()).collect();
+    let client_id = format!("cwgen-{}", std::process::id());
+    loop {
+        let result = Subscriber::connect(broker, &client_id, &filters).and_then(|mut subscriber| {
+            println!("Subscribed to {} on {}", filters.join(", "), broker);
+            loop {
+                let publish = subscriber.next()?;
+                let text = String::from_utf8_lossy(&publish.payload).trim().to_string();
+                if text.is_empty() {
+                    continue;
+                }
+                let alert = sound(topics, &publish.topic, text, timing, tone);
+                let alert = Alert { priority: args.priority_of(&publish.topic, ALERT_PRIORITY), ..alert };
+                if alerts.send(alert).is_err() {
+                    return Ok(());
+                }
+            }
+        });
+        match result {
+            Ok(()) => return,
+            Err(e) => eprintln!("Error: MQTT: {:#} (retrying in {} s)", e, RECONNECT_DELAY.as_secs()),
+        }
+        thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+// The first topic spec that matches decides the speed and pitch
+fn sound(topics: &[TopicSpec], topic: &str, text: String, timing: Timing, tone: u32) -> Alert {
+    let spec = topics.iter().find(|spec| mqtt::topic_matches(&spec.filter, topic));
+    Alert::new(
+        text,
+        spec.and_then(|spec| spec.wpm).map_or(timing, |wpm| Timing::new(wpm, 0).with_charset(timing.charset).with_envelope(timing.envelope).with_binaural(timing.binaural)),
+        spec.and_then(|spec| spec.tone).unwrap_or(tone),
+        topic.to_string(),
+    )
+}
+
+// The local time, at each multiple of `minutes` past the hour (or midnight,
+// for intervals over an hour)
+fn clock(minutes: u32, timing: Timing, tone: u32, priority: u8, alerts: Sender<Alert>) {
+    loop {
+        let now = Local::now();
+        let into_day = now.num_seconds_from_midnight();
+        let period = minutes * 60;
+        thread::sleep(Duration::from_secs((period - into_day % period) as u64));
+        let text = Local::now().format("%H%M").to_string();
+        if alerts.send(Alert { priority, ..Alert::new(text, timing, tone, "clock".into()) }).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(text: &str, priority: u8, arrived: u64) -> Alert {
+        Alert { priority, arrived, ..Alert::new(text.into(), Timing::new(20, 0), 600, "fifo".into()) }
+    }
+
+    #[test]
+    fn test_topic_specs() {
+        let door: TopicSpec = "home/+/door@25/900".parse().unwrap();
+        assert_eq!(door, TopicSpec { filter: "home/+/door".into(), wpm: Some(25), tone: Some(900) });
+        let washer: TopicSpec = "home/washer@12".parse().unwrap();
+        assert_eq!((washer.wpm, washer.tone), (Some(12), None));
+        assert!("home/#".parse::<TopicSpec>().unwrap().wpm.is_none());
+        assert!("home/door@fast".parse::<TopicSpec>().is_err());
+        assert!("home/door@20/5000".parse::<TopicSpec>().is_err());
+    }
+
+    #[test]
+    fn test_topic_sound() {
+        let topics: [TopicSpec; 2] = ["home/+/door@25/900".parse().unwrap(), "home/#".parse().unwrap()];
+        let alert = sound(&topics, "home/front/door", "DOOR".into(), Timing::new(18, 0), 600);
+        assert_eq!((alert.timing.dot, alert.tone), (Timing::new(25, 0).dot, 900));
+        let alert = sound(&topics, "home/garage", "OPEN".into(), Timing::new(18, 0), 600);
+        assert_eq!((alert.timing.dot, alert.tone), (Timing::new(18, 0).dot, 600));
+    }
+
+    #[test]
+    fn test_priorities() {
+        let mut queue = BinaryHeap::from([alert("PRACTICE", 0, 1), alert("LATER", 5, 3), alert("FIRST", 5, 2), alert("FIRE", 9, 4)]);
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop()).map(|a| a.text).collect();
+        assert_eq!(order, ["FIRE", "FIRST", "LATER", "PRACTICE"]);
+    }
+
+    #[test]
+    fn test_preempted_remainder() {
+        // PARIS is 3 s at 20 WPM: cut off 4 s in, the second word starts over
+        let practice = alert("PARIS PARIS PARIS", 0, 1);
+        let rest = practice.remainder(Duration::from_secs(4)).unwrap();
+        assert_eq!((rest.text.as_str(), rest.arrived), ("PARIS PARIS", 1));
+        assert!(practice.remainder(Duration::from_secs(10)).is_none());
+    }
+
+    #[test]
+    fn test_priority_of() {
+        let args = DaemonArgs {
+            fifo: None,
+            mqtt: None,
+            topic: Vec::new(),
+            clock: None,
+            practice: None,
+            priority: vec![parse_priority("home/alarm/#=9").unwrap(), parse_priority("clock=7").unwrap()],
+            preempt: true,
+        };
+        assert_eq!(args.priority_of("home/alarm/smoke", ALERT_PRIORITY), 9);
+        assert_eq!(args.priority_of("clock", CLOCK_PRIORITY), 7);
+        assert_eq!(args.priority_of("practice", PRACTICE_PRIORITY), PRACTICE_PRIORITY);
+    }
+}
@@ -0,0 +1,971 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use hound::{SampleFormat, WavReader};
+use serde::Serialize;
+use std::io::IsTerminal;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::audio::{Export, MorseAudio, ToneShape};
+use crate::conditions::Conditions;
+use crate::extract::{self, Found};
+use crate::morse::{Element, MorseError, Timing, REVERSE_MORSE};
+use crate::pcm::RawInput;
+use crate::resample::resample;
+
+// Recordings are converted to this rate before decoding: CW sits well under
+// 4 kHz, and 48 kHz input would only make the pitch search six times slower
+pub const DECODE_RATE: u32 = 8000;
+// Envelope analysis resolution: short enough for 50+ WPM dots
+pub(crate) const BLOCK_SECS: f64 = 0.004;
+// Elements used to lock the initial clock before tracking takes over
+const LOCK_ELEMENTS: usize = 40;
+// How far each classified element pulls the tracked unit toward itself
+const TRACK_RATE: f64 = 0.15;
+// Pitch range searched for signals, and the bin spacing of the search
+pub const SEARCH_HZ: (f64, f64) = (200.0, 1500.0);
+const SEARCH_STEP_HZ: f64 = 10.0;
+// Two signals closer than this are treated as one
+const MIN_SEPARATION_HZ: f64 = 80.0;
+// Bandwidth of the filter that isolates each signal
+pub(crate) const SIGNAL_BANDWIDTH_HZ: f64 = 100.0;
+
+// ---------- Decoded result -------------------------------------------------
+#[derive(Debug, Clone, Serialize)]
+pub struct Decoded {
+    pub text: String,
+    /// Estimated sending speed (PARIS)
+    pub wpm: f64,
+    /// Estimated tone frequency in Hz
+    pub tone_hz: f64,
+    /// Tone distance from the strongest signal, when several were separated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset_hz: Option<f64>,
+    /// 0.0-1.0: mean of the per-character confidences
+    pub confidence: f64,
+    pub chars: Vec<DecodedChar>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedChar {
+    pub ch: char,
+    /// 0.0-1.0: how far every element and gap sat from a decision boundary
+    pub confidence: f64,
+    /// Other plausible readings of the same stretch, most likely first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<String>,
+    /// Local sending speed when this character was sent (not set for spaces)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wpm: Option<f64>,
+    /// Local tone frequency of this character (not set for spaces)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tone_hz: Option<f64>,
+}
+
+// ---------- Input ----------------------------------------------------------
+/// Read a WAV file as mono f32 samples, averaging channels.
+pub fn read_wav(path: &str) -> Result<(Vec<f32>, u32)> {
+    let mut reader = WavReader::open(path).with_context(|| format!("opening {}", path))?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|v| v as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let channels = spec.channels.max(1) as usize;
+    let mono = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+// Extensions read through symphonia rather than hound
+const COMPRESSED_EXTENSIONS: [&str; 5] = ["mp3", "ogg", "oga", "flac", "fla"];
+
+/// Read a recording as mono f32 samples: WAV directly, MP3, Ogg Vorbis and
+/// FLAC through symphonia.
+pub fn read_audio(path: &str) -> Result<(Vec<f32>, u32)> {
+    let compressed = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| COMPRESSED_EXTENSIONS.iter().any(|c| ext.eq_ignore_ascii_case(c)));
+    if compressed {
+        read_compressed(path)
+    } else {
+        read_wav(path)
+    }
+}
+
+fn read_compressed(path: &str) -> Result<(Vec<f32>, u32)> {
+    let file = std::fs::File::open(path).with_context(|| format!("opening {}", path))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .with_context(|| format!("unrecognized audio in {}", path))?;
+    let mut format = probed.format;
+    let track = format
+        .default_track()
+        .with_context(|| format!("no audio track in {}", path))?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .with_context(|| format!("unknown sample rate in {}", path))?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .with_context(|| format!("unsupported codec in {}", path))?;
+
+    let mut mono = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt frame costs a few milliseconds of audio, not the file
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(e).with_context(|| format!("decoding {}", path)),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        mono.extend(
+            buffer
+                .samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+    }
+    Ok((mono, sample_rate))
+}
+
+// ---------- Envelope detection ---------------------------------------------
+/// Decode keyed CW from raw audio: RMS envelope, adaptive threshold with
+/// hysteresis, then timing classification of the resulting on/off runs.
+pub fn decode_samples(samples: &[f32], sample_rate: u32) -> Decoded {
+    let block = ((sample_rate as f64 * BLOCK_SECS) as usize).max(1);
+    let envelope: Vec<f32> = samples
+        .chunks(block)
+        .map(|chunk| (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt())
+        .collect();
+
+    let keyed = threshold(&envelope);
+    let block_duration = Duration::from_secs_f64(block as f64 / sample_rate as f64);
+    let (lead, elements) = runs(&keyed, block_duration);
+    let spans = sample_spans(lead * block, &elements, sample_rate);
+    let mark_spans = |range: Range<usize>| {
+        range.filter(|i| elements[*i].key_down).map(|i| spans[i].clone())
+    };
+    let tone_hz = estimate_pitch(samples, mark_spans(0..elements.len()), sample_rate);
+
+    // Lock the clock on the opening elements, then follow the sender
+    let opening = &elements[..elements.len().min(LOCK_ELEMENTS)];
+    let initial = estimate_unit(opening);
+    let bias = envelope_bias(opening, initial);
+    let units = track_units(&elements, initial, bias);
+
+    let chars: Vec<DecodedChar> = classify(&elements, &units, bias)
+        .into_iter()
+        .map(|(mut c, range)| {
+            if c.ch != ' ' {
+                c.tone_hz = Some(estimate_pitch(samples, mark_spans(range), sample_rate));
+            }
+            c
+        })
+        .collect();
+    let text = chars.iter().map(|c| c.ch).collect();
+    let wpm = median(&units).map_or(0.0, |unit| 1.2 / unit);
+    let letters: Vec<f64> = chars.iter().filter(|c| c.ch != ' ').map(|c| c.confidence).collect();
+    let confidence = if letters.is_empty() {
+        0.0
+    } else {
+        letters.iter().sum::<f64>() / letters.len() as f64
+    };
+
+    Decoded { text, wpm, tone_hz, offset_hz: None, confidence, chars }
+}
+
+// Key state per envelope block. The threshold sits halfway between the noise
+// floor and the signal level, with hysteresis so noise doesn't chatter.
+fn threshold(envelope: &[f32]) -> Vec<bool> {
+    let Some((floor, peak)) = levels(envelope) else {
+        return vec![false; envelope.len()];
+    };
+    let on = floor + (peak - floor) * 0.5;
+    let off = floor + (peak - floor) * 0.35;
+
+    let mut key_down = false;
+    envelope
+        .iter()
+        .map(|&level| {
+            key_down = if key_down { level > off } else { level > on };
+            key_down
+        })
+        .collect()
+}
+
+// Noise floor and keyed signal level of an envelope, or None when no keyed
+// signal stands out from the noise.
+pub(crate) fn levels(envelope: &[f32]) -> Option<(f32, f32)> {
+    if envelope.is_empty() {
+        return None;
+    }
+    let mut sorted = envelope.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let floor = sorted[sorted.len() / 5];
+    let peak = sorted[sorted.len() * 19 / 20];
+    (peak > floor * 2.0).then_some((floor, peak))
+}
+
+// Collapse per-block key states into alternating intervals, dropping the
+// silence before the first mark. Also returns how many blocks were dropped.
+pub(crate) fn runs(keyed: &[bool], block: Duration) -> (usize, Vec<Element>) {
+    let lead = keyed.iter().take_while(|k| !**k).count();
+    let mut elements: Vec<Element> = Vec::new();
+    for &key_down in &keyed[lead..] {
+        match elements.last_mut() {
+            Some(last) if last.key_down == key_down => last.duration += block,
+            _ => elements.push(Element { key_down, duration: block }),
+        }
+    }
+    (lead, elements)
+}
+
+// Sample range covered by each element
+fn sample_spans(start: usize, elements: &[Element], sample_rate: u32) -> Vec<Range<usize>> {
+    let mut position = start;
+    elements
+        .iter()
+        .map(|e| {
+            let len = (e.duration.as_secs_f64() * sample_rate as f64).round() as usize;
+            position += len;
+            position - len..position
+        })
+        .collect()
+}
+
+// Zero-crossing rate over the given key-down stretches
+fn estimate_pitch(samples: &[f32], spans: impl Iterator<Item = Range<usize>>, sample_rate: u32) -> f64 {
+    let mut crossings = 0usize;
+    let mut count = 0usize;
+    for span in spans {
+        let span = &samples[span.start.min(samples.len())..span.end.min(samples.len())];
+        crossings += span.windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count();
+        count += span.len();
+    }
+    if count == 0 {
+        return 0.0;
+    }
+    crossings as f64 / 2.0 / (count as f64 / sample_rate as f64)
+}
+
+// ---------- Timing classification ------------------------------------------
+// Dot length in seconds: split mark lengths into dots and dashes with a small
+// 2-means pass. When every mark is the same kind, the element gaps (one unit
+// each) break the tie. Envelope smoothing shortens marks and lengthens gaps
+// by the same amount, so the final unit averages marks with element gaps.
+pub(crate) fn estimate_unit(elements: &[Element]) -> f64 {
+    let marks: Vec<f64> = elements
+        .iter()
+        .filter(|e| e.key_down)
+        .map(|e| e.duration.as_secs_f64())
+        .collect();
+    let gaps: Vec<f64> = elements
+        .iter()
+        .filter(|e| !e.key_down)
+        .map(|e| e.duration.as_secs_f64())
+        .collect();
+    if marks.is_empty() {
+        return 0.0;
+    }
+    let (short, long) = two_means(&marks);
+    let mark_unit = if long / short > 2.0 {
+        // Weighted blend of both clusters, dashes counting as three units
+        let dashes = marks.iter().filter(|m| (*m - short).abs() >= (*m - long).abs()).count();
+        marks.iter().sum::<f64>() / (marks.len() + 2 * dashes) as f64
+    } else {
+        let shortest_gap = gaps.iter().copied().fold(f64::INFINITY, f64::min);
+        if shortest_gap.is_finite() && short > shortest_gap * 2.0 {
+            short / 3.0
+        } else {
+            short
+        }
+    };
+
+    let element_gaps: Vec<f64> = gaps.into_iter().filter(|g| *g < 2.0 * mark_unit).collect();
+    if element_gaps.is_empty() {
+        return mark_unit;
+    }
+    let gap_unit = element_gaps.iter().sum::<f64>() / element_gaps.len() as f64;
+    (mark_unit + gap_unit) / 2.0
+}
+
+// Envelope smoothing makes marks short and gaps long by the same amount (in
+// seconds); measure it on the element gaps, which should be one unit each.
+pub(crate) fn envelope_bias(elements: &[Element], unit: f64) -> f64 {
+    let element_gaps: Vec<f64> = elements
+        .iter()
+        .filter(|e| !e.key_down && e.duration.as_secs_f64() < 2.0 * unit)
+        .map(|e| e.duration.as_secs_f64())
+        .collect();
+    if element_gaps.is_empty() {
+        return 0.0;
+    }
+    element_gaps.iter().sum::<f64>() / element_gaps.len() as f64 - unit
+}
+
+/// Unit (seconds) in effect at each element. Every mark and element gap,
+/// once classified, pulls the estimate toward the unit it implies, so the
+/// decoder follows a sender who speeds up or slows down.
+fn track_units(elements: &[Element], initial: f64, bias: f64) -> Vec<f64> {
+    let mut unit = initial;
+    elements
+        .iter()
+        .map(|e| {
+            let current = unit;
+            unit = track(unit, e, bias);
+            current
+        })
+        .collect()
+}
+
+/// The unit after one more element: marks and element gaps pull it toward
+/// the unit they imply, character and word gaps leave it alone.
+pub(crate) fn track(unit: f64, element: &Element, bias: f64) -> f64 {
+    let secs = element.duration.as_secs_f64();
+    if element.key_down {
+        let mark = secs + bias;
+        let implied = if mark < 2.0 * unit { mark } else { mark / 3.0 };
+        unit + TRACK_RATE * (implied - unit)
+    } else if secs - bias < 2.0 * unit {
+        unit + TRACK_RATE * (secs - bias - unit)
+    } else {
+        unit
+    }
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    sorted.get(sorted.len() / 2).copied()
+}
+
+// Centers of a two-cluster split of one-dimensional data
+fn two_means(values: &[f64]) -> (f64, f64) {
+    let mut low = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let mut high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    for _ in 0..10 {
+        let mid = (low + high) / 2.0;
+        let (lows, highs): (Vec<f64>, Vec<f64>) = values.iter().partition(|v| **v <= mid);
+        if !lows.is_empty() {
+            low = lows.iter().sum::<f64>() / lows.len() as f64;
+        }
+        if !highs.is_empty() {
+            high = highs.iter().sum::<f64>() / highs.len() as f64;
+        }
+    }
+    (low, high)
+}
+
+// Char/word gap boundary in units. Standard spacing puts it between 3 and 7;
+// stretched (Farnsworth) spacing is found by clustering the long gaps.
+fn word_gap_threshold(gaps: &[f64]) -> f64 {
+    let long_gaps: Vec<f64> = gaps.iter().copied().filter(|g| *g > 2.0).collect();
+    if long_gaps.len() >= 2 {
+        let (short, long) = two_means(&long_gaps);
+        if long / short > 1.6 {
+            return (short * long).sqrt();
+        }
+    }
+    5.0
+}
+
+// Distance from a decision boundary scaled to 0-1. Ideal elements sit a full
+// unit away; anything beyond 0.7 units counts as certain.
+pub(crate) fn margin(value: f64, boundary: f64) -> f64 {
+    ((value - boundary).abs() / 0.7).min(1.0)
+}
+
+// Below this margin an element could plausibly have gone the other way
+const AMBIGUOUS: f64 = 0.5;
+
+/// Turn key intervals into characters, each with the range of elements it
+/// was read from. Durations are converted to units using the clock tracked
+/// at each element and corrected for envelope bias.
+fn classify(elements: &[Element], units: &[f64], bias: f64) -> Vec<(DecodedChar, Range<usize>)> {
+    if units.first().is_none_or(|u| *u <= 0.0) {
+        return Vec::new();
+    }
+    // Trailing silence says nothing about the sender's spacing
+    let gaps: Vec<f64> = elements[..elements.len() - 1]
+        .iter()
+        .zip(units)
+        .filter(|(e, _)| !e.key_down)
+        .map(|(e, unit)| (e.duration.as_secs_f64() - bias) / unit)
+        .collect();
+    let word_gap = word_gap_threshold(&gaps);
+
+    let mut chars = Vec::new();
+    let mut start = 0;
+    let mut marks: Vec<f64> = Vec::new();
+    let mut inner_gaps: Vec<f64> = Vec::new();
+    let read_at = |marks: &[f64], inner_gaps: &[f64], boundary: f64, start: usize| DecodedChar {
+        wpm: Some(1.2 / units[start]),
+        ..read_char(marks, inner_gaps, boundary)
+    };
+    for (i, (element, unit)) in elements.iter().zip(units).enumerate() {
+        let secs = element.duration.as_secs_f64();
+        if element.key_down {
+            if marks.is_empty() {
+                start = i;
+            }
+            marks.push((secs + bias) / unit);
+            continue;
+        }
+        let gap = (secs - bias) / unit;
+        let last = i + 1 == elements.len();
+        if gap < 2.0 && !last {
+            inner_gaps.push(gap);
+            continue;
+        }
+        // Trailing silence ends the last character whatever its length
+        let boundary = if last { 1.0 } else { margin(gap, 2.0).min(margin(gap, word_gap)) };
+        chars.push((read_at(&marks, &inner_gaps, boundary, start), start..i));
+        marks.clear();
+        inner_gaps.clear();
+        if gap >= word_gap && !last {
+            let space = DecodedChar {
+                ch: ' ', confidence: margin(gap, word_gap), alternatives: Vec::new(), wpm: None, tone_hz: None,
+            };
+            chars.push((space, i..i + 1));
+        }
+    }
+    if !marks.is_empty() {
+        chars.push((read_at(&marks, &inner_gaps, 1.0, start), start..elements.len()));
+    }
+    chars
+}
+
+fn code_of(marks: &[f64]) -> String {
+    marks.iter().map(|m| if *m < 2.0 { '.' } else { '-' }).collect()
+}
+
+fn lookup(code: &str) -> Option<char> {
+    REVERSE_MORSE.get(code).copied()
+}
+
+// One character from its marks and the gaps between them. Alternatives come
+// from flipping the least certain mark (dot/dash) and from splitting at the
+// least certain gap (one character heard as two).
+pub(crate) fn read_char(marks: &[f64], inner_gaps: &[f64], boundary_margin: f64) -> DecodedChar {
+    let code = code_of(marks);
+    let Some(ch) = lookup(&code) else {
+        return DecodedChar { ch: '*', confidence: 0.0, alternatives: Vec::new(), wpm: None, tone_hz: None };
+    };
+
+    let mark_margins: Vec<f64> = marks.iter().map(|m| margin(*m, 2.0)).collect();
+    let gap_margins: Vec<f64> = inner_gaps.iter().map(|g| margin(*g, 2.0)).collect();
+    let confidence = mark_margins
+        .iter()
+        .chain(&gap_margins)
+        .copied()
+        .fold(boundary_margin, f64::min);
+
+    let mut alternatives: Vec<(f64, String)> = Vec::new();
+    let weakest = |margins: &[f64]| {
+        margins
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(b.1))
+            .filter(|(_, m)| **m < AMBIGUOUS)
+            .map(|(i, m)| (i, *m))
+    };
+    if let Some((i, m)) = weakest(&mark_margins) {
+        let mut flipped: Vec<char> = code.chars().collect();
+        flipped[i] = if flipped[i] == '.' { '-' } else { '.' };
+        let flipped: String = flipped.into_iter().collect();
+        if let Some(alt) = lookup(&flipped) {
+            alternatives.push((m, alt.to_string()));
+        }
+    }
+    if let Some((i, m)) = weakest(&gap_margins) {
+        if let (Some(a), Some(b)) = (lookup(&code[..=i]), lookup(&code[i + 1..])) {
+            alternatives.push((m, format!("{}{}", a, b)));
+        }
+    }
+    alternatives.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    DecodedChar {
+        ch,
+        confidence,
+        alternatives: alternatives.into_iter().map(|(_, alt)| alt).collect(),
+        wpm: None,
+        tone_hz: None,
+    }
+}
+
+// ---------- Signal separation ----------------------------------------------
+/// Decode up to `count` simultaneous signals at different pitches, strongest
+/// first. Each is isolated with a narrow band-pass filter and decoded on its
+/// own; `offset_hz` is measured from the strongest.
+pub fn decode_signals(samples: &[f32], sample_rate: u32, count: usize) -> Vec<Decoded> {
+    let pitches = find_signals(samples, sample_rate, SEARCH_HZ, count);
+    let strongest = pitches.first().copied().unwrap_or_default();
+    pitches
+        .into_iter()
+        .map(|pitch| {
+            let isolated = bandpass(samples, sample_rate, pitch, SIGNAL_BANDWIDTH_HZ);
+            let mut decoded = decode_samples(&isolated, sample_rate);
+            decoded.offset_hz = Some(decoded.tone_hz - strongest);
+            decoded
+        })
+        .collect()
+}
+
+/// Decode the one signal on `center` Hz, whatever else is in the recording
+/// around it: a message hidden under music (see `overlay --band`), which can
+/// be anywhere below Nyquist, so the recording is taken at its own rate.
+pub fn decode_band(samples: &[f32], sample_rate: u32, center: f64) -> Decoded {
+    let isolated = bandpass(samples, sample_rate, center, SIGNAL_BANDWIDTH_HZ);
+    let mut decoded = decode_samples(&isolated, sample_rate);
+    decoded.tone_hz = center;
+    for c in decoded.chars.iter_mut().filter(|c| c.tone_hz.is_some()) {
+        c.tone_hz = Some(center);
+    }
+    decoded
+}
+
+/// Pitches of the strongest tones between `range.0` and `range.1` Hz,
+/// strongest first: a Goertzel bank averaged over the samples, peaks at least
+/// MIN_SEPARATION_HZ apart and within 10 dB of the strongest. A peak must
+/// stand 6 dB above the median bin (the noise) and above the spectrum
+/// MIN_SEPARATION_HZ to either side, which a narrow CW tone does and a hump
+/// of band noise doesn't.
+pub fn find_signals(samples: &[f32], sample_rate: u32, range: (f64, f64), count: usize) -> Vec<f64> {
+    let top = range.1.min(sample_rate as f64 * 0.45);
+    let bins: Vec<f64> = (0..)
+        .map(|i| range.0 + i as f64 * SEARCH_STEP_HZ)
+        .take_while(|f| *f <= top)
+        .collect();
+    // Frames as long as the bin spacing is fine, Hann-windowed against leakage
+    let frame = (sample_rate as f64 / SEARCH_STEP_HZ) as usize;
+    let window: Vec<f32> = (0..frame)
+        .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / frame as f32).cos())
+        .collect();
+    let mut power = vec![0.0; bins.len()];
+    for chunk in samples.chunks_exact(frame) {
+        let windowed: Vec<f32> = chunk.iter().zip(&window).map(|(s, w)| s * w).collect();
+        for (total, &freq) in power.iter_mut().zip(&bins) {
+            *total += goertzel(&windowed, sample_rate, freq);
+        }
+    }
+
+    let mut order: Vec<usize> = (0..bins.len()).collect();
+    order.sort_by(|a, b| power[*b].total_cmp(&power[*a]));
+    let loudest = order.first().map_or(0.0, |i| power[*i]);
+    let noise = order.get(order.len() / 2).map_or(0.0, |i| power[*i]);
+    let offset = (MIN_SEPARATION_HZ / SEARCH_STEP_HZ) as usize;
+    let mut found: Vec<f64> = Vec::new();
+    for i in order {
+        if found.len() == count || power[i] <= noise * 4.0 || power[i] < loudest * 0.1 {
+            break;
+        }
+        let shoulder = [i.checked_sub(offset), Some(i + offset)]
+            .into_iter()
+            .flatten()
+            .filter_map(|j| power.get(j).copied())
+            .fold(0.0, f64::max);
+        if power[i] > shoulder * 4.0 && found.iter().all(|f| (f - bins[i]).abs() >= MIN_SEPARATION_HZ) {
+            found.push(bins[i]);
+        }
+    }
+    found
+}
+
+// Signal power at one frequency over a block
+pub(crate) fn goertzel(block: &[f32], sample_rate: u32, freq: f64) -> f64 {
+    let coeff = 2.0 * (std::f64::consts::TAU * freq / sample_rate as f64).cos();
+    let (mut s1, mut s2) = (0.0, 0.0);
+    for &x in block {
+        let s0 = x as f64 + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+    s1 * s1 + s2 * s2 - coeff * s1 * s2
+}
+
+// Two cascaded band-pass biquads (0 dB at the center frequency)
+fn bandpass(samples: &[f32], sample_rate: u32, center: f64, bandwidth: f64) -> Vec<f32> {
+    let mut filter = Bandpass::new(sample_rate, center, bandwidth);
+    samples.iter().map(|&x| filter.process(x)).collect()
+}
+
+/// Streaming form of the band-pass filter used to isolate one signal.
+pub(crate) struct Bandpass {
+    b0: f64,
+    a1: f64,
+    a2: f64,
+    // x1, x2, y1, y2 for each of the two stages
+    state: [[f64; 4]; 2],
+}
+
+impl Bandpass {
+    pub(crate) fn new(sample_rate: u32, center: f64, bandwidth: f64) -> Self {
+        let w0 = std::f64::consts::TAU * center / sample_rate as f64;
+        let alpha = w0.sin() / (2.0 * center / bandwidth);
+        let a0 = 1.0 + alpha;
+        Bandpass {
+            b0: alpha / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha) / a0,
+            state: [[0.0; 4]; 2],
+        }
+    }
+
+    pub(crate) fn process(&mut self, sample: f32) -> f32 {
+        let mut x = sample as f64;
+        for [x1, x2, y1, y2] in self.state.iter_mut() {
+            // b1 is zero and b2 = -b0 for a band-pass
+            let y = self.b0 * (x - *x2) - self.a1 * *y1 - self.a2 * *y2;
+            *x2 = *x1;
+            *x1 = x;
+            *y2 = *y1;
+            *y1 = y;
+            x = y;
+        }
+        x as f32
+    }
+}
+
+// ---------- Batch decoding -------------------------------------------------
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DecodeFormat {
+    /// One human-readable line per file
+    Text,
+    /// One JSON object per line
+    Jsonl,
+}
+
+#[derive(Serialize)]
+struct BatchRecord<'a> {
+    file: &'a str,
+    #[serde(flatten)]
+    decoded: Option<Decoded>,
+    /// Callsigns, grids and reports in the text, with `--extract`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extracted: Option<Vec<Found>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Recordings (WAV, MP3, Ogg, FLAC) directly inside `dir`, sorted by name.
+pub fn audio_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("reading {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    ext.eq_ignore_ascii_case("wav") || COMPRESSED_EXTENSIONS.iter().any(|c| ext.eq_ignore_ascii_case(c))
+                })
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Decode every file (a recording, or raw PCM as described by `raw`, with "-" for
+/// stdin) and print one result per line (per signal, with
+/// `signals` > 1). A file that fails to decode is reported in place and
+/// doesn't stop the batch.
+/// With `verbose`, per-character confidences and alternatives are shown
+/// (text) or kept (JSONL); otherwise JSONL carries confidences only. With
+/// `extract`, callsigns, grids and reports are highlighted and listed (text)
+/// or added as an `extracted` array (JSONL). With a `band`, only the signal
+/// on that frequency is decoded.
+pub fn decode_batch(
+    files: &[PathBuf],
+    format: DecodeFormat,
+    verbose: bool,
+    signals: usize,
+    extract: bool,
+    band: Option<f64>,
+    raw: &RawInput,
+) -> Result<()> {
+    let color = std::io::stdout().is_terminal();
+    for path in files {
+        let name = path.display().to_string();
+        let audio = match raw.input_format {
+            Some(format) => raw.read(format, &name).map(|samples| (samples, raw.rate)),
+            None => read_audio(&name),
+        };
+        let result = audio.and_then(|(samples, rate)| match band {
+            Some(band) if band >= rate as f64 / 2.0 => {
+                anyhow::bail!("--band {} Hz is above what a {} Hz recording can hold", band, rate)
+            }
+            Some(band) => Ok(vec![decode_band(&samples, rate, band)]),
+            None => resample(&samples, rate, DECODE_RATE).map(|samples| match signals {
+                1 => vec![decode_samples(&samples, DECODE_RATE)],
+                _ => decode_signals(&samples, DECODE_RATE, signals),
+            }),
+        });
+        let decoded = match result {
+            Ok(mut decoded) => {
+                if !verbose {
+                    for d in &mut decoded {
+                        d.chars.iter_mut().for_each(|c| c.alternatives.clear());
+                    }
+                }
+                decoded
+            }
+            Err(e) => {
+                match format {
+                    DecodeFormat::Text => eprintln!("{}: error: {:#}", name, e),
+                    DecodeFormat::Jsonl => {
+                        let record = BatchRecord {
+                            file: &name, decoded: None, extracted: None, error: Some(format!("{:#}", e)),
+                        };
+                        println!("{}", serde_json::to_string(&record)?);
+                    }
+                }
+                continue;
+            }
+        };
+        for d in decoded {
+            let found = extract.then(|| extract::extract(&d.text));
+            match format {
+                DecodeFormat::Text => {
+                    let label = match d.offset_hz {
+                        Some(offset) => format!("{} [{:+.0} Hz]", name, offset),
+                        None => name.clone(),
+                    };
+                    let text = match &found {
+                        Some(found) if color => extract::highlight(&d.text, found),
+                        _ => d.text.clone(),
+                    };
+                    let mut line = format!(
+                        "{}: {} ({} WPM, {:.0} Hz, confidence {:.2})",
+                        label, text, speed_range(&d.chars, d.wpm), d.tone_hz, d.confidence
+                    );
+                    if let Some(summary) = found.as_deref().map(extract::summary).filter(|s| !s.is_empty()) {
+                        line = format!("{}  {}", line, summary);
+                    }
+                    println!("{}", line);
+                    if verbose {
+                        println!("  {}", describe_chars(&d.chars));
+                    }
+                }
+                DecodeFormat::Jsonl => {
+                    let record = BatchRecord { file: &name, decoded: Some(d), extracted: found, error: None };
+                    println!("{}", serde_json::to_string(&record)?);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// "20" for a steady sender, "14-31" for one who drifted
+fn speed_range(chars: &[DecodedChar], wpm: f64) -> String {
+    let speeds = chars.iter().filter_map(|c| c.wpm);
+    let low = speeds.clone().fold(wpm, f64::min);
+    let high = speeds.fold(wpm, f64::max);
+    if high - low < 2.0 {
+        format!("{:.0}", wpm)
+    } else {
+        format!("{:.0}-{:.0}", low, high)
+    }
+}
+
+// "C .98  Q .41 (Y|TA)  / ..." - one entry per character, '/' for spaces
+fn describe_chars(chars: &[DecodedChar]) -> String {
+    chars
+        .iter()
+        .map(|c| {
+            if c.ch == ' ' {
+                return "/".to_string();
+            }
+            let mut entry = format!("{} {:.2}", c.ch, c.confidence);
+            if !c.alternatives.is_empty() {
+                entry.push_str(&format!(" ({})", c.alternatives.join("|")));
+            }
+            entry
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+// ---------- Re-synthesis ---------------------------------------------------
+/// Decode a recording and send the same text again, slower/faster and at a
+/// different pitch, as a clean WAV at `output_rate` (the input's sample rate
+/// if not given).
+pub fn reprocess(
+    input: &str,
+    output: &str,
+    wpm_scale: f64,
+    shift_hz: i32,
+    tone_shape: ToneShape,
+    output_rate: Option<u32>,
+    export: Export,
+) -> Result<Decoded> {
+    let (samples, input_rate) = read_audio(input)?;
+    let decoded = decode_samples(&resample(&samples, input_rate, DECODE_RATE)?, DECODE_RATE);
+    if decoded.text.is_empty() {
+        anyhow::bail!("no morse found in {}", input);
+    }
+
+    let wpm = (decoded.wpm * wpm_scale).round() as u32;
+    if !(1..=100).contains(&wpm) {
+        return Err(MorseError::InvalidSpeed(wpm).into());
+    }
+    let tone = (decoded.tone_hz.round() as i32 + shift_hz).max(0) as u32;
+    if !(100..=3000).contains(&tone) {
+        return Err(MorseError::InvalidTone(tone).into());
+    }
+
+    // Unknown characters come back as '*', which has no morse of its own
+    let text = decoded.text.replace('*', "?");
+    let sample_rate = output_rate.unwrap_or(input_rate);
+    let audio = MorseAudio::new_with_sample_rate(
+        sample_rate, &text, Timing::new(wpm, 0), tone, Conditions::default(), tone_shape, None,
+    );
+    export.write(output, sample_rate, audio.get_samples().to_vec())?;
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{write_wav, MorseAudio, ToneShape};
+    use crate::conditions::Conditions;
+    use crate::morse::{key_timeline, Timing};
+
+    #[test]
+    fn test_decode_rendered_audio() {
+        for wpm in [12, 20, 35] {
+            let audio = MorseAudio::new_with_sample_rate(
+                8000, "PARIS CQ DE W1AW", Timing::new(wpm, 0), 700,
+                Conditions::default(), ToneShape::Sine, None,
+            );
+            let decoded = decode_samples(audio.get_samples(), 8000);
+            assert_eq!(decoded.text, "PARIS CQ DE W1AW");
+            assert!((decoded.wpm - wpm as f64).abs() < wpm as f64 * 0.05, "wpm {}", decoded.wpm);
+            assert!((decoded.tone_hz - 700.0).abs() < 10.0, "tone {}", decoded.tone_hz);
+            assert!(decoded.confidence > 0.85, "confidence {}", decoded.confidence);
+        }
+    }
+
+    #[test]
+    fn test_ambiguous_mark_alternatives() {
+        let unit = Duration::from_millis(60);
+        let on = |n: f64| Element { key_down: true, duration: unit.mul_f64(n) };
+        let off = |n: f64| Element { key_down: false, duration: unit.mul_f64(n) };
+        // A clean 'A' then an 'N' whose dash came out barely longer than a dot
+        let elements = [on(1.0), off(1.0), on(3.0), off(3.0), on(2.1), off(1.0), on(1.0)];
+        let chars: Vec<DecodedChar> = classify(&elements, &[0.06; 7], 0.0)
+            .into_iter()
+            .map(|(c, _)| c)
+            .collect();
+        assert_eq!(chars[0].ch, 'A');
+        assert!(chars[0].confidence > 0.9);
+        assert_eq!(chars[1].ch, 'N');
+        assert!(chars[1].confidence < 0.2);
+        assert_eq!(chars[1].alternatives, vec!["I".to_string()]);
+    }
+
+    #[test]
+    fn test_track_drifting_sender() {
+        // Same operator warming up from 12 to 36 WPM over a few words
+        let mut elements = Vec::new();
+        for wpm in [12, 15, 19, 24, 30, 36] {
+            if let Some(last) = elements.last_mut() {
+                *last = Element { key_down: false, duration: Timing::new(wpm, 0).wrd };
+            }
+            elements.extend(key_timeline("PARIS ", &Timing::new(wpm, 0)));
+        }
+        elements.pop();
+        let initial = estimate_unit(&elements[..LOCK_ELEMENTS]);
+        let units = track_units(&elements, initial, 0.0);
+        let chars: Vec<DecodedChar> = classify(&elements, &units, 0.0)
+            .into_iter()
+            .map(|(c, _)| c)
+            .collect();
+        let text: String = chars.iter().map(|c| c.ch).collect();
+        assert_eq!(text, ["PARIS"; 6].join(" "));
+        let last_wpm = chars.last().and_then(|c| c.wpm).unwrap();
+        assert!(last_wpm > 30.0, "wpm {}", last_wpm);
+    }
+
+    #[test]
+    fn test_decode_farnsworth_spacing() {
+        let audio = MorseAudio::new_with_sample_rate(
+            8000, "TEST ME NOW", Timing::new_farnsworth(25, 12, 0), 600,
+            Conditions::default(), ToneShape::Sine, None,
+        );
+        assert_eq!(decode_samples(audio.get_samples(), 8000).text, "TEST ME NOW");
+    }
+
+    #[test]
+    fn test_read_through_symphonia() {
+        let audio = MorseAudio::new_with_sample_rate(
+            8000, "CQ", Timing::new(20, 0), 700, Conditions::default(), ToneShape::Sine, None,
+        );
+        let path = std::env::temp_dir().join(format!("cwgen-symphonia-{}.wav", std::process::id()));
+        let path = path.to_string_lossy();
+        write_wav(&path, 8000, audio.get_samples()).unwrap();
+        let (direct, _) = read_wav(&path).unwrap();
+        let (probed, rate) = read_compressed(&path).unwrap();
+        std::fs::remove_file(&*path).unwrap();
+        assert_eq!(rate, 8000);
+        assert_eq!(probed.len(), direct.len());
+        assert!(probed.iter().zip(&direct).all(|(a, b)| (a - b).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_decode_two_signals() {
+        let strong = MorseAudio::new_with_sample_rate(
+            8000, "CQ TEST DE W1AW", Timing::new(20, 0), 600,
+            Conditions::default(), ToneShape::Sine, None,
+        );
+        let weak = MorseAudio::new_with_sample_rate(
+            8000, "K1ABC 599 MA", Timing::new(26, 0), 850,
+            Conditions::default(), ToneShape::Sine, None,
+        );
+        let (a, b) = (strong.get_samples(), weak.get_samples());
+        let mixed: Vec<f32> = (0..a.len().max(b.len()))
+            .map(|i| a.get(i).unwrap_or(&0.0) + 0.6 * b.get(i).unwrap_or(&0.0))
+            .collect();
+
+        let decoded = decode_signals(&mixed, 8000, 2);
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].text, "CQ TEST DE W1AW");
+        assert_eq!(decoded[1].text, "K1ABC 599 MA");
+        let offset = decoded[1].offset_hz.unwrap();
+        assert!((offset - 250.0).abs() < 15.0, "offset {}", offset);
+    }
+}
@@ -0,0 +1,177 @@
+use clap::ValueEnum;
+use rodio::Source;
+use std::time::Duration;
+
+// ---------- Listening EQ ----------------------------------------------------
+// A CW note is a single narrow tone, so a resonance in headphones or a small
+// speaker right where it sits makes it shrill or boomy in a way music never
+// shows up. `--eq` picks a profile of peaking filters for the kind of
+// listening device, and `--eq-band HZ:DB:Q` adds bands of your own, e.g.
+// `700:-6:2` to take 6 dB out of a peak at 700 Hz. The EQ is for the
+// listener's ears, so it goes on what is played, after any recording tap,
+// and never on written files, VOX keying or test signals.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EqProfile {
+    /// Closed-back headphones: a dip for the common resonance around 700 Hz
+    ClosedBack,
+    /// Open-back headphones: a gentle dip through the CW range
+    OpenBack,
+    /// In-ear buds: a dip at 800 Hz and less of the harsh harmonics above
+    Earbuds,
+    /// Laptop and other small speakers: more body under the note, less edge
+    Laptop,
+}
+
+/// One peaking filter: its center, gain and sharpness.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Band {
+    pub hz: f64,
+    pub db: f64,
+    pub q: f64,
+}
+
+impl EqProfile {
+    pub fn bands(self) -> Vec<Band> {
+        let band = |hz, db, q| Band { hz, db, q };
+        match self {
+            EqProfile::ClosedBack => vec![band(700.0, -6.0, 2.0)],
+            EqProfile::OpenBack => vec![band(1000.0, -3.0, 1.0)],
+            EqProfile::Earbuds => vec![band(800.0, -5.0, 1.5), band(3000.0, -4.0, 1.0)],
+            EqProfile::Laptop => vec![band(500.0, 4.0, 1.0), band(2500.0, -3.0, 1.0)],
+        }
+    }
+}
+
+/// "700:-6:2" is 6 dB out at 700 Hz with a Q of 2.
+pub fn parse_band(band: &str) -> Result<Band, String> {
+    let invalid = || format!("invalid EQ band: {} (expected HZ:DB:Q, e.g. 700:-6:2)", band);
+    let parts: Vec<f64> = band.split(':').map(|part| part.trim().parse()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+    let [hz, db, q] = parts[..] else { return Err(invalid()) };
+    if !(20.0..=20000.0).contains(&hz) || !(-24.0..=12.0).contains(&db) || !(0.1..=20.0).contains(&q) {
+        return Err(format!("{}: HZ must be 20 to 20000, DB -24 to 12 and Q 0.1 to 20", invalid()));
+    }
+    Ok(Band { hz, db, q })
+}
+
+// An RBJ peaking biquad, with its state for each channel
+struct Peaking {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: Vec<[f64; 2]>,
+}
+
+impl Peaking {
+    fn new(band: Band, sample_rate: u32, channels: usize) -> Self {
+        let gain = 10f64.powf(band.db / 40.0);
+        // Bands above Nyquist are left where they can still be heard
+        let w0 = std::f64::consts::TAU * band.hz.min(sample_rate as f64 * 0.45) / sample_rate as f64;
+        let alpha = w0.sin() / (2.0 * band.q);
+        let a0 = 1.0 + alpha / gain;
+        Peaking {
+            b: [(1.0 + alpha * gain) / a0, -2.0 * w0.cos() / a0, (1.0 - alpha * gain) / a0],
+            a: [-2.0 * w0.cos() / a0, (1.0 - alpha / gain) / a0],
+            state: vec![[0.0; 2]; channels],
+        }
+    }
+
+    fn process(&mut self, channel: usize, x: f64) -> f64 {
+        let state = &mut self.state[channel];
+        let y = self.b[0] * x + state[0];
+        state[0] = self.b[1] * x - self.a[0] * y + state[1];
+        state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// A source through the listening EQ.
+pub struct Equalizer<S> {
+    source: S,
+    filters: Vec<Peaking>,
+    channels: usize,
+    channel: usize,
+}
+
+impl<S: Source<Item = f32>> Equalizer<S> {
+    /// `source` through `bands`.
+    pub fn new(source: S, bands: &[Band]) -> Self {
+        let channels = source.channels().max(1) as usize;
+        let filters = bands.iter().map(|&band| Peaking::new(band, source.sample_rate(), channels)).collect();
+        Equalizer { source, filters, channels, channel: 0 }
+    }
+}
+
+/// `source` through `bands`, if any.
+pub fn equalize<S: Source<Item = f32>>(source: S, bands: &[Band]) -> Equalizer<S> {
+    Equalizer::new(source, bands)
+}
+
+impl<S: Source<Item = f32>> Iterator for Equalizer<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.source.next()?;
+        let channel = self.channel;
+        self.channel = (self.channel + 1) % self.channels;
+        if self.filters.is_empty() {
+            return Some(sample);
+        }
+        let out = self.filters.iter_mut().fold(sample as f64, |x, filter| filter.process(channel, x));
+        Some(out as f32)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Equalizer<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    // The peak of a steady `hz` tone through `bands`, once settled
+    fn peak(hz: f64, bands: &[Band]) -> f32 {
+        let tone: Vec<f32> = (0..8000).map(|i| (std::f64::consts::TAU * hz * i as f64 / 8000.0).sin() as f32).collect();
+        let out: Vec<f32> = Equalizer::new(SamplesBuffer::new(1, 8000, tone), bands).collect();
+        out[4000..].iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn test_equalizer() {
+        let bands = EqProfile::ClosedBack.bands();
+        // 6 dB out at the center, next to nothing an octave and more away
+        assert!((peak(700.0, &bands) - 0.5).abs() < 0.01, "{}", peak(700.0, &bands));
+        assert!(peak(2500.0, &bands) > 0.95);
+    }
+
+    #[test]
+    fn test_no_bands_passes_through() {
+        assert!((peak(700.0, &[]) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_parse_band() {
+        assert_eq!(parse_band("700:-6:2"), Ok(Band { hz: 700.0, db: -6.0, q: 2.0 }));
+        assert!(parse_band("700:-6").is_err());
+        assert!(parse_band("700:-40:2").is_err());
+    }
+}
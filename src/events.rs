@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::captions;
+use crate::replay::{self, History};
+use crate::morse::{char_starts, key_timeline, Timing};
+
+// The "version" on every event line. Wrappers are expected to skip fields
+// they don't know, so adding one leaves it alone; renaming, dropping or
+// redefining one moves it on.
+const SCHEMA_VERSION: u32 = 1;
+
+// ---------- Progress events -------------------------------------------------
+// With `--json-events`, what cwgen is doing is also written as one JSON
+// object per line, for wrappers, GUIs and stream overlays:
+//
+//     {"source":"cwgen","version":1,"time":"2026-10-17T14:03:22.512Z","type":"char_sent","ch":"K"}
+//
+// `type` is one of:
+//   playback_started text, duration_s              text starts playing
+//   char_sent        ch                            a character as it is heard
+//   item_scored      item, expected, answer,       an exam answer is graded
+//                    correct
+//   session_ended    mode, items, correct,         practice, exam or
+//                    duration_s                    interactive session is over
+#[derive(Serialize)]
+struct Line<'a> {
+    source: &'static str,
+    version: u32,
+    time: String,
+    #[serde(flatten)]
+    event: Event<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event<'a> {
+    PlaybackStarted {
+        text: &'a str,
+        duration_s: f64,
+    },
+    CharSent {
+        ch: char,
+    },
+    ItemScored {
+        item: usize,
+        expected: &'a str,
+        answer: &'a str,
+        correct: bool,
+    },
+    SessionEnded {
+        mode: &'a str,
+        items: usize,
+        correct: Option<usize>,
+        duration_s: f64,
+    },
+}
+
+static EVENTS: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Write events to stdout (`-`) or a TCP listener at HOST:PORT.
+pub fn open(target: &str) -> Result<()> {
+    let out: Box<dyn Write + Send> = match target {
+        "-" => Box::new(std::io::stdout()),
+        address => Box::new(TcpStream::connect(address).with_context(|| format!("connecting to {} for --json-events", address))?),
+    };
+    let _ = EVENTS.set(Mutex::new(out));
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    EVENTS.get().is_some()
+}
+
+/// Write `event`, if events are on. A listener going away shouldn't stop
+/// the session, so failures are only printed.
+pub fn emit(event: Event) {
+    let Some(out) = EVENTS.get() else { return };
+    let line = Line {
+        source: "cwgen",
+        version: SCHEMA_VERSION,
+        time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        event,
+    };
+    let mut out = out.lock().unwrap();
+    let written = serde_json::to_string(&line)
+        .map_err(std::io::Error::from)
+        .and_then(|json| writeln!(out, "{}", json))
+        .and_then(|()| out.flush());
+    if let Err(e) = written {
+        eprintln!("Warning: --json-events: {}", e);
+    }
+}
+
+/// Emit `char_sent` for each character of `text` as it is heard, playback
+/// having begun at `started` with replays from `history`. Returns when the
+/// last one has been sent. `--captions` are kept in step too.
+pub fn chars_as_heard(text: &str, timing: &Timing, started: Instant, history: Option<&History>) {
+    std::thread::scope(|scope| {
+        if captions::enabled() {
+            scope.spawn(|| captions::as_heard(text, timing, started, history));
+        }
+        if enabled() {
+            let clock = replay::Clock::start(history);
+            for (ch, offset) in char_offsets(text, timing) {
+                if !clock.sleep_until(started + offset) {
+                    break;
+                }
+                emit(Event::CharSent { ch });
+            }
+        }
+    });
+}
+
+// When each character starts keying, from the start of the text
+fn char_offsets(text: &str, timing: &Timing) -> Vec<(char, Duration)> {
+    char_starts(text, timing)
+        .into_iter()
+        .filter(|(ch, _)| key_timeline(&ch.to_string(), timing).iter().any(|e| e.key_down))
+        .map(|(ch, at)| (ch.to_ascii_uppercase(), at))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_lines() {
+        let line = Line {
+            source: "cwgen",
+            version: SCHEMA_VERSION,
+            time: "2026-10-17T14:03:22.512Z".into(),
+            event: Event::ItemScored { item: 3, expected: "W1AW", answer: "w1aw", correct: true },
+        };
+        let json: serde_json::Value = serde_json::to_value(&line).unwrap();
+        assert_eq!(json["type"], "item_scored");
+        assert_eq!(json["expected"], "W1AW");
+        assert_eq!(json["correct"], true);
+    }
+
+    #[test]
+    fn test_char_offsets() {
+        // 20 WPM: E is a dot and a letter gap (240 ms), the space 240 more
+        let offsets = char_offsets("ee t", &Timing::new(20, 0));
+        let ms = Duration::from_millis;
+        assert_eq!(offsets, [('E', ms(0)), ('E', ms(240)), ('T', ms(720))]);
+    }
+}
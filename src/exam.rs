@@ -0,0 +1,286 @@
+use anyhow::Result;
+use rand::seq::IndexedRandom;
+use rand::Rng;
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+use crate::audio::{play_audio, Speaker, ToneShape};
+use crate::conditions::Conditions;
+use crate::events::{self, Event};
+use crate::morse::{key_timeline, random_callsign, Timing};
+use crate::record::Recorder;
+
+// ---------- Code exam -------------------------------------------------------
+// The old FCC/ARRL element 1 format: about five minutes of a plain-language
+// QSO, then ten questions about what was sent (callsigns, name, QTH, rig...).
+// Seven right is a pass.
+const EXAM_SECS: u64 = 300;
+pub const PASS_MARK: usize = 7;
+
+const NAMES: &[&str] = &["BOB", "JIM", "SUE", "ANN", "TOM", "BILL", "MARY", "JOE", "PAT", "DAVE", "KAREN", "MIKE"];
+const QTHS: &[&str] = &[
+    "DENVER CO", "AUSTIN TX", "SALEM OR", "DAYTON OH", "BANGOR ME", "TULSA OK", "FRESNO CA", "MACON GA",
+    "OMAHA NE", "TAMPA FL", "BOISE ID", "ERIE PA",
+];
+const RIGS: &[&str] = &["IC 7300", "FT 991", "TS 590", "K3", "FT 817", "IC 718", "HW 101", "TS 520"];
+const ANTENNAS: &[&str] = &["DIPOLE", "VERTICAL", "YAGI", "LONG WIRE", "LOOP", "G5RV", "WINDOM"];
+const WEATHER: &[&str] = &["SUNNY", "CLOUDY", "RAINY", "SNOWING", "WINDY", "FOGGY", "CLEAR"];
+const JOBS: &[&str] = &["TEACHER", "FARMER", "ENGINEER", "NURSE", "PILOT", "CARPENTER", "RETIRED"];
+const RSTS: &[&str] = &["599", "579", "589", "559", "479", "569"];
+
+/// One fill-in question and the answer that was sent.
+#[derive(Debug, Clone)]
+pub struct Question {
+    pub prompt: &'static str,
+    pub answer: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Exam {
+    pub qso: String,
+    pub questions: Vec<Question>,
+}
+
+impl Exam {
+    /// A random QSO long enough to take five minutes at `timing`, and its
+    /// ten questions.
+    pub fn generate(rng: &mut impl Rng, timing: &Timing) -> Exam {
+        let pick = |rng: &mut _, list: &[&str]| list.choose(rng).unwrap().to_string();
+        let (caller, called) = (random_callsign(rng), random_callsign(rng));
+        let facts = [
+            ("Which station called (the one sending)?", caller.clone()),
+            ("Which station was being worked?", called.clone()),
+            ("What signal report (RST) was given?", pick(rng, RSTS)),
+            ("What is the sender's name?", pick(rng, NAMES)),
+            ("Where is the sender (QTH)?", pick(rng, QTHS)),
+            ("What rig is the sender using?", pick(rng, RIGS)),
+            ("How much power (watts)?", (rng.random_range(1..=20) * 5).to_string()),
+            ("What antenna is the sender using?", pick(rng, ANTENNAS)),
+            ("What is the weather?", pick(rng, WEATHER)),
+            ("What is the temperature (F)?", rng.random_range(20..=95).to_string()),
+        ];
+        let [_, _, rst, name, qth, rig, power, antenna, wx, temp] = facts.clone().map(|(_, answer)| answer);
+
+        let mut qso = vec![
+            format!("VVV VVV {} DE {} =", called, caller),
+            format!("GM OM ES TNX FER THE CALL = UR RST {} {} =", rst, rst),
+            format!("NAME IS {} {} ES QTH IS {} {} =", name, name, qth, qth),
+            format!("RIG HR IS {} RUNNING {} WATTS =", rig, power),
+            format!("ANT IS A {} UP 40 FT =", antenna),
+            format!("WX HR IS {} ES TEMP IS {} DEGREES =", wx, temp),
+        ];
+        let closing = format!("HW CPY? {} DE {} K", called, caller);
+        // Small talk that isn't asked about, until it runs five minutes
+        let target = Duration::from_secs(EXAM_SECS);
+        while duration(&format!("{} {}", qso.join(" "), closing), timing) < target {
+            let filler = match rng.random_range(0..4) {
+                0 => format!("I HAVE BEEN A HAM FOR {} YEARS =", rng.random_range(2..=50)),
+                1 => format!("I WORK AS A {} =", pick(rng, JOBS)),
+                2 => format!("MY FRIEND {} ALSO OPERATES CW =", pick(rng, NAMES)),
+                _ => format!("CONDX ARE {} TODAY =", pick(rng, &["GOOD", "FAIR", "POOR"])),
+            };
+            qso.push(filler);
+        }
+        qso.push(closing);
+
+        let questions = facts.into_iter().map(|(prompt, answer)| Question { prompt, answer }).collect();
+        Exam { qso: qso.join(" "), questions }
+    }
+
+    /// How many `answers` match, ignoring case and spacing.
+    pub fn grade(&self, answers: &[String]) -> usize {
+        self.questions
+            .iter()
+            .zip(answers)
+            .filter(|(question, answer)| normalize(answer) == normalize(&question.answer))
+            .count()
+    }
+}
+
+/// Each character of `sent` and whether `copied` has it, lined up as the
+/// longest common subsequence so one dropped letter doesn't fail the rest.
+/// Spaces aren't counted.
+pub fn copied_characters(sent: &str, copied: &str) -> Vec<(char, bool)> {
+    let sent: Vec<char> = sent.chars().filter(|c| !c.is_whitespace()).map(|c| c.to_ascii_uppercase()).collect();
+    let copied: Vec<char> = copied.chars().filter(|c| !c.is_whitespace()).map(|c| c.to_ascii_uppercase()).collect();
+    // lengths[i][j]: LCS of sent[i..] and copied[j..]
+    let mut lengths = vec![vec![0usize; copied.len() + 1]; sent.len() + 1];
+    for i in (0..sent.len()).rev() {
+        for j in (0..copied.len()).rev() {
+            lengths[i][j] = if sent[i] == copied[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut result = Vec::with_capacity(sent.len());
+    while i < sent.len() {
+        if j < copied.len() && sent[i] == copied[j] {
+            result.push((sent[i], true));
+            i += 1;
+            j += 1;
+        } else if j < copied.len() && lengths[i][j + 1] > lengths[i + 1][j] {
+            j += 1;
+        } else {
+            result.push((sent[i], false));
+            i += 1;
+        }
+    }
+    result
+}
+
+/// Each character of `sent` and what was copied in its place: itself, a
+/// character it was mistaken for, or nothing if it was dropped. Lined up by
+/// the fewest edits, so a U copied as V shows as just that. Spaces aren't
+/// counted.
+pub fn copied_as(sent: &str, copied: &str) -> Vec<(char, Option<char>)> {
+    let sent: Vec<char> = sent.chars().filter(|c| !c.is_whitespace()).map(|c| c.to_ascii_uppercase()).collect();
+    let copied: Vec<char> = copied.chars().filter(|c| !c.is_whitespace()).map(|c| c.to_ascii_uppercase()).collect();
+    // edits[i][j]: edits from sent[i..] to copied[j..]
+    let mut edits = vec![vec![0usize; copied.len() + 1]; sent.len() + 1];
+    for i in (0..=sent.len()).rev() {
+        for j in (0..=copied.len()).rev() {
+            edits[i][j] = match (i < sent.len(), j < copied.len()) {
+                (false, _) => copied.len() - j,
+                (true, false) => sent.len() - i,
+                (true, true) => (edits[i + 1][j + 1] + usize::from(sent[i] != copied[j]))
+                    .min(edits[i + 1][j] + 1)
+                    .min(edits[i][j + 1] + 1),
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut result = Vec::with_capacity(sent.len());
+    while i < sent.len() {
+        if j < copied.len() && edits[i][j] == edits[i + 1][j + 1] + usize::from(sent[i] != copied[j]) {
+            result.push((sent[i], Some(copied[j])));
+            i += 1;
+            j += 1;
+        } else if edits[i][j] == edits[i + 1][j] + 1 {
+            result.push((sent[i], None));
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    result
+}
+
+fn normalize(answer: &str) -> String {
+    answer.split_whitespace().collect::<Vec<_>>().join(" ").to_ascii_uppercase()
+}
+
+fn duration(text: &str, timing: &Timing) -> Duration {
+    key_timeline(text, timing).iter().map(|element| element.duration).sum()
+}
+
+// ---------- Exam session ----------------------------------------------------
+/// How an exam went.
+pub struct Graded {
+    pub score: usize,
+    pub questions: usize,
+    /// Every character asked about, and what was copied in its place
+    pub characters: Vec<(char, Option<char>)>,
+}
+
+/// Send the QSO, then ask the questions on the terminal and grade them.
+pub fn run(rng: &mut impl Rng, timing: Timing, tone: u32, conditions: Conditions, tone_shape: ToneShape, record: Option<&str>, speaker: &Speaker) -> Result<Graded> {
+    let exam = Exam::generate(rng, &timing);
+    let recorder = record.map(|_| Recorder::new(crate::audio::playback_rate()));
+
+    println!(
+        "Code exam – about {} minutes of QSO, then {} questions. Copy on paper as you go.",
+        EXAM_SECS / 60,
+        exam.questions.len(),
+    );
+    play_audio(&exam.qso, timing, tone, conditions, tone_shape, None, recorder.as_ref(), speaker)?;
+    if let (Some(recorder), Some(path)) = (&recorder, record) {
+        recorder.save(path)?;
+        println!("Session recorded to: {}", path);
+    }
+
+    println!("\nAnswer from your copy (Enter to skip):\n");
+    let stdin = std::io::stdin();
+    let mut answers = Vec::new();
+    for (i, question) in exam.questions.iter().enumerate() {
+        print!("{:2}. {} ", i + 1, question.prompt);
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        stdin.lock().read_line(&mut answer)?;
+        answers.push(answer.trim().to_string());
+    }
+
+    let score = exam.grade(&answers);
+    for (item, (question, answer)) in exam.questions.iter().zip(&answers).enumerate() {
+        let correct = normalize(answer) == normalize(&question.answer);
+        events::emit(Event::ItemScored { item: item + 1, expected: &question.answer, answer, correct });
+    }
+    println!();
+    for (question, answer) in exam.questions.iter().zip(&answers) {
+        let mark = if normalize(answer) == normalize(&question.answer) { "✓" } else { "✗" };
+        println!("{} {} {}", mark, question.prompt, question.answer);
+    }
+    println!(
+        "\nScore: {}/{} – {}",
+        score,
+        exam.questions.len(),
+        if score >= PASS_MARK { "PASS" } else { "FAIL" },
+    );
+    println!("\nThe QSO was:\n{}", exam.qso);
+    let characters = exam
+        .questions
+        .iter()
+        .zip(&answers)
+        .flat_map(|(question, answer)| copied_as(&question.answer, answer))
+        .collect();
+    Ok(Graded { score, questions: exam.questions.len(), characters })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn exam(timing: &Timing) -> Exam {
+        Exam::generate(&mut rand::rngs::StdRng::seed_from_u64(5), timing)
+    }
+
+    #[test]
+    fn test_exam_runs_five_minutes() {
+        let timing = Timing::new(13, 0);
+        let exam = exam(&timing);
+        let secs = duration(&exam.qso, &timing).as_secs();
+        assert!((EXAM_SECS..EXAM_SECS + 20).contains(&secs), "{} s", secs);
+        assert_eq!(exam.questions.len(), 10);
+        assert!(exam.questions.iter().all(|q| exam.qso.contains(&q.answer)));
+    }
+
+    #[test]
+    fn test_exam_grades() {
+        let exam = exam(&Timing::new(13, 0));
+        let mut answers: Vec<String> = exam.questions.iter().map(|q| q.answer.to_lowercase()).collect();
+        answers[4] = format!("  {}  ", answers[4].replace(' ', "   "));
+        assert_eq!(exam.grade(&answers), 10);
+        answers[0] = "N0PE".into();
+        answers.truncate(8);
+        assert_eq!(exam.grade(&answers), 7);
+    }
+
+    #[test]
+    fn test_copied_characters() {
+        let copied = copied_characters("K5 ABC", "k5ac");
+        assert_eq!(copied, vec![('K', true), ('5', true), ('A', true), ('B', false), ('C', true)]);
+        assert!(copied_characters("599", "").iter().all(|(_, ok)| !ok));
+    }
+
+    #[test]
+    fn test_copied_as() {
+        assert_eq!(copied_as("PARIS", "pahis")[2], ('R', Some('H')));
+        assert_eq!(copied_as("K5 ABC", "k5ac"), vec![('K', Some('K')), ('5', Some('5')), ('A', Some('A')), ('B', None), ('C', Some('C'))]);
+        assert_eq!(copied_as("UV", "VV"), vec![('U', Some('V')), ('V', Some('V'))]);
+        assert_eq!(copied_as("TEST", "TEXST").iter().filter(|(c, copy)| Some(*c) != *copy).count(), 0);
+        assert_eq!(copied_as("599", ""), vec![('5', None), ('9', None), ('9', None)]);
+    }
+}
@@ -0,0 +1,115 @@
+use crossterm::style::Stylize;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+
+// ---------- Patterns --------------------------------------------------------
+// Prefix (letters, or a letter and a digit either way round), a digit, then a
+// 1-4 letter suffix, with an optional /P, /QRP, /7 ... Matches W1AW, 9A1AA
+// and K1ABC/P, but not 5NN, 599 or TEST.
+pub const CALLSIGN_PATTERN: &str = r"\b(?:[A-Z]{1,2}|[A-Z][0-9]|[0-9][A-Z])[0-9][A-Z]{1,4}(?:/[A-Z0-9]+)?\b";
+// Maidenhead locator: field, square and optional subsquare (FN31, FN31PR)
+const GRID_PATTERN: &str = r"\b[A-R]{2}[0-9]{2}(?:[A-X]{2})?\b";
+// RST with readability 3-5, cut numbers allowed (599, 579, 5NN)
+const REPORT_PATTERN: &str = r"\b[345][1-9N][1-9N]\b";
+
+lazy_static! {
+    static ref PATTERNS: [(Kind, Regex); 3] = [
+        (Kind::Callsign, Regex::new(CALLSIGN_PATTERN).unwrap()),
+        (Kind::Grid, Regex::new(GRID_PATTERN).unwrap()),
+        (Kind::Report, Regex::new(REPORT_PATTERN).unwrap()),
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Callsign,
+    Grid,
+    Report,
+}
+
+/// A recognized piece of decoded text and where it sits (byte offsets).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Found {
+    pub kind: Kind,
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Callsigns, grid squares and signal reports in `text`, in order. Where
+/// patterns overlap, the earlier kind in that list wins.
+pub fn extract(text: &str) -> Vec<Found> {
+    let mut found: Vec<Found> = Vec::new();
+    for (kind, pattern) in PATTERNS.iter() {
+        for m in pattern.find_iter(text) {
+            if found.iter().all(|f| m.end() <= f.start || m.start() >= f.end) {
+                found.push(Found { kind: *kind, text: m.as_str().to_string(), start: m.start(), end: m.end() });
+            }
+        }
+    }
+    found.sort_by_key(|f| f.start);
+    found
+}
+
+/// `text` with every found item colored for the terminal.
+pub fn highlight(text: &str, found: &[Found]) -> String {
+    let mut out = String::new();
+    let mut position = 0;
+    for f in found {
+        out.push_str(&text[position..f.start]);
+        let styled = match f.kind {
+            Kind::Callsign => f.text.as_str().yellow().bold(),
+            Kind::Grid => f.text.as_str().cyan(),
+            Kind::Report => f.text.as_str().green(),
+        };
+        out.push_str(&styled.to_string());
+        position = f.end;
+    }
+    out.push_str(&text[position..]);
+    out
+}
+
+/// "[W1AW K1ABC | FN31 | 599]": distinct items of each kind, kinds that
+/// weren't found left out.
+pub fn summary(found: &[Found]) -> String {
+    let groups: Vec<String> = [Kind::Callsign, Kind::Grid, Kind::Report]
+        .into_iter()
+        .filter_map(|kind| {
+            let mut items: Vec<&str> = Vec::new();
+            for f in found.iter().filter(|f| f.kind == kind) {
+                if !items.contains(&f.text.as_str()) {
+                    items.push(&f.text);
+                }
+            }
+            (!items.is_empty()).then(|| items.join(" "))
+        })
+        .collect();
+    if groups.is_empty() {
+        String::new()
+    } else {
+        format!("[{}]", groups.join(" | "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_exchange() {
+        let text = "W1AW DE K1ABC/P UR 5NN 5NN FN31PR TEST 73";
+        let found = extract(text);
+        let kinds: Vec<(Kind, &str)> = found.iter().map(|f| (f.kind, f.text.as_str())).collect();
+        assert_eq!(kinds, [
+            (Kind::Callsign, "W1AW"),
+            (Kind::Callsign, "K1ABC/P"),
+            (Kind::Report, "5NN"),
+            (Kind::Report, "5NN"),
+            (Kind::Grid, "FN31PR"),
+        ]);
+        assert_eq!(&text[found[4].start..found[4].end], "FN31PR");
+        assert_eq!(summary(&found), "[W1AW K1ABC/P | FN31PR | 5NN]");
+    }
+}
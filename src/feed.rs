@@ -0,0 +1,153 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use id3::{Tag, TagLike};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::decode::{audio_files, read_audio};
+use crate::markup::escape;
+
+// ---------- Podcast feed ----------------------------------------------------
+// An RSS 2.0 feed (with the iTunes tags podcast apps look for) listing the
+// rendered files in a directory, so a course can be published next to its
+// files and subscribed to. Episodes are ordered by their track tag, then file
+// name, and dated a minute apart ending at the newest file, so apps that sort
+// by date still list lesson 1 first.
+pub struct Channel {
+    pub title: String,
+    pub description: String,
+    /// URL the files are served from; each episode is this plus its file name
+    pub base_url: String,
+}
+
+#[derive(Debug)]
+pub struct Episode {
+    pub path: PathBuf,
+    pub title: String,
+    pub track: Option<u32>,
+    pub bytes: u64,
+    pub secs: f64,
+    pub published: DateTime<Utc>,
+}
+
+/// Every audio file in `dir`, in course order. Titles and track numbers come
+/// from the files' tags where they have them.
+pub fn episodes(dir: &Path) -> Result<Vec<Episode>> {
+    let mut episodes = Vec::new();
+    let mut newest = DateTime::<Utc>::UNIX_EPOCH;
+    for path in audio_files(dir)? {
+        let metadata = std::fs::metadata(&path).with_context(|| format!("reading {}", path.display()))?;
+        newest = newest.max(metadata.modified().map(DateTime::<Utc>::from).unwrap_or(newest));
+        let (samples, rate) = read_audio(&path.to_string_lossy())?;
+        let tag = Tag::read_from_path(&path).ok();
+        let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        episodes.push(Episode {
+            title: tag.as_ref().and_then(|t| t.title()).map(String::from).unwrap_or(stem),
+            track: tag.as_ref().and_then(|t| t.track()),
+            bytes: metadata.len(),
+            secs: samples.len() as f64 / rate as f64,
+            published: newest,
+            path,
+        });
+    }
+    if episodes.is_empty() {
+        bail!("no audio files in {}", dir.display());
+    }
+    episodes.sort_by(|a, b| (a.track.is_none(), a.track, &a.path).cmp(&(b.track.is_none(), b.track, &b.path)));
+    let count = episodes.len() as i64;
+    for (i, episode) in episodes.iter_mut().enumerate() {
+        episode.published = newest - Duration::minutes(count - 1 - i as i64);
+    }
+    Ok(episodes)
+}
+
+/// Album tag of the first episode that has one, as a default feed title.
+pub fn album(episodes: &[Episode]) -> Option<String> {
+    episodes
+        .iter()
+        .find_map(|e| Tag::read_from_path(&e.path).ok().and_then(|t| t.album().map(String::from)))
+}
+
+/// The feed XML.
+pub fn rss(channel: &Channel, episodes: &[Episode]) -> String {
+    let base = channel.base_url.trim_end_matches('/');
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\">\n<channel>\n");
+    let _ = writeln!(xml, "  <title>{}</title>", escape(&channel.title));
+    let _ = writeln!(xml, "  <link>{}/</link>", escape(base));
+    let _ = writeln!(xml, "  <description>{}</description>", escape(&channel.description));
+    xml.push_str("  <itunes:type>serial</itunes:type>\n");
+    for episode in episodes {
+        let file_name = episode.path.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let url = format!("{}/{}", base, percent_encode(&file_name));
+        let secs = episode.secs.round() as u64;
+        xml.push_str("  <item>\n");
+        let _ = writeln!(xml, "    <title>{}</title>", escape(&episode.title));
+        let _ = writeln!(
+            xml,
+            "    <enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>",
+            escape(&url), episode.bytes, mime_type(&episode.path),
+        );
+        let _ = writeln!(xml, "    <guid isPermaLink=\"false\">{}</guid>", escape(&url));
+        let _ = writeln!(xml, "    <pubDate>{}</pubDate>", episode.published.to_rfc2822());
+        let _ = writeln!(xml, "    <itunes:duration>{:02}:{:02}:{:02}</itunes:duration>", secs / 3600, secs / 60 % 60, secs % 60);
+        if let Some(track) = episode.track {
+            let _ = writeln!(xml, "    <itunes:episode>{}</itunes:episode>", track);
+        }
+        xml.push_str("  </item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") | Some("oga") => "audio/ogg",
+        Some("flac") | Some("fla") => "audio/flac",
+        _ => "audio/wav",
+    }
+}
+
+// File names go into URLs as-is apart from anything outside the unreserved set
+fn percent_encode(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::write_wav;
+    use crate::tags::Tags;
+
+    #[test]
+    fn test_feed_lists_lessons_in_track_order() {
+        let dir = std::env::temp_dir().join(format!("cwgen-feed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, track) in [("b lesson.wav", 1), ("a.wav", 2)] {
+            let path = dir.join(name).to_string_lossy().into_owned();
+            write_wav(&path, 8000, &[0.0; 16000]).unwrap();
+            let title = Some(format!("Lesson {} & more", track));
+            Tags { title, album: Some("Koch".into()), track: Some(track), ..Tags::default() }.write_to_wav(&path).unwrap();
+        }
+        let episodes = episodes(&dir).unwrap();
+        let feed_title = album(&episodes).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(episodes[0].title, "Lesson 1 & more");
+        assert!(episodes[0].published < episodes[1].published);
+        let channel = Channel { title: feed_title, description: String::new(), base_url: "https://example.com/cw/".into() };
+        let xml = rss(&channel, &episodes);
+        assert!(xml.contains("<title>Koch</title>"));
+        assert!(xml.contains("<title>Lesson 1 &amp; more</title>"));
+        assert!(xml.contains("url=\"https://example.com/cw/b%20lesson.wav\""));
+        assert!(xml.contains("<itunes:duration>00:00:02</itunes:duration>"));
+        assert!(xml.find("b%20lesson.wav").unwrap() < xml.find("a.wav").unwrap());
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use crate::morse::{Timing, REVERSE_MORSE, UNKNOWN_CHAR};
+
+// ---------- Fist analysis ---------------------------------------------------
+// What a straight key session sent, and how well it was timed. Each mark is
+// called a dit or a dah, and each space between marks an element, character
+// or word space, by whichever the selected speed puts it nearer to; what
+// they spell is then looked up like any other code. How far the lengths
+// stray from the selected speed, and how much they wander, is what shows a
+// fist up: dahs that are too short, characters that run together, words
+// jammed up against each other.
+
+/// How long a run of marks or spaces of one kind were.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Spread {
+    pub count: usize,
+    /// Seconds
+    pub mean: f64,
+    pub deviation: f64,
+}
+
+impl Spread {
+    fn of(lengths: &[f64]) -> Spread {
+        if lengths.is_empty() {
+            return Spread::default();
+        }
+        let count = lengths.len();
+        let mean = lengths.iter().sum::<f64>() / count as f64;
+        let variance = lengths.iter().map(|length| (length - mean).powi(2)).sum::<f64>() / count as f64;
+        Spread { count, mean, deviation: variance.sqrt() }
+    }
+}
+
+/// A straight key session, decoded and measured.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fist {
+    pub text: String,
+    pub dits: Spread,
+    pub dahs: Spread,
+    pub element_spaces: Spread,
+    pub character_spaces: Spread,
+    pub word_spaces: Spread,
+}
+
+impl Fist {
+    /// Analyse key-down times, each its start and length from the start of
+    /// the session, against the speed `timing` sets.
+    pub fn analyse(marks: &[(Duration, Duration)], timing: &Timing) -> Fist {
+        let dah_from = (timing.dot + timing.dash).as_secs_f64() / 2.0;
+        let character_from = (timing.sym + timing.chr).as_secs_f64() / 2.0;
+        let word_from = (timing.chr + timing.wrd).as_secs_f64() / 2.0;
+
+        let (mut dits, mut dahs) = (Vec::new(), Vec::new());
+        let (mut element_spaces, mut character_spaces, mut word_spaces) = (Vec::new(), Vec::new(), Vec::new());
+        let (mut text, mut code) = (String::new(), String::new());
+        let end_character = |code: &mut String, text: &mut String| {
+            if !code.is_empty() {
+                text.push(REVERSE_MORSE.get(code.as_str()).copied().unwrap_or(UNKNOWN_CHAR));
+                code.clear();
+            }
+        };
+        for (i, &(start, length)) in marks.iter().enumerate() {
+            if i > 0 {
+                let (previous_start, previous_length) = marks[i - 1];
+                let space = start.saturating_sub(previous_start + previous_length).as_secs_f64();
+                if space < character_from {
+                    element_spaces.push(space);
+                } else {
+                    end_character(&mut code, &mut text);
+                    match space < word_from {
+                        true => character_spaces.push(space),
+                        false => {
+                            word_spaces.push(space);
+                            text.push(' ');
+                        }
+                    }
+                }
+            }
+            let length = length.as_secs_f64();
+            match length < dah_from {
+                true => {
+                    dits.push(length);
+                    code.push('.');
+                }
+                false => {
+                    dahs.push(length);
+                    code.push('-');
+                }
+            }
+        }
+        end_character(&mut code, &mut text);
+        Fist {
+            text,
+            dits: Spread::of(&dits),
+            dahs: Spread::of(&dahs),
+            element_spaces: Spread::of(&element_spaces),
+            character_spaces: Spread::of(&character_spaces),
+            word_spaces: Spread::of(&word_spaces),
+        }
+    }
+
+    /// The dah to dit ratio sent, 3 being perfect.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.dits.count > 0 && self.dahs.count > 0).then(|| self.dahs.mean / self.dits.mean)
+    }
+
+    /// The speed the dits (or, failing those, the dahs) were sent at.
+    pub fn wpm(&self) -> Option<f64> {
+        let unit = match (self.dits.count, self.dahs.count) {
+            (0, 0) => return None,
+            (0, _) => self.dahs.mean / 3.0,
+            _ => self.dits.mean,
+        };
+        Some(1.2 / unit)
+    }
+
+    /// The analysis as a table, against the speed `timing` sets.
+    pub fn report(&self, timing: &Timing) -> String {
+        let mut report = format!("Sent: {}\n\n", self.text);
+        let _ = writeln!(report, "{:<18}{:>6}{:>10}{:>9}{:>7}{:>10}", "", "count", "average", "ideal", "off", "spread");
+        let rows = [
+            ("Dits", self.dits, timing.dot),
+            ("Dahs", self.dahs, timing.dash),
+            ("Element spaces", self.element_spaces, timing.sym),
+            ("Character spaces", self.character_spaces, timing.chr),
+            ("Word spaces", self.word_spaces, timing.wrd),
+        ];
+        for (name, spread, ideal) in rows {
+            if spread.count == 0 {
+                continue;
+            }
+            let ideal = ideal.as_secs_f64();
+            let _ = writeln!(
+                report,
+                "{:<18}{:>6}{:>7.0} ms{:>6.0} ms{:>+6.0}%{:>7.0} ms",
+                name,
+                spread.count,
+                spread.mean * 1000.0,
+                ideal * 1000.0,
+                (spread.mean / ideal - 1.0) * 100.0,
+                spread.deviation * 1000.0,
+            );
+        }
+        if let Some(ratio) = self.ratio() {
+            let _ = writeln!(report, "\nDah/dit ratio {:.1} (ideal {:.1})", ratio, timing.dash.as_secs_f64() / timing.dot.as_secs_f64());
+        }
+        if let Some(wpm) = self.wpm() {
+            let _ = writeln!(report, "Keyed at about {:.0} WPM", wpm);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "TEN T" at 20 WPM (60 ms a unit) from a fist with short dahs and
+    // characters run close together
+    fn fist(timing: &Timing) -> Fist {
+        let ms = Duration::from_millis;
+        let mut marks = Vec::new();
+        let mut at = ms(0);
+        for (length, space) in [(150, 120), (60, 120), (150, 60), (60, 420), (150, 0)] {
+            marks.push((at, ms(length)));
+            at += ms(length + space);
+        }
+        Fist::analyse(&marks, timing)
+    }
+
+    #[test]
+    fn test_fist_analysis() {
+        let fist = fist(&Timing::new(20, 0));
+        assert_eq!(fist.text, "TEN T");
+        assert_eq!((fist.dits.count, fist.dahs.count), (2, 3));
+        assert!((fist.dahs.mean - 0.15).abs() < 1e-9 && fist.dahs.deviation < 1e-9);
+        assert_eq!((fist.element_spaces.count, fist.character_spaces.count, fist.word_spaces.count), (1, 2, 1));
+    }
+
+    #[test]
+    fn test_fist_ratio_and_speed() {
+        let fist = fist(&Timing::new(20, 0));
+        assert!((fist.ratio().unwrap() - 2.5).abs() < 1e-9);
+        assert!((fist.wpm().unwrap() - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fist_report() {
+        let timing = Timing::new(20, 0);
+        let report = fist(&timing).report(&timing);
+        assert!(report.starts_with("Sent: TEN T\n"));
+        assert!(report.contains("Dahs                   3    150 ms   180 ms   -17%      0 ms"), "{}", report);
+        assert!(report.contains("Dah/dit ratio 2.5 (ideal 3.0)"));
+    }
+
+    #[test]
+    fn test_empty_fist_report() {
+        let timing = Timing::new(20, 0);
+        assert_eq!(Fist::analyse(&[], &timing).report(&timing), format!("Sent: \n\n{:<18}{:>6}{:>10}{:>9}{:>7}{:>10}\n", "", "count", "average", "ideal", "off", "spread"));
+    }
+}
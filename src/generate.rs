@@ -0,0 +1,279 @@
+use rand::seq::IndexedRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+// ---------- Generated practice text -----------------------------------------
+// Text with realistic letter patterns that can't be learned by heart: a fixed
+// word list gets memorized after a few sessions, these never repeat.
+
+// Items generated per practice session
+pub const COUNT: usize = 200;
+
+// Letters weighted roughly by English frequency, so pseudo-words exercise
+// common characters most
+const CONSONANTS: &[(&str, u32)] = &[
+    ("T", 9), ("N", 7), ("S", 6), ("R", 6), ("H", 6), ("D", 4), ("L", 4), ("C", 3), ("M", 3), ("W", 2),
+    ("F", 2), ("G", 2), ("P", 2), ("B", 2), ("V", 1), ("K", 1), ("J", 1), ("X", 1), ("Q", 1), ("Z", 1),
+    ("TH", 3), ("ST", 2), ("CH", 1), ("SH", 1), ("TR", 1), ("PL", 1), ("BR", 1),
+];
+const VOWELS: &[(&str, u32)] = &[("E", 12), ("A", 8), ("O", 7), ("I", 7), ("U", 3), ("Y", 1), ("EA", 1), ("OU", 1)];
+const CODAS: &[(&str, u32)] = &[
+    ("N", 5), ("R", 5), ("S", 4), ("T", 4), ("L", 3), ("D", 2), ("M", 2), ("NG", 2), ("ST", 1), ("CK", 1), ("X", 1),
+];
+
+fn weighted<'a>(rng: &mut impl Rng, choices: &[(&'a str, u32)]) -> &'a str {
+    choices.choose_weighted(rng, |c| c.1).map(|c| c.0).unwrap_or("E")
+}
+
+/// A pronounceable made-up word of one to three syllables.
+pub fn pseudo_word(rng: &mut impl Rng) -> String {
+    let mut word = String::new();
+    for _ in 0..rng.random_range(1..=3) {
+        if word.is_empty() || rng.random_bool(0.8) {
+            word.push_str(weighted(rng, CONSONANTS));
+        }
+        word.push_str(weighted(rng, VOWELS));
+        if rng.random_bool(0.4) {
+            word.push_str(weighted(rng, CODAS));
+        }
+    }
+    word
+}
+
+pub fn pseudo_words(rng: &mut impl Rng, count: usize) -> Vec<String> {
+    (0..count).map(|_| pseudo_word(rng)).collect()
+}
+
+// ---------- Letter-group drills ---------------------------------------------
+// The most common English bigrams and trigrams (percent of all pairs or
+// triples in a large English corpus), each sent as a group of its own so it
+// gets heard as one sound. This bridges copying single characters and
+// recognizing whole words.
+const BIGRAMS: &[(&str, f64)] = &[
+    ("TH", 3.56), ("HE", 3.07), ("IN", 2.43), ("ER", 2.05), ("AN", 1.99), ("RE", 1.85), ("ON", 1.76),
+    ("AT", 1.49), ("EN", 1.45), ("ND", 1.35), ("TI", 1.34), ("ES", 1.34), ("OR", 1.28), ("TE", 1.20),
+    ("OF", 1.17), ("ED", 1.17), ("IS", 1.13), ("IT", 1.12), ("AL", 1.09), ("AR", 1.07), ("ST", 1.05),
+    ("TO", 1.04), ("NT", 1.04), ("NG", 0.95), ("SE", 0.93), ("HA", 0.93), ("AS", 0.87), ("OU", 0.87),
+    ("IO", 0.83), ("LE", 0.83),
+];
+const TRIGRAMS: &[(&str, f64)] = &[
+    ("THE", 3.51), ("AND", 1.59), ("ING", 1.15), ("ION", 0.73), ("TIO", 0.72), ("ENT", 0.68),
+    ("ATI", 0.56), ("FOR", 0.55), ("HER", 0.53), ("TER", 0.46), ("HAT", 0.44), ("THA", 0.44),
+    ("ERE", 0.43), ("ATE", 0.41), ("HIS", 0.41), ("CON", 0.40), ("RES", 0.38), ("VER", 0.37),
+    ("ALL", 0.37), ("ONS", 0.36),
+];
+
+/// `count` groups drawn by frequency, bigrams and trigrams about equally.
+pub fn letter_groups(rng: &mut impl Rng, count: usize) -> Vec<String> {
+    (0..count)
+        .map(|_| {
+            let table = if rng.random_bool(0.5) { BIGRAMS } else { TRIGRAMS };
+            table.choose_weighted(rng, |g| g.1).map(|g| g.0).unwrap_or("THE").to_string()
+        })
+        .collect()
+}
+
+// ---------- Numeric traffic -------------------------------------------------
+// Numbers the way they turn up on the air rather than as "123 456": UTC times,
+// frequencies in the CW end of each HF band, signal reports (often with cut
+// numbers, N for 9 and T for 0, and a contest serial) and Maidenhead grid
+// squares, which mix letters and digits.
+
+// CW segments in kHz, 160 m to 10 m
+const CW_SEGMENTS: &[(u32, u32)] = &[
+    (1810, 1840), (3500, 3570), (7000, 7040), (10100, 10130), (14000, 14070),
+    (18068, 18095), (21000, 21070), (24890, 24915), (28000, 28070),
+];
+
+/// A UTC time such as 1432Z.
+pub fn utc_time(rng: &mut impl Rng) -> String {
+    format!("{:02}{:02}Z", rng.random_range(0..24), rng.random_range(0..60))
+}
+
+/// A frequency in kHz in the CW end of an HF band, such as 14025.
+pub fn frequency(rng: &mut impl Rng) -> String {
+    let (low, high) = CW_SEGMENTS.choose(rng).copied().unwrap_or((7000, 7040));
+    rng.random_range(low..=high).to_string()
+}
+
+/// A signal report, as sent: 579, or 5NN, often with a contest serial
+/// whose zeros are cut to T too (5NN TT7).
+pub fn report(rng: &mut impl Rng) -> String {
+    let cut = rng.random_bool(0.5);
+    let mut report = format!("5{}9", rng.random_range(3..=9));
+    if cut {
+        report = report.replace('9', "N");
+    }
+    if rng.random_bool(0.5) {
+        let serial = format!("{:03}", rng.random_range(1..=999));
+        let serial = if cut { serial.replace('0', "T") } else { serial };
+        report = format!("{} {}", report, serial);
+    }
+    report
+}
+
+/// A Maidenhead grid square, four characters or six (FN31, FN31PR).
+pub fn grid(rng: &mut impl Rng) -> String {
+    let mut grid = String::new();
+    grid.extend((0..2).map(|_| char::from(b'A' + rng.random_range(0..18))));
+    grid.extend((0..2).map(|_| char::from(b'0' + rng.random_range(0..10))));
+    if rng.random_bool(0.5) {
+        grid.extend((0..2).map(|_| char::from(b'A' + rng.random_range(0..24))));
+    }
+    grid
+}
+
+// ---------- Markov text -----------------------------------------------------
+// Letter-level chain over the words of a corpus: each letter is drawn from
+// what followed the previous two in the corpus. Words that happen to come
+// out as real corpus words are thrown back, so the result is all new.
+const ORDER: usize = 2;
+const START: char = '^';
+const END: char = '$';
+const MAX_LETTERS: usize = 12;
+
+pub struct Markov {
+    transitions: HashMap<String, Vec<(char, u32)>>,
+    known: HashSet<String>,
+}
+
+impl Markov {
+    /// Learn from `corpus`; anything that can't be sent in Morse is dropped.
+    pub fn train(corpus: &str) -> Markov {
+        let mut counts: HashMap<String, HashMap<char, u32>> = HashMap::new();
+        let mut known = HashSet::new();
+        for word in corpus.split_whitespace() {
+            let word: String = word
+                .chars()
+                .map(|c| c.to_ascii_uppercase())
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect();
+            if word.is_empty() {
+                continue;
+            }
+            let padded: Vec<char> = std::iter::repeat_n(START, ORDER).chain(word.chars()).chain([END]).collect();
+            for window in padded.windows(ORDER + 1) {
+                let context: String = window[..ORDER].iter().collect();
+                *counts.entry(context).or_default().entry(window[ORDER]).or_default() += 1;
+            }
+            known.insert(word);
+        }
+        let transitions = counts.into_iter().map(|(context, next)| (context, next.into_iter().collect())).collect();
+        Markov { transitions, known }
+    }
+
+    fn word(&self, rng: &mut impl Rng) -> String {
+        let mut context: Vec<char> = vec![START; ORDER];
+        let mut word = String::new();
+        while word.len() < MAX_LETTERS {
+            let key: String = context[context.len() - ORDER..].iter().collect();
+            let Some(next) = self.transitions.get(&key) else { break };
+            let Ok(&(c, _)) = next.choose_weighted(rng, |n| n.1) else { break };
+            if c == END {
+                break;
+            }
+            word.push(c);
+            context.push(c);
+        }
+        word
+    }
+
+    /// `count` words that aren't in the corpus (or as close as a small
+    /// corpus allows).
+    pub fn words(&self, rng: &mut impl Rng, count: usize) -> Vec<String> {
+        let mut words = Vec::with_capacity(count);
+        let mut tries = 0;
+        while words.len() < count && tries < count * 50 {
+            tries += 1;
+            let word = self.word(rng);
+            if word.len() >= 2 && (!self.known.contains(&word) || tries > count * 40) {
+                words.push(word);
+            }
+        }
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use regex::Regex;
+
+    const CORPUS: &str = "the quick brown fox jumps over the lazy dog while the other dogs sleep \
+                          then they quietly wander over there through the rather thick brown heather";
+
+    fn rng() -> rand::rngs::StdRng {
+        rand::rngs::StdRng::seed_from_u64(7)
+    }
+
+    #[test]
+    fn test_pseudo_words() {
+        let words = pseudo_words(&mut rng(), 50);
+        assert!(words.iter().all(|w| (1..=18).contains(&w.len()) && w.chars().all(|c| c.is_ascii_uppercase())));
+        assert!(words.iter().any(|w| w.contains(['A', 'E', 'I', 'O', 'U'])));
+    }
+
+    #[test]
+    fn test_markov_words() {
+        let words = Markov::train(CORPUS).words(&mut rng(), 30);
+        assert_eq!(words.len(), 30);
+        let known: HashSet<String> = CORPUS.split_whitespace().map(str::to_ascii_uppercase).collect();
+        assert!(words.iter().filter(|w| !known.contains(*w)).count() > 20, "{:?}", words);
+        // Every letter pair comes from the corpus
+        let pairs: HashSet<&[u8]> = known.iter().flat_map(|w| w.as_bytes().windows(2)).collect();
+        assert!(words.iter().all(|w| w.as_bytes().windows(2).all(|pair| pairs.contains(pair))));
+    }
+
+    #[test]
+    fn test_markov_untrained() {
+        assert!(Markov::train("").words(&mut rng(), 5).is_empty());
+    }
+
+    #[test]
+    fn test_letter_groups() {
+        let groups = letter_groups(&mut rng(), 2000);
+        let the = groups.iter().filter(|g| *g == "THE").count();
+        let onsets = groups.iter().filter(|g| *g == "ONS").count();
+        assert!(groups.iter().all(|g| (2..=3).contains(&g.len())));
+        assert!(the > 5 * onsets, "THE {} ONS {}", the, onsets);
+    }
+
+    #[test]
+    fn test_utc_time() {
+        let mut rng = rng();
+        for _ in 0..200 {
+            let time = utc_time(&mut rng);
+            assert!(time.len() == 5 && time[..2] < *"24" && time[2..4] < *"60" && time.ends_with('Z'));
+        }
+    }
+
+    #[test]
+    fn test_frequency() {
+        let mut rng = rng();
+        for _ in 0..200 {
+            let khz: u32 = frequency(&mut rng).parse().unwrap();
+            assert!(CW_SEGMENTS.iter().any(|&(low, high)| (low..=high).contains(&khz)));
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let mut rng = rng();
+        let reports = Regex::new(r"^5[3-9N][9N]( [0-9T]{3})?$").unwrap();
+        for _ in 0..200 {
+            let report = report(&mut rng);
+            assert!(reports.is_match(&report), "{}", report);
+            assert!(!report.contains('N') || !report.contains('0'), "{}", report);
+        }
+    }
+
+    #[test]
+    fn test_grid() {
+        let mut rng = rng();
+        let grids = Regex::new(r"^[A-R]{2}[0-9]{2}([A-X]{2})?$").unwrap();
+        for _ in 0..200 {
+            assert!(grids.is_match(&grid(&mut rng)));
+        }
+    }
+}
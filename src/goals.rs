@@ -0,0 +1,164 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::Deserialize;
+
+use crate::profile::Stats;
+
+// ---------- Goals -----------------------------------------------------------
+// Set in a profile's config.toml:
+//
+//     [goals]
+//     minutes_per_day = 15
+//     wpm = 25
+//     by = "2027-03-01"
+//
+// A day counts towards the streak once it has the daily minutes (or any
+// practice, without a daily goal). Progress towards the speed goal is
+// projected from the fastest speed practiced on each of the last few weeks'
+// days, as a straight line.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Goals {
+    pub minutes_per_day: Option<f64>,
+    pub wpm: Option<u32>,
+    /// Date to reach `wpm` by, as YYYY-MM-DD
+    pub by: Option<String>,
+}
+
+// How far back the speed trend looks
+const TREND_DAYS: i64 = 28;
+
+fn practiced(stats: &Stats) -> impl Iterator<Item = (NaiveDate, f64, u32)> + '_ {
+    stats.days.iter().filter_map(|(date, day)| {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+        Some((date, day.seconds, day.wpm))
+    })
+}
+
+/// Days in a row the goal was met, ending today (or yesterday, if today's
+/// practice isn't done yet).
+pub fn streak(stats: &Stats, goals: &Goals, today: NaiveDate) -> u32 {
+    let needed = goals.minutes_per_day.unwrap_or(0.0) * 60.0;
+    let met = |date: NaiveDate| {
+        stats
+            .days
+            .get(&date.format("%Y-%m-%d").to_string())
+            .is_some_and(|day| day.seconds > 0.0 && day.seconds >= needed)
+    };
+    let mut day = if met(today) { today } else { today - Duration::days(1) };
+    let mut streak = 0;
+    while met(day) {
+        streak += 1;
+        day -= Duration::days(1);
+    }
+    streak
+}
+
+/// Seconds practiced since Monday.
+pub fn this_week(stats: &Stats, today: NaiveDate) -> f64 {
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    practiced(stats).filter(|(date, _, _)| (monday..=today).contains(date)).map(|(_, seconds, _)| seconds).sum()
+}
+
+/// The date the speed trend reaches `target` WPM, or None if there isn't
+/// enough recent practice to tell or speed isn't going up.
+pub fn projected(stats: &Stats, target: u32, today: NaiveDate) -> Option<NaiveDate> {
+    let points: Vec<(f64, f64)> = practiced(stats)
+        .filter(|(date, _, wpm)| *wpm > 0 && (today - *date).num_days() < TREND_DAYS)
+        .map(|(date, _, wpm)| ((date - today).num_days() as f64, wpm as f64))
+        .collect();
+    let latest = points.last()?.1;
+    if latest >= target as f64 {
+        return Some(today);
+    }
+    if points.len() < 2 {
+        return None;
+    }
+    // Least-squares slope in WPM per day
+    let n = points.len() as f64;
+    let (mean_x, mean_y) = (points.iter().map(|p| p.0).sum::<f64>() / n, points.iter().map(|p| p.1).sum::<f64>() / n);
+    let covariance: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let slope = covariance / variance;
+    if slope.is_nan() || slope <= 0.0 {
+        return None;
+    }
+    // Where the fitted line crosses the target, counted from today
+    let days = (target as f64 - (mean_y - slope * mean_x)) / slope;
+    Some(today + Duration::days(days.ceil().max(0.0) as i64))
+}
+
+/// Lines for `cwgen stats` when goals are set (or there's a streak going).
+pub fn report(stats: &Stats, goals: &Goals, today: NaiveDate) -> Vec<String> {
+    let mut lines = Vec::new();
+    let streak = streak(stats, goals, today);
+    let week = this_week(stats, today) / 60.0;
+    match goals.minutes_per_day {
+        Some(minutes) => lines.push(format!(
+            "Streak: {} day{} of {:.0}+ min; {:.0} min this week (goal {:.0})",
+            streak, if streak == 1 { "" } else { "s" }, minutes, week, minutes * 7.0,
+        )),
+        None => lines.push(format!("Streak: {} day{}; {:.0} min this week", streak, if streak == 1 { "" } else { "s" }, week)),
+    }
+    if let Some(target) = goals.wpm {
+        let by = goals.by.as_deref().and_then(|by| NaiveDate::parse_from_str(by, "%Y-%m-%d").ok());
+        let line = match (projected(stats, target, today), by) {
+            (Some(date), _) if date <= today => format!("Speed goal: {} WPM reached", target),
+            (Some(date), Some(by)) if date <= by => format!("Speed goal: {} WPM around {} (on track for {})", target, date, by),
+            (Some(date), Some(by)) => format!("Speed goal: {} WPM around {} (behind; goal is {})", target, date, by),
+            (Some(date), None) => format!("Speed goal: {} WPM around {}", target, date),
+            (None, _) => format!("Speed goal: {} WPM (practice at rising speeds to see a projection)", target),
+        };
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::DayStats;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 15).unwrap() // a Thursday
+    }
+
+    // Ten days up to yesterday, 10 min a day but half that on the 10th, a
+    // WPM faster every other day
+    fn stats() -> Stats {
+        let mut stats = Stats::default();
+        for i in 1..=10 {
+            let date = (today() - Duration::days(i)).format("%Y-%m-%d").to_string();
+            stats.days.insert(date, DayStats { seconds: 600.0, wpm: 20 - (i as u32).div_ceil(2) });
+        }
+        stats.days.get_mut("2026-10-10").unwrap().seconds = 300.0;
+        stats
+    }
+
+    fn goals() -> Goals {
+        Goals { minutes_per_day: Some(10.0), wpm: Some(25), by: Some("2026-11-30".into()) }
+    }
+
+    #[test]
+    fn test_streak() {
+        assert_eq!(streak(&stats(), &goals(), today()), 4);
+        assert_eq!(streak(&stats(), &Goals::default(), today()), 10);
+    }
+
+    #[test]
+    fn test_this_week() {
+        assert_eq!(this_week(&stats(), today()), 1800.0);
+    }
+
+    #[test]
+    fn test_projection() {
+        let today = today();
+        let date = projected(&stats(), 25, today).unwrap();
+        assert!((today + Duration::days(9)..today + Duration::days(14)).contains(&date), "{}", date);
+        assert_eq!(projected(&stats(), 15, today), Some(today));
+    }
+
+    #[test]
+    fn test_report_on_track() {
+        assert!(report(&stats(), &goals(), today())[1].contains("on track"));
+    }
+}
@@ -0,0 +1,239 @@
+// Without the window, only the tests use the trainer
+#![cfg_attr(not(feature = "gui"), allow(dead_code))]
+
+use anyhow::Result;
+use rand::seq::SliceRandom;
+
+use crate::audio::{Speaker, ToneShape};
+use crate::conditions::Conditions;
+use crate::exam::copied_characters;
+use crate::morse::PracticeMode;
+
+// ---------- Desktop trainer -------------------------------------------------
+// `cwgen gui` opens a small window for people who would rather not use a
+// terminal: pick the material and speed, press Start, and type each item as
+// it is heard; the score updates as answers are graded. It is a front end
+// over the same pieces as the terminal trainer (practice content, the audio
+// renderer and the exam's character scoring), and the window itself comes
+// from the optional `gui` feature (egui); without it, asking for the GUI is
+// an error.
+#[derive(Debug, Clone, Copy)]
+pub struct GuiSettings {
+    pub wpm: u32,
+    pub tone: u32,
+    pub gap_ms: u64,
+    pub mode: PracticeMode,
+    pub tone_shape: ToneShape,
+    pub conditions: Conditions,
+}
+
+// Material a session can be drawn from without further input
+pub const MODES: &[(PracticeMode, &str)] = &[
+    (PracticeMode::RandomWords, "Words"),
+    (PracticeMode::Callsigns, "Callsigns"),
+    (PracticeMode::QCodes, "Q-codes"),
+    (PracticeMode::Numbers, "Numbers"),
+    (PracticeMode::PseudoWords, "Pseudo-words"),
+    (PracticeMode::Ngrams, "Letter groups"),
+];
+
+/// Items to send, and the running score over the answers to them.
+pub struct Trainer {
+    items: Vec<String>,
+    next: usize,
+    pub current: Option<String>,
+    /// Characters copied, of those sent
+    pub copied: usize,
+    pub sent: usize,
+    pub answered: usize,
+}
+
+impl Trainer {
+    pub fn new(mode: PracticeMode) -> Result<Self> {
+        let mut rng = rand::rng();
+        let mut items = mode.get_content(&mut rng, None, 1, None);
+        if items.is_empty() {
+            anyhow::bail!("nothing to practice: no items for this mode");
+        }
+        items.shuffle(&mut rng);
+        Ok(Trainer { items, next: 0, current: None, copied: 0, sent: 0, answered: 0 })
+    }
+
+    /// Move on to the next item, starting over when they run out.
+    pub fn next_item(&mut self) -> &str {
+        let item = self.items[self.next % self.items.len()].clone();
+        self.next += 1;
+        self.current.insert(item)
+    }
+
+    /// Score `copy` against the current item: each character sent, and
+    /// whether it was copied.
+    pub fn grade(&mut self, copy: &str) -> Vec<(char, bool)> {
+        let Some(current) = &self.current else { return Vec::new() };
+        let marks = copied_characters(current, copy);
+        self.copied += marks.iter().filter(|(_, copied)| *copied).count();
+        self.sent += marks.len();
+        self.answered += 1;
+        marks
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.sent == 0 { 0.0 } else { 100.0 * self.copied as f64 / self.sent as f64 }
+    }
+}
+
+#[cfg(feature = "gui")]
+pub fn run(settings: GuiSettings, speaker: Speaker) -> Result<()> {
+    let app = window::Trainer::new(settings, speaker)?;
+    let options = eframe::NativeOptions {
+        viewport: eframe::egui::ViewportBuilder::default().with_inner_size([440.0, 300.0]),
+        ..Default::default()
+    };
+    eframe::run_native("cwgen", options, Box::new(|_| Ok(Box::new(app)))).map_err(|e| anyhow::anyhow!("GUI failed: {}", e))
+}
+
+#[cfg(not(feature = "gui"))]
+pub fn run(_settings: GuiSettings, _speaker: Speaker) -> Result<()> {
+    anyhow::bail!("this cwgen was built without the GUI; rebuild with `cargo build --release --features gui`")
+}
+
+#[cfg(feature = "gui")]
+mod window {
+    use anyhow::Result;
+    use eframe::egui::{self, Color32, Key, RichText};
+    use rodio::{OutputStream, Sink};
+
+    use super::{GuiSettings, MODES};
+    use crate::audio::{output_stream, playback_rate, MorseSynth, Speaker};
+    use crate::eq::equalize;
+    use crate::morse::{MorseError, Timing};
+
+    pub struct Trainer {
+        settings: GuiSettings,
+        trainer: super::Trainer,
+        running: bool,
+        answer: String,
+        // The last item and how it was copied
+        last: Option<Vec<(char, bool)>>,
+        _stream: OutputStream,
+        sink: Sink,
+        sample_rate: u32,
+        speaker: Speaker,
+    }
+
+    impl Trainer {
+        pub fn new(settings: GuiSettings, speaker: Speaker) -> Result<Self> {
+            let (stream, handle) = output_stream()?;
+            let sink = Sink::try_new(&handle).map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+            Ok(Trainer {
+                trainer: super::Trainer::new(settings.mode)?,
+                settings,
+                running: false,
+                answer: String::new(),
+                last: None,
+                _stream: stream,
+                sink,
+                sample_rate: playback_rate(),
+                speaker,
+            })
+        }
+
+        fn play(&self) {
+            let Some(item) = &self.trainer.current else { return };
+            let s = &self.settings;
+            let timing = Timing::new(s.wpm, s.gap_ms);
+            self.sink.stop();
+            self.sink.append(equalize(MorseSynth::new(self.sample_rate, item, timing, s.tone, s.conditions, s.tone_shape, None), &self.speaker.eq));
+        }
+
+        fn next(&mut self) {
+            self.trainer.next_item();
+            self.play();
+        }
+
+        fn restart(&mut self) {
+            match super::Trainer::new(self.settings.mode) {
+                Ok(trainer) => self.trainer = trainer,
+                Err(e) => eprintln!("Error: {}", e),
+            }
+            self.last = None;
+            if self.running {
+                self.next();
+            }
+        }
+    }
+
+    impl eframe::App for Trainer {
+        fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.heading("cwgen trainer");
+                ui.add(egui::Slider::new(&mut self.settings.wpm, 5..=40).text("WPM"));
+                ui.add(egui::Slider::new(&mut self.settings.tone, 300..=1200).step_by(10.0).text("Hz"));
+                let mode = self.settings.mode;
+                let name = MODES.iter().find(|(m, _)| *m == mode).map_or("", |(_, name)| *name);
+                egui::ComboBox::from_label("Material").selected_text(name).show_ui(ui, |ui| {
+                    for (choice, label) in MODES {
+                        ui.selectable_value(&mut self.settings.mode, *choice, *label);
+                    }
+                });
+                if mode != self.settings.mode {
+                    self.restart();
+                }
+
+                ui.horizontal(|ui| {
+                    if !self.running && ui.button("Start").clicked() {
+                        self.running = true;
+                        self.next();
+                    }
+                    if self.running && ui.button("Stop").clicked() {
+                        self.running = false;
+                        self.sink.stop();
+                    }
+                    if self.running && ui.button("Repeat").clicked() {
+                        self.play();
+                    }
+                });
+
+                if self.running {
+                    let field = ui.add(egui::TextEdit::singleline(&mut self.answer).hint_text("Type what you heard, then Enter"));
+                    field.request_focus();
+                    if field.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter)) {
+                        self.last = Some(self.trainer.grade(&self.answer));
+                        self.answer.clear();
+                        self.next();
+                    }
+                }
+
+                if let Some(marks) = &self.last {
+                    ui.horizontal(|ui| {
+                        for (ch, copied) in marks {
+                            let colour = if *copied { Color32::GREEN } else { Color32::RED };
+                            ui.label(RichText::new(ch.to_string()).monospace().size(20.0).color(colour));
+                        }
+                    });
+                }
+                let t = &self.trainer;
+                ui.label(format!("Score: {}/{} characters ({:.0}%), {} answered", t.copied, t.sent, t.percent(), t.answered));
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trainer_score() {
+        let mut trainer = Trainer::new(PracticeMode::QCodes).unwrap();
+        let first = trainer.next_item().to_string();
+        let marks = trainer.grade(&first.to_lowercase());
+        assert!(marks.iter().all(|(_, copied)| *copied));
+        trainer.next_item();
+        trainer.grade("");
+        assert_eq!(trainer.copied, 3);
+        assert_eq!(trainer.sent, 6);
+        assert_eq!(trainer.answered, 2);
+        assert_eq!(trainer.percent(), 50.0);
+    }
+}
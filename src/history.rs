@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+// ---------- Interactive history ---------------------------------------------
+// Each line keyed in interactive mode (ended with Enter) is kept so it can be
+// recalled with ↑/↓ and sent again. With a session log, lines are also
+// appended to it as they are finished, one per line after a UTC timestamp:
+//
+//     2026-10-17T14:03:22Z	CQ CQ DE W1AW K
+//
+// and the lines already in the log are loaded first, so the history carries
+// over from one session to the next.
+pub struct History {
+    lines: Vec<String>,
+    // Index into `lines` while scrolling back; None when at the bottom
+    selected: Option<usize>,
+    log: Option<File>,
+}
+
+impl History {
+    pub fn open(log: Option<&str>) -> Result<Self> {
+        let mut lines = Vec::new();
+        let log = match log {
+            Some(path) => {
+                if let Ok(previous) = std::fs::read_to_string(path) {
+                    lines.extend(previous.lines().filter_map(logged_line).map(str::to_string));
+                }
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("opening session log {}", path))?;
+                Some(file)
+            }
+            None => None,
+        };
+        Ok(History { lines, selected: None, log })
+    }
+
+    /// Keep a finished line, and log it. Blank lines and a repeat of the
+    /// last line aren't kept.
+    pub fn push(&mut self, line: &str) -> Result<()> {
+        self.selected = None;
+        let line = line.trim();
+        if line.is_empty() || self.lines.last().is_some_and(|last| last == line) {
+            return Ok(());
+        }
+        self.lines.push(line.to_string());
+        if let Some(log) = &mut self.log {
+            writeln!(log, "{}\t{}", Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true), line)?;
+        }
+        Ok(())
+    }
+
+    /// Scroll back a line (stopping at the oldest).
+    pub fn older(&mut self) -> Option<&str> {
+        let at = match self.selected {
+            Some(at) => at.saturating_sub(1),
+            None => self.lines.len().checked_sub(1)?,
+        };
+        self.selected = Some(at);
+        self.selected()
+    }
+
+    /// Scroll forward a line; past the newest, nothing is selected.
+    pub fn newer(&mut self) -> Option<&str> {
+        self.selected = self.selected.map(|at| at + 1).filter(|&at| at < self.lines.len());
+        self.selected()
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.map(|at| self.lines[at].as_str())
+    }
+}
+
+// The text of a log line, without its timestamp
+fn logged_line(line: &str) -> Option<&str> {
+    let text = line.split_once('\t').map_or(line, |(_, text)| text).trim();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_log() {
+        let path = std::env::temp_dir().join(format!("cwgen-history-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let mut history = History::open(Some(path)).unwrap();
+        assert_eq!(history.older(), None);
+        history.push("CQ CQ DE W1AW").unwrap();
+        history.push("  ").unwrap();
+        history.push("QRL?").unwrap();
+        history.push("QRL?").unwrap();
+
+        // A later session starts with the earlier lines
+        let mut history = History::open(Some(path)).unwrap();
+        assert_eq!(history.older(), Some("QRL?"));
+        assert_eq!(history.older(), Some("CQ CQ DE W1AW"));
+        assert_eq!(history.older(), Some("CQ CQ DE W1AW"));
+        assert_eq!(history.newer(), Some("QRL?"));
+        assert_eq!(history.newer(), None);
+
+        let log = std::fs::read_to_string(path).unwrap();
+        assert_eq!(log.lines().count(), 2);
+        assert!(log.lines().all(|line| line.contains("Z\t")));
+        std::fs::remove_file(path).unwrap();
+    }
+}
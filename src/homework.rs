@@ -0,0 +1,155 @@
+use anyhow::{bail, Context, Result};
+
+use crate::morse::Timing;
+
+// ---------- CW Academy homework ---------------------------------------------
+// Homework session files are practice text under a short header that says how
+// fast to send it and what kind of copy it is:
+//
+//     Title: Beginner Session 4
+//     Speed: 20/10
+//     Content: words
+//
+//     RIG ANT WX TEMP ...
+//
+// Speed is the character speed, then the effective (Farnsworth) speed if
+// different. Word lists, callsigns, numbers and letter groups are drills,
+// run through practice mode item by item; QSOs and sentences are sent
+// straight through like any other text. Lines starting with # are comments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Content {
+    Words,
+    Callsigns,
+    Numbers,
+    Groups,
+    Text,
+}
+
+impl Content {
+    fn parse(s: &str) -> Result<Content> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "words" | "word list" => Content::Words,
+            "callsigns" | "calls" => Content::Callsigns,
+            "numbers" => Content::Numbers,
+            "letters" | "groups" | "code groups" => Content::Groups,
+            "qso" | "qsos" | "sentences" | "text" => Content::Text,
+            other => bail!("unknown content type {:?} (expected words, callsigns, numbers, groups, qso or text)", other),
+        })
+    }
+
+    /// Copied item by item rather than as running text.
+    pub fn is_drill(self) -> bool {
+        self != Content::Text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Homework {
+    pub title: Option<String>,
+    /// Character speed in WPM
+    pub char_wpm: Option<u32>,
+    /// Effective speed, when it's slower than the character speed
+    pub effective_wpm: Option<u32>,
+    pub content: Content,
+    pub text: String,
+}
+
+impl Homework {
+    pub fn load(path: &str) -> Result<Homework> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?;
+        Homework::parse(&text).with_context(|| format!("in {}", path))
+    }
+
+    pub fn parse(source: &str) -> Result<Homework> {
+        let mut homework = Homework { title: None, char_wpm: None, effective_wpm: None, content: Content::Text, text: String::new() };
+        let mut lines = source.lines().filter(|line| !line.trim_start().starts_with('#')).peekable();
+        while let Some(line) = lines.next_if(|line| !line.trim().is_empty()) {
+            let Some((key, value)) = line.split_once(':') else {
+                bail!("expected a header line like \"Speed: 20/10\", got {:?}", line);
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "title" | "session" => homework.title = Some(value.to_string()),
+                "speed" | "wpm" => {
+                    let (char_wpm, effective_wpm) = parse_speed(value)?;
+                    homework.char_wpm = Some(char_wpm);
+                    homework.effective_wpm = effective_wpm;
+                }
+                "content" | "type" => homework.content = Content::parse(value)?,
+                _ => {}
+            }
+        }
+        homework.text = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+        if homework.text.is_empty() {
+            bail!("no practice text after the header");
+        }
+        Ok(homework)
+    }
+
+    /// Timing for the header's speed, or `fallback` if it has none.
+    pub fn timing(&self, gap_ms: u64, fallback: Timing) -> Timing {
+        match (self.char_wpm, self.effective_wpm) {
+            (Some(char_wpm), Some(effective)) => Timing::new_farnsworth(char_wpm, effective, gap_ms),
+            (Some(wpm), None) => Timing::new(wpm, gap_ms),
+            _ => fallback,
+        }
+    }
+}
+
+// "20/10", "20 / 10 wpm" or "15"
+fn parse_speed(value: &str) -> Result<(u32, Option<u32>)> {
+    let value = value.to_ascii_lowercase().replace("wpm", "");
+    let number = |s: &str| -> Result<u32> {
+        let wpm: u32 = s.trim().parse().with_context(|| format!("invalid speed {:?}", value.trim()))?;
+        if !(1..=100).contains(&wpm) {
+            bail!("speed must be 1-100 WPM, got {}", wpm);
+        }
+        Ok(wpm)
+    };
+    match value.split_once('/') {
+        Some((char_wpm, effective)) => {
+            let (char_wpm, effective) = (number(char_wpm)?, number(effective)?);
+            if effective > char_wpm {
+                bail!("effective speed {} is faster than the character speed {}", effective, char_wpm);
+            }
+            Ok((char_wpm, (effective < char_wpm).then_some(effective)))
+        }
+        None => Ok((number(&value)?, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_homework() {
+        let homework = Homework::parse(
+            "# from the session 4 handout\nTitle: Beginner Session 4\nSpeed: 20/10 wpm\nContent: words\n\nRIG ANT\nWX TEMP\n",
+        )
+        .unwrap();
+        assert_eq!(homework.title.as_deref(), Some("Beginner Session 4"));
+        assert_eq!((homework.char_wpm, homework.effective_wpm), (Some(20), Some(10)));
+        assert!(homework.content.is_drill());
+        assert_eq!(homework.text, "RIG ANT\nWX TEMP");
+    }
+
+    #[test]
+    fn test_homework_timing() {
+        let homework = Homework::parse("Speed: 20/10 wpm\n\nRIG ANT").unwrap();
+        let timing = homework.timing(0, Timing::new(25, 0));
+        assert_eq!(timing.dot.as_millis(), 60);
+    }
+
+    #[test]
+    fn test_parse_qso_homework() {
+        let qso = Homework::parse("Speed: 15\nContent: QSO\n\nCQ CQ DE W1AW").unwrap();
+        assert_eq!((qso.char_wpm, qso.effective_wpm, qso.content), (Some(15), None, Content::Text));
+    }
+
+    #[test]
+    fn test_invalid_homework() {
+        assert!(Homework::parse("Speed: 10/20\n\nCQ").is_err());
+        assert!(Homework::parse("Speed: 20\n\n").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::audio::Export;
+use crate::loudness::{limit, report};
+use crate::wav::WavFile;
+
+// ---------- Incremental rendering -------------------------------------------
+// A render is normally built whole in memory and written at the end, so a
+// render of hours of text that is stopped leaves nothing. With
+// `--incremental` the text is rendered CHUNK_WORDS words at a time, and each
+// chunk is written as soon as it is ready with the WAV header brought up to
+// date after it: whatever happens, the file plays up to the last chunk. How
+// far it got is kept beside it in FILE.progress, and `--resume` carries on
+// from there, cutting off any part of a chunk written after that point.
+// Normalizing needs the whole render, so it can't be combined.
+const CHUNK_WORDS: usize = 25;
+
+/// How a render went: chunks written, of which this many were already there
+/// from an earlier run, and the length of the file.
+pub struct Rendered {
+    pub chunks: usize,
+    pub resumed: usize,
+    pub seconds: f64,
+}
+
+/// The text in chunks of CHUNK_WORDS words, each but the last ending with
+/// the space before the next.
+pub fn chunks(text: &str) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let count = words.len().div_ceil(CHUNK_WORDS);
+    words
+        .chunks(CHUNK_WORDS)
+        .enumerate()
+        .map(|(i, words)| words.join(" ") + if i + 1 < count { " " } else { "" })
+        .collect()
+}
+
+/// Render `text` to `path` a chunk at a time with `render`, which is given
+/// each chunk and the time it starts at. With `resume`, an interrupted
+/// render of the same text to the same file is continued.
+pub fn render(
+    path: &str,
+    sample_rate: u32,
+    export: &Export,
+    text: &str,
+    resume: bool,
+    mut render: impl FnMut(&str, f64) -> Vec<f32>,
+) -> Result<Rendered> {
+    let is_wav = Path::new(path).extension().is_some_and(|e| e.eq_ignore_ascii_case("wav"));
+    if !is_wav {
+        anyhow::bail!("--incremental writes WAV: give {} a .wav name, and convert the finished file afterwards", path);
+    }
+    let chunks = chunks(text);
+    let progress = format!("{}.progress", path);
+    let (mut writer, resumed) = match resume && Path::new(path).exists() {
+        true => {
+            let (done, bytes) = read_progress(&progress)?;
+            if done > chunks.len() {
+                anyhow::bail!("{} goes past the end of the text: is it the same text?", progress);
+            }
+            (WavFile::resume(path, sample_rate, export.format, export.channels, bytes)?, done)
+        }
+        false => (WavFile::create(path, sample_rate, export.format, export.channels)?, 0),
+    };
+
+    let mut rng = rand::rng();
+    for (i, chunk) in chunks.iter().enumerate().skip(resumed) {
+        let mut samples = render(chunk, writer.frames() as f64 / sample_rate as f64);
+        if let Some(limited) = limit(&mut samples, sample_rate) {
+            eprintln!("Warning: {}: {}", path, report(&limited, samples.len()));
+        }
+        for sample in samples {
+            writer.write(sample, export.channels, &mut rng)?;
+        }
+        writer.flush()?;
+        write_progress(&progress, i + 1, writer.data_bytes())?;
+    }
+    let seconds = writer.frames() as f64 / sample_rate as f64;
+    writer.finish()?;
+    std::fs::remove_file(&progress).with_context(|| format!("removing {}", progress))?;
+    if !export.tags.is_empty() {
+        export.tags.write_to_wav(path)?;
+    }
+    Ok(Rendered { chunks: chunks.len(), resumed, seconds })
+}
+
+// Chunks written and the bytes of samples they came to, replaced as a whole
+// so a crash leaves the old line or the new one
+fn write_progress(path: &str, chunks: usize, bytes: u64) -> Result<()> {
+    let partial = format!("{}.new", path);
+    std::fs::write(&partial, format!("{} {}\n", chunks, bytes)).with_context(|| format!("writing {}", partial))?;
+    std::fs::rename(&partial, path).with_context(|| format!("writing {}", path))
+}
+
+fn read_progress(path: &str) -> Result<(usize, u64)> {
+    let line = std::fs::read_to_string(path)
+        .with_context(|| format!("reading {} (was the file written with --incremental?)", path))?;
+    let mut fields = line.split_whitespace().map(str::parse::<u64>);
+    match (fields.next(), fields.next()) {
+        (Some(Ok(chunks)), Some(Ok(bytes))) => Ok((chunks as usize, bytes)),
+        _ => anyhow::bail!("{} isn't a progress file", path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::WavFormat;
+
+    fn text(words: usize) -> String {
+        (0..words).map(|i| format!("W{}", i)).collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn test_chunks() {
+        let chunks = chunks(&text(60));
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].ends_with("W24 "));
+    }
+
+    #[test]
+    fn test_incremental_resume() {
+        let path = std::env::temp_dir().join(format!("cwgen-incremental-{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+        let export = Export { format: WavFormat::Float32, ..Export::default() };
+
+        // A run that got through the first chunk, and part of the next,
+        // before it stopped
+        render(path, 8000, &export, &text(50), false, |_, _| vec![0.5; 100]).unwrap();
+        write_progress(&format!("{}.progress", path), 1, 400).unwrap();
+
+        let mut starts = Vec::new();
+        let rendered = render(path, 8000, &export, &text(60), true, |_, start| {
+            starts.push(start);
+            vec![0.25; 100]
+        })
+        .unwrap();
+        assert_eq!((rendered.chunks, rendered.resumed), (3, 1));
+        assert_eq!(starts, [100.0 / 8000.0, 200.0 / 8000.0]);
+
+        let samples: Vec<f32> = hound::WavReader::open(path).unwrap().samples().map(Result::unwrap).collect();
+        assert_eq!(samples.len(), 300);
+        assert!(samples[..100].iter().all(|&s| s == 0.5) && samples[100..].iter().all(|&s| s == 0.25));
+        assert!(!Path::new(&format!("{}.progress", path)).exists());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_incremental_only_wav() {
+        // Whatever the name says
+        let export = Export { format: WavFormat::Float32, ..Export::default() };
+        assert!(render("lesson.opus", 8000, &export, "CQ", false, |_, _| vec![0.5; 100]).is_err());
+        assert!(!Path::new("lesson.opus").exists());
+    }
+}
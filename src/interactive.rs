@@ -1,117 +1,638 @@
 use anyhow::Result;
-use crossterm::event::{self, Event, KeyCode};
+use clap::ValueEnum;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags};
+use crossterm::style::Stylize;
 use crossterm::terminal;
 use rand::seq::SliceRandom;
-use rodio::{OutputStream, Sink};
+use rand::Rng;
+use rodio::buffer::SamplesBuffer;
+use rodio::{Sink, Source};
+use std::collections::VecDeque;
 use std::io::Write;
+use std::time::{Duration, Instant};
 
-use crate::morse::{Timing, PracticeMode, text_to_morse, MorseError};
-use crate::audio::{play_audio, MorseAudio, NoiseSource, ToneShape};
+use crate::morse::{key_timeline, koch_characters, koch_groups, Charset, Envelope, Timing, text_to_code, text_to_morse, MorseError, KOCH_LESSONS, KOCH_PASS, REVERSE_MORSE, UNKNOWN_CHAR};
+use crate::audio::{output_stream, play_audio, playback_rate, MorseSynth, NoiseSource, Sidetone, Speaker, ToneShape};
+use crate::captions;
+use crate::conditions::Conditions;
+use crate::eq::{equalize, Equalizer};
+use crate::compose::{Action, Screen};
+use crate::events;
+use crate::exam::{copied_as, copied_characters};
+use crate::fist::Fist;
+use crate::history::History;
+use crate::latency::{LatencyArgs, Output};
+use crate::paddle::{Element, Iambic, IambicMode};
+use crate::partner::{Partner, Remote};
+use crate::quiz::prompt;
+use crate::record::Recorder;
+use crate::replay::{self, keep};
+use crate::safety::SafetyArgs;
+use crate::sounder;
+use crate::spaced::Weights;
+use crate::vox::{self, VoxArgs};
+use crate::speech::{announcement, phonetic_announcement, Speech};
 use crate::OutputMode;
 
-const PRACTICE_SAMPLE_RATE: u32 = 44100;
-
 // ---------- Interactive mode ----------------------------------------------
+// How often the keyer looks for a partner's characters while idle
+const PARTNER_POLL: Duration = Duration::from_millis(5);
+// How long a spoken-answer session waits for a key before announcing
+const ANSWER_DELAY: Duration = Duration::from_secs(2);
+
+// A character to print once it has been heard; a newline ends a line
+struct Echo {
+    due: Instant,
+    c: char,
+    remote: bool,
+}
+
+// Local and partner echoes interleave; keep the queue in order of `due`
+fn schedule_echo(echoes: &mut VecDeque<Echo>, echo: Echo) {
+    let at = echoes.iter().position(|queued| queued.due > echo.due).unwrap_or(echoes.len());
+    echoes.insert(at, echo);
+}
+
+// With audio, this is a keyboard keyer: keys can be typed ahead while earlier
+// characters are still sounding, and each character is echoed when it is
+// heard. The output stream stays open for the whole session, each character
+// queued on its sink and made as it plays rather than rendered first, and
+// the echo is scheduled for when the sound leaves the speaker rather than when it was
+// handed to the device. VOX keying gets its lead-in whenever the queue has
+// run dry, since that's when the rig will have dropped out of transmit, and
+// that also starts a new transmission for the keying limits: a character
+// that would key too long is refused, and one that would run the
+// transmission too long stops the queue. A practice partner's characters
+// sound on a sink of their own, mixed with the local keying (but never keyed
+// to the rig), and are echoed in another colour. Enter ends a line for the
+// history, which ↑/↓ scroll through and Tab sends again; in compose mode
+// nothing is keyed until a whole line has been written. F2 switches
+// between sound, text and both as the session goes (the sound device is
+// opened the first time it's needed); VOX keying and the sounder stay as
+// they are.
+#[allow(clippy::too_many_arguments)]
 pub fn interactive_mode(
     timing: Timing,
     tone: u32,
-    output: OutputMode,
-    qrm: u8,
+    mut output: OutputMode,
+    conditions: Conditions,
     tone_shape: ToneShape,
+    record: Option<&str>,
+    latency: &LatencyArgs,
+    vox: &VoxArgs,
+    safety: &SafetyArgs,
+    mut partner: Option<Partner>,
+    mut history: History,
+    compose: bool,
+    speaker: &Speaker,
 ) -> Result<()> {
-    println!("Interactive mode – type away (Esc to quit):\n");
+    let mut keyer = match plays(output) {
+        true => Some(Output::open(latency.buffer_size)?),
+        false => None,
+    };
+    let sample_rate = keyer.as_ref().map(|o| o.sample_rate).unwrap_or_else(playback_rate);
+    let recorder = record.map(|_| Recorder::new(sample_rate));
+    let mut sink = keyer.as_ref().map(Output::sink);
+    let mut remote_sink = match output {
+        OutputMode::Vox => None,
+        _ => keyer.as_ref().filter(|_| partner.is_some()).map(Output::sink),
+    };
 
+    match compose {
+        true => println!("Compose mode – write a line and press Enter to send it (↑/↓ recall one, Esc to quit):\n"),
+        false => println!("Interactive mode – type away (Enter ends a line, ↑/↓ recall one, Tab sends it again, Esc to quit):\n"),
+    }
+
+    // When the queued sound runs out, and the characters still to be echoed
+    let mut queue_end = Instant::now();
+    let mut transmission_start = queue_end;
+    let mut remote_end = queue_end;
+    let mut echoes: VecDeque<Echo> = VecDeque::new();
     let mut buf = String::new();
+    // The line being typed, for the history
+    let mut line = String::new();
+    let session_start = Instant::now();
+    let mut keyed = 0;
 
     terminal::enable_raw_mode()?;
+    let mut screen = Screen::new(compose);
     let result = (|| {
     loop {
+        let now = Instant::now();
+        while let Some(echo) = echoes.front() {
+            if echo.due > now {
+                break;
+            }
+            match echo.c {
+                '\n' => screen.newline(),
+                c if echo.remote => screen.echo(&c.cyan().to_string(), 1),
+                c => match (output, text_to_code(&c.to_string(), timing.charset)) {
+                    (OutputMode::Both, Ok(morse)) => screen.echo(&format!("{} ", morse), morse.chars().count() + 1),
+                    _ => screen.echo(&c.to_string(), 1),
+                },
+            }
+            if !echo.remote {
+                captions::heard(echo.c);
+            }
+            if !echo.remote && echo.c != '\n' {
+                events::emit(events::Event::CharSent { ch: echo.c });
+            }
+            echoes.pop_front();
+        }
+        let keyed_latency = || {
+            let reported = keyer.as_ref().map(Output::reported_latency).unwrap_or_default();
+            latency.latency_ms.map(Duration::from_millis).unwrap_or(reported)
+        };
+        while let Some(remote) = partner.as_ref().and_then(|p| p.incoming.try_recv().ok()) {
+            match remote {
+                Remote::Keyed(keyed) => match remote_sink.as_ref().filter(|_| plays(output)) {
+                    Some(remote_sink) => {
+                        let audio = MorseSynth::new(sample_rate, &keyed.ch.to_string(), keyed.timing, keyed.tone, conditions, tone_shape, None);
+                        let length = audio.total_duration().unwrap_or_default();
+                        let starts = remote_end.max(now);
+                        remote_end = starts + length;
+                        schedule_echo(&mut echoes, Echo { due: starts + keyed_latency(), c: keyed.ch, remote: true });
+                        match &recorder {
+                            Some(recorder) => remote_sink.append(equalize(recorder.tap(audio), &speaker.eq)),
+                            None => remote_sink.append(equalize(audio, &speaker.eq)),
+                        }
+                    }
+                    None => match text_to_morse(&keyed.ch.to_string()) {
+                        Ok(morse) if !matches!(output, OutputMode::Vox) => screen.message(&morse.cyan().to_string()),
+                        _ => schedule_echo(&mut echoes, Echo { due: now, c: keyed.ch, remote: true }),
+                    },
+                },
+                Remote::Gone(reason) => {
+                    screen.message(&format!("Partner left: {}", reason));
+                    partner = None;
+                }
+            }
+        }
+        let mut wait = echoes.front().map(|echo| echo.due.saturating_duration_since(now)).unwrap_or(Duration::from_secs(60));
+        if partner.is_some() {
+            wait = wait.min(PARTNER_POLL);
+        }
+        if !event::poll(wait)? {
+            continue;
+        }
         if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Esc => break,
-                KeyCode::Char(c) => {
-                    buf.clear();
-                    buf.push(c);
-
-                    match output {
-                        OutputMode::Text => {
-                            match text_to_morse(&buf) {
-                                Ok(morse) => print!("\r\n{}\r\n", morse),
-                                Err(e) => print!("\r\nError: {}\r\n", e),
-                            }
+            if key.code == KeyCode::Esc {
+                break;
+            }
+            if key.code == KeyCode::F(2) {
+                let next = match output {
+                    OutputMode::Audio => OutputMode::Text,
+                    OutputMode::Text => OutputMode::Both,
+                    OutputMode::Both => OutputMode::Audio,
+                    OutputMode::Vox | OutputMode::KeyerC | OutputMode::Sounder | OutputMode::Tape | OutputMode::Waterfall => continue,
+                };
+                if plays(next) && keyer.is_none() {
+                    match Output::open(latency.buffer_size) {
+                        Ok(opened) => {
+                            sink = Some(opened.sink());
+                            remote_sink = partner.as_ref().map(|_| opened.sink());
+                            keyer = Some(opened);
+                        }
+                        Err(e) => {
+                            screen.message(&format!("Error: {}", e));
+                            continue;
+                        }
+                    }
+                }
+                output = next;
+                let name = output.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default();
+                screen.message(&format!("Output: {}", name).dim().to_string());
+                continue;
+            }
+            let (typed, ends_line): (Vec<char>, bool) = match screen.editor().map(|editor| editor.key(key, &mut history)) {
+                Some(Action::Send(text)) => (text.chars().collect(), true),
+                Some(Action::Edited) => {
+                    screen.draw();
+                    continue;
+                }
+                Some(Action::Ignored) => continue,
+                None => match key.code {
+                    KeyCode::Char(c) => (vec![c], false),
+                    KeyCode::Tab => match history.selected() {
+                        Some(recalled) => (recalled.chars().collect(), false),
+                        None => continue,
+                    },
+                    KeyCode::Enter => (Vec::new(), true),
+                    KeyCode::Up | KeyCode::Down => {
+                        let recalled = match key.code {
+                            KeyCode::Up => history.older(),
+                            _ => history.newer(),
+                        };
+                        if let Some(recalled) = recalled {
+                            screen.message(&format!("» {} (Tab to send)", recalled).dim().to_string());
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                },
+            };
+            for c in typed {
+                buf.clear();
+                buf.push(c);
+                if text_to_code(&buf, timing.charset).is_ok() {
+                    keyed += 1;
+                    line.push(c);
+                    if let Some(Err(e)) = partner.as_mut().map(|p| p.send(c, &timing, tone)) {
+                        screen.message(&format!("Partner left: {}", e));
+                        partner = None;
+                    }
+                }
+
+                match sink.as_ref().filter(|_| plays(output)) {
+                    Some(sink) => {
+                        if let Err(e) = text_to_code(&buf, timing.charset) {
+                            screen.message(&format!("Error: {}", e));
+                            continue;
                         }
-                        OutputMode::Audio => {
-                            if let Err(e) = play_audio(&buf, timing, tone, qrm, tone_shape, None) {
-                                print!("\r\nAudio error: {}\r\n", e);
+                        let now = Instant::now();
+                        // Made as it plays, so the character sounds the
+                        // moment the queue reaches it
+                        let audio: Box<dyn Source<Item = f32> + Send> = match output {
+                            OutputMode::Vox => {
+                                if queue_end <= now {
+                                    transmission_start = now;
+                                }
+                                let keying = safety
+                                    .check(&key_timeline(&buf, &timing), Duration::MAX)
+                                    .and_then(|_| safety.check_transmission(queue_end.max(now) - transmission_start));
+                                if let Err(e) = keying {
+                                    sink.stop();
+                                    echoes.retain(|echo| echo.remote);
+                                    queue_end = now;
+                                    screen.message(&format!("Error: {}", e));
+                                    break;
+                                }
+                                let lead_in = if queue_end <= now { Duration::from_millis(vox.vox_lead_in) } else { Duration::ZERO };
+                                Box::new(SamplesBuffer::new(1, sample_rate, vox::render(&buf, &timing, tone, sample_rate, lead_in)))
                             }
+                            OutputMode::Sounder => Box::new(SamplesBuffer::new(1, sample_rate, sounder::render(&buf, &timing, sample_rate))),
+                            _ => Box::new(MorseSynth::new(sample_rate, &buf, timing, tone, conditions, tone_shape, None)),
+                        };
+                        let length = audio.total_duration().unwrap_or_default();
+                        let starts = queue_end.max(now);
+                        queue_end = starts + length;
+                        schedule_echo(&mut echoes, Echo { due: starts + keyed_latency(), c, remote: false });
+                        // VOX keys a radio, and isn't for ears
+                        let bands = match output {
+                            OutputMode::Vox => &[][..],
+                            _ => &speaker.eq[..],
+                        };
+                        match &recorder {
+                            Some(recorder) => sink.append(Equalizer::new(recorder.tap(audio), bands)),
+                            None => sink.append(Equalizer::new(audio, bands)),
                         }
                     }
+                    None => match text_to_code(&buf, timing.charset) {
+                        Ok(morse) => {
+                            screen.message(&morse);
+                            captions::heard(c);
+                            events::emit(events::Event::CharSent { ch: c });
+                        }
+                        Err(e) => screen.message(&format!("Error: {}", e)),
+                    },
+                }
+            }
+            if ends_line {
+                history.push(&line)?;
+                line.clear();
+                match sink.as_ref().filter(|_| plays(output)) {
+                    Some(_) => schedule_echo(&mut echoes, Echo { due: queue_end.max(Instant::now()) + keyed_latency(), c: '\n', remote: false }),
+                    None => screen.newline(),
                 }
-                _ => {}
             }
         }
     }
+    history.push(&line)?;
     Ok(())
     })();
     terminal::disable_raw_mode()?;
+    if let Some(sink) = &sink {
+        sink.sleep_until_end();
+    }
+    events::emit(events::Event::SessionEnded {
+        mode: "interactive",
+        items: keyed,
+        correct: None,
+        duration_s: session_start.elapsed().as_secs_f64(),
+    });
+    if let (Some(recorder), Some(path)) = (&recorder, record) {
+        recorder.save(path)?;
+        println!("\nSession recorded to: {}", path);
+    }
     result
 }
 
+// ---------- Paddles ---------------------------------------------------------
+// `--paddles` turns two keys into an iambic paddle (see paddle.rs) with a
+// live sidetone, for practising paddle technique without a radio. Elements
+// go out at `timing`'s speed and what they spell is printed as each
+// character ends. Holding a key needs the terminal to say when it is let go,
+// which those speaking the kitty keyboard protocol do (kitty, WezTerm, foot,
+// Ghostty, recent Alacritty); elsewhere each press, and each key repeat,
+// sends one element.
+pub fn paddle_mode(timing: Timing, tone: u32, mode: IambicMode, keys: (char, char), latency: &LatencyArgs, speaker: &Speaker) -> Result<()> {
+    let output = Output::open(latency.buffer_size)?;
+    let (sidetone, key) = Sidetone::new(tone, output.sample_rate);
+    let sink = output.sink();
+    sink.append(equalize(sidetone, &speaker.eq));
+
+    let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    println!(
+        "Paddles – {} is dit, {} is dah, iambic {:?}{}. Esc to quit:\n",
+        keys.0,
+        keys.1,
+        mode,
+        if releases { "" } else { " (this terminal doesn't report key releases: one element a press)" },
+    );
+    terminal::enable_raw_mode()?;
+    if releases {
+        crossterm::execute!(std::io::stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+    }
+    let session_start = Instant::now();
+    let mut sent = 0;
+    let result = (|| {
+        let mut keyer = Iambic::new(mode);
+        let (mut dit, mut dah) = (false, false);
+        // When the key goes up, and when the element's space after it ends
+        let (mut key_up, mut element_end) = (None::<Instant>, Instant::now());
+        let mut code = String::new();
+        let mut in_word = false;
+        let mut out = std::io::stdout();
+        loop {
+            let now = Instant::now();
+            if key_up.is_some_and(|up| up <= now) {
+                key.store(false, std::sync::atomic::Ordering::Relaxed);
+                key_up = None;
+            }
+            if key_up.is_none() && element_end <= now {
+                if let Some(element) = keyer.next() {
+                    let length = match element {
+                        Element::Dit => timing.dot,
+                        Element::Dah => timing.dash,
+                    };
+                    key.store(true, std::sync::atomic::Ordering::Relaxed);
+                    key_up = Some(now + length);
+                    element_end = now + length + timing.sym;
+                    code.push(element.symbol());
+                    continue;
+                }
+                // Idle: a character ends after a character space, a word
+                // after a word space
+                let idle = now - element_end;
+                if !code.is_empty() && idle >= timing.chr - timing.sym {
+                    let ch = REVERSE_MORSE.get(code.as_str()).copied().unwrap_or(UNKNOWN_CHAR);
+                    write!(out, "{}", ch)?;
+                    out.flush()?;
+                    captions::heard(ch);
+                    events::emit(events::Event::CharSent { ch });
+                    code.clear();
+                    in_word = true;
+                    sent += 1;
+                } else if in_word && code.is_empty() && idle >= timing.wrd - timing.sym {
+                    write!(out, " ")?;
+                    out.flush()?;
+                    captions::heard(' ');
+                    in_word = false;
+                }
+            }
+            let deadline = match key_up {
+                Some(up) => up,
+                None if element_end > now => element_end,
+                None if !code.is_empty() => element_end + timing.chr - timing.sym,
+                None if in_word => element_end + timing.wrd - timing.sym,
+                None => now + Duration::from_secs(60),
+            };
+            if !event::poll(deadline.saturating_duration_since(now))? {
+                continue;
+            }
+            let Event::Key(event) = event::read()? else { continue };
+            let element = match event.code {
+                KeyCode::Esc => break,
+                KeyCode::Char(c) if c.eq_ignore_ascii_case(&keys.0) => Element::Dit,
+                KeyCode::Char(c) if c.eq_ignore_ascii_case(&keys.1) => Element::Dah,
+                _ => continue,
+            };
+            match (releases, event.kind) {
+                (true, kind) => {
+                    let down = kind != KeyEventKind::Release;
+                    match element {
+                        Element::Dit => dit = down,
+                        Element::Dah => dah = down,
+                    }
+                    keyer.paddles(dit, dah);
+                }
+                (false, KeyEventKind::Release) => {}
+                (false, _) => keyer.tap(element),
+            }
+        }
+        Ok(())
+    })();
+    key.store(false, std::sync::atomic::Ordering::Relaxed);
+    if releases {
+        let _ = crossterm::execute!(std::io::stdout(), PopKeyboardEnhancementFlags);
+    }
+    terminal::disable_raw_mode()?;
+    println!();
+    events::emit(events::Event::SessionEnded {
+        mode: "paddles",
+        items: sent,
+        correct: None,
+        duration_s: session_start.elapsed().as_secs_f64(),
+    });
+    result
+}
+
+// ---------- Straight key ----------------------------------------------------
+// `--straight-key` keys the sidetone for as long as the space bar is held,
+// showing each element as it ends and a gap as each character or word ends,
+// and once Esc is pressed prints what was sent and how it was timed against
+// the selected speed (see fist.rs). Timing a hold needs the terminal to say
+// when the key is let go, which only those speaking the kitty keyboard
+// protocol do.
+pub fn straight_key_mode(timing: Timing, tone: u32, latency: &LatencyArgs, speaker: &Speaker) -> Result<()> {
+    if !terminal::supports_keyboard_enhancement().unwrap_or(false) {
+        anyhow::bail!(
+            "--straight-key needs a terminal that reports key releases (kitty, WezTerm, foot, Ghostty or a recent Alacritty)"
+        );
+    }
+    let output = Output::open(latency.buffer_size)?;
+    let (sidetone, key) = Sidetone::new(tone, output.sample_rate);
+    let sink = output.sink();
+    sink.append(equalize(sidetone, &speaker.eq));
+
+    println!("Straight key – hold Space to key. Esc to finish:\n");
+    terminal::enable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+    let session_start = Instant::now();
+    let mut marks: Vec<(Duration, Duration)> = Vec::new();
+    let result = (|| {
+        let mut down = None::<Instant>;
+        let mut out = std::io::stdout();
+        loop {
+            let Event::Key(event) = event::read()? else { continue };
+            match (event.code, event.kind) {
+                (KeyCode::Esc, _) => break,
+                (KeyCode::Char(' '), KeyEventKind::Press) if down.is_none() => {
+                    let now = Instant::now();
+                    key.store(true, std::sync::atomic::Ordering::Relaxed);
+                    if let Some(&(start, length)) = marks.last() {
+                        let space = now - (session_start + start + length);
+                        match space {
+                            space if space >= (timing.chr + timing.wrd) / 2 => write!(out, "   ")?,
+                            space if space >= (timing.sym + timing.chr) / 2 => write!(out, " ")?,
+                            _ => {}
+                        }
+                    }
+                    down = Some(now);
+                }
+                (KeyCode::Char(' '), KeyEventKind::Release) => {
+                    let Some(start) = down.take() else { continue };
+                    key.store(false, std::sync::atomic::Ordering::Relaxed);
+                    let length = start.elapsed();
+                    marks.push((start - session_start, length));
+                    write!(out, "{}", if length < (timing.dot + timing.dash) / 2 { '.' } else { '-' })?;
+                    out.flush()?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    })();
+    key.store(false, std::sync::atomic::Ordering::Relaxed);
+    let _ = crossterm::execute!(std::io::stdout(), PopKeyboardEnhancementFlags);
+    terminal::disable_raw_mode()?;
+    println!("\n");
+    let fist = Fist::analyse(&marks, &timing);
+    print!("{}", fist.report(&timing));
+    events::emit(events::Event::SessionEnded {
+        mode: "straight key",
+        items: fist.text.chars().filter(|c| !c.is_whitespace()).count(),
+        correct: None,
+        duration_s: session_start.elapsed().as_secs_f64(),
+    });
+    result
+}
+
+/// Whether `output` is heard (rather than only printed).
+pub fn plays(output: OutputMode) -> bool {
+    matches!(output, OutputMode::Audio | OutputMode::Vox | OutputMode::Both | OutputMode::Sounder)
+}
+
 // ---------- Practice mode ----------------------------------------------
+#[allow(clippy::too_many_arguments)]
 pub fn practice_mode(
+    rng: &mut impl Rng,
     initial_wpm: u32,
     gap_ms: u64,
     farnsworth: Option<u32>,
     tone: u32,
-    mode: PracticeMode,
-    custom_text: Option<&str>,
-    qrm: u8,
+    mut content: Vec<String>,
+    conditions: Conditions,
     tone_shape: ToneShape,
-) -> Result<()> {
-    let mut content = mode.get_content(custom_text);
-    content.shuffle(&mut rand::rng());
+    record: Option<&str>,
+    speak: bool,
+    charset: Charset,
+    envelope: Envelope,
+    binaural: Option<u32>,
+    phonetics: bool,
+    speaker: &Speaker,
+) -> Result<usize> {
+    let mut speech = speak.then(Speech::new).transpose()?;
+    let sample_rate = playback_rate();
+    let recorder = record.map(|_| Recorder::new(sample_rate));
+    if content.is_empty() {
+        anyhow::bail!("nothing to practice: the text has no usable words");
+    }
+    content.shuffle(rng);
 
     println!("Practice mode – {} words available", content.len());
-    println!("Press Space for next, J/← for previous, R to repeat, ↑/↓ to adjust WPM, ? to reveal, Esc to quit:\n");
+    let replays = if speaker.history.is_some() { ", Tab to replay the last few seconds" } else { "" };
+    println!("Press Space for next, J/← for previous, R to repeat{}, ↑/↓ to adjust WPM, ? to reveal, Esc to quit:\n", replays);
 
     let mut current_index = 0;
     let mut current_word = &content[current_index];
     let mut wpm = initial_wpm;
     // Farnsworth requires char_speed > overall_speed, so cap overall WPM below the char speed.
     let max_wpm = farnsworth.map(|f| f.saturating_sub(1)).unwrap_or(100).min(100);
-    let mut timing = build_timing(wpm, gap_ms, farnsworth);
+    let mut timing = build_timing(wpm, gap_ms, farnsworth, charset, envelope, binaural);
 
     // Persistent audio: a continuous QRM sink runs across the entire session
     // so the noise floor never drops between words, repeats, or WPM changes.
     // The tone sink receives a fresh signal-only buffer for each word and gets
     // mixed against the noise by rodio.
-    let (_stream, handle) = OutputStream::try_default()
-        .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+    let (_stream, handle) = output_stream()?;
     let noise_sink = Sink::try_new(&handle)
         .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
-    noise_sink.append(NoiseSource::new(qrm, PRACTICE_SAMPLE_RATE));
+    let noise = NoiseSource::new(conditions, tone, sample_rate);
+    match &recorder {
+        Some(recorder) => noise_sink.append(equalize(keep(recorder.tap(noise), speaker.history.as_ref()), &speaker.eq)),
+        None => noise_sink.append(equalize(keep(noise, speaker.history.as_ref()), &speaker.eq)),
+    }
     let tone_sink = Sink::try_new(&handle)
         .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
 
+    // The noise sink follows the scenario trend on its own; each word's
+    // fading depth is sampled from the same session clock.
+    let session_start = std::time::Instant::now();
+    let mut sent = 0;
+
     terminal::enable_raw_mode()?;
     let result = (|| {
     loop {
-        tone_sink.append(MorseAudio::new_signal_only(
-            PRACTICE_SAMPLE_RATE,
+        let signal = MorseSynth::signal_only(
+            sample_rate,
             current_word,
             timing,
             tone,
+            conditions.at(session_start.elapsed().as_secs_f64()),
             tone_shape,
             None,
-        ));
-        tone_sink.sleep_until_end();
+        )
+        .binaural(timing.binaural);
+        let duration_s = signal.total_duration().unwrap_or_default().as_secs_f64();
+        match &recorder {
+            Some(recorder) => tone_sink.append(equalize(keep(recorder.tap(signal), speaker.history.as_ref()), &speaker.eq)),
+            None => tone_sink.append(equalize(keep(signal, speaker.history.as_ref()), &speaker.eq)),
+        }
+        events::emit(events::Event::PlaybackStarted { text: current_word, duration_s });
+        let mut pressed = std::thread::scope(|scope| {
+            scope.spawn(|| events::chars_as_heard(current_word, &timing, std::time::Instant::now(), speaker.history.as_ref()));
+            replay::wait(&handle, &[&tone_sink, &noise_sink], speaker)
+        })?;
+        sent += 1;
 
-        match event::read()? {
-            Event::Key(key) => match key.code {
+        // Spoken answers make the session hands-free: if no key comes within
+        // the copy time, announce the item and move on
+        if let (Some(speech), None) = (&mut speech, &pressed) {
+            if !event::poll(ANSWER_DELAY)? {
+                speech.say(&match phonetics {
+                    true => phonetic_announcement(current_word),
+                    false => announcement(current_word),
+                })?;
+                print_item(current_word, phonetics);
+                current_index = (current_index + 1) % content.len();
+                current_word = &content[current_index];
+                continue;
+            }
+        }
+
+        // A replay neither moves on nor sends the word again
+        let pressed = loop {
+            match pressed.take().map_or_else(event::read, Ok)? {
+                Event::Key(key) if key.code == replay::KEY && speaker.history.is_some() => replay::replay(&handle, &[&noise_sink], speaker)?,
+                pressed => break pressed,
+            }
+        };
+        if let Event::Key(key) = pressed {
+            match key.code {
                 KeyCode::Esc => break,
                 KeyCode::Char(' ') => {
-                    print!("{} ", current_word);
-                    let _ = std::io::stdout().flush();
+                    print_item(current_word, phonetics);
                     current_index = (current_index + 1) % content.len();
                     current_word = &content[current_index];
                 }
@@ -126,35 +647,323 @@ pub fn practice_mode(
                 KeyCode::Char('r') | KeyCode::Char('R') => {}
                 KeyCode::Up => {
                     wpm = (wpm + 5).min(max_wpm);
-                    timing = build_timing(wpm, gap_ms, farnsworth);
+                    timing = build_timing(wpm, gap_ms, farnsworth, charset, envelope, binaural);
                     print!("({}wpm) ", wpm);
                     let _ = std::io::stdout().flush();
                 }
                 KeyCode::Down => {
                     wpm = wpm.saturating_sub(5).max(1);
-                    timing = build_timing(wpm, gap_ms, farnsworth);
+                    timing = build_timing(wpm, gap_ms, farnsworth, charset, envelope, binaural);
                     print!("({}wpm) ", wpm);
                     let _ = std::io::stdout().flush();
                 }
                 KeyCode::Char('?') => {
-                    print!("[{}]", current_word);
+                    match phonetics {
+                        true => print!("[{}]", crate::phonetics::annotate(current_word)),
+                        false => print!("[{}]", current_word),
+                    }
                     let _ = std::io::stdout().flush();
                 }
                 _ => {}
-            },
-            _ => {}
+            }
         }
     }
     Ok(())
     })();
     terminal::disable_raw_mode()?;
-    result
+    if let (Some(recorder), Some(path)) = (&recorder, record) {
+        recorder.save(path)?;
+        println!("\nSession recorded to: {}", path);
+    }
+    result.map(|()| sent)
+}
+
+// ---------- Copy quiz -------------------------------------------------------
+// `--quiz` turns the word practice modes into a copying test: each word is
+// played, typed as it was copied and checked against what was sent, with
+// the running score after every answer and each word's result at the end.
+// A word counts as copied when every character of it was; the character
+// score gives credit for the near misses. Enter alone plays the word again;
+// q ends the quiz.
+
+/// A copy quiz so far: each word sent, and what was typed for it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CopyQuiz {
+    pub answers: Vec<(String, String)>,
+    pub sent: usize,
+    pub copied: usize,
+}
+
+impl CopyQuiz {
+    /// Score `copy` of `word`: each character and whether it was copied.
+    pub fn score(&mut self, word: &str, copy: &str) -> Vec<(char, bool)> {
+        let marks = copied_characters(word, copy);
+        self.sent += marks.len();
+        self.copied += marks.iter().filter(|(_, copied)| *copied).count();
+        self.answers.push((word.to_string(), copy.to_string()));
+        marks
+    }
+
+    /// Each word, and whether it was copied in full.
+    pub fn words(&self) -> Vec<(String, bool)> {
+        self.answers
+            .iter()
+            .map(|(word, copy)| (word.clone(), copied_characters(word, copy).iter().all(|(_, copied)| *copied)))
+            .collect()
+    }
+
+    /// Words copied in full.
+    pub fn words_copied(&self) -> usize {
+        self.words().iter().filter(|(_, copied)| *copied).count()
+    }
+
+    /// The share of characters copied.
+    pub fn accuracy(&self) -> f64 {
+        if self.sent == 0 { 0.0 } else { self.copied as f64 / self.sent as f64 }
+    }
+
+    /// Each word's result, and the totals.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for (i, (word, copy)) in self.answers.iter().enumerate() {
+            let marks = copied_characters(word, copy);
+            let right = marks.iter().filter(|(_, copied)| *copied).count();
+            let line = match right == marks.len() {
+                true => format!("{:3}. ✓ {}", i + 1, word),
+                false => format!("{:3}. ✗ {:<12} copied {:<12} {:3.0}%", i + 1, word, copy, 100.0 * right as f64 / marks.len().max(1) as f64),
+            };
+            report.push_str(line.trim_end());
+            report.push('\n');
+        }
+        report.push_str(&format!(
+            "\n{}/{} words, {:.0}% of characters ({}/{})\n",
+            self.words_copied(),
+            self.answers.len(),
+            100.0 * self.accuracy(),
+            self.copied,
+            self.sent,
+        ));
+        report
+    }
+}
+
+/// Play practice words, each typed as it was copied and scored.
+#[allow(clippy::too_many_arguments)]
+pub fn copy_quiz_mode(
+    rng: &mut impl Rng,
+    mut content: Vec<String>,
+    timing: Timing,
+    tone: u32,
+    conditions: Conditions,
+    tone_shape: ToneShape,
+    record: Option<&str>,
+    speaker: &Speaker,
+) -> Result<CopyQuiz> {
+    if content.is_empty() {
+        anyhow::bail!("nothing to practice: the text has no usable words");
+    }
+    let recorder = record.map(|_| Recorder::new(playback_rate()));
+    content.shuffle(rng);
+    println!("Copy quiz – type each word as you copied it; Enter alone repeats, q quits.\n");
+    let mut quiz = CopyQuiz::default();
+    for (i, word) in content.iter().cycle().enumerate() {
+        let copy = loop {
+            play_audio(word, timing, tone, conditions, tone_shape, None, recorder.as_ref(), speaker)?;
+            match prompt(&format!("{:3}. > ", i + 1))? {
+                Some(copy) if copy.is_empty() => continue,
+                copy => break copy,
+            }
+        };
+        let Some(copy) = copy else { break };
+        let marks = quiz.score(word, &copy);
+        let right = marks.iter().all(|(_, copied)| *copied);
+        let shown: String = marks
+            .iter()
+            .map(|&(c, copied)| if copied { c.to_string() } else { c.red().to_string() })
+            .collect();
+        println!(
+            "     {} {}   ({}/{} words, {:.0}%)",
+            if right { "✓" } else { "✗" },
+            shown,
+            quiz.words_copied(),
+            quiz.answers.len(),
+            100.0 * quiz.accuracy(),
+        );
+        events::emit(events::Event::ItemScored { item: i + 1, expected: word, answer: &copy, correct: right });
+    }
+    if !quiz.answers.is_empty() {
+        print!("\n{}", quiz.report());
+    }
+    if let (Some(recorder), Some(path)) = (&recorder, record) {
+        recorder.save(path)?;
+        println!("Session recorded to: {}", path);
+    }
+    Ok(quiz)
+}
+
+// ---------- Koch lessons ---------------------------------------------------
+// `--practice koch` sends KOCH_SESSION groups of the lesson's characters (see
+// morse.rs), each typed as it was copied and scored character by character,
+// and at the end says whether copy is good enough to go on to the next.
+const KOCH_SESSION: usize = 20;
+
+/// How a Koch lesson is going: characters sent and copied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KochLesson {
+    pub lesson: usize,
+    pub sent: usize,
+    pub copied: usize,
+    /// Every character sent, and what was copied in its place
+    pub copied_as: Vec<(char, Option<char>)>,
+}
+
+impl KochLesson {
+    pub fn new(lesson: usize) -> Self {
+        KochLesson { lesson, sent: 0, copied: 0, copied_as: Vec::new() }
+    }
+
+    /// Score `copy` of `group`: each character and whether it was copied.
+    pub fn score(&mut self, group: &str, copy: &str) -> Vec<(char, bool)> {
+        let marks = copied_characters(group, copy);
+        self.copied_as.extend(copied_as(group, copy));
+        self.sent += marks.len();
+        self.copied += marks.iter().filter(|(_, copied)| *copied).count();
+        marks
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        if self.sent == 0 { 0.0 } else { self.copied as f64 / self.sent as f64 }
+    }
+
+    /// The lesson to go on to, once copy is KOCH_PASS or better.
+    pub fn next_lesson(&self) -> Option<usize> {
+        (self.sent > 0 && self.accuracy() >= KOCH_PASS && self.lesson < KOCH_LESSONS).then_some(self.lesson + 1)
+    }
+}
+
+/// A Koch lesson: groups from the characters learned by `lesson`, each
+/// copied and scored, and recorded to `record` if given.
+#[allow(clippy::too_many_arguments)]
+pub fn koch_mode(
+    rng: &mut impl Rng,
+    lesson: usize,
+    weights: Option<&Weights>,
+    timing: Timing,
+    tone: u32,
+    conditions: Conditions,
+    tone_shape: ToneShape,
+    record: Option<&str>,
+    speaker: &Speaker,
+) -> Result<KochLesson> {
+    let recorder = record.map(|_| Recorder::new(playback_rate()));
+    let learned = koch_characters(lesson);
+    let names: Vec<String> = learned.iter().map(char::to_string).collect();
+    println!("Koch lesson {}: {} (new: {})", lesson, names.join(" "), learned[learned.len() - 1]);
+    if let Some(weak) = weights.map(|weights| weights.weak(learned)).filter(|weak| !weak.is_empty()) {
+        let weak: Vec<String> = weak.iter().map(char::to_string).collect();
+        println!("Coming up more, missed lately: {}", weak.join(" "));
+    }
+    println!("{} groups. Type each as you copied it; Enter alone repeats, q quits.\n", KOCH_SESSION);
+    let groups = koch_groups(rng, lesson, KOCH_SESSION, weights);
+    let mut state = KochLesson::new(lesson);
+    for (i, group) in groups.iter().enumerate() {
+        let copy = loop {
+            play_audio(group, timing, tone, conditions, tone_shape, None, recorder.as_ref(), speaker)?;
+            match prompt(&format!("{:2}. > ", i + 1))? {
+                Some(copy) if copy.is_empty() => continue,
+                copy => break copy,
+            }
+        };
+        let Some(copy) = copy else { break };
+        let marks = state.score(group, &copy);
+        let right = marks.iter().all(|(_, copied)| *copied);
+        let shown: String = marks
+            .iter()
+            .map(|&(c, copied)| if copied { c.to_string() } else { c.red().to_string() })
+            .collect();
+        println!("    {} {}", if right { "✓" } else { "✗" }, shown);
+        events::emit(events::Event::ItemScored { item: i + 1, expected: group, answer: &copy, correct: right });
+    }
+    if state.sent > 0 {
+        println!("\nCopy {:.0}% ({}/{} characters)", 100.0 * state.accuracy(), state.copied, state.sent);
+        match state.next_lesson() {
+            Some(next) => println!(
+                "Over {:.0}%: on to lesson {}, adding {} (--practice koch --lesson {})",
+                100.0 * KOCH_PASS,
+                next,
+                koch_characters(next)[next],
+                next,
+            ),
+            None if state.lesson == KOCH_LESSONS => println!("That's every character: time for plain text and callsigns"),
+            None => println!("Stay on lesson {} until copy is {:.0}%", lesson, 100.0 * KOCH_PASS),
+        }
+    }
+    if let (Some(recorder), Some(path)) = (&recorder, record) {
+        recorder.save(path)?;
+        println!("Session recorded to: {}", path);
+    }
+    Ok(state)
 }
 
-fn build_timing(wpm: u32, gap_ms: u64, farnsworth: Option<u32>) -> Timing {
-    match farnsworth {
+// An item once it has been sent; with --phonetics, spelled out on a line of
+// its own
+fn print_item(item: &str, phonetics: bool) {
+    match phonetics {
+        true => print!("{}\r\n", crate::phonetics::annotate(item)),
+        false => print!("{} ", item),
+    }
+    let _ = std::io::stdout().flush();
+}
+
+fn build_timing(
+    wpm: u32,
+    gap_ms: u64,
+    farnsworth: Option<u32>,
+    charset: Charset,
+    envelope: Envelope,
+    binaural: Option<u32>,
+) -> Timing {
+    let timing = match farnsworth {
         Some(char_speed) => Timing::new_farnsworth(char_speed, wpm, gap_ms),
         None => Timing::new(wpm, gap_ms),
-    }
+    };
+    timing.with_charset(charset).with_envelope(envelope).with_binaural(binaural)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_koch_lesson() {
+        let mut lesson = KochLesson::new(2);
+        assert_eq!(lesson.next_lesson(), None);
+        for _ in 0..9 {
+            lesson.score("KMUKM", "kmukm");
+        }
+        // One character dropped in fifty is still a pass
+        assert_eq!(lesson.score("MMUKK", "MMUK"), [('M', true), ('M', true), ('U', true), ('K', true), ('K', false)]);
+        assert_eq!((lesson.sent, lesson.copied), (50, 49));
+        assert_eq!(lesson.next_lesson(), Some(3));
+        lesson.score("UUUUU", "KKKKK");
+        assert_eq!(lesson.next_lesson(), None);
+    }
+
+    #[test]
+    fn test_last_koch_lesson() {
+        assert_eq!(KochLesson { copied: 1, sent: 1, ..KochLesson::new(KOCH_LESSONS) }.next_lesson(), None);
+    }
+
+    #[test]
+    fn test_copy_quiz() {
+        let mut quiz = CopyQuiz::default();
+        assert_eq!(quiz.score("PARIS", "paris"), [('P', true), ('A', true), ('R', true), ('I', true), ('S', true)]);
+        quiz.score("HOUSE", "HOSE");
+        assert_eq!((quiz.words_copied(), quiz.sent, quiz.copied), (1, 10, 9));
+        assert_eq!(
+            quiz.report(),
+            "  1. ✓ PARIS\n  2. ✗ HOUSE        copied HOSE          80%\n\n1/2 words, 90% of characters (9/10)\n"
+        );
+    }
+}
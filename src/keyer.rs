@@ -0,0 +1,123 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use std::fmt::Write as _;
+
+use crate::morse::{key_timeline, Timing};
+
+// ---------- Keyer firmware tables -------------------------------------------
+// `--output keyer-c` turns text into a C array of key-down/key-up durations
+// for keyer and beacon firmware, so a greeting or beacon message can be
+// flashed without working out the timing by hand. The array alternates
+// starting with key-down and ends with the word gap, so firmware can loop it.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum KeyerUnits {
+    /// Milliseconds
+    Ms,
+    /// Dot lengths (Farnsworth gaps are rounded to whole dots)
+    Dots,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct KeyerArgs {
+    /// Units of the durations in --output keyer-c
+    #[arg(long, value_enum, default_value_t = KeyerUnits::Ms)]
+    pub keyer_units: KeyerUnits,
+
+    /// Fail if the keyer-c table would have more entries than this
+    #[arg(long, value_name = "N")]
+    pub keyer_max: Option<usize>,
+
+    /// Name of the C array in --output keyer-c
+    #[arg(long, value_name = "IDENT", default_value = "morse_timing")]
+    pub keyer_name: String,
+
+    /// Put the keyer-c table in AVR flash with PROGMEM
+    #[arg(long)]
+    pub progmem: bool,
+}
+
+// Values per line in the generated array
+const PER_LINE: usize = 12;
+
+/// Key-down/key-up durations for `text`, alternating and starting key-down
+/// and ending with at least a word gap.
+pub fn durations(text: &str, timing: &Timing, units: KeyerUnits) -> Vec<u32> {
+    let dot_ms = timing.dot.as_millis().max(1) as f64;
+    let mut elements: Vec<_> = key_timeline(text, timing).into_iter().skip_while(|element| !element.key_down).collect();
+    if let Some(last) = elements.last_mut() {
+        last.duration = last.duration.max(timing.wrd);
+    }
+    elements
+        .into_iter()
+        .map(|element| match units {
+            KeyerUnits::Ms => element.duration.as_millis() as u32,
+            KeyerUnits::Dots => (element.duration.as_millis() as f64 / dot_ms).round() as u32,
+        })
+        .collect()
+}
+
+/// The C source for `text`: an include, a comment with the settings, the
+/// array and its length.
+pub fn c_table(text: &str, timing: &Timing, wpm: u32, args: &KeyerArgs) -> Result<String> {
+    let values = durations(text, timing, args.keyer_units);
+    if values.is_empty() {
+        bail!("nothing to key: the text has no Morse characters");
+    }
+    if let Some(max) = args.keyer_max {
+        if values.len() > max {
+            bail!("the table needs {} entries but --keyer-max is {}; shorten the text", values.len(), max);
+        }
+    }
+    let kind = if values.iter().all(|&v| v <= u16::MAX as u32) { "uint16_t" } else { "uint32_t" };
+    let units = match args.keyer_units {
+        KeyerUnits::Ms => "ms",
+        KeyerUnits::Dots => "dot lengths",
+    };
+    let progmem = if args.progmem { " PROGMEM" } else { "" };
+
+    let mut c = String::new();
+    let _ = writeln!(c, "// \"{}\" at {} WPM", text.trim().replace('\n', " "), wpm);
+    let _ = writeln!(c, "// Key-down/key-up durations in {}, alternating and starting key-down", units);
+    c.push_str("#include <stdint.h>\n");
+    if args.progmem {
+        c.push_str("#include <avr/pgmspace.h>\n");
+    }
+    let _ = writeln!(c, "\nconst {} {}[]{} = {{", kind, args.keyer_name, progmem);
+    for line in values.chunks(PER_LINE) {
+        let line: Vec<String> = line.iter().map(u32::to_string).collect();
+        let _ = writeln!(c, "    {},", line.join(", "));
+    }
+    c.push_str("};\n");
+    let _ = writeln!(c, "const uint16_t {}_len = {};", args.keyer_name, values.len());
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args() -> KeyerArgs {
+        KeyerArgs { keyer_units: KeyerUnits::Ms, keyer_max: None, keyer_name: "cq".into(), progmem: true }
+    }
+
+    #[test]
+    fn test_durations() {
+        let timing = Timing::new(20, 0);
+        // E, then the word gap before it repeats
+        assert_eq!(durations(" E ", &timing, KeyerUnits::Ms), vec![60, 420]);
+        assert_eq!(durations("AN", &timing, KeyerUnits::Dots), vec![1, 1, 3, 3, 3, 1, 1, 7]);
+    }
+
+    #[test]
+    fn test_keyer_table() {
+        let c = c_table("CQ", &Timing::new(20, 0), 20, &args()).unwrap();
+        assert!(c.contains("const uint16_t cq[] PROGMEM = {\n    180, 60, 60, 60, 180, 60, 60, 180,"));
+        assert!(c.contains("const uint16_t cq_len = 16;"));
+    }
+
+    #[test]
+    fn test_keyer_max() {
+        let args = KeyerArgs { keyer_max: Some(8), ..args() };
+        assert!(c_table("CQ", &Timing::new(20, 0), 20, &args).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::Result;
+use rodio::buffer::SamplesBuffer;
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{self, BufferSize, SampleFormat};
+use rodio::dynamic_mixer::{self, DynamicMixerController};
+use rodio::{Sink, Source};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::audio::{host, ToneGenerator, ToneShape};
+use crate::live::Input;
+use crate::morse::MorseError;
+
+// ---------- Output latency --------------------------------------------------
+// A keyer is only usable if the sidetone follows the key closely. Sound
+// reaches the speaker a buffer or more after it is handed over, so the
+// interactive keyer keeps one output stream open (at `--buffer-size` if
+// given), and schedules everything it shows around the latency: measured by
+// `--measure-latency`, given with `--latency-ms`, or else as the driver
+// reports it.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct LatencyArgs {
+    /// Estimate the sound card's output latency (and the round trip, with the
+    /// output looped back to the input) and exit
+    #[arg(long)]
+    pub measure_latency: bool,
+
+    /// Output buffer size in frames for the interactive keyer; smaller
+    /// buffers answer faster but may crackle
+    #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(16..=16384))]
+    pub buffer_size: Option<u32>,
+
+    /// Output latency the keyer compensates for, in ms (default: as the
+    /// driver reports it)
+    #[arg(long, value_name = "MS")]
+    pub latency_ms: Option<u64>,
+}
+
+/// An open output stream that sinks can be added to.
+pub struct Output {
+    // Playback stops when the stream is dropped
+    _stream: cpal::Stream,
+    mixer: Arc<DynamicMixerController<f32>>,
+    pub sample_rate: u32,
+    // Latest playback-minus-callback time the driver gave, in µs
+    reported: Arc<AtomicU64>,
+}
+
+impl Output {
+    /// The default output device, with `buffer_size` frames per buffer if
+    /// given (rodio always uses the device default).
+    pub fn open(buffer_size: Option<u32>) -> Result<Self> {
+        let device = host()?
+            .default_output_device()
+            .ok_or_else(|| MorseError::AudioDeviceError("no output device".into()))?;
+        let supported = device
+            .default_output_config()
+            .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+        let sample_rate = supported.sample_rate().0;
+        let format = supported.sample_format();
+        let mut config: cpal::StreamConfig = supported.into();
+        if let Some(frames) = buffer_size {
+            config.buffer_size = BufferSize::Fixed(frames);
+        }
+
+        let (mixer, mut source) = dynamic_mixer::mixer::<f32>(config.channels, sample_rate);
+        let reported = Arc::new(AtomicU64::new(0));
+        let latest = reported.clone();
+        let note_latency = move |info: &cpal::OutputCallbackInfo| {
+            let timestamp = info.timestamp();
+            if let Some(latency) = timestamp.playback.duration_since(&timestamp.callback) {
+                latest.store(latency.as_micros() as u64, Ordering::Relaxed);
+            }
+        };
+        let on_error = |e| eprintln!("Audio output error: {}", e);
+        let stream = match format {
+            SampleFormat::F32 => device.build_output_stream(
+                &config,
+                move |data: &mut [f32], info: &_| {
+                    note_latency(info);
+                    data.iter_mut().for_each(|d| *d = source.next().unwrap_or(0.0));
+                },
+                on_error,
+                None,
+            ),
+            SampleFormat::I16 => device.build_output_stream(
+                &config,
+                move |data: &mut [i16], info: &_| {
+                    note_latency(info);
+                    data.iter_mut().for_each(|d| *d = (source.next().unwrap_or(0.0).clamp(-1.0, 1.0) * 32767.0) as i16);
+                },
+                on_error,
+                None,
+            ),
+            format => return Err(MorseError::AudioDeviceError(format!("unsupported sample format {}", format)).into()),
+        }
+        .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+        stream.play().map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+        Ok(Output { _stream: stream, mixer, sample_rate, reported })
+    }
+
+    pub fn sink(&self) -> Sink {
+        let (sink, queue) = Sink::new_idle();
+        self.mixer.add(queue);
+        sink
+    }
+
+    /// Output latency as the driver last reported it (zero until the first
+    /// buffer has gone out, or if the driver doesn't say).
+    pub fn reported_latency(&self) -> Duration {
+        Duration::from_micros(self.reported.load(Ordering::Relaxed))
+    }
+}
+
+// ---------- Measuring -------------------------------------------------------
+// Clicks (short 1 kHz bursts) are played at known times while the default
+// input listens. With a cable from output to input, or the microphone held
+// to the speaker, each click comes back after the round trip.
+const CLICKS: usize = 5;
+const CLICK_SECS: f64 = 0.02;
+const CLICK_INTERVAL_SECS: f64 = 0.5;
+const CLICK_HZ: u32 = 1000;
+// A click counts once the input is this far over its noise floor
+const ONSET_RATIO: f32 = 8.0;
+const ONSET_MINIMUM: f32 = 0.01;
+
+pub fn measure(args: &LatencyArgs) -> Result<()> {
+    let output = Output::open(args.buffer_size)?;
+    let rate = output.sample_rate;
+    if let Some(frames) = args.buffer_size {
+        println!("Output buffer: {} frames ({:.1} ms at {} Hz)", frames, frames as f64 * 1000.0 / rate as f64, rate);
+    }
+    let input = match Input::soundcard() {
+        Ok(input) => Some(input),
+        Err(e) => {
+            eprintln!("No input to measure the round trip with: {}", e);
+            None
+        }
+    };
+
+    let mut tone = ToneGenerator::new(CLICK_HZ, rate, ToneShape::Sine, None);
+    let interval = (CLICK_INTERVAL_SECS * rate as f64) as usize;
+    let click = (CLICK_SECS * rate as f64) as usize;
+    let samples: Vec<f32> = (0..interval * (CLICKS + 1))
+        .map(|i| if i >= interval && i % interval < click { 0.5 * tone.next_sample(0.0) } else { 0.0 })
+        .collect();
+    let started = Arc::new(Mutex::new(None));
+    let sink = output.sink();
+    sink.append(Stamped { source: SamplesBuffer::new(1, rate, samples), started: started.clone(), noted: false });
+
+    // Time-stamp the input as it arrives, so it can be lined up with the
+    // moment the clicks were handed to the device
+    let mut captured = Vec::new();
+    let mut first_block: Option<Instant> = None;
+    let listen_until = Instant::now() + Duration::from_secs_f64(CLICK_INTERVAL_SECS * (CLICKS + 2) as f64);
+    while Instant::now() < listen_until {
+        match &input {
+            Some(input) => {
+                if let Some(block) = input.block_within(Duration::from_millis(100)) {
+                    let arrived = Instant::now() - Duration::from_secs_f64(block.len() as f64 / input.sample_rate as f64);
+                    first_block.get_or_insert(arrived);
+                    captured.extend(block);
+                }
+            }
+            None => std::thread::sleep(Duration::from_millis(100)),
+        }
+    }
+    sink.stop();
+
+    let reported = output.reported_latency();
+    if reported.is_zero() {
+        println!("Output latency: not reported by the driver");
+    } else {
+        println!("Output latency reported by the driver: {:.1} ms", reported.as_secs_f64() * 1000.0);
+    }
+    let started = *started.lock().unwrap();
+    let trips = match (input, first_block, started) {
+        (Some(input), Some(first_block), Some(started)) => {
+            let offset = started.saturating_duration_since(first_block).as_secs_f64();
+            round_trips(&captured, input.sample_rate, offset)
+        }
+        _ => Vec::new(),
+    };
+    match median(&trips) {
+        Some(trip) => {
+            println!("Round trip through the input: {:.1} ms ({} of {} clicks heard)", trip * 1000.0, trips.len(), CLICKS);
+            println!("The keyer can compensate with --latency-ms {:.0} (or latency_ms in the profile)", trip * 1000.0 / 2.0);
+        }
+        None => println!(
+            "No clicks came back: connect the output to the input (or hold the microphone to the speaker) to measure the round trip"
+        ),
+    }
+    Ok(())
+}
+
+/// Seconds from each click being sent to it being heard in `captured`,
+/// whose first sample came `offset` seconds before the first click's
+/// interval started.
+fn round_trips(captured: &[f32], sample_rate: u32, offset: f64) -> Vec<f64> {
+    let floor = noise_floor(captured);
+    let threshold = (floor * ONSET_RATIO).max(ONSET_MINIMUM);
+    (1..=CLICKS)
+        .filter_map(|n| {
+            let sent = offset + n as f64 * CLICK_INTERVAL_SECS;
+            let from = (sent * sample_rate as f64) as usize;
+            let until = ((sent + CLICK_INTERVAL_SECS) * sample_rate as f64) as usize;
+            let found = onset(captured.get(from..until.min(captured.len()))?, threshold)?;
+            Some(found as f64 / sample_rate as f64)
+        })
+        .collect()
+}
+
+/// Index of the first sample at or over `threshold`.
+fn onset(samples: &[f32], threshold: f32) -> Option<usize> {
+    samples.iter().position(|s| s.abs() >= threshold)
+}
+
+// Median absolute level: the clicks are too short to move it
+fn noise_floor(samples: &[f32]) -> f32 {
+    let mut levels: Vec<f32> = samples.iter().map(|s| s.abs()).collect();
+    levels.sort_by(f32::total_cmp);
+    levels.get(levels.len() / 2).copied().unwrap_or(0.0)
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted.get(sorted.len() / 2).copied()
+}
+
+// Notes when the output first pulls from the source, which is when its
+// first sample went into a device buffer
+struct Stamped<S> {
+    source: S,
+    started: Arc<Mutex<Option<Instant>>>,
+    noted: bool,
+}
+
+impl<S: Source<Item = f32>> Iterator for Stamped<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if !self.noted {
+            *self.started.lock().unwrap() = Some(Instant::now());
+            self.noted = true;
+        }
+        self.source.next()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Stamped<S> {
+    fn current_frame_len(&self) -> Option<usize> { self.source.current_frame_len() }
+    fn channels(&self) -> u16 { self.source.channels() }
+    fn sample_rate(&self) -> u32 { self.source.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.source.total_duration() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        // Quiet noise, then each click coming back 30 ms late
+        let rate = 8000;
+        let offset = 0.2;
+        let mut captured: Vec<f32> = (0..rate as usize * 4).map(|i| 0.001 * ((i * 7919) % 13) as f32 / 13.0).collect();
+        for n in 1..=CLICKS {
+            let at = ((offset + n as f64 * CLICK_INTERVAL_SECS + 0.03) * rate as f64) as usize;
+            for sample in &mut captured[at..at + 160] {
+                *sample = 0.3;
+            }
+        }
+        let trips = round_trips(&captured, rate, offset);
+        assert_eq!(trips.len(), CLICKS);
+        assert!((median(&trips).unwrap() - 0.03).abs() < 0.001, "{:?}", trips);
+        assert!(round_trips(&vec![0.0; 800], rate, offset).is_empty());
+    }
+}
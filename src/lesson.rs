@@ -0,0 +1,126 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::audio::{MorseAudio, ToneShape};
+use crate::conditions::Conditions;
+use crate::decode::read_audio;
+use crate::mix::place;
+use crate::morse::Timing;
+use crate::resample::resample;
+
+// ---------- Lesson markup ---------------------------------------------------
+// Lesson text may mark where recorded prompts go, in braces:
+//
+//     {intro.wav} PARIS PARIS {next-group.mp3} QRZ QRZ
+//
+// Everything outside the braces is sent as CW. Prompt paths are relative to
+// `base` (the lesson file's directory) unless absolute.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Prompt(PathBuf),
+}
+
+pub fn has_prompts(text: &str) -> bool {
+    text.contains('{')
+}
+
+pub fn segments<'a>(text: &'a str, base: Option<&Path>) -> Result<Vec<Segment<'a>>> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..].find('}').map(|i| open + i).context("unclosed { in lesson text")?;
+        let name = rest[open + 1..close].trim();
+        if name.is_empty() {
+            bail!("empty {{}} in lesson text");
+        }
+        segments.push(Segment::Text(rest[..open].trim()));
+        segments.push(Segment::Prompt(base.map_or_else(|| PathBuf::from(name), |base| base.join(name))));
+        rest = &rest[close + 1..];
+    }
+    if rest.contains('}') {
+        bail!("unmatched }} in lesson text");
+    }
+    segments.push(Segment::Text(rest.trim()));
+    segments.retain(|s| !matches!(s, Segment::Text("")));
+    Ok(segments)
+}
+
+/// The lesson's CW with the prompts left out, for text output.
+pub fn text_only(segments: &[Segment]) -> String {
+    let texts: Vec<&str> = segments
+        .iter()
+        .filter_map(|s| match s {
+            Segment::Text(t) => Some(*t),
+            Segment::Prompt(_) => None,
+        })
+        .collect();
+    texts.join(" ")
+}
+
+// ---------- Rendering -------------------------------------------------------
+/// Render the lesson at `sample_rate`: each CW passage as usual, and each
+/// prompt read from its file (WAV, MP3, Ogg or FLAC), with a word space
+/// either side so speech never runs into the code.
+#[allow(clippy::too_many_arguments)]
+pub fn render(
+    segments: &[Segment],
+    timing: Timing,
+    tone: u32,
+    conditions: Conditions,
+    tone_shape: ToneShape,
+    drift_percentage: Option<u8>,
+    sample_rate: u32,
+) -> Result<Vec<f32>> {
+    let pause = (timing.wrd.as_secs_f64() * sample_rate as f64) as usize;
+    let mut lesson = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        match segment {
+            Segment::Text(text) => {
+                let audio = MorseAudio::new_with_sample_rate(
+                    sample_rate, text, timing, tone, conditions, tone_shape, drift_percentage,
+                );
+                lesson.extend_from_slice(audio.get_samples());
+            }
+            Segment::Prompt(path) => {
+                let name = path.display().to_string();
+                let (samples, rate) = read_audio(&name).with_context(|| format!("reading prompt {}", name))?;
+                let start = if i == 0 { 0 } else { lesson.len() + pause };
+                place(&mut lesson, &resample(&samples, rate, sample_rate)?, start, 0.0);
+                if i + 1 < segments.len() {
+                    lesson.resize(lesson.len() + pause, 0.0);
+                }
+            }
+        }
+    }
+    Ok(lesson)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "{intro.wav}\nPARIS PARIS {/prompts/next.mp3} QRZ";
+
+    #[test]
+    fn test_lesson_segments() {
+        let segments = segments(TEXT, Some(Path::new("course"))).unwrap();
+        assert_eq!(segments, [
+            Segment::Prompt(PathBuf::from("course/intro.wav")),
+            Segment::Text("PARIS PARIS"),
+            Segment::Prompt(PathBuf::from("/prompts/next.mp3")),
+            Segment::Text("QRZ"),
+        ]);
+    }
+
+    #[test]
+    fn test_text_only() {
+        assert_eq!(text_only(&segments(TEXT, None).unwrap()), "PARIS PARIS QRZ");
+    }
+
+    #[test]
+    fn test_unbalanced_braces() {
+        assert!(segments("PARIS {next.wav", None).is_err());
+        assert!(segments("PARIS } QRZ", None).is_err());
+    }
+}
@@ -0,0 +1,639 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{self, SampleFormat};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::audio::{host, write_wav};
+use crate::decode::{
+    envelope_bias, estimate_unit, find_signals, levels, margin, read_char, track, Bandpass,
+    DecodedChar, BLOCK_SECS, SIGNAL_BANDWIDTH_HZ,
+};
+use crate::morse::{Element, MorseError};
+use crate::pcm::RawInput;
+use crate::udp::Broadcast;
+
+// Audio gathered before searching for a signal
+const SEARCH_SECS: f64 = 1.0;
+// Audio kept from before a transmission is detected, so recordings start
+// before the search and squelch caught up with it
+const PREROLL_SECS: f64 = SEARCH_SECS + 1.0;
+// Marks timed at the start of each transmission before decoding it
+const TRAINING_MARKS: usize = 12;
+// Gaps (in units) past which a character or word is certainly over
+const CHAR_DONE: f64 = 3.0;
+const WORD_GAP: f64 = 5.0;
+const WORD_DONE: f64 = 7.0;
+// Per-block smoothing of the tracked signal level and noise floor: both
+// follow the envelope quickly in their own direction and slowly otherwise
+const PEAK_ATTACK: f32 = 0.1;
+const PEAK_DECAY: f32 = 0.999;
+const FLOOR_FALL: f32 = 0.005;
+const FLOOR_RISE: f32 = 0.0005;
+
+pub enum LiveEvent {
+    /// Found a signal and locked onto its pitch (Hz)
+    Locked(f64),
+    /// The squelch opened on a keyed signal
+    SignalStart,
+    Char(DecodedChar),
+    /// Nothing keyed for the timeout; the transmission is over
+    SignalEnd,
+}
+
+// ---------- Streaming decoder ----------------------------------------------
+/// Decodes CW as audio arrives. It gathers a second of audio, searches it
+/// with the Goertzel bank (unless the tone was given), then locks onto the
+/// strongest signal and follows it with a narrow filter, priming its level
+/// and speed estimates from that first second. Characters come out as soon
+/// as the following gap settles them. The speed is estimated afresh from the
+/// first few characters of every transmission, then tracked.
+///
+/// Keying only counts while the squelch is open (signal level at least
+/// `squelch_db` over the noise floor). A transmission ends after `timeout`
+/// seconds without keying; when the tone was found by searching, the decoder
+/// then goes back to searching for the next signal.
+pub struct LiveDecoder {
+    sample_rate: u32,
+    search: (f64, f64),
+    tone: Option<f64>,
+    squelch: f32,
+    timeout_blocks: usize,
+    active: bool,
+    quiet_blocks: usize,
+    pending: Vec<f32>,
+    filter: Option<(f64, Bandpass)>,
+    block: usize,
+    block_energy: f32,
+    block_len: usize,
+    floor: f32,
+    peak: f32,
+    key_down: bool,
+    run_blocks: usize,
+    unit: f64,
+    bias: f64,
+    training: Option<Vec<Element>>,
+    marks: Vec<f64>,
+    inner_gaps: Vec<f64>,
+    char_done: bool,
+    word_done: bool,
+}
+
+impl LiveDecoder {
+    /// Decoder that searches `search` (Hz) for a signal, or listens on
+    /// `tone` directly when it is given.
+    pub fn new(sample_rate: u32, tone: Option<f64>, search: (f64, f64), squelch_db: f64, timeout: f64) -> Self {
+        let block = ((sample_rate as f64 * BLOCK_SECS) as usize).max(1);
+        LiveDecoder {
+            sample_rate,
+            search,
+            tone,
+            squelch: 10f32.powf(squelch_db as f32 / 20.0),
+            timeout_blocks: (timeout * sample_rate as f64 / block as f64) as usize,
+            active: false,
+            quiet_blocks: 0,
+            pending: Vec::new(),
+            filter: None,
+            block,
+            block_energy: 0.0,
+            block_len: 0,
+            floor: 0.0,
+            peak: 0.0,
+            key_down: false,
+            run_blocks: 0,
+            unit: 0.0,
+            bias: 0.0,
+            training: None,
+            marks: Vec::new(),
+            inner_gaps: Vec::new(),
+            char_done: true,
+            word_done: true,
+        }
+    }
+
+    pub fn feed(&mut self, samples: &[f32]) -> Vec<LiveEvent> {
+        let mut events = Vec::new();
+        self.process(samples, &mut events);
+        events
+    }
+
+    /// Emit whatever character is still waiting for its closing gap, and
+    /// close the transmission in progress.
+    pub fn flush(&mut self) -> Vec<LiveEvent> {
+        let mut events = Vec::new();
+        self.end_transmission(&mut events);
+        events
+    }
+
+    fn end_transmission(&mut self, events: &mut Vec<LiveEvent>) {
+        self.train(events);
+        if !self.char_done && !self.marks.is_empty() {
+            events.push(LiveEvent::Char(self.take_char(1.0)));
+        }
+        if self.active {
+            events.push(LiveEvent::SignalEnd);
+            self.active = false;
+        }
+    }
+
+    // Gather audio until there's enough to search, then lock and decode it
+    fn search(&mut self, samples: &[f32], events: &mut Vec<LiveEvent>) {
+        self.pending.extend_from_slice(samples);
+        if (self.pending.len() as f64) < SEARCH_SECS * self.sample_rate as f64 {
+            return;
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let found = self.tone.or_else(|| find_signals(&pending, self.sample_rate, self.search, 1).first().copied());
+        if let Some(tone) = found {
+            events.push(LiveEvent::Locked(tone));
+            self.lock(tone, &pending);
+            self.process(&pending, events);
+        }
+    }
+
+    // Prime the level trackers from the audio the search ran on
+    fn lock(&mut self, tone: f64, audio: &[f32]) {
+        let mut filter = Bandpass::new(self.sample_rate, tone, SIGNAL_BANDWIDTH_HZ);
+        let filtered: Vec<f32> = audio.iter().map(|&x| filter.process(x)).collect();
+        let envelope: Vec<f32> = filtered
+            .chunks(self.block)
+            .map(|chunk| (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt())
+            .collect();
+        let mean = envelope.iter().sum::<f32>() / envelope.len().max(1) as f32;
+        (self.floor, self.peak) = levels(&envelope).unwrap_or((mean, mean));
+        self.filter = Some((tone, Bandpass::new(self.sample_rate, tone, SIGNAL_BANDWIDTH_HZ)));
+        self.key_down = false;
+        self.run_blocks = 0;
+        self.quiet_blocks = 0;
+    }
+
+    fn process(&mut self, samples: &[f32], events: &mut Vec<LiveEvent>) {
+        for (i, &sample) in samples.iter().enumerate() {
+            let Some((_, filter)) = self.filter.as_mut() else {
+                // Lost the signal part way through; search the rest
+                return self.search(&samples[i..], events);
+            };
+            let x = filter.process(sample);
+            self.block_energy += x * x;
+            self.block_len += 1;
+            if self.block_len == self.block {
+                let level = (self.block_energy / self.block_len as f32).sqrt();
+                self.block_energy = 0.0;
+                self.block_len = 0;
+                self.step(level, events);
+            }
+        }
+    }
+
+    // One envelope block: track levels, key with hysteresis, time the runs
+    fn step(&mut self, level: f32, events: &mut Vec<LiveEvent>) {
+        if level > self.peak {
+            self.peak += (level - self.peak) * PEAK_ATTACK;
+        } else {
+            self.peak *= PEAK_DECAY;
+        }
+        let rate = if level < self.floor { FLOOR_FALL } else { FLOOR_RISE };
+        self.floor += (level - self.floor) * rate;
+        let span = self.peak - self.floor;
+        let open = self.peak > self.floor * self.squelch;
+        let key_down = open && if self.key_down {
+            level > self.floor + span * 0.35
+        } else {
+            level > self.floor + span * 0.5
+        };
+
+        if key_down {
+            self.quiet_blocks = 0;
+            if !self.active {
+                self.active = true;
+                self.training = Some(Vec::new());
+                events.push(LiveEvent::SignalStart);
+            }
+        } else {
+            self.quiet_blocks += 1;
+            if self.quiet_blocks >= self.timeout_blocks {
+                self.end_transmission(events);
+                // A searched-for signal that went quiet may come back
+                // elsewhere, and a noise peak mistaken for one never keys
+                if self.tone.is_none() {
+                    self.filter = None;
+                }
+                return;
+            }
+        }
+
+        if key_down == self.key_down {
+            self.run_blocks += 1;
+            if !key_down && self.training.is_none() {
+                self.check_gap(events);
+            }
+            return;
+        }
+
+        let element = Element { key_down: self.key_down, duration: self.block_duration() * self.run_blocks as u32 };
+        self.key_down = key_down;
+        self.run_blocks = 1;
+        match &mut self.training {
+            // The silence before the transmission says nothing about it
+            Some(elements) if elements.is_empty() && !element.key_down => {}
+            Some(elements) => {
+                elements.push(element);
+                if elements.iter().filter(|e| e.key_down).count() >= TRAINING_MARKS {
+                    self.train(events);
+                }
+            }
+            None => self.element(element, events),
+        }
+    }
+
+    // Estimate the speed from the elements timed so far, then decode them
+    fn train(&mut self, events: &mut Vec<LiveEvent>) {
+        let Some(elements) = self.training.take() else { return };
+        let unit = estimate_unit(&elements);
+        if unit <= 0.0 {
+            return;
+        }
+        self.unit = unit;
+        self.bias = envelope_bias(&elements, unit);
+        for element in elements {
+            self.element(element, events);
+        }
+    }
+
+    // One finished key-down or key-up run
+    fn element(&mut self, element: Element, events: &mut Vec<LiveEvent>) {
+        let secs = element.duration.as_secs_f64();
+        if element.key_down {
+            self.marks.push((secs + self.bias) / self.unit);
+            self.char_done = false;
+            self.word_done = false;
+        } else {
+            let gap = (secs - self.bias) / self.unit;
+            if gap < 2.0 {
+                self.inner_gaps.push(gap);
+            } else {
+                if !self.char_done {
+                    let boundary = margin(gap, 2.0).min(margin(gap, WORD_GAP));
+                    events.push(LiveEvent::Char(self.take_char(boundary)));
+                }
+                if gap >= WORD_GAP && !self.word_done {
+                    events.push(LiveEvent::Char(space(margin(gap, WORD_GAP))));
+                    self.word_done = true;
+                }
+            }
+        }
+        self.unit = track(self.unit, &element, self.bias);
+    }
+
+    // While the key is up, settle the character and word once the gap is
+    // long enough that nothing else could follow.
+    fn check_gap(&mut self, events: &mut Vec<LiveEvent>) {
+        let gap = (self.run_blocks as f64 * self.block_duration().as_secs_f64() - self.bias) / self.unit;
+        if !self.char_done && gap >= CHAR_DONE && !self.marks.is_empty() {
+            events.push(LiveEvent::Char(self.take_char(margin(gap, 2.0))));
+        }
+        if self.char_done && !self.word_done && gap >= WORD_DONE {
+            events.push(LiveEvent::Char(space(1.0)));
+            self.word_done = true;
+        }
+    }
+
+    fn take_char(&mut self, boundary: f64) -> DecodedChar {
+        let c = DecodedChar {
+            wpm: Some(1.2 / self.unit),
+            tone_hz: self.filter.as_ref().map(|(tone, _)| *tone),
+            ..read_char(&self.marks, &self.inner_gaps, boundary)
+        };
+        self.marks.clear();
+        self.inner_gaps.clear();
+        self.char_done = true;
+        c
+    }
+
+    fn block_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.block as f64 / self.sample_rate as f64)
+    }
+}
+
+fn space(confidence: f64) -> DecodedChar {
+    DecodedChar { ch: ' ', confidence, alternatives: Vec::new(), wpm: None, tone_hz: None }
+}
+
+// ---------- Soundcard input ------------------------------------------------
+/// Receiver settings shared by the commands that decode live audio.
+#[derive(clap::Args, Debug, Clone)]
+pub struct ReceiverArgs {
+    /// Tone to listen on; searched for automatically when omitted
+    #[arg(long)]
+    pub tone: Option<u32>,
+
+    /// Frequency range (Hz) searched for a signal
+    #[arg(long, default_value = "300-1200", value_parser = parse_hz_range)]
+    pub search: (f64, f64),
+
+    /// Signal-to-noise ratio (dB) needed before keying is decoded
+    #[arg(long, default_value_t = 12.0)]
+    pub squelch: f64,
+
+    /// Seconds without keying that end a transmission
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: u64,
+
+    #[command(flatten)]
+    pub raw: RawInput,
+}
+
+impl ReceiverArgs {
+    pub fn decoder(&self, sample_rate: u32) -> LiveDecoder {
+        match self.tone {
+            Some(tone) => eprintln!("Listening on {} Hz (Ctrl-C to stop)", tone),
+            None => eprintln!("Searching {:.0}-{:.0} Hz for a signal (Ctrl-C to stop)", self.search.0, self.search.1),
+        }
+        LiveDecoder::new(sample_rate, self.tone.map(f64::from), self.search, self.squelch, self.timeout as f64)
+    }
+}
+
+/// Live audio, delivered as mono blocks as it arrives: the default input
+/// device, or raw PCM on stdin when the receiver asks for it.
+pub struct Input {
+    // Soundcard capture stops when the stream is dropped
+    _stream: Option<cpal::Stream>,
+    pub sample_rate: u32,
+    blocks: mpsc::Receiver<Vec<f32>>,
+}
+
+impl Input {
+    pub fn open(receiver: &ReceiverArgs) -> Result<Self> {
+        if let Some(format) = receiver.raw.input_format {
+            let (tx, blocks) = mpsc::channel();
+            receiver.raw.stream_stdin(format, tx);
+            return Ok(Input { _stream: None, sample_rate: receiver.raw.rate, blocks });
+        }
+        Self::soundcard()
+    }
+
+    /// The default input device, whatever `--input-format` says.
+    pub fn soundcard() -> Result<Self> {
+        let device = host()?
+            .default_input_device()
+            .ok_or_else(|| MorseError::AudioDeviceError("no input device".into()))?;
+        let config = device
+            .default_input_config()
+            .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+        let sample_rate = config.sample_rate().0;
+        let channels = config.channels() as usize;
+
+        // The callback downmixes to mono and hands blocks to the decoding loop
+        let (tx, blocks) = mpsc::channel::<Vec<f32>>();
+        let on_error = |e| eprintln!("Audio input error: {}", e);
+        let stream = match config.sample_format() {
+            SampleFormat::F32 => device.build_input_stream(
+                &config.into(),
+                move |data: &[f32], _: &_| {
+                    let _ = tx.send(data.chunks(channels).map(|f| f.iter().sum::<f32>() / channels as f32).collect());
+                },
+                on_error,
+                None,
+            ),
+            SampleFormat::I16 => device.build_input_stream(
+                &config.into(),
+                move |data: &[i16], _: &_| {
+                    let _ = tx.send(
+                        data.chunks(channels)
+                            .map(|f| f.iter().map(|&s| s as f32 / 32768.0).sum::<f32>() / channels as f32)
+                            .collect(),
+                    );
+                },
+                on_error,
+                None,
+            ),
+            format => return Err(MorseError::AudioDeviceError(format!("unsupported sample format {}", format)).into()),
+        }
+        .map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+        stream.play().map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+        Ok(Input { _stream: Some(stream), sample_rate, blocks })
+    }
+
+    /// Captured audio; ends when stdin closes or the device goes away.
+    pub fn blocks(&self) -> impl Iterator<Item = Vec<f32>> + '_ {
+        self.blocks.iter()
+    }
+
+    /// The next block, if one arrives within `timeout`.
+    pub fn block_within(&self, timeout: Duration) -> Option<Vec<f32>> {
+        self.blocks.recv_timeout(timeout).ok()
+    }
+}
+
+/// Decode from the default input device until interrupted, printing
+/// characters as they are read and a newline after each transmission. With
+/// `record_dir`, every transmission is also saved there as a WAV file.
+pub fn listen(receiver: &ReceiverArgs, record_dir: Option<&Path>, mut udp: Option<Broadcast>) -> Result<()> {
+    let input = Input::open(receiver)?;
+    let mut decoder = receiver.decoder(input.sample_rate);
+    let mut capture = record_dir.map(|dir| Capture::new(dir, input.sample_rate));
+    for samples in input.blocks() {
+        if let Some(capture) = &mut capture {
+            capture.push(&samples);
+        }
+        let events = decoder.feed(&samples);
+        show(&events, capture.as_mut(), udp.as_mut())?;
+    }
+    // The input stream went away; finish the transmission in progress
+    show(&decoder.flush(), capture.as_mut(), udp.as_mut())
+}
+
+fn show(events: &[LiveEvent], mut capture: Option<&mut Capture>, mut udp: Option<&mut Broadcast>) -> Result<()> {
+    for event in events {
+        if let Some(udp) = udp.as_deref_mut() {
+            udp.event(event);
+        }
+        match event {
+            LiveEvent::Locked(tone) => eprintln!("Locked on {:.0} Hz", tone),
+            LiveEvent::SignalStart => {
+                if let Some(capture) = capture.as_deref_mut() {
+                    capture.start();
+                }
+            }
+            LiveEvent::Char(c) => print!("{}", c.ch),
+            LiveEvent::SignalEnd => {
+                println!();
+                if let Some(path) = capture.as_deref_mut().map(Capture::finish).transpose()?.flatten() {
+                    eprintln!("Recorded: {}", path.display());
+                }
+            }
+        }
+    }
+    let _ = std::io::stdout().flush();
+    Ok(())
+}
+
+// ---------- Transmissions --------------------------------------------------
+/// One transmission as the decoder heard it.
+pub struct Transmission {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub text: String,
+    pub tone_hz: Option<f64>,
+    pub wpm: Option<f64>,
+}
+
+/// Assembles transmissions from decoder events.
+#[derive(Default)]
+pub struct Transmissions {
+    start: Option<DateTime<Utc>>,
+    text: String,
+    tone_hz: Option<f64>,
+    wpm: Option<f64>,
+}
+
+impl Transmissions {
+    /// Feed an event; returns the transmission it finished, if it has text.
+    pub fn update(&mut self, event: &LiveEvent) -> Option<Transmission> {
+        match event {
+            LiveEvent::Locked(_) => None,
+            LiveEvent::SignalStart => {
+                self.start = Some(Utc::now());
+                None
+            }
+            LiveEvent::Char(c) => {
+                self.text.push(c.ch);
+                self.tone_hz = c.tone_hz.or(self.tone_hz);
+                self.wpm = c.wpm.or(self.wpm);
+                None
+            }
+            LiveEvent::SignalEnd => {
+                let finished = std::mem::take(self);
+                let text = finished.text.trim();
+                let start = finished.start?;
+                (!text.is_empty()).then(|| Transmission {
+                    start,
+                    end: Utc::now(),
+                    text: text.to_string(),
+                    tone_hz: finished.tone_hz,
+                    wpm: finished.wpm,
+                })
+            }
+        }
+    }
+}
+
+// Input audio for "record on signal": a rolling pre-roll while quiet, the
+// whole transmission once the squelch opens.
+struct Capture {
+    dir: PathBuf,
+    sample_rate: u32,
+    preroll: VecDeque<f32>,
+    recording: Option<Vec<f32>>,
+}
+
+impl Capture {
+    fn new(dir: &Path, sample_rate: u32) -> Self {
+        Capture { dir: dir.to_path_buf(), sample_rate, preroll: VecDeque::new(), recording: None }
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        match &mut self.recording {
+            Some(recording) => recording.extend_from_slice(samples),
+            None => {
+                self.preroll.extend(samples);
+                let keep = (PREROLL_SECS * self.sample_rate as f64) as usize;
+                let excess = self.preroll.len().saturating_sub(keep);
+                self.preroll.drain(..excess);
+            }
+        }
+    }
+
+    fn start(&mut self) {
+        self.recording = Some(self.preroll.drain(..).collect());
+    }
+
+    fn finish(&mut self) -> Result<Option<PathBuf>> {
+        let Some(recording) = self.recording.take() else {
+            return Ok(None);
+        };
+        // Stamped to the millisecond, and numbered should that still clash
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let stamp = format!("cw-{}-{:03}", stamp.as_secs(), stamp.subsec_millis());
+        let path = (1..)
+            .map(|n| match n {
+                1 => self.dir.join(format!("{}.wav", stamp)),
+                n => self.dir.join(format!("{}-{}.wav", stamp, n)),
+            })
+            .find(|path| !path.exists())
+            .unwrap_or_default();
+        write_wav(&path.to_string_lossy(), self.sample_rate, &recording)?;
+        Ok(Some(path))
+    }
+}
+
+/// Parse a frequency range such as "300-1200".
+pub fn parse_hz_range(s: &str) -> Result<(f64, f64), String> {
+    let (low, high) = s.split_once('-').ok_or("expected LOW-HIGH, e.g. 300-1200")?;
+    let low: f64 = low.trim().parse().map_err(|_| format!("invalid frequency: {}", low))?;
+    let high: f64 = high.trim().parse().map_err(|_| format!("invalid frequency: {}", high))?;
+    if low < 50.0 || high <= low + 100.0 {
+        return Err("range must start at 50 Hz or more and span over 100 Hz".into());
+    }
+    Ok((low, high))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{MorseAudio, NoiseSource, ToneShape};
+    use crate::conditions::Conditions;
+    use crate::morse::Timing;
+
+    // Locked tone, and the text with a newline for each finished transmission
+    fn run(decoder: &mut LiveDecoder, samples: &[f32]) -> (Option<f64>, String) {
+        let mut locked = None;
+        let mut text = String::new();
+        let mut events: Vec<LiveEvent> = samples.chunks(256).flat_map(|c| decoder.feed(c)).collect();
+        events.extend(decoder.flush());
+        for event in events {
+            match event {
+                LiveEvent::Locked(tone) => locked = Some(tone),
+                LiveEvent::Char(c) => text.push(c.ch),
+                LiveEvent::SignalEnd => text.push('\n'),
+                LiveEvent::SignalStart => {}
+            }
+        }
+        (locked, text)
+    }
+
+    fn render(text: &str, wpm: u32, tone: u32, conditions: Conditions) -> Vec<f32> {
+        MorseAudio::new_with_sample_rate(8000, text, Timing::new(wpm, 0), tone, conditions, ToneShape::Sine, None)
+            .get_samples()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_live_search_and_lock() {
+        let audio = render("CQ TEST DE W1AW", 22, 640, Conditions::default());
+        let mut decoder = LiveDecoder::new(8000, None, (300.0, 1200.0), 12.0, 3.0);
+        let (locked, text) = run(&mut decoder, &audio);
+        assert!((locked.unwrap() - 640.0).abs() <= 10.0);
+        assert_eq!(text.trim_end(), "CQ TEST DE W1AW");
+    }
+
+    #[test]
+    fn test_squelch_splits_transmissions() {
+        let noisy = Conditions { qrm: 2, ..Conditions::default() };
+        let quiet: Vec<f32> = NoiseSource::new(noisy, 700, 8000).take(8000 * 5).collect();
+        let mut audio = quiet.clone();
+        audio.extend(render("CQ TEST", 20, 700, noisy));
+        audio.extend(&quiet);
+        audio.extend(render("DE W1AW", 20, 700, noisy));
+        audio.extend(&quiet);
+
+        let mut decoder = LiveDecoder::new(8000, Some(700.0), (300.0, 1200.0), 12.0, 3.0);
+        let (_, text) = run(&mut decoder, &audio);
+        let lines: Vec<&str> = text.lines().map(str::trim).collect();
+        assert_eq!(lines, ["CQ TEST", "DE W1AW"]);
+    }
+}
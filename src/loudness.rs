@@ -0,0 +1,492 @@
+use rodio::Source;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// ---------- Levels ----------------------------------------------------------
+/// Highest absolute sample in dBFS (-inf for silence).
+pub fn peak_db(samples: &[f32]) -> f64 {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    20.0 * (peak as f64).log10()
+}
+
+/// Scale `samples` so their peak sits at `target_db` dBFS. Silence is left alone.
+pub fn normalize_peak(samples: &mut [f32], target_db: f64) {
+    let peak = peak_db(samples);
+    if peak.is_finite() {
+        gain(samples, target_db - peak);
+    }
+}
+
+fn gain(samples: &mut [f32], db: f64) {
+    let gain = 10f64.powf(db / 20.0) as f32;
+    samples.iter_mut().for_each(|s| *s *= gain);
+}
+
+// ---------- Limiter ---------------------------------------------------------
+// Interferers, noise and normalization can add up past full scale, which the
+// i16 conversion turns into harsh clipping. The limiter pulls the gain down
+// just enough, starting a little before each overload so the change isn't a
+// click: the needed gain is held at its minimum for a window either side of
+// every peak, then smoothed with a moving average half that long, which still
+// reaches the minimum at the peak itself.
+const CEILING: f32 = 0.98;
+const LIMITER_WINDOW_SECS: f64 = 0.005;
+
+/// What the limiter had to do to a buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limited {
+    /// Samples that were past full scale
+    pub clipped: usize,
+    pub max_reduction_db: f64,
+}
+
+/// Keep `samples` under the ceiling. Returns None if nothing was over full
+/// scale, in which case the samples are untouched.
+pub fn limit(samples: &mut [f32], sample_rate: u32) -> Option<Limited> {
+    let clipped = samples.iter().filter(|s| s.abs() > 1.0).count();
+    if clipped == 0 {
+        return None;
+    }
+    let needed: Vec<f32> = samples.iter().map(|s| (CEILING / s.abs()).min(1.0)).collect();
+    let reach = ((LIMITER_WINDOW_SECS * sample_rate as f64) as usize).max(1);
+    let held = sliding_min(&needed, reach);
+
+    // Moving average of the held gain over `reach` samples, centered
+    let half = reach / 2;
+    let mut sums = vec![0.0f64; held.len() + 1];
+    for (i, g) in held.iter().enumerate() {
+        sums[i + 1] = sums[i] + *g as f64;
+    }
+    let mut min_gain = 1.0f32;
+    for (i, s) in samples.iter_mut().enumerate() {
+        let (from, to) = (i.saturating_sub(half), (i + half + 1).min(held.len()));
+        let gain = (((sums[to] - sums[from]) / (to - from) as f64) as f32).min(needed[i]);
+        min_gain = min_gain.min(gain);
+        *s *= gain;
+    }
+    Some(Limited { clipped, max_reduction_db: -20.0 * (min_gain as f64).log10() })
+}
+
+// Minimum of `values` over [i - reach, i + reach] for every i
+fn sliding_min(values: &[f32], reach: usize) -> Vec<f32> {
+    let mut out = Vec::with_capacity(values.len());
+    let mut window: std::collections::VecDeque<usize> = std::collections::VecDeque::new();
+    let mut next = 0;
+    for i in 0..values.len() {
+        while next < values.len() && next <= i + reach {
+            while window.back().is_some_and(|&j| values[j] >= values[next]) {
+                window.pop_back();
+            }
+            window.push_back(next);
+            next += 1;
+        }
+        while window.front().is_some_and(|&j| j + reach < i) {
+            window.pop_front();
+        }
+        out.push(values[window[0]]);
+    }
+    out
+}
+
+/// "1234 samples (0.30%) clipped; limited by up to 4.1 dB"
+pub fn report(limited: &Limited, total: usize) -> String {
+    format!(
+        "{} samples ({:.2}%) clipped; limited by up to {:.1} dB",
+        limited.clipped,
+        100.0 * limited.clipped as f64 / total.max(1) as f64,
+        limited.max_reduction_db,
+    )
+}
+
+/// The limiter as a stream, for audio made as it plays: the same gain as
+/// `limit`, worked out over a lookahead of a window and a half. Interleaved
+/// channels are limited a frame at a time, every channel of a frame by the
+/// same gain, over a window of the same length in time. What it had to do
+/// so far is in the returned `Limited`.
+pub struct Limiting<S> {
+    source: S,
+    channels: u16,
+    reach: usize,
+    half: usize,
+    // Samples read but not yet given out, and per frame its sample count
+    // and peak, the first frame being number `next`
+    ahead: VecDeque<f32>,
+    frames: VecDeque<(usize, f32)>,
+    next: usize,
+    read: usize,
+    // Rising needed gains, by frame, for the minimum over a window
+    minima: VecDeque<(usize, f32)>,
+    // Held gains from frame `held_from` on, their sum, and how many are
+    // under 1
+    held: VecDeque<f32>,
+    held_from: usize,
+    held_sum: f64,
+    reduced: usize,
+    exhausted: bool,
+    // The gain for the frame being given out, and its samples still to go
+    gain: f32,
+    left: usize,
+    limited: Arc<Mutex<Limited>>,
+}
+
+impl<S: Iterator<Item = f32>> Limiting<S> {
+    /// Limit `source`, `channels` interleaved at `sample_rate` frames a second.
+    pub fn new(source: S, sample_rate: u32, channels: u16) -> (Self, Arc<Mutex<Limited>>) {
+        let reach = ((LIMITER_WINDOW_SECS * sample_rate as f64) as usize).max(1);
+        let limited = Arc::new(Mutex::new(Limited { clipped: 0, max_reduction_db: 0.0 }));
+        let limiting = Limiting {
+            source,
+            channels: channels.max(1),
+            reach,
+            half: reach / 2,
+            ahead: VecDeque::new(),
+            frames: VecDeque::new(),
+            next: 0,
+            read: 0,
+            minima: VecDeque::new(),
+            held: VecDeque::new(),
+            held_from: 0,
+            held_sum: 0.0,
+            reduced: 0,
+            exhausted: false,
+            gain: 1.0,
+            left: 0,
+            limited: limited.clone(),
+        };
+        (limiting, limited)
+    }
+
+    // The gain held for frame `index`: every frame within reach of it has
+    // been read, and none further
+    fn hold(&mut self, index: usize) {
+        while self.minima.front().is_some_and(|&(j, _)| j + self.reach < index) {
+            self.minima.pop_front();
+        }
+        let gain = self.minima.front().map_or(1.0, |&(_, gain)| gain);
+        self.held.push_back(gain);
+        self.held_sum += gain as f64;
+        self.reduced += (gain < 1.0) as usize;
+    }
+
+    // Read the next frame from the source; false once it has run out
+    fn read_frame(&mut self) -> bool {
+        let before = self.ahead.len();
+        self.ahead.extend(self.source.by_ref().take(self.channels as usize));
+        let len = self.ahead.len() - before;
+        if len == 0 {
+            return false;
+        }
+        let peak = self.ahead.range(before..).fold(0.0f32, |peak, s| peak.max(s.abs()));
+        let needed = (CEILING / peak).min(1.0);
+        while self.minima.back().is_some_and(|&(_, gain)| gain >= needed) {
+            self.minima.pop_back();
+        }
+        self.minima.push_back((self.read, needed));
+        self.frames.push_back((len, peak));
+        self.read += 1;
+        if let Some(index) = self.read.checked_sub(self.reach + 1) {
+            self.hold(index);
+        }
+        true
+    }
+
+    // Start giving out the next frame, with its gain; false when none is left
+    fn start_frame(&mut self) -> bool {
+        while !self.exhausted && self.read <= self.next + self.half + self.reach {
+            if !self.read_frame() {
+                self.exhausted = true;
+                // Nothing more to come within reach of the rest
+                for index in self.held_from + self.held.len()..self.read {
+                    self.hold(index);
+                }
+            }
+        }
+        let Some((len, peak)) = self.frames.pop_front() else { return false };
+        while self.held_from + self.half < self.next {
+            let gain = self.held.pop_front().unwrap_or(1.0);
+            self.held_sum -= gain as f64;
+            self.reduced -= (gain < 1.0) as usize;
+            self.held_from += 1;
+        }
+        self.next += 1;
+        self.left = len;
+        self.gain = 1.0;
+        if self.reduced > 0 || peak > 1.0 {
+            // Moving average of the held gain over the window, centered
+            let average = (self.held_sum / self.held.len() as f64) as f32;
+            self.gain = average.min((CEILING / peak).min(1.0));
+            let mut limited = self.limited.lock().unwrap();
+            limited.max_reduction_db = limited.max_reduction_db.max(-20.0 * (self.gain as f64).log10());
+        }
+        true
+    }
+}
+
+impl<S: Iterator<Item = f32>> Iterator for Limiting<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.left == 0 && !self.start_frame() {
+            return None;
+        }
+        let sample = self.ahead.pop_front()?;
+        self.left -= 1;
+        if sample.abs() > 1.0 {
+            self.limited.lock().unwrap().clipped += 1;
+        }
+        Some(sample * self.gain)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Limiting<S> {
+    fn current_frame_len(&self) -> Option<usize> { None }
+
+    fn channels(&self) -> u16 { self.source.channels() }
+
+    fn sample_rate(&self) -> u32 { self.source.sample_rate() }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+// ---------- EBU R128 --------------------------------------------------------
+// Integrated loudness per ITU-R BS.1770-4: K-weighting (a high shelf for the
+// head, then a high-pass), mean square over 400 ms blocks overlapping by 75%,
+// an absolute gate at -70 LUFS and a relative gate 10 LU under the ungated
+// level. The filters are the standard analog prototypes, so any sample rate
+// works, not just 48 kHz.
+const BLOCK_SECS: f64 = 0.4;
+const BLOCK_STEP: usize = 4;
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+    [shelf, high_pass]
+}
+
+/// Integrated loudness of mono `samples` in LUFS, or None if everything is
+/// gated out (silence, or shorter than one 400 ms block).
+pub fn integrated_lufs(samples: &[f32], sample_rate: u32) -> Option<f64> {
+    let [mut shelf, mut high_pass] = k_weighting(sample_rate);
+    let squares: Vec<f64> = samples
+        .iter()
+        .map(|&s| high_pass.process(shelf.process(s as f64)).powi(2))
+        .collect();
+
+    let block = (BLOCK_SECS * sample_rate as f64) as usize;
+    if block == 0 || squares.len() < block {
+        return None;
+    }
+    let step = (block / BLOCK_STEP).max(1);
+    let blocks: Vec<f64> = (0..=(squares.len() - block) / step)
+        .map(|i| squares[i * step..i * step + block].iter().sum::<f64>() / block as f64)
+        .collect();
+
+    let lufs = |power: f64| -0.691 + 10.0 * power.log10();
+    let gated_mean = |threshold: f64| {
+        let kept: Vec<f64> = blocks.iter().copied().filter(|&p| lufs(p) > threshold).collect();
+        (!kept.is_empty()).then(|| kept.iter().sum::<f64>() / kept.len() as f64)
+    };
+    let ungated = gated_mean(ABSOLUTE_GATE)?;
+    gated_mean(lufs(ungated) + RELATIVE_GATE).map(lufs)
+}
+
+// ---------- Normalization ---------------------------------------------------
+/// Target level for `--normalize`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalize {
+    /// Integrated loudness in LUFS
+    Lufs(f64),
+    /// Sample peak in dBFS
+    Peak(f64),
+}
+
+/// "lufs:-16", "peak:-3dB" or "peak:-3".
+pub fn parse_normalize(s: &str) -> Result<Normalize, String> {
+    let (kind, level) = s.split_once(':').ok_or("expected lufs:LEVEL or peak:LEVEL, e.g. lufs:-16")?;
+    let number = level.trim().trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let value: f64 = number.parse().map_err(|_| format!("invalid level: {}", level))?;
+    if value > 0.0 {
+        return Err(format!("level must be at or below 0, got {}", level));
+    }
+    match kind.to_ascii_lowercase().as_str() {
+        "lufs" => Ok(Normalize::Lufs(value)),
+        "peak" => Ok(Normalize::Peak(value)),
+        _ => Err(format!("unknown normalization {} (expected lufs or peak)", kind)),
+    }
+}
+
+impl Normalize {
+    /// Bring `samples` to the target level. Clips too short or quiet to
+    /// measure are left as they are.
+    pub fn apply(self, samples: &mut [f32], sample_rate: u32) {
+        match self {
+            Normalize::Peak(target) => normalize_peak(samples, target),
+            Normalize::Lufs(target) => {
+                if let Some(lufs) = integrated_lufs(samples, sample_rate) {
+                    gain(samples, target - lufs);
+                }
+            }
+        }
+    }
+
+    /// As `apply`, for interleaved stereo: one gain for both channels, from
+    /// the two channels' loudness together.
+    pub fn apply_stereo(self, frames: &mut [f32], sample_rate: u32) {
+        match self {
+            Normalize::Peak(target) => normalize_peak(frames, target),
+            Normalize::Lufs(target) => {
+                let channel = |c: usize| frames.iter().skip(c).step_by(2).copied().collect::<Vec<f32>>();
+                let (Some(left), Some(right)) = (integrated_lufs(&channel(0), sample_rate), integrated_lufs(&channel(1), sample_rate)) else {
+                    return;
+                };
+                let lufs = 10.0 * (10f64.powf(left / 10.0) + 10f64.powf(right / 10.0)).log10();
+                gain(frames, target - lufs);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_peak() {
+        let mut samples = [0.1, -0.25, 0.2];
+        normalize_peak(&mut samples, -6.0206);
+        assert!((samples[1] + 0.5).abs() < 1e-4);
+        assert!((peak_db(&samples) + 6.0206).abs() < 1e-3);
+        let mut silence = [0.0; 4];
+        normalize_peak(&mut silence, 0.0);
+        assert_eq!(silence, [0.0; 4]);
+    }
+
+    #[test]
+    fn test_limiter() {
+        // 1 s of 500 Hz at 8 kHz, twice full scale for the middle 100 ms
+        let mut samples: Vec<f32> = (0..8000)
+            .map(|i| {
+                let level = if (4000..4800).contains(&i) { 2.0 } else { 0.5 };
+                level * (std::f64::consts::TAU * 500.0 * i as f64 / 8000.0).sin() as f32
+            })
+            .collect();
+        let original = samples.clone();
+        let limited = limit(&mut samples, 8000).unwrap();
+        assert!(samples.iter().all(|s| s.abs() <= CEILING));
+        assert!(limited.clipped > 300);
+        assert!((limited.max_reduction_db - 6.2).abs() < 0.1);
+        // Well away from the overload, nothing changes
+        assert_eq!(samples[..3900], original[..3900]);
+        assert_eq!(samples[4900..], original[4900..]);
+        assert_eq!(limit(&mut samples, 8000), None);
+    }
+
+    // The same overload as above
+    fn overload() -> Vec<f32> {
+        (0..8000)
+            .map(|i| {
+                let level = if (4000..4800).contains(&i) { 2.0 } else { 0.5 };
+                level * (std::f64::consts::TAU * 500.0 * i as f64 / 8000.0).sin() as f32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_streaming_limiter() {
+        // Limited as it streams past just as it would be all at once
+        let samples = overload();
+        let mut batch = samples.clone();
+        let expected = limit(&mut batch, 8000).unwrap();
+        let (limiting, limited) = Limiting::new(samples.clone().into_iter(), 8000, 1);
+        let streamed: Vec<f32> = limiting.collect();
+        assert_eq!(streamed.len(), batch.len());
+        assert!(streamed.iter().zip(&batch).all(|(a, b)| (a - b).abs() < 1e-5));
+        let limited = *limited.lock().unwrap();
+        assert_eq!(limited.clipped, expected.clipped);
+        assert!((limited.max_reduction_db - expected.max_reduction_db).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_short_stream_limited() {
+        // All of it within the lookahead
+        let (limiting, _) = Limiting::new([0.5, 2.0, -0.5].into_iter(), 8000, 1);
+        assert!(limiting.collect::<Vec<f32>>().iter().all(|s| s.abs() <= CEILING));
+    }
+
+    #[test]
+    fn test_stereo_limited_together() {
+        // The right channel a quarter of the left: each frame is limited as
+        // the left alone would be, and the right by the same gain
+        let samples = overload();
+        let mut batch = samples.clone();
+        let expected = limit(&mut batch, 8000).unwrap();
+        let frames: Vec<f32> = samples.iter().flat_map(|&s| [s, s / 4.0]).collect();
+        let (limiting, limited) = Limiting::new(frames.clone().into_iter(), 8000, 2);
+        let streamed: Vec<f32> = limiting.collect();
+        assert_eq!(streamed.len(), frames.len());
+        for (frame, mono) in streamed.chunks(2).zip(&batch) {
+            assert!((frame[0] - mono).abs() < 1e-5 && (frame[1] - mono / 4.0).abs() < 1e-5);
+        }
+        assert_eq!(limited.lock().unwrap().clipped, expected.clipped);
+    }
+
+    #[test]
+    fn test_integrated_loudness_of_sine() {
+        // BS.1770 reference: a 997 Hz sine at -20 dBFS reads -23.0 LUFS, at
+        // any sample rate
+        for rate in [48000, 8000] {
+            let mut samples: Vec<f32> = (0..rate * 5)
+                .map(|i| 0.1 * (std::f64::consts::TAU * 997.0 * i as f64 / rate as f64).sin() as f32)
+                .collect();
+            let lufs = integrated_lufs(&samples, rate).unwrap();
+            assert!((lufs + 23.01).abs() < 0.05, "{} Hz: {}", rate, lufs);
+            // As much silence again is gated out (it would cost 3 dB otherwise)
+            samples.resize(samples.len() * 2, 0.0);
+            assert!((integrated_lufs(&samples, rate).unwrap() + 23.01).abs() < 0.2);
+
+            Normalize::Lufs(-16.0).apply(&mut samples, rate);
+            assert!((integrated_lufs(&samples, rate).unwrap() + 16.0).abs() < 0.01);
+        }
+        assert_eq!(parse_normalize("peak:-3dB"), Ok(Normalize::Peak(-3.0)));
+        assert!(parse_normalize("lufs:6").is_err());
+    }
+}
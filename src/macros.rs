@@ -0,0 +1,94 @@
+use anyhow::{bail, Result};
+use rand::seq::IndexedRandom;
+use rand::Rng;
+
+use crate::morse::{random_callsign, PracticeMode};
+
+// ---------- Text macros -----------------------------------------------------
+// Input text may hold macros in braces that are filled with fresh random
+// material each time it is rendered, so one lesson file gives a new drill on
+// every run:
+//
+//     Copy the calls. {callsign} {callsign} {callsign}
+//     Now groups. {group:5} {group:5} = {number:3} {word}
+//
+// `{group:N}` is N random letters, `{number:N}` N random digits (both 5 when
+// N is left out), `{word}` a word from the built-in list and `{callsign}` a
+// made-up call. Braces around a file name are voice prompts (see lesson.rs)
+// and are left alone; anything else in braces is an error.
+const DEFAULT_LENGTH: usize = 5;
+const MAX_LENGTH: usize = 50;
+
+/// `text` with every macro replaced.
+pub fn expand(text: &str, rng: &mut impl Rng) -> Result<String> {
+    let words = PracticeMode::RandomWords.get_content(rng, None, 1, None);
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}').map(|i| open + i) else { break };
+        expanded.push_str(&rest[..open]);
+        let inside = rest[open + 1..close].trim();
+        // A voice prompt, whose path may hold a colon of its own
+        if inside.contains('.') {
+            expanded.push_str(&rest[open..=close]);
+            rest = &rest[close + 1..];
+            continue;
+        }
+        let (name, length) = match inside.split_once(':') {
+            Some((name, length)) => match length.trim().parse::<usize>() {
+                Ok(length @ 1..=MAX_LENGTH) => (name.trim(), length),
+                _ => bail!("{{{}}}: the length must be 1-{}", inside, MAX_LENGTH),
+            },
+            None => (inside, DEFAULT_LENGTH),
+        };
+        match name {
+            "callsign" => expanded.push_str(&random_callsign(rng)),
+            "group" => expanded.extend((0..length).map(|_| char::from(b'A' + rng.random_range(0..26)))),
+            "number" => expanded.extend((0..length).map(|_| char::from(b'0' + rng.random_range(0..10)))),
+            "word" => expanded.push_str(words.choose(rng).map_or("CQ", String::as_str)),
+            _ => bail!("unknown macro {{{}}} (callsign, group:N, number:N or word)", inside),
+        }
+        rest = &rest[close + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn rng() -> rand::rngs::StdRng {
+        rand::rngs::StdRng::seed_from_u64(3)
+    }
+
+    #[test]
+    fn test_expand_macros() {
+        let text = expand("GROUPS {group:4} {number} = {word} DE {callsign} {intro.wav} K", &mut rng()).unwrap();
+        let words: Vec<&str> = text.split_whitespace().collect();
+        assert_eq!(words[0], "GROUPS");
+        assert!(words[1].len() == 4 && words[1].chars().all(|c| c.is_ascii_uppercase()));
+        assert!(words[2].len() == 5 && words[2].chars().all(|c| c.is_ascii_digit()));
+        assert!(!words[4].contains('{'));
+        assert!(words[6].chars().any(|c| c.is_ascii_digit()));
+        assert_eq!(&words[7..], ["{intro.wav}", "K"]);
+    }
+
+    #[test]
+    fn test_prompts_left_alone() {
+        assert_eq!(expand("{C:\\prompts\\a.wav} {D:/b.mp3}", &mut rng()).unwrap(), "{C:\\prompts\\a.wav} {D:/b.mp3}");
+    }
+
+    #[test]
+    fn test_fresh_each_time() {
+        let mut rng = rng();
+        assert_ne!(expand("{group:10}", &mut rng).unwrap(), expand("{group:10}", &mut rng).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_macros() {
+        assert!(expand("{callsigns}", &mut rng()).is_err());
+        assert!(expand("{group:0}", &mut rng()).is_err());
+    }
+}
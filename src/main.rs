@@ -1,19 +1,95 @@
-use anyhow::Result;
-use clap::{Parser, ValueEnum};
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 mod morse;
+mod pcm;
+mod anki;
+mod answer;
 mod audio;
+mod automation;
+mod beacon;
+mod bridge;
+mod captions;
+mod compose;
+mod conditions;
+mod copybehind;
+mod daemon;
+mod decode;
+mod eq;
+mod events;
+mod exam;
+mod extract;
+mod feed;
+mod fist;
+mod generate;
+mod goals;
+mod gui;
+mod history;
+mod homework;
+mod incremental;
 mod interactive;
+mod keyer;
+mod latency;
+mod lesson;
+mod live;
+mod loudness;
+mod macros;
+mod markup;
+mod mix;
+mod monitor;
+mod mqtt;
+mod opus;
+mod paddle;
+mod partner;
+mod phonetics;
+mod pileup;
+mod plot;
+mod preamble;
+mod procedure;
+mod profile;
+mod ptt;
+mod puzzle;
+mod quiz;
+mod record;
+mod replay;
+mod report;
+mod resample;
+mod rig;
+mod ringtone;
+mod safety;
+mod scoreboard;
+mod sounder;
+mod spaced;
+mod speech;
+mod tags;
+mod tape;
+mod testsignal;
+mod tune;
+mod udp;
+mod vox;
+mod waterfall;
+mod wav;
 
-use morse::{MorseError, Timing, PracticeMode, text_to_morse};
-use audio::{play_audio, ToneShape, save_audio_to_wav};
+use morse::{Charset, MorseError, Timing, PracticeMode, text_to_code};
+use audio::{ToneShape, save_audio_to_wav};
+use automation::Automation;
+use conditions::{Conditions, ConditionsPreset, Scenario};
+use decode::DecodeFormat;
 use interactive::{interactive_mode, practice_mode};
 
 // ---------- CLI ------------------------------------------------------------
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("session").args(["interactive", "practice", "homework"]).multiple(true)))]
 struct Args {
+    /// Keep settings and statistics separate for this user
+    #[arg(long, value_name = "NAME")]
+    user: Option<String>,
+
     /// Speed in WPM (PARIS standard)
     #[arg(short, long, default_value_t = 20)]
     wpm: u32,
@@ -34,13 +110,51 @@ struct Args {
     #[arg(short, long)]
     file: Option<String>,
 
+    /// Run a CW Academy homework session file (its header sets the speed)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["file", "practice", "interactive"])]
+    homework: Option<String>,
+
     /// Interactive typing mode (press Esc to quit)
     #[arg(short, long)]
     interactive: bool,
 
     /// Background QRM: S0 (no noise) … S9 (extreme)  (0-9)
-    #[arg(long, value_name = "S", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=9))]
-    qrm: u8,
+    #[arg(long, value_name = "S", value_parser = clap::value_parser!(u8).range(0..=9))]
+    qrm: Option<u8>,
+
+    /// Atmospheric static crashes (0-9)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=9))]
+    qrn: Option<u8>,
+
+    /// Signal fading depth (0-9)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=9))]
+    qsb: Option<u8>,
+
+    /// Random element-length jitter in percent (0-50) - simulates a hand key
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=50))]
+    jitter: Option<u8>,
+
+    /// Band noise to play before the first element and after the last, in
+    /// ms, when there is QRM or QRN (0-10000)
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u32).range(0..=conditions::MAX_NOISE_PAD_MS as i64))]
+    noise_pad: Option<u32>,
+
+    /// Random per-word speed variation in percent (0-50)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=50))]
+    speed_variance: Option<u8>,
+
+    /// Band conditions preset; individual impairment flags override it
+    #[arg(long, value_enum)]
+    conditions: Option<ConditionsPreset>,
+
+    /// Load band conditions from a scenario TOML file
+    #[arg(long, conflicts_with = "conditions")]
+    scenario: Option<String>,
+
+    /// Change speed, tone and noise over a render from an automation file
+    /// of times (or word counts) and values
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["incremental", "practice", "interactive"])]
+    automation: Option<String>,
 
     /// Practice mode (random words, callsigns, Q-codes, numbers)
     #[arg(short, long, value_enum)]
@@ -50,73 +164,1014 @@ struct Args {
     #[arg(long, requires = "practice")]
     custom_text: Option<String>,
 
+    /// Text for --practice markov to learn letter patterns from (default:
+    /// the built-in word list)
+    #[arg(long, value_name = "FILE", requires = "practice")]
+    corpus: Option<String>,
+
+    /// Speak each practice item after a short pause and move on by itself
+    /// (needs a build with the `tts` feature)
+    #[arg(long, requires = "practice")]
+    speak: bool,
+
+    /// Send practice words without waiting, each to be typed while the
+    /// word N later is sent
+    #[arg(long, value_name = "N", requires = "practice", conflicts_with = "speak", value_parser = clap::value_parser!(u8).range(1..=5))]
+    copy_behind: Option<u8>,
+
+    /// In practice mode, type each word as you copied it and be scored
+    #[arg(long, requires = "practice", conflicts_with_all = ["speak", "copy_behind"])]
+    quiz: bool,
+
+    /// In --practice meanings, type each meaning instead of picking it from
+    /// four
+    #[arg(long, requires = "practice")]
+    free_text: bool,
+
+    /// Koch lesson for --practice koch: 1 is K and M, and each lesson after
+    /// adds a character
+    #[arg(long, value_name = "N", requires = "practice", default_value_t = 1,
+          value_parser = clap::value_parser!(u64).range(1..=morse::KOCH_LESSONS as u64))]
+    lesson: u64,
+
+    /// In --practice koch groups, draw the characters missed lately more
+    /// often, until copy of them recovers
+    #[arg(long, requires = "practice")]
+    focus_weak: bool,
+
+    /// Most stations calling at once in --practice pileup
+    #[arg(long, value_name = "N", requires = "practice", default_value_t = pileup::CALLERS,
+          value_parser = clap::value_parser!(u8).range(1..=8))]
+    callers: u8,
+
     /// Tone shape
     #[arg(long, value_enum, default_value_t = ToneShape::Sine)]
     tone_shape: ToneShape,
 
+    /// Keying envelope: how each element's edges rise and fall
+    #[arg(long, value_enum, default_value_t = morse::EnvelopeShape::Hard)]
+    envelope: morse::EnvelopeShape,
+
+    /// Rise time of each element in ms (default: 15% of a dot)
+    #[arg(long, value_name = "MS", value_parser = parse_edge)]
+    rise: Option<f64>,
+
+    /// Fall time of each element in ms (default: 25% of a dot)
+    #[arg(long, value_name = "MS", value_parser = parse_edge)]
+    fall: Option<f64>,
+
+    /// Share of each keying edge that clicks instead of being shaped (0-100)
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    click: Option<u8>,
+
+    /// Depth of mains hum on the note (0-100)
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    hum: Option<u8>,
+
+    /// Sound like this kind of rig: sets the tone shape, envelope, edge
+    /// times, click, hum and drift, unless given themselves
+    #[arg(long, value_enum)]
+    rig: Option<rig::Rig>,
+
     /// Use Farnsworth timing for learning (specify character speed)
     #[arg(long)]
     farnsworth: Option<u32>,
 
+    /// Code to send: International, or American (railroad) Morse
+    #[arg(long, value_enum, default_value_t = Charset::International)]
+    charset: Charset,
+
     /// Save audio to WAV file instead of playing
     #[arg(long)]
     output_file: Option<String>,
 
+    /// Sample rate of written WAV files in Hz (--output-file defaults to 8000,
+    /// reprocess and mix to their first input's rate)
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(4000..=192000))]
+    sample_rate: Option<u32>,
+
+    /// Normalize written files to a loudness (lufs:-16) or peak (peak:-3dB)
+    #[arg(long, value_name = "TARGET", value_parser = loudness::parse_normalize)]
+    normalize: Option<loudness::Normalize>,
+
+    /// Sample format of written WAV files
+    #[arg(long, value_enum, default_value_t = audio::WavFormat::Int16)]
+    bit_depth: audio::WavFormat,
+
+    /// Channels in written WAV files; 2 writes dual-mono for players that
+    /// reject mono files
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=2))]
+    channels: u16,
+
+    /// Bitrate of written .opus files in kbps (needs a build with the `opus`
+    /// feature)
+    #[arg(long, value_name = "KBPS", default_value_t = opus::DEFAULT_BITRATE_KBPS, value_parser = clap::value_parser!(u32).range(6..=128))]
+    opus_bitrate: u32,
+
+    /// Include the answer in written practice files: slowly at the end, or
+    /// hidden until `cwgen answer FILE`
+    #[arg(long, value_enum, value_name = "HOW", requires = "output_file")]
+    answer_key: Option<answer::AnswerKey>,
+
+    /// Write --output-file as it is rendered, so a long render that stops
+    /// leaves a playable file (see --resume)
+    #[arg(long, requires = "output_file", conflicts_with_all = ["normalize", "answer_key"])]
+    incremental: bool,
+
+    /// Carry on an --incremental render of the same text that was stopped
+    #[arg(long, requires = "incremental")]
+    resume: bool,
+
+    #[command(flatten)]
+    tags: tags::Tags,
+
+    #[command(flatten)]
+    keyer: keyer::KeyerArgs,
+
+    #[command(flatten)]
+    latency: latency::LatencyArgs,
+
+    #[command(flatten)]
+    vox: vox::VoxArgs,
+
+    #[command(flatten)]
+    ptt: ptt::PttArgs,
+
+    #[command(flatten)]
+    safety: safety::SafetyArgs,
+
+    #[command(flatten)]
+    partner: partner::PartnerArgs,
+
+    #[command(flatten)]
+    bridge: bridge::BridgeArgs,
+
+    /// Also write progress as JSON lines, to stdout or a TCP listener at
+    /// HOST:PORT
+    #[arg(long, value_name = "TARGET", num_args = 0..=1, default_missing_value = "-")]
+    json_events: Option<String>,
+
+    /// Keep FILE holding the text just sent, in step with the audio, for a
+    /// streaming overlay (OBS Text source, "Read from file")
+    #[arg(long, value_name = "FILE")]
+    captions: Option<String>,
+
+    /// Seed for practice material: everyone given the same seed gets the
+    /// same session
+    #[arg(long, value_name = "N")]
+    seed: Option<u64>,
+
+    /// Post each finished practice or exam session to the club scoreboard
+    /// at this http:// address
+    #[arg(long, value_name = "URL")]
+    scoreboard: Option<String>,
+
+    /// Key results are signed with for the scoreboard (default: the
+    /// CWGEN_CLUB_KEY environment variable)
+    #[arg(long, value_name = "KEY")]
+    club_key: Option<String>,
+
+    /// Audio system to play and capture through
+    #[arg(long, value_enum, default_value_t = audio::AudioHost::Default)]
+    audio_host: audio::AudioHost,
+
+    /// EQ what is played for this kind of headphones or speakers
+    #[arg(long, value_enum, value_name = "PROFILE")]
+    eq: Option<eq::EqProfile>,
+
+    /// Add a peaking EQ band, HZ:DB:Q (e.g. 700:-6:2); may be repeated
+    #[arg(long, value_name = "HZ:DB:Q", value_parser = eq::parse_band, allow_hyphen_values = true)]
+    eq_band: Vec<eq::Band>,
+
+    /// In interactive mode, write each line before sending it
+    #[arg(long, requires = "interactive")]
+    compose: bool,
+
+    /// In interactive mode, key with two keys as an iambic paddle instead
+    /// of typing
+    #[arg(long, requires = "interactive", conflicts_with = "compose")]
+    paddles: bool,
+
+    /// In interactive mode, key by holding the space bar as a straight key,
+    /// and see how the timing went at the end
+    #[arg(long, requires = "interactive", conflicts_with_all = ["compose", "paddles"])]
+    straight_key: bool,
+
+    /// Iambic mode for --paddles
+    #[arg(long, value_enum, default_value_t = paddle::IambicMode::B)]
+    iambic: paddle::IambicMode,
+
+    /// The dit and dah keys for --paddles
+    #[arg(long, value_name = "KEYS", default_value = "zx", value_parser = parse_paddle_keys)]
+    paddle_keys: (char, char),
+
+    /// In interactive mode, append each line sent to FILE, and start with
+    /// the lines already in it for ↑/↓
+    #[arg(long, value_name = "FILE", requires = "interactive")]
+    session_log: Option<String>,
+
+    /// Record everything played in interactive or practice mode to a WAV file
+    #[arg(long, value_name = "FILE", requires = "session", conflicts_with_all = ["paddles", "straight_key"])]
+    record: Option<String>,
+
+    /// With text or both output, also print the plain text being sent
+    #[arg(long)]
+    show_text: bool,
+
+    /// Read dots and dashes (.- -... / -.-.) from stdin or --file and print
+    /// the text they spell, instead of sending anything
+    #[arg(long, conflicts_with = "session")]
+    decode: bool,
+
+    /// Spell shown text and practice answers in the NATO phonetic alphabet
+    /// too (W1AW = Whiskey One Alpha Whiskey)
+    #[arg(long)]
+    phonetics: bool,
+
     /// Frequency drift percentage (0-100) - simulates homebrew transmitter
     #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
     drift: Option<u8>,
+
+    /// Before the text, send VVV or a carrier for about SECONDS, for
+    /// listeners to set their level and decoders to lock on (e.g. vvv:5)
+    #[arg(long, value_name = "KIND[:SECONDS]", value_parser = preamble::parse_preamble)]
+    preamble: Option<preamble::Preamble>,
+
+    /// Play the tone this many Hz apart between the ears (1-30), for binaural
+    /// listening on headphones
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..=30))]
+    binaural: Option<u32>,
+
+    /// Keep the last SECONDS played (10 if not given) for Tab to replay,
+    /// during playback and practice
+    #[arg(long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "10", value_parser = clap::value_parser!(u32).range(1..=120))]
+    replay: Option<u32>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Decode a CW recording and re-send it at a different speed and pitch
+    Reprocess {
+        /// Recording to decode (WAV, MP3, Ogg or FLAC)
+        input: String,
+
+        /// Where to write the re-synthesized WAV
+        #[arg(short, long)]
+        output: String,
+
+        /// Speed multiplier applied to the detected WPM (0.8 = 20% slower)
+        #[arg(long, default_value_t = 1.0)]
+        wpm_scale: f64,
+
+        /// Pitch shift in Hz applied to the detected tone
+        #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+        shift_hz: i32,
+    },
+
+    /// Decode recordings (WAV, MP3, Ogg, FLAC) to text
+    Decode {
+        /// Recordings to decode
+        files: Vec<PathBuf>,
+
+        /// Decode every recording in this directory
+        #[arg(long)]
+        dir: Option<PathBuf>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DecodeFormat::Text)]
+        format: DecodeFormat,
+
+        /// Show per-character confidence and alternative readings
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Separate and decode up to this many signals at different pitches
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=4))]
+        signals: u8,
+
+        /// Highlight callsigns, grid squares and signal reports (and list
+        /// them in JSONL output)
+        #[arg(long)]
+        extract: bool,
+
+        /// Decode only the signal on this frequency, such as a message
+        /// hidden with overlay --band
+        #[arg(long, value_name = "HZ", conflicts_with = "signals", value_parser = clap::value_parser!(u32).range(100..=24000))]
+        band: Option<u32>,
+
+        #[command(flatten)]
+        raw: pcm::RawInput,
+    },
+
+    /// Decode CW live from the soundcard input
+    Listen {
+        #[command(flatten)]
+        receiver: live::ReceiverArgs,
+
+        /// Save each transmission as a WAV file in this directory
+        #[arg(long, value_name = "DIR")]
+        record_dir: Option<PathBuf>,
+
+        /// Send decoded characters and transmissions as JSON datagrams
+        #[arg(long, value_name = "HOST:PORT")]
+        udp: Option<String>,
+    },
+
+    /// Decode continuously and log each transmission with a timestamp
+    Monitor {
+        #[command(flatten)]
+        receiver: live::ReceiverArgs,
+
+        /// Log file to append to
+        #[arg(long, default_value = "cwgen-monitor.log")]
+        log: PathBuf,
+
+        /// Pick out callsigns and list them after each logged transmission
+        #[arg(long)]
+        calls: bool,
+
+        /// Regular expression used for --calls instead of the built-in one
+        #[arg(long, value_name = "REGEX", requires = "calls")]
+        call_regex: Option<String>,
+
+        /// Send decoded characters and transmissions as JSON datagrams
+        #[arg(long, value_name = "HOST:PORT")]
+        udp: Option<String>,
+    },
+
+    /// Join or overlay rendered clips (lessons, prompts, noise beds) into one WAV
+    Mix {
+        /// Clips in order, each FILE[,gain=DB][,offset=SECS][,loop]
+        #[arg(required = true, value_parser = mix::parse_clip)]
+        clips: Vec<mix::Clip>,
+
+        /// Where to write the mixed WAV
+        #[arg(short, long)]
+        output: String,
+
+        /// Start every clip at 0 instead of after the previous one
+        #[arg(long)]
+        overlay: bool,
+    },
+
+    /// Mix a CW message into an existing recording at a given time and level
+    Overlay {
+        /// Recording to add the message to (WAV, MP3, Ogg or FLAC)
+        #[arg(long)]
+        input: String,
+
+        /// Message to send
+        #[arg(long)]
+        text: String,
+
+        /// Where the message starts: [H:]M:SS, or seconds
+        #[arg(long, value_name = "TIME", value_parser = parse_at)]
+        at: f64,
+
+        /// Peak level of the message, e.g. -20dB
+        #[arg(long, value_name = "DB", default_value = "-20dB", allow_hyphen_values = true, value_parser = mix::parse_level)]
+        level: f64,
+
+        /// Hide the message this far under the recording's own level, e.g.
+        /// 30dB, instead of at --level
+        #[arg(long, value_name = "DB", conflicts_with = "level", value_parser = mix::parse_margin)]
+        under: Option<f64>,
+
+        /// Send the message on this frequency instead of the tone, with the
+        /// recording notched out around it, for decode --band to recover
+        #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(100..=24000))]
+        band: Option<u32>,
+
+        /// Where to write the result (default: the input's name with -cw
+        /// added, as WAV)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Render a puzzle's clues to numbered files, with an answer key, from a
+    /// TOML manifest
+    Puzzle {
+        /// The manifest: a title, [defaults] and one [[clue]] per file
+        manifest: String,
+
+        /// Directory for the clues and answers.md (default: the manifest's
+        /// name, next to it)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Render text with the effects given and plot its waveform and
+    /// spectrogram
+    Render {
+        /// Text to send (default: --file, or stdin)
+        text: Option<String>,
+
+        /// Image to write: PNG for a .png file, SVG otherwise
+        #[arg(long, value_name = "FILE")]
+        plot: String,
+    },
+
+    /// Render text as a short, normalized phone ringtone or notification sound
+    Ringtone {
+        /// Text to send, e.g. "CALL FROM MOM"
+        text: String,
+
+        /// Length limit, loudness and ending for the kind of sound
+        #[arg(long, value_enum, default_value_t = ringtone::RingtonePreset::Ringtone)]
+        preset: ringtone::RingtonePreset,
+
+        /// Where to write the WAV (default: named after the text)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Generate a tone, two-tone or sweep test signal for checking audio chains
+    TestSignal {
+        #[command(flatten)]
+        signal: testsignal::SignalSpec,
+
+        /// Write a WAV instead of playing
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Tune across a band full of CW stations with the arrow keys
+    Tune {
+        /// How many stations are on the band
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u8).range(1..=30))]
+        stations: u8,
+
+        /// Make the band a DX working split, with --stations callers, and
+        /// find where it is listening
+        #[arg(long)]
+        split: bool,
+
+        /// Receiver filter width in Hz to start with (w switches between
+        /// 250, 500 and 2400)
+        #[arg(long, value_name = "HZ", default_value_t = 500, value_parser = clap::value_parser!(u32).range(100..=3000))]
+        filter: u32,
+    },
+
+    /// Send a message over and over as a beacon, identifying as the licence
+    /// requires
+    Beacon {
+        /// Message to send, e.g. "VVV VVV FN31"
+        message: String,
+
+        /// Callsign to identify with
+        #[arg(long)]
+        callsign: String,
+
+        /// Whose identification rules to follow
+        #[arg(long, value_enum, default_value_t = beacon::IdRule::Fcc)]
+        id_rule: beacon::IdRule,
+
+        /// Longest between IDs within a transmission, in minutes (default:
+        /// as --id-rule says)
+        #[arg(long, value_name = "MIN", value_parser = clap::value_parser!(u64).range(1..))]
+        id_interval: Option<u64>,
+
+        /// Pause between transmissions, in seconds
+        #[arg(long, value_name = "SECS", default_value_t = 60)]
+        every: u64,
+
+        /// Stop after this many transmissions
+        #[arg(long, value_name = "N")]
+        count: Option<u32>,
+    },
+
+    /// Send messages arriving on a named pipe or MQTT topics as CW alerts
+    Daemon {
+        #[command(flatten)]
+        sources: daemon::DaemonArgs,
+    },
+
+    /// Open the trainer in a window (needs the `gui` feature)
+    Gui,
+
+    /// Serve the club scoreboard that --scoreboard posts results to
+    Scoreboard {
+        /// Port to listen on
+        #[arg(long, default_value_t = scoreboard::DEFAULT_PORT)]
+        port: u16,
+
+        /// File the results are kept in
+        #[arg(long, default_value = "cwgen-scores.jsonl")]
+        data: PathBuf,
+    },
+
+    /// Show the answer hidden in a file written with --answer-key hidden
+    Answer {
+        /// Practice file
+        file: String,
+    },
+
+    /// Show practice and exam statistics (for --user, or the default profile)
+    Stats {
+        #[command(subcommand)]
+        action: Option<StatsAction>,
+    },
+
+    /// Write a podcast RSS feed for a directory of rendered lessons
+    Feed {
+        /// Directory holding the rendered files
+        dir: PathBuf,
+
+        /// URL the directory is served from
+        #[arg(long, value_name = "URL")]
+        base_url: String,
+
+        /// Feed title (default: the files' album tag)
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Feed description
+        #[arg(long, default_value = "Morse code lessons generated by cwgen")]
+        description: String,
+
+        /// Where to write the feed (default: feed.xml in the directory)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StatsAction {
+    /// Write settings and statistics to one JSON archive
+    Export {
+        /// Archive to write
+        file: PathBuf,
+    },
+
+    /// Load settings and statistics from an archive made by `stats export`
+    Import {
+        /// Archive to read
+        file: PathBuf,
+
+        /// Replace the profile's existing settings and statistics
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Show the characters missed most, and what each was copied as
+    Characters,
+
+    /// Write the characters and words missed most as an Anki deck: notes to
+    /// import, and a sound for each
+    ExportAnki {
+        /// Directory to write the notes and sounds to
+        dir: PathBuf,
+
+        /// Most cards to write
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+
+        /// Deck to import the cards into
+        #[arg(long, default_value = "cwgen missed")]
+        deck: String,
+    },
+
+    /// Write a report of the statistics: HTML with charts, or CSV
+    Report {
+        #[arg(long, value_enum, default_value_t = report::ReportFormat::Html)]
+        format: report::ReportFormat,
+
+        /// Table to write as CSV
+        #[arg(long, value_enum, default_value_t = report::ReportTable::Days)]
+        table: report::ReportTable,
+
+        /// Where to write the report (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum OutputMode {
     Audio,
     Text,
+    /// C array of key-down/key-up durations for keyer or beacon firmware
+    #[value(name = "keyer-c")]
+    KeyerC,
+    /// Keying tone for rig interfaces that key on audio (tone = key down)
+    Vox,
+    /// Print the morse while playing it
+    Both,
+    /// Telegraph sounder clicks at key-down and key-up instead of a tone
+    Sounder,
+    /// The keying drawn as inker tape, SVG (or PNG for a .png --output-file)
+    Tape,
+    /// Play the signal and draw it as a scrolling waterfall, as on an SDR
+    Waterfall,
 }
 
 // ---------- Text output ----------------------------------------------------
-fn print_morse(text: &str) -> Result<()> {
-    let morse = text_to_morse(text)?;
+fn print_morse(text: &str, charset: Charset) -> Result<()> {
+    let morse = text_to_code(text, charset)?;
     println!("{}", morse);
     Ok(())
 }
 
+fn print_keyer_table(args: &Args, text: &str, timing: Timing) -> Result<()> {
+    print!("{}", keyer::c_table(text, &timing, args.wpm, &args.keyer)?);
+    Ok(())
+}
+
+fn sounder_output(args: &Args, speaker: &audio::Speaker, text: &str, timing: Timing) -> Result<()> {
+    match &args.output_file {
+        Some(output_path) => {
+            let sample_rate = args.sample_rate.unwrap_or(audio::FILE_SAMPLE_RATE);
+            export(args).write(output_path, sample_rate, sounder::render(text, &timing, sample_rate))?;
+            println!("Saved sounder clicks to: {}", output_path);
+            Ok(())
+        }
+        None => {
+            let sample_rate = audio::playback_rate();
+            let samples = sounder::render(text, &timing, sample_rate);
+            let duration_s = samples.len() as f64 / sample_rate as f64;
+            events::emit(events::Event::PlaybackStarted { text, duration_s });
+            audio::play_samples(samples, sample_rate, speaker)
+        }
+    }
+}
+
+fn tape_output(args: &Args, text: &str, timing: Timing) -> Result<()> {
+    let rows = tape::layout(text, &timing);
+    match &args.output_file {
+        Some(output_path) => {
+            tape::write(output_path, &rows)?;
+            println!("Saved tape to: {}", output_path);
+        }
+        None => print!("{}", tape::svg(&rows)),
+    }
+    Ok(())
+}
+
+fn waterfall_output(args: &Args, speaker: &audio::Speaker, text: &str, timing: Timing, tone: u32, conditions: Conditions) -> Result<()> {
+    let sample_rate = audio::playback_rate();
+    let audio = audio::MorseAudio::new_with_sample_rate(
+        sample_rate, text, timing, tone, conditions, args.tone_shape, args.drift,
+    );
+    waterfall::show(audio.get_samples().to_vec(), sample_rate, tone, timing.dot, speaker)
+}
+
+fn vox_output(args: &Args, text: &str, timing: Timing, tone: u32) -> Result<()> {
+    match &args.output_file {
+        Some(output_path) => {
+            let sample_rate = args.sample_rate.unwrap_or(audio::FILE_SAMPLE_RATE);
+            match vox_channels(args, text, timing, tone, sample_rate)? {
+                (key, None) => export(args).write(output_path, sample_rate, key)?,
+                (key, Some(ptt)) => export(args).write_stereo(output_path, sample_rate, &key, &ptt)?,
+            }
+            println!("Saved VOX keying to: {}", output_path);
+            Ok(())
+        }
+        None => {
+            let sample_rate = audio::playback_rate();
+            let duration_s = morse::key_timeline(text, &timing).iter().map(|e| e.duration.as_secs_f64()).sum();
+            events::emit(events::Event::PlaybackStarted { text, duration_s });
+            match vox_channels(args, text, timing, tone, sample_rate)? {
+                (key, None) => audio::play_signal(key, sample_rate),
+                (key, Some(ptt)) => audio::play_stereo(&key, &ptt, sample_rate),
+            }
+        }
+    }
+}
+
+// The keying, and with --ptt the PTT channel to go beside it. PTT's lead
+// covers the switch-over, so then the keying needs no lead-in of its own.
+fn vox_channels(args: &Args, text: &str, timing: Timing, tone: u32, sample_rate: u32) -> Result<(Vec<f32>, Option<Vec<f32>>)> {
+    let key = morse::key_timeline(text, &timing);
+    // A gap long enough for PTT to drop ends a transmission, with or without it
+    args.safety.check(&key, args.ptt.hang())?;
+    if !args.ptt.ptt {
+        let lead_in = Duration::from_millis(args.vox.vox_lead_in);
+        return Ok((vox::render(text, &timing, tone, sample_rate, lead_in), None));
+    }
+    let schedule = ptt::schedule(&key, args.ptt.lead(), args.ptt.hang());
+    let channel = |elements: &[morse::Element]| vox::render_elements(elements, tone, sample_rate, Duration::ZERO);
+    Ok((channel(&schedule.key), Some(channel(&schedule.ptt))))
+}
+
+// Text sent as it comes (beacon, daemon) through whichever output is chosen
+fn send_live(args: &Args, speaker: &audio::Speaker, text: &str, timing: Timing, tone: u32, conditions: Conditions) -> Result<()> {
+    match args.output {
+        OutputMode::Text => {
+            show_text(args, text);
+            print_morse(text, args.charset)
+        }
+        OutputMode::KeyerC => print_keyer_table(args, text, timing),
+        OutputMode::Vox => vox_output(args, text, timing, tone),
+        OutputMode::Sounder => sounder_output(args, speaker, text, timing),
+        OutputMode::Tape => tape_output(args, text, timing),
+        OutputMode::Waterfall => waterfall_output(args, speaker, text, timing, tone, conditions),
+        OutputMode::Audio | OutputMode::Both => play(args, speaker, text, timing, tone, conditions),
+    }
+}
+
+// Played through the speakers; `--output both` prints the morse as it's heard
+fn play(args: &Args, speaker: &audio::Speaker, text: &str, timing: Timing, tone: u32, conditions: Conditions) -> Result<()> {
+    match args.output {
+        OutputMode::Both => {
+            show_text(args, text);
+            audio::play_text(text, timing, tone, conditions, args.tone_shape, args.drift, args.preamble, speaker, true)
+        }
+        _ => audio::play_text(text, timing, tone, conditions, args.tone_shape, args.drift, args.preamble, speaker, false),
+    }
+}
+
+// The plain text ahead of its morse, with --show-text
+fn show_text(args: &Args, text: &str) {
+    if args.show_text {
+        println!("{}", text.split_whitespace().collect::<Vec<_>>().join(" ").to_uppercase());
+        if args.phonetics {
+            let spelled: Vec<String> = text.split_whitespace().map(phonetics::spell).collect();
+            println!("{}", spelled.join(" / "));
+        }
+    }
+}
+
+// Plays one text at a time without waiting for it, so the daemon can cut it
+// off for something more urgent
+struct LiveSender<'a> {
+    args: &'a Args,
+    speaker: &'a audio::Speaker,
+    conditions: Conditions,
+    // Opened with the first text, and kept
+    output: Option<(rodio::OutputStream, rodio::Sink, u32)>,
+}
+
+impl daemon::Transmitter for LiveSender<'_> {
+    fn start(&mut self, alert: &daemon::Alert) -> Result<Duration> {
+        let args = self.args;
+        if let OutputMode::Text | OutputMode::KeyerC | OutputMode::Tape | OutputMode::Waterfall = args.output {
+            send_live(args, self.speaker, &alert.text, alert.timing, alert.tone, self.conditions)?;
+            return Ok(Duration::ZERO);
+        }
+        if let OutputMode::Both = args.output {
+            print_morse(&alert.text, args.charset)?;
+        }
+        let output = match self.output.take() {
+            Some(output) => output,
+            None => {
+                let (stream, handle) = audio::output_stream()?;
+                let sink = rodio::Sink::try_new(&handle).map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+                (stream, sink, audio::playback_rate())
+            }
+        };
+        let (_, sink, sample_rate) = self.output.insert(output);
+        let (channels, samples) = match args.output {
+            OutputMode::Vox => match vox_channels(args, &alert.text, alert.timing, alert.tone, *sample_rate)? {
+                (key, None) => (1, key),
+                (key, Some(ptt)) => (2, audio::interleave(&key, &ptt)),
+            },
+            OutputMode::Sounder => (1, sounder::render(&alert.text, &alert.timing, *sample_rate)),
+            _ => {
+                let audio = audio::MorseAudio::new_with_sample_rate(
+                    *sample_rate, &alert.text, alert.timing, alert.tone, self.conditions, args.tone_shape, args.drift,
+                );
+                (1, audio.get_samples().to_vec())
+            }
+        };
+        let length = Duration::from_secs_f64(samples.len() as f64 / channels as f64 / *sample_rate as f64);
+        let bands = match args.output {
+            OutputMode::Vox => &[][..],
+            _ => &self.speaker.eq[..],
+        };
+        sink.append(eq::Equalizer::new(rodio::buffer::SamplesBuffer::new(channels, *sample_rate, samples), bands));
+        events::emit(events::Event::PlaybackStarted { text: &alert.text, duration_s: length.as_secs_f64() });
+        Ok(length)
+    }
+
+    fn stop(&mut self) {
+        if let Some((_, sink, _)) = &self.output {
+            sink.stop();
+        }
+    }
+}
+
 // ---------- Main -----------------------------------------------------------
 fn main() -> Result<()> {
-    let args = Args::parse();
+    match cwgen() {
+        // Ctrl+C where cwgen reads keys itself ends the run the way it would
+        // have otherwise
+        Err(e) if matches!(e.downcast_ref(), Some(MorseError::Interrupted)) => std::process::exit(130),
+        result => result,
+    }
+}
+
+fn cwgen() -> Result<()> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches)?;
+    // Profile settings fill in defaults for every run, but a run that
+    // records nothing mustn't fail for want of a profile: without one to
+    // read, the defaults stand
+    match profile::Profile::open(args.user.as_deref()).and_then(|profile| profile.settings()) {
+        Ok(settings) => apply_settings(&mut args, &settings, &matches),
+        Err(e) => eprintln!("Warning: {:#}; going on with the default settings", e),
+    }
+    apply_rig(&mut args, &matches);
+    audio::set_host(args.audio_host);
 
     // Validate arguments
     if let Err(e) = validate_args(&args) {
         eprintln!("Error: {}", e);
         std::process::exit(1);
     }
+    check_mode_flags(&args, &matches)?;
+    if let Some(target) = &args.json_events {
+        events::open(target)?;
+    }
+    if let Some(path) = &args.captions {
+        captions::open(path)?;
+    }
+    // Held to the end, so a device created for the session goes with it
+    let _voice_chat = bridge::VirtualDevice::open(&args.bridge)?;
+    let speaker = audio::Speaker {
+        eq: args.eq.map(eq::EqProfile::bands).unwrap_or_default().into_iter().chain(args.eq_band.iter().copied()).collect(),
+        history: args.replay.map(|seconds| replay::History::new(audio::playback_rate(), Duration::from_secs(seconds.into()))),
+    };
 
-    let timing = if let Some(char_speed) = args.farnsworth {
-        Timing::new_farnsworth(char_speed, args.wpm, args.gap_ms)
-    } else {
-        Timing::new(args.wpm, args.gap_ms)
+    if args.scoreboard.is_some() {
+        club_key(&args)?;
+    }
+    if let Some(command) = &args.command {
+        return run_command(command, &args, &speaker);
+    }
+    if args.latency.measure_latency {
+        return latency::measure(&args.latency);
+    }
+
+    let scenario = args.scenario.as_deref().map(Scenario::load).transpose()?;
+    if let Some(scenario) = &scenario {
+        if let Some(name) = &scenario.name {
+            eprintln!("Scenario: {}", name);
+        }
+        if let Some(description) = &scenario.description {
+            eprintln!("  {}", description);
+        }
+    }
+    let conditions = resolve_conditions(&args, scenario.as_ref());
+    let automation = args.automation.as_deref().map(Automation::load).transpose()?;
+
+    let mut timing = timing(&args);
+    let mut rng = morse::practice_rng(args.seed);
+    let weights = match args.focus_weak {
+        true => Some(spaced::Weights::from_stats(&profile::Profile::open(args.user.as_deref())?.stats()?)),
+        false => None,
     };
 
+    // Homework drills run as practice sessions; running text falls through
+    // to the usual output below
+    let homework = args.homework.as_deref().map(homework::Homework::load).transpose()?;
+    if let Some(homework) = &homework {
+        if let Some(title) = &homework.title {
+            eprintln!("Homework: {}", title);
+        }
+        timing = homework.timing(args.gap_ms, timing).with_charset(args.charset).with_envelope(envelope(&args)).with_binaural(args.binaural);
+        if homework.content.is_drill() {
+            let (wpm, farnsworth) = match (homework.char_wpm, homework.effective_wpm) {
+                (Some(char_wpm), Some(effective)) => (effective, Some(char_wpm)),
+                (Some(wpm), None) => (wpm, None),
+                _ => (args.wpm, args.farnsworth),
+            };
+            let session = Session::start(&args, wpm, conditions.qrm)?;
+            let content = PracticeMode::Custom.get_content(&mut rng, Some(&homework.text), args.lesson as usize, None);
+            let sent = practice_mode(
+                &mut rng,
+                wpm,
+                args.gap_ms,
+                farnsworth,
+                args.tone,
+                content,
+                conditions,
+                args.tone_shape,
+                args.record.as_deref(),
+                args.speak,
+                args.charset,
+                envelope(&args),
+                args.binaural,
+                args.phonetics,
+                &speaker,
+            )?;
+            return session.finish("homework", sent, None, &[], &[]);
+        }
+    }
+
     // Handle practice mode
+    if let Some(PracticeMode::Exam) = args.practice {
+        let session = Session::start(&args, args.wpm, conditions.qrm)?;
+        let graded = exam::run(&mut rng, timing, args.tone, conditions, args.tone_shape, args.record.as_deref(), &speaker)?;
+        let seconds = session.end("exam", graded.questions, Some(graded.score));
+        return session.profile.record_exam(args.wpm, conditions.qrm, seconds, &graded);
+    }
+    if let (Some(PracticeMode::Phonetics), None) = (args.practice, args.copy_behind) {
+        let session = Session::start(&args, args.wpm, conditions.qrm)?;
+        let (items, correct) = phonetics::drill(&mut rng, timing, args.tone, conditions, args.tone_shape, args.record.as_deref(), &speaker)?;
+        return session.finish("phonetics", items, Some(correct), &[], &[]);
+    }
+    if let (Some(PracticeMode::Pileup), None) = (args.practice, args.copy_behind) {
+        let session = Session::start(&args, args.wpm, conditions.qrm)?;
+        let run = pileup::run(
+            &mut rng,
+            timing,
+            args.wpm,
+            args.callers,
+            args.tone,
+            conditions,
+            args.tone_shape,
+            args.record.as_deref(),
+            &speaker,
+        )?;
+        return session.finish("pileup", run.logged, Some(run.good), &run.copied_as, &[]);
+    }
+    if let (Some(PracticeMode::Koch), None) = (args.practice, args.copy_behind) {
+        let session = Session::start(&args, args.wpm, conditions.qrm)?;
+        let lesson = interactive::koch_mode(&mut rng, args.lesson as usize, weights.as_ref(), timing, args.tone, conditions, args.tone_shape, args.record.as_deref(), &speaker)?;
+        let name = format!("koch {}", lesson.lesson);
+        return session.finish(&name, lesson.sent, Some(lesson.copied), &lesson.copied_as, &[]);
+    }
+    if let (Some(PracticeMode::Meanings), None) = (args.practice, args.copy_behind) {
+        let session = Session::start(&args, args.wpm, conditions.qrm)?;
+        let (items, correct) = quiz::run(&mut rng, timing, args.tone, conditions, args.tone_shape, args.free_text, args.record.as_deref(), &speaker)?;
+        return session.finish("meanings", items, Some(correct), &[], &[]);
+    }
+    if let (Some(mode), Some(depth)) = (args.practice, args.copy_behind) {
+        let session = Session::start(&args, args.wpm, conditions.qrm)?;
+        let content = mode.get_content(&mut rng, args.custom_text.as_deref(), args.lesson as usize, weights.as_ref());
+        let window = copybehind::run(&mut rng, depth as usize, timing, args.tone, content, conditions, args.tone_shape, args.record.as_deref(), &speaker)?;
+        let name = format!("{} copy-behind", mode_name(mode));
+        return session.finish(&name, window.scored, Some(window.correct), &window.copied_as, &window.words);
+    }
     if let Some(mode) = args.practice {
-        return practice_mode(
+        let corpus = args
+            .corpus
+            .as_deref()
+            .map(|path| std::fs::read_to_string(path).with_context(|| format!("reading {}", path)))
+            .transpose()?;
+        let custom_text = match mode {
+            PracticeMode::Markov => corpus.as_deref(),
+            _ => args.custom_text.as_deref(),
+        };
+        let session = Session::start(&args, args.wpm, conditions.qrm)?;
+        let content = mode.get_content(&mut rng, custom_text, args.lesson as usize, weights.as_ref());
+        if args.quiz {
+            let quiz = interactive::copy_quiz_mode(
+                &mut rng,
+                content,
+                timing,
+                args.tone,
+                conditions,
+                args.tone_shape,
+                args.record.as_deref(),
+                &speaker,
+            )?;
+            let name = format!("{} quiz", mode_name(mode));
+            let copied: Vec<(char, Option<char>)> = quiz.answers.iter().flat_map(|(word, copy)| exam::copied_as(word, copy)).collect();
+            return session.finish(&name, quiz.answers.len(), Some(quiz.words_copied()), &copied, &quiz.words());
+        }
+        let sent = practice_mode(
+            &mut rng,
             args.wpm,
             args.gap_ms,
             args.farnsworth,
             args.tone,
-            mode,
-            args.custom_text.as_deref(),
-            args.qrm,
+            content,
+            conditions,
             args.tone_shape,
-        );
+            args.record.as_deref(),
+            args.speak,
+            args.charset,
+            envelope(&args),
+            args.binaural,
+            args.phonetics,
+            &speaker,
+        )?;
+        return session.finish(&mode_name(mode), sent, None, &[], &[]);
     }
 
     // Handle interactive mode
+    if args.interactive && args.paddles {
+        return interactive::paddle_mode(timing, args.tone, args.iambic, args.paddle_keys, &args.latency, &speaker);
+    }
+    if args.interactive && args.straight_key {
+        return interactive::straight_key_mode(timing, args.tone, &args.latency, &speaker);
+    }
+    if args.interactive && args.record.is_some() && !interactive::plays(args.output) {
+        let output = args.output.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default();
+        anyhow::bail!("--output {} plays nothing for --record to record", output);
+    }
     if args.interactive {
-        return interactive_mode(timing, args.tone, args.output, args.qrm, args.tone_shape);
+        return interactive_mode(timing, args.tone, args.output, conditions, args.tone_shape, args.record.as_deref(), &args.latency, &args.vox, &args.safety, partner::Partner::connect(&args.partner)?, history::History::open(args.session_log.as_deref())?, args.compose, &speaker);
     }
 
     // Read input text
-    let text = if let Some(path) = &args.file {
+    let text = if let Some(homework) = homework {
+        homework.text
+    } else if let Some(path) = &args.file {
         std::fs::read_to_string(path)?
     } else {
         let mut buf = String::new();
@@ -124,32 +1179,653 @@ fn main() -> Result<()> {
         buf
     };
 
+    if args.decode {
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let decoded = morse::morse_to_text(line)?;
+            if decoded.contains(morse::UNKNOWN_CHAR) {
+                eprintln!("warning: unknown code in '{}', shown as {}", line.trim(), morse::UNKNOWN_CHAR);
+            }
+            println!("{}", decoded);
+        }
+        return Ok(());
+    }
+    let text = macros::expand(&text, &mut rand::rng())?;
+
+    if lesson::has_prompts(&text) {
+        if automation.is_some() {
+            anyhow::bail!("--automation can't be used with a lesson with voice prompts");
+        }
+        return lesson_output(&args, &speaker, &text, timing, conditions);
+    }
+
     // Process based on output mode
     match args.output {
-        OutputMode::Text => print_morse(&text),
-        OutputMode::Audio => {
+        OutputMode::Text => {
+            show_text(&args, &text);
+            print_morse(&text, args.charset)
+        }
+        OutputMode::KeyerC => print_keyer_table(&args, &text, timing),
+        OutputMode::Vox => vox_output(&args, &text, timing, args.tone),
+        OutputMode::Sounder => sounder_output(&args, &speaker, &text, timing),
+        OutputMode::Tape => tape_output(&args, &text, timing),
+        OutputMode::Waterfall => {
+            let sample_rate = audio::playback_rate();
+            let samples = render_text(&args, automation.as_ref(), &text, timing, conditions, sample_rate);
+            waterfall::show(samples, sample_rate, args.tone, timing.dot, &speaker)
+        }
+        OutputMode::Audio | OutputMode::Both => {
             if let Some(output_path) = &args.output_file {
+                export(&args).check(output_path)?;
+                answer::check(args.answer_key, output_path)?;
+                if let OutputMode::Both = args.output {
+                    show_text(&args, &text);
+                    print_morse(&text, args.charset)?;
+                }
                 // Save to WAV file
-                save_audio_to_wav(&text, timing, args.tone, args.qrm, args.tone_shape, args.drift, output_path)?;
+                let sample_rate = args.sample_rate.unwrap_or(audio::FILE_SAMPLE_RATE);
+                if args.incremental {
+                    let rendered = incremental::render(output_path, sample_rate, &export(&args), &text, args.resume, |chunk, start| {
+                        audio::MorseAudio::new_with_sample_rate(
+                            sample_rate, chunk, timing, args.tone, conditions.at(start), args.tone_shape, args.drift,
+                        )
+                        .get_samples()
+                        .to_vec()
+                    })?;
+                    if rendered.resumed > 0 {
+                        println!("Resumed after {} of {} chunks", rendered.resumed, rendered.chunks);
+                    }
+                    println!("Saved {:.1} s of morse code to: {}", rendered.seconds, output_path);
+                    return Ok(());
+                }
+                match args.answer_key {
+                    None if automation.is_none() => save_audio_to_wav(&text, timing, args.tone, conditions, args.tone_shape, args.drift, args.preamble, output_path, sample_rate, export(&args))?,
+                    key => {
+                        let mut samples = render_text(&args, automation.as_ref(), &text, timing, conditions, sample_rate);
+                        if let Some(answer::AnswerKey::Trailer) = key {
+                            samples.extend(answer::trailer(&text, args.tone, args.tone_shape, sample_rate));
+                        }
+                        export(&args).write(output_path, sample_rate, samples)?;
+                        if let Some(answer::AnswerKey::Hidden) = key {
+                            answer::hide(output_path, &text)?;
+                        }
+                    }
+                }
                 println!("Saved morse code to: {}", output_path);
                 Ok(())
+            } else if automation.is_some() {
+                if let OutputMode::Both = args.output {
+                    show_text(&args, &text);
+                    print_morse(&text, args.charset)?;
+                }
+                let sample_rate = audio::playback_rate();
+                audio::play_samples(render_text(&args, automation.as_ref(), &text, timing, conditions, sample_rate), sample_rate, &speaker)
             } else {
                 // Play audio normally
-                play_audio(&text, timing, args.tone, args.qrm, args.tone_shape, args.drift)
+                play(&args, &speaker, &text, timing, args.tone, conditions)
+            }
+        }
+    }
+}
+
+// Profile defaults fill in whatever wasn't given on the command line
+fn apply_settings(args: &mut Args, settings: &profile::Settings, matches: &clap::ArgMatches) {
+    let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+    if let (true, Some(wpm)) = (unset("wpm"), settings.wpm) {
+        args.wpm = wpm;
+    }
+    if let (true, Some(tone)) = (unset("tone"), settings.tone) {
+        args.tone = tone;
+    }
+    if let (true, Some(farnsworth)) = (unset("farnsworth"), settings.farnsworth) {
+        args.farnsworth = Some(farnsworth);
+    }
+    if let (true, Some(gap_ms)) = (unset("gap_ms"), settings.gap_ms) {
+        args.gap_ms = gap_ms;
+    }
+    if let (true, Some(frames)) = (unset("buffer_size"), settings.buffer_size) {
+        args.latency.buffer_size = Some(frames);
+    }
+    if let (true, Some(ms)) = (unset("latency_ms"), settings.latency_ms) {
+        args.latency.latency_ms = Some(ms);
+    }
+}
+
+// The --rig preset fills in whichever of its knobs weren't given
+fn apply_rig(args: &mut Args, matches: &clap::ArgMatches) {
+    let Some(rig) = args.rig else { return };
+    let sound = rig.sound();
+    if matches.value_source("tone_shape") != Some(ValueSource::CommandLine) {
+        args.tone_shape = sound.tone_shape;
+    }
+    if matches.value_source("envelope") != Some(ValueSource::CommandLine) {
+        args.envelope = sound.envelope.shape;
+    }
+    let ms = |edge: Option<Duration>| edge.map(|edge| edge.as_secs_f64() * 1000.0);
+    args.rise = args.rise.or(ms(sound.envelope.rise));
+    args.fall = args.fall.or(ms(sound.envelope.fall));
+    args.click = args.click.or(Some((sound.envelope.click * 100.0).round() as u8));
+    args.hum = args.hum.or(Some((sound.envelope.hum * 100.0).round() as u8));
+    args.drift = args.drift.or(sound.drift);
+}
+
+// How written files are encoded and leveled
+fn export(args: &Args) -> audio::Export {
+    let tags = args.tags.expand(&[
+        ("wpm", args.wpm.to_string()),
+        ("farnsworth", args.farnsworth.unwrap_or(args.wpm).to_string()),
+        ("tone", args.tone.to_string()),
+        ("track", args.tags.track.map(|t| t.to_string()).unwrap_or_default()),
+    ]);
+    audio::Export {
+        format: args.bit_depth,
+        normalize: args.normalize,
+        channels: args.channels,
+        tags,
+        opus_bitrate_kbps: args.opus_bitrate,
+    }
+}
+
+fn timing(args: &Args) -> Timing {
+    timing_at(args, args.wpm)
+}
+
+// The timing at `wpm` overall, keeping the Farnsworth character speed while
+// it is the faster of the two
+fn timing_at(args: &Args, wpm: u32) -> Timing {
+    let timing = match args.farnsworth {
+        Some(char_speed) if char_speed > wpm => Timing::new_farnsworth(char_speed, wpm, args.gap_ms),
+        _ => Timing::new(wpm, args.gap_ms),
+    };
+    timing.with_charset(args.charset).with_envelope(envelope(args)).with_binaural(args.binaural)
+}
+
+fn envelope(args: &Args) -> morse::Envelope {
+    morse::Envelope {
+        shape: args.envelope,
+        rise: args.rise.map(|ms| Duration::from_secs_f64(ms / 1000.0)),
+        fall: args.fall.map(|ms| Duration::from_secs_f64(ms / 1000.0)),
+        click: args.click.unwrap_or(0) as f32 / 100.0,
+        hum: args.hum.unwrap_or(0) as f32 / 100.0,
+    }
+}
+
+// The text rendered at `sample_rate`, following --automation if given
+fn render_text(
+    args: &Args,
+    automation: Option<&Automation>,
+    text: &str,
+    timing: Timing,
+    conditions: Conditions,
+    sample_rate: u32,
+) -> Vec<f32> {
+    // The preamble alone: the synth with no text
+    let mut samples: Vec<f32> = audio::MorseSynth::new(sample_rate, "", timing, args.tone, conditions, args.tone_shape, args.drift)
+        .preamble(args.preamble, &timing)
+        .collect();
+    let Some(automation) = automation else {
+        let audio = audio::MorseAudio::new_with_sample_rate(
+            sample_rate, text, timing, args.tone, conditions, args.tone_shape, args.drift,
+        );
+        samples.extend_from_slice(audio.get_samples());
+        return samples;
+    };
+    samples.extend(automation.render(text, sample_rate, |step, start, settings| {
+        let mut conditions = conditions.at(start);
+        if let Some(qrm) = settings.qrm {
+            conditions.qrm = qrm;
+        }
+        if let Some(snr) = settings.snr {
+            conditions.snr = Some(snr);
+        }
+        let timing = settings.wpm.map_or(timing, |wpm| timing_at(args, wpm));
+        let tone = settings.tone.unwrap_or(args.tone);
+        audio::MorseAudio::new_with_sample_rate(sample_rate, step, timing, tone, conditions, args.tone_shape, args.drift)
+            .get_samples()
+            .to_vec()
+    }));
+    samples
+}
+
+// Lesson text with {prompt} markers: prompts are mixed in between the CW
+fn lesson_output(args: &Args, speaker: &audio::Speaker, text: &str, timing: Timing, conditions: Conditions) -> Result<()> {
+    let base = args.file.as_deref().and_then(|f| Path::new(f).parent());
+    let segments = lesson::segments(text, base)?;
+    if args.incremental {
+        anyhow::bail!("--incremental can't write a lesson with voice prompts");
+    }
+    match args.output {
+        OutputMode::Text => return print_morse(&lesson::text_only(&segments), args.charset),
+        OutputMode::KeyerC => return print_keyer_table(args, &lesson::text_only(&segments), timing),
+        OutputMode::Vox => return vox_output(args, &lesson::text_only(&segments), timing, args.tone),
+        OutputMode::Sounder => return sounder_output(args, speaker, &lesson::text_only(&segments), timing),
+        OutputMode::Tape => return tape_output(args, &lesson::text_only(&segments), timing),
+        OutputMode::Waterfall => return waterfall_output(args, speaker, &lesson::text_only(&segments), timing, args.tone, conditions),
+        OutputMode::Both => print_morse(&lesson::text_only(&segments), args.charset)?,
+        OutputMode::Audio => {}
+    }
+    let sample_rate = match &args.output_file {
+        Some(_) => args.sample_rate.unwrap_or(audio::FILE_SAMPLE_RATE),
+        None => audio::playback_rate(),
+    };
+    let samples = lesson::render(&segments, timing, args.tone, conditions, args.tone_shape, args.drift, sample_rate)?;
+    match &args.output_file {
+        Some(output_path) => {
+            export(args).write(output_path, sample_rate, samples)?;
+            println!("Saved lesson to: {}", output_path);
+            Ok(())
+        }
+        None => audio::play_samples(samples, sample_rate, speaker),
+    }
+}
+
+fn run_command(command: &Command, args: &Args, speaker: &audio::Speaker) -> Result<()> {
+    match command {
+        Command::Reprocess { input, output, wpm_scale, shift_hz } => {
+            let decoded = decode::reprocess(input, output, *wpm_scale, *shift_hz, args.tone_shape, args.sample_rate, export(args))?;
+            println!("Decoded: {}", decoded.text);
+            println!(
+                "Re-sent at {:.0} WPM / {:.0} Hz (was {:.0} WPM / {:.0} Hz) to: {}",
+                decoded.wpm * wpm_scale, decoded.tone_hz + *shift_hz as f64,
+                decoded.wpm, decoded.tone_hz, output,
+            );
+            Ok(())
+        }
+        Command::Decode { files, dir, format, verbose, signals, extract, band, raw } => {
+            let mut files = files.clone();
+            if let Some(dir) = dir {
+                files.extend(decode::audio_files(dir)?);
+            }
+            if files.is_empty() && raw.input_format.is_some() {
+                files.push(PathBuf::from("-"));
+            }
+            if files.is_empty() {
+                anyhow::bail!("nothing to decode: pass WAV files or --dir");
+            }
+            decode::decode_batch(&files, *format, *verbose, *signals as usize, *extract, band.map(f64::from), raw)
+        }
+        Command::Listen { receiver, record_dir, udp } => {
+            let udp = udp.as_deref().map(udp::Broadcast::new).transpose()?;
+            live::listen(receiver, record_dir.as_deref(), udp)
+        }
+        Command::Monitor { receiver, log, calls, call_regex, udp } => {
+            let calls = calls.then(|| monitor::callsign_regex(call_regex.as_deref())).transpose()?;
+            let udp = udp.as_deref().map(udp::Broadcast::new).transpose()?;
+            monitor::monitor(receiver, log, calls.as_ref(), udp)
+        }
+        Command::Render { text, plot } => {
+            let text = match (text, &args.file) {
+                (Some(text), _) => text.clone(),
+                (None, Some(path)) => std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?,
+                (None, None) => {
+                    let mut buf = String::new();
+                    std::io::stdin().read_to_string(&mut buf)?;
+                    buf
+                }
+            };
+            let text = macros::expand(&text, &mut rand::rng())?;
+            let scenario = args.scenario.as_deref().map(Scenario::load).transpose()?;
+            let conditions = resolve_conditions(args, scenario.as_ref());
+            let automation = args.automation.as_deref().map(Automation::load).transpose()?;
+            let sample_rate = args.sample_rate.unwrap_or(audio::FILE_SAMPLE_RATE);
+            let samples = render_text(args, automation.as_ref(), &text, timing(args), conditions, sample_rate);
+            plot::Plot::new(&samples, sample_rate, args.tone).write(plot)?;
+            println!("Saved plot to: {}", plot);
+            if let Some(output_path) = &args.output_file {
+                export(args).write(output_path, sample_rate, samples)?;
+                println!("Saved audio to: {}", output_path);
+            }
+            Ok(())
+        }
+        Command::Ringtone { text, preset, output } => {
+            let samples = ringtone::render(text, timing(args), args.tone, args.tone_shape, *preset)?;
+            let output = output.clone().unwrap_or_else(|| ringtone::default_file_name(text));
+            let secs = samples.len() as f64 / ringtone::RINGTONE_SAMPLE_RATE as f64;
+            export(args).write(&output, ringtone::RINGTONE_SAMPLE_RATE, samples)?;
+            println!(
+                "Saved {:.1} s {} to: {}",
+                secs,
+                preset.name(),
+                output,
+            );
+            Ok(())
+        }
+        Command::TestSignal { signal, output } => match output {
+            Some(output) => {
+                let sample_rate = args.sample_rate.unwrap_or(audio::FILE_SAMPLE_RATE);
+                export(args).write(output, sample_rate, signal.render(sample_rate)?)?;
+                println!("Saved {:.1} s test signal to: {}", signal.duration, output);
+                Ok(())
+            }
+            None => {
+                let sample_rate = audio::playback_rate();
+                audio::play_signal(signal.render(sample_rate)?, sample_rate)
+            }
+        },
+        Command::Tune { stations, split, filter } => {
+            if args.output_file.is_some() {
+                anyhow::bail!("the receiver runs live: leave out --output-file");
+            }
+            let scenario = args.scenario.as_deref().map(Scenario::load).transpose()?;
+            tune::run(*stations as usize, *split, *filter, resolve_conditions(args, scenario.as_ref()), args.tone, speaker)
+        }
+        Command::Beacon { message, callsign, id_rule, id_interval, every, count } => {
+            if args.output_file.is_some() {
+                anyhow::bail!("a beacon runs live: leave out --output-file");
+            }
+            let timing = timing(args);
+            let id = beacon::StationId::new(callsign, *id_rule, *id_interval);
+            let conditions = resolve_conditions(args, None);
+            beacon::run(message, &id, &timing, Duration::from_secs(*every), *count, |text| {
+                send_live(args, speaker, text, timing, args.tone, conditions)
+            })
+        }
+        Command::Daemon { sources } => {
+            if args.output_file.is_some() {
+                anyhow::bail!("the daemon runs live: leave out --output-file");
+            }
+            let mut sender = LiveSender { args, speaker, conditions: resolve_conditions(args, None), output: None };
+            daemon::run(sources, timing(args), args.tone, &mut sender)
+        }
+        Command::Gui => {
+            let mode = match args.practice {
+                Some(mode) if gui::MODES.iter().any(|(m, _)| *m == mode) => mode,
+                _ => PracticeMode::RandomWords,
+            };
+            let settings = gui::GuiSettings {
+                wpm: args.wpm,
+                tone: args.tone,
+                gap_ms: args.gap_ms,
+                mode,
+                tone_shape: args.tone_shape,
+                conditions: resolve_conditions(args, None),
+            };
+            gui::run(settings, speaker.clone())
+        }
+        Command::Scoreboard { port, data } => scoreboard::serve(*port, &club_key(args)?, data),
+        Command::Answer { file } => {
+            match answer::reveal(file)? {
+                Some(text) => println!("{}", text),
+                None => anyhow::bail!("{} has no hidden answer (write it with --answer-key hidden)", file),
+            }
+            Ok(())
+        }
+        Command::Stats { action } => {
+            let profile = profile::Profile::open(args.user.as_deref())?;
+            let who = profile.user.as_deref().unwrap_or("the default profile");
+            match action {
+                None => {
+                    println!("Statistics for {}:", who);
+                    let stats = profile.stats()?;
+                    println!("{}", stats.summary());
+                    let goals = profile.settings()?.goals;
+                    if !stats.days.is_empty() || goals != goals::Goals::default() {
+                        println!();
+                        for line in goals::report(&stats, &goals, chrono::Local::now().date_naive()) {
+                            println!("{}", line);
+                        }
+                    }
+                }
+                Some(StatsAction::Characters) => {
+                    println!("Characters missed by {}:", who);
+                    print!("{}", report::characters(&profile.stats()?));
+                }
+                Some(StatsAction::ExportAnki { dir, limit, deck }) => {
+                    let cards = anki::missed(&profile.stats()?, *limit);
+                    if cards.is_empty() {
+                        println!("Nothing missed by {} yet (misses come from {})", who, profile::MISSES_FROM);
+                        return Ok(());
+                    }
+                    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+                    let sample_rate = args.sample_rate.unwrap_or(audio::FILE_SAMPLE_RATE);
+                    let (timing, conditions) = (timing(args), resolve_conditions(args, None));
+                    for card in &cards {
+                        let audio = audio::MorseAudio::new_with_sample_rate(sample_rate, &card.text, timing, args.tone, conditions, args.tone_shape, args.drift);
+                        let file = dir.join(card.media_name());
+                        export(args).write(&file.to_string_lossy(), sample_rate, audio.get_samples().to_vec())?;
+                    }
+                    let notes = dir.join(anki::NOTES_FILE);
+                    std::fs::write(&notes, anki::notes(&cards, deck, args.charset)).with_context(|| format!("writing {}", notes.display()))?;
+                    println!("Wrote {} cards for {} to: {}", cards.len(), who, dir.display());
+                    println!("Copy the .wav files into Anki's media folder, then import {} (File → Import)", notes.display());
+                }
+                Some(StatsAction::Export { file }) => {
+                    let archive = serde_json::to_string_pretty(&profile.export()?)?;
+                    std::fs::write(file, archive).with_context(|| format!("writing {}", file.display()))?;
+                    println!("Exported {} to: {}", who, file.display());
+                }
+                Some(StatsAction::Import { file, force }) => {
+                    let text = std::fs::read_to_string(file).with_context(|| format!("reading {}", file.display()))?;
+                    let archive: profile::Archive =
+                        serde_json::from_str(&text).with_context(|| format!("reading {}", file.display()))?;
+                    profile.import(&archive, *force)?;
+                    println!("Imported {} into {}", file.display(), who);
+                }
+                Some(StatsAction::Report { format, table, output }) => {
+                    let stats = profile.stats()?;
+                    let report = match format {
+                        report::ReportFormat::Html => report::html(&stats, who),
+                        report::ReportFormat::Csv => report::csv(&stats, *table),
+                    };
+                    match output {
+                        Some(output) => {
+                            std::fs::write(output, report).with_context(|| format!("writing {}", output.display()))?;
+                            println!("Wrote report for {} to: {}", who, output.display());
+                        }
+                        None => print!("{}", report),
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Feed { dir, base_url, title, description, output } => {
+            let episodes = feed::episodes(dir)?;
+            let title = title.clone().or_else(|| feed::album(&episodes)).unwrap_or_else(|| "cwgen lessons".into());
+            let channel = feed::Channel { title, description: description.clone(), base_url: base_url.clone() };
+            let output = output.clone().unwrap_or_else(|| dir.join("feed.xml"));
+            std::fs::write(&output, feed::rss(&channel, &episodes))?;
+            println!("Wrote feed with {} episodes to: {}", episodes.len(), output.display());
+            Ok(())
+        }
+        Command::Overlay { input, text, at, level, under, band, output } => {
+            let (mut samples, input_rate) = decode::read_audio(input)?;
+            let sample_rate = args.sample_rate.unwrap_or(input_rate);
+            if sample_rate != input_rate {
+                samples = resample::resample(&samples, input_rate, sample_rate)?;
+            }
+            if band.is_some_and(|band| band as f64 >= sample_rate as f64 / 2.0 - mix::NOTCH_HZ) {
+                anyhow::bail!("--band must be well under half the sample rate ({} Hz)", sample_rate);
+            }
+            let message = audio::MorseAudio::new_with_sample_rate(
+                sample_rate,
+                text,
+                timing(args),
+                band.unwrap_or(args.tone),
+                resolve_conditions(args, None),
+                args.tone_shape,
+                args.drift,
+            );
+            match under {
+                Some(under) => mix::hide(&mut samples, message.get_samples(), *at, sample_rate, *under, band.map(f64::from))?,
+                None => {
+                    if let Some(band) = band {
+                        mix::notch(&mut samples, sample_rate, *band as f64);
+                    }
+                    mix::overlay(&mut samples, message.get_samples(), *at, sample_rate, *level)?;
+                }
+            }
+            let output = output.clone().unwrap_or_else(|| {
+                let path = Path::new(input);
+                let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+                path.with_file_name(format!("{}-cw.wav", stem)).to_string_lossy().into_owned()
+            });
+            export(args).write(&output, sample_rate, samples)?;
+            println!("Added \"{}\" at {} to: {}", text, format_at(*at), output);
+            Ok(())
+        }
+        Command::Puzzle { manifest, output } => {
+            let path = Path::new(manifest);
+            let puzzle = puzzle::Manifest::load(path)?;
+            let base = puzzle::Base {
+                wpm: args.wpm,
+                farnsworth: args.farnsworth,
+                gap_ms: args.gap_ms,
+                tone: args.tone,
+                conditions: resolve_conditions(args, None),
+                tone_shape: args.tone_shape,
+                drift: args.drift,
+                charset: args.charset,
+                envelope: envelope(args),
+            };
+            let sample_rate = args.sample_rate.unwrap_or(audio::FILE_SAMPLE_RATE);
+            let clues = puzzle.render(&base, sample_rate)?;
+            let directory = output.as_ref().map(PathBuf::from).unwrap_or_else(|| match path.extension() {
+                Some(_) => path.with_extension(""),
+                None => path.with_extension("clues"),
+            });
+            std::fs::create_dir_all(&directory).with_context(|| format!("creating {}", directory.display()))?;
+            let key = puzzle.answer_key(&clues);
+            let count = clues.len();
+            for clue in clues {
+                let file = directory.join(&clue.file_name);
+                export(args).write(&file.to_string_lossy(), sample_rate, clue.samples)?;
+                println!("{}: {}", file.display(), clue.description);
             }
+            let answers = directory.join("answers.md");
+            std::fs::write(&answers, key).with_context(|| format!("writing {}", answers.display()))?;
+            println!("{} clues, answer key in {}", count, answers.display());
+            Ok(())
         }
+        Command::Mix { clips, output, overlay } => {
+            let (samples, sample_rate) = mix::mix(clips, *overlay, args.sample_rate)?;
+            let secs = samples.len() as f64 / sample_rate as f64;
+            export(args).write(output, sample_rate, samples)?;
+            println!("Mixed {} clips ({:.1} s at {} Hz) to: {}", clips.len(), secs, sample_rate, output);
+            Ok(())
+        }
+    }
+}
+
+fn parse_edge(ms: &str) -> Result<f64, String> {
+    match ms.trim_end_matches("ms").parse::<f64>() {
+        Ok(ms) if (0.0..=50.0).contains(&ms) => Ok(ms),
+        _ => Err(format!("invalid edge time: {} (expected 0 to 50 ms)", ms)),
+    }
+}
+
+// Two different characters: dit key, then dah key
+fn parse_paddle_keys(keys: &str) -> Result<(char, char), String> {
+    match keys.chars().collect::<Vec<_>>()[..] {
+        [dit, dah] if !dit.eq_ignore_ascii_case(&dah) => Ok((dit, dah)),
+        _ => Err(format!("expected two different keys, dit then dah (e.g. zx), got {:?}", keys)),
+    }
+}
+
+fn parse_at(time: &str) -> Result<f64, String> {
+    automation::parse_time(time).ok_or_else(|| format!("invalid time: {} (expected [H:]M:SS or seconds)", time))
+}
+
+fn format_at(seconds: f64) -> String {
+    format!("{}:{:04.1}", seconds as u64 / 60, seconds % 60.0)
+}
+
+// The scoreboard key, from --club-key or the environment
+fn club_key(args: &Args) -> Result<String> {
+    match args.club_key.clone().or_else(|| std::env::var("CWGEN_CLUB_KEY").ok()) {
+        Some(key) if !key.is_empty() => Ok(key),
+        _ => anyhow::bail!("the scoreboard needs the club's key: give --club-key or set CWGEN_CLUB_KEY"),
+    }
+}
+
+// ---------- Practice sessions -----------------------------------------------
+// Every session ends the same way: announced on --json-events, posted to
+// --scoreboard and recorded to the profile, all under the one name.
+struct Session<'a> {
+    args: &'a Args,
+    profile: profile::Profile,
+    started: std::time::Instant,
+    wpm: u32,
+    qrm: u8,
+}
+
+impl<'a> Session<'a> {
+    // Opens the profile first, so a session isn't run only to be lost
+    fn start(args: &'a Args, wpm: u32, qrm: u8) -> Result<Self> {
+        let profile = profile::Profile::open(args.user.as_deref())?;
+        Ok(Session { args, profile, started: std::time::Instant::now(), wpm, qrm })
+    }
+
+    // Announce and post the session as `mode`; returns how long it took
+    fn end(&self, mode: &str, items: usize, correct: Option<usize>) -> f64 {
+        let seconds = self.started.elapsed().as_secs_f64();
+        events::emit(events::Event::SessionEnded { mode, items, correct, duration_s: seconds });
+        post_result(self.args, &self.profile, mode, self.wpm, items, correct, seconds);
+        seconds
+    }
+
+    // End the session and record it, with the characters and words copied
+    // in it (see Stats::copied), in one save
+    fn finish(self, mode: &str, items: usize, correct: Option<usize>, copied_as: &[(char, Option<char>)], words: &[(String, bool)]) -> Result<()> {
+        let seconds = self.end(mode, items, correct);
+        self.profile.update(|stats| {
+            stats.practiced(mode, items, correct, seconds, self.wpm, self.qrm);
+            stats.copied(copied_as, words);
+        })
+    }
+}
+
+// The name a practice mode goes by on the command line
+fn mode_name(mode: PracticeMode) -> String {
+    mode.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default()
+}
+
+// Post a finished session to --scoreboard, if given. A scoreboard that
+// can't be reached mustn't lose the session, so failures are only printed.
+fn post_result(args: &Args, profile: &profile::Profile, mode: &str, wpm: u32, items: usize, correct: Option<usize>, seconds: f64) {
+    let Some(url) = &args.scoreboard else { return };
+    let name = profile.user.clone().or_else(|| std::env::var("USER").ok()).unwrap_or_else(|| "anonymous".into());
+    let entry = scoreboard::Entry {
+        name,
+        seed: args.seed,
+        mode: mode.to_string(),
+        wpm,
+        items,
+        correct,
+        seconds,
+        time: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        signature: String::new(),
+    };
+    match club_key(args).and_then(|key| scoreboard::post(url, &key, entry)) {
+        Ok(()) => println!("Posted to the scoreboard at {}", url),
+        Err(e) => eprintln!("Couldn't post to the scoreboard: {:#}", e),
+    }
+}
+
+// Start from the scenario or preset (if any) and let explicit flags win
+fn resolve_conditions(args: &Args, scenario: Option<&Scenario>) -> Conditions {
+    let base = match scenario {
+        Some(scenario) => scenario.conditions(),
+        None => args.conditions.map(ConditionsPreset::conditions).unwrap_or_default(),
+    };
+    Conditions {
+        qrm: args.qrm.unwrap_or(base.qrm),
+        qrn: args.qrn.unwrap_or(base.qrn),
+        qsb: args.qsb.unwrap_or(base.qsb),
+        jitter: args.jitter.unwrap_or(base.jitter),
+        speed_variance: args.speed_variance.unwrap_or(base.speed_variance),
+        noise_pad_ms: args.noise_pad.unwrap_or(base.noise_pad_ms),
+        ..base
     }
 }
 
 fn validate_args(args: &Args) -> Result<(), MorseError> {
-    if args.wpm < 1 || args.wpm > 100 {
+    if !(1..=100).contains(&args.wpm) {
         return Err(MorseError::InvalidSpeed(args.wpm));
     }
-    if args.tone < 100 || args.tone > 3000 {
+    if !(100..=3000).contains(&args.tone) {
         return Err(MorseError::InvalidTone(args.tone));
     }
     if let Some(farnsworth) = args.farnsworth {
-        if farnsworth < 5 || farnsworth > 40 {
+        if !(5..=40).contains(&farnsworth) {
             return Err(MorseError::InvalidSpeed(farnsworth));
         }
         if farnsworth <= args.wpm {
@@ -159,3 +1835,32 @@ fn validate_args(args: &Args) -> Result<(), MorseError> {
     Ok(())
 }
 
+// The --practice modes that run a session of their own instead of sending
+// practice items one by one
+const OWN_SESSIONS: [PracticeMode; 5] =
+    [PracticeMode::Exam, PracticeMode::Phonetics, PracticeMode::Pileup, PracticeMode::Koch, PracticeMode::Meanings];
+
+// Flags that only mean something to one --practice mode, refused with the
+// rest, and flags for sending practice items, refused with OWN_SESSIONS
+fn check_mode_flags(args: &Args, matches: &clap::ArgMatches) -> Result<()> {
+    let given = |id| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let name = |mode: PracticeMode| mode.to_possible_value().expect("practice modes all have names").get_name().to_string();
+    let flags = [
+        ("free_text", "--free-text", PracticeMode::Meanings),
+        ("focus_weak", "--focus-weak", PracticeMode::Koch),
+        ("callers", "--callers", PracticeMode::Pileup),
+    ];
+    for (id, flag, mode) in flags {
+        if given(id) && args.practice != Some(mode) {
+            anyhow::bail!("{} only goes with --practice {}", flag, name(mode));
+        }
+    }
+    let Some(mode) = args.practice.filter(|mode| OWN_SESSIONS.contains(mode)) else { return Ok(()) };
+    for (id, flag) in [("speak", "--speak"), ("quiz", "--quiz")] {
+        if given(id) {
+            anyhow::bail!("{} doesn't go with --practice {}", flag, name(mode));
+        }
+    }
+    Ok(())
+}
+
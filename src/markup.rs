@@ -0,0 +1,50 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+// ---------- Markup and pictures ---------------------------------------------
+// What the feed, the reports, the scoreboard, the Anki notes and the drawn
+// pictures have in common: text going into XML or HTML, and a picture that
+// is written as a PNG for a .png file and as SVG otherwise.
+
+/// `text` safe inside XML or HTML, in an element or an attribute.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Write a picture to `path`: PNG for a .png file, SVG otherwise.
+pub fn write_picture(path: &str, png: impl FnOnce() -> Result<Vec<u8>>, svg: impl FnOnce() -> Result<String>) -> Result<()> {
+    let is_png = Path::new(path).extension().is_some_and(|e| e.eq_ignore_ascii_case("png"));
+    let bytes = if is_png { png()? } else { svg()?.into_bytes() };
+    std::fs::write(path, bytes).with_context(|| format!("writing {}", path))
+}
+
+/// Eight-bit `pixels`, rows top down, as a PNG.
+pub fn png(pixels: &[u8], width: usize, height: usize, color: png::ColorType) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width as u32, height as u32);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(pixels)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("<b>\"R&R\" 'n'</b>"), "&lt;b&gt;&quot;R&amp;R&quot; &apos;n&apos;&lt;/b&gt;");
+    }
+
+    #[test]
+    fn test_png() {
+        let grey = png(&[0x00, 0xff, 0x80, 0x40], 2, 2, png::ColorType::Grayscale).unwrap();
+        assert!(grey.starts_with(b"\x89PNG"));
+        assert!(png(&[0; 5], 2, 2, png::ColorType::Rgb).is_err());
+    }
+}
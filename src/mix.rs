@@ -0,0 +1,250 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+use crate::decode::read_audio;
+use crate::loudness::peak_db;
+use crate::resample::resample;
+
+// ---------- Clips -----------------------------------------------------------
+/// One input to `cwgen mix`, parsed from `FILE[,gain=DB][,offset=SECS][,loop]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    pub path: PathBuf,
+    pub gain_db: f64,
+    /// Seconds from where the clip would otherwise start: the end of the
+    /// previous clip, or 0 with `--overlay`. Negative overlaps the previous one.
+    pub offset: f64,
+    /// Repeat from its start to the end of the mix (noise beds)
+    pub looped: bool,
+}
+
+pub fn parse_clip(spec: &str) -> Result<Clip, String> {
+    let mut parts = spec.split(',');
+    let path = parts.next().filter(|p| !p.is_empty()).ok_or("missing file name")?;
+    let mut clip = Clip { path: PathBuf::from(path), gain_db: 0.0, offset: 0.0, looped: false };
+    for option in parts {
+        match option.split_once('=') {
+            Some(("gain", db)) => clip.gain_db = db.parse().map_err(|_| format!("invalid gain: {}", db))?,
+            Some(("offset", secs)) => clip.offset = secs.parse().map_err(|_| format!("invalid offset: {}", secs))?,
+            None if option == "loop" => clip.looped = true,
+            _ => return Err(format!("unknown clip option {} (expected gain=DB, offset=SECS or loop)", option)),
+        }
+    }
+    Ok(clip)
+}
+
+// ---------- Mixing ----------------------------------------------------------
+/// Add `samples`, scaled by `gain_db`, into `mix` from sample `start` on,
+/// growing the mix as needed.
+pub fn place(mix: &mut Vec<f32>, samples: &[f32], start: usize, gain_db: f64) {
+    let gain = 10f64.powf(gain_db / 20.0) as f32;
+    if mix.len() < start + samples.len() {
+        mix.resize(start + samples.len(), 0.0);
+    }
+    for (out, sample) in mix[start..].iter_mut().zip(samples) {
+        *out += sample * gain;
+    }
+}
+
+/// Read every clip (WAV, MP3, Ogg or FLAC), convert it to `sample_rate` (the
+/// first clip's rate if not given), and lay them out end to end, or all from
+/// the start with `overlay`.
+pub fn mix(clips: &[Clip], overlay: bool, sample_rate: Option<u32>) -> Result<(Vec<f32>, u32)> {
+    let mut loaded = Vec::new();
+    let mut rate = sample_rate;
+    for clip in clips {
+        let name = clip.path.display().to_string();
+        let (samples, clip_rate) = read_audio(&name)?;
+        let rate = *rate.get_or_insert(clip_rate);
+        let samples = resample(&samples, clip_rate, rate).with_context(|| format!("converting {}", name))?;
+        loaded.push((clip, samples));
+    }
+    let rate = rate.context("nothing to mix")?;
+    Ok((lay_out(&loaded, overlay, rate), rate))
+}
+
+fn lay_out(clips: &[(&Clip, Vec<f32>)], overlay: bool, sample_rate: u32) -> Vec<f32> {
+    let mut out = Vec::new();
+    let mut cursor = 0i64;
+    let mut looped = Vec::new();
+    for (clip, samples) in clips {
+        let base = if overlay { 0 } else { cursor };
+        let start = (base + (clip.offset * sample_rate as f64).round() as i64).max(0);
+        if clip.looped {
+            looped.push((start as usize, clip, samples));
+            continue;
+        }
+        place(&mut out, samples, start as usize, clip.gain_db);
+        cursor = start + samples.len() as i64;
+    }
+    // Beds run under whatever the other clips add up to
+    let end = out.len();
+    for (mut start, clip, samples) in looped {
+        while start < end && !samples.is_empty() {
+            let take = samples.len().min(end - start);
+            place(&mut out, &samples[..take], start, clip.gain_db);
+            start += take;
+        }
+    }
+    out
+}
+
+// ---------- Overlays --------------------------------------------------------
+// `cwgen overlay` mixes a CW message into an existing recording: a call sign
+// slipped into a podcast, an answer hidden under music for a puzzle, a
+// practice line over band noise. The message goes in at `--at` with its
+// peak at `--level` dBFS, whatever the tone shape or conditions it was
+// rendered with, and the recording is otherwise untouched.
+
+/// A level such as "-20dB" or "-20", in dBFS.
+pub fn parse_level(level: &str) -> Result<f64, String> {
+    let number = level.trim().trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    match number.parse::<f64>() {
+        Ok(db) if db <= 0.0 => Ok(db),
+        Ok(_) => Err(format!("level must be at or below 0 dB, got {}", level)),
+        Err(_) => Err(format!("invalid level: {} (e.g. -20dB)", level)),
+    }
+}
+
+/// Mix `message` into `recording` from `at` seconds on, its peak at
+/// `level_db`.
+pub fn overlay(recording: &mut Vec<f32>, message: &[f32], at: f64, sample_rate: u32, level_db: f64) -> Result<()> {
+    let start = start(recording, at, sample_rate)?;
+    let peak = peak_db(message);
+    if peak.is_finite() {
+        place(recording, message, start, level_db - peak);
+    }
+    Ok(())
+}
+
+// The sample `at` seconds in, which must be inside the recording
+fn start(recording: &[f32], at: f64, sample_rate: u32) -> Result<usize> {
+    let start = (at * sample_rate as f64).round() as usize;
+    if start >= recording.len() {
+        anyhow::bail!(
+            "--at {:.1} s is past the end of the recording ({:.1} s long)",
+            at,
+            recording.len() as f64 / sample_rate as f64,
+        );
+    }
+    Ok(start)
+}
+
+// ---------- Hidden messages -------------------------------------------------
+// For puzzles and geocaches the message is meant to be found, not heard:
+// `overlay --under 30dB` puts it that far below the recording's own level
+// where it goes (RMS against RMS), down in the music where the ear won't pick
+// it out. `--band HZ` sends it on that frequency instead of the tone, with
+// the recording notched out NOTCH_HZ wide around it first, so nothing else is
+// there: however far under it is, `decode --band HZ` gets it back.
+pub const NOTCH_HZ: f64 = 300.0;
+// Notch filters in a row: each is only a few dB deep just off its frequency
+const NOTCH_STAGES: usize = 4;
+
+/// An amount such as "30dB" or "30", in dB.
+pub fn parse_margin(margin: &str) -> Result<f64, String> {
+    let number = margin.trim().trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    match number.parse::<f64>() {
+        Ok(db) if db.is_finite() => Ok(db.abs()),
+        _ => Err(format!("invalid amount: {} (e.g. 30dB)", margin)),
+    }
+}
+
+fn rms_db(samples: &[f32]) -> f64 {
+    let mean_square = samples.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / samples.len().max(1) as f64;
+    10.0 * mean_square.log10()
+}
+
+/// Cut `band` Hz, NOTCH_HZ wide, out of `samples`.
+pub fn notch(samples: &mut [f32], sample_rate: u32, band: f64) {
+    let w0 = std::f64::consts::TAU * band / sample_rate as f64;
+    let alpha = w0.sin() / (2.0 * band / NOTCH_HZ);
+    let (a0, cos) = (1.0 + alpha, w0.cos());
+    // b0 = b2 and b1 = a1 for a notch
+    let (b0, a1, a2) = (1.0 / a0, -2.0 * cos / a0, (1.0 - alpha) / a0);
+    for _ in 0..NOTCH_STAGES {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        for sample in samples.iter_mut() {
+            let x = *sample as f64;
+            let y = b0 * (x + x2) + a1 * (x1 - y1) - a2 * y2;
+            (x2, x1, y2, y1) = (x1, x, y1, y);
+            *sample = y as f32;
+        }
+    }
+}
+
+/// Mix `message` into `recording` from `at` seconds on, `under_db` below
+/// the recording's level over the same stretch. With a `band` (the
+/// frequency `message` was sent on), the recording is notched there first.
+pub fn hide(recording: &mut Vec<f32>, message: &[f32], at: f64, sample_rate: u32, under_db: f64, band: Option<f64>) -> Result<()> {
+    let start = start(recording, at, sample_rate)?;
+    if let Some(band) = band {
+        notch(recording, sample_rate, band);
+    }
+    let end = (start + message.len()).min(recording.len());
+    let level = rms_db(&recording[start..end]);
+    if !level.is_finite() {
+        anyhow::bail!("the recording is silent at --at {:.1} s: give a --level instead", at);
+    }
+    let message_level = rms_db(message);
+    if message_level.is_finite() {
+        place(recording, message, start, level - under_db - message_level);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lay_out_clips() {
+        let clip = |spec| parse_clip(spec).unwrap();
+        let (intro, text, bed) = (clip("intro.wav"), clip("text.wav,offset=-0.2,gain=-6.0206"), clip("noise.wav,loop"));
+        let clips = [(&bed, vec![0.1, 0.2]), (&intro, vec![1.0; 5]), (&text, vec![1.0; 4])];
+        // 10 Hz: the second clip starts 2 samples before the first one ends
+        let out = lay_out(&clips, false, 10);
+        let expected = [1.1, 1.2, 1.1, 1.7, 1.6, 0.7, 0.6];
+        assert_eq!(out.len(), expected.len());
+        assert!(out.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-4));
+
+        let overlaid = lay_out(&clips[1..], true, 10);
+        assert_eq!(overlaid.len(), 5);
+        assert!(parse_clip("a.wav,gain=loud").is_err());
+        assert!(parse_clip("a.wav,speed=2").is_err());
+    }
+
+    #[test]
+    fn test_overlay_message() {
+        let mut recording = vec![0.1; 40];
+        overlay(&mut recording, &[0.5, -1.0, 0.25], 2.0, 10, -6.0206).unwrap();
+        let expected: Vec<f32> = [vec![0.1; 20], vec![0.35, -0.4, 0.225], vec![0.1; 17]].concat();
+        assert!(recording.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-4));
+        assert!(overlay(&mut recording, &[1.0], 4.0, 10, -20.0).is_err());
+        assert_eq!(parse_level("-20dB"), Ok(-20.0));
+        assert!(parse_level("6dB").is_err());
+    }
+
+    #[test]
+    fn test_hide_message() {
+        // "Music" at 200 Hz and 1 kHz, the message on 1 kHz 30 dB under it
+        let rate = 8000;
+        let tone = |hz: f32, i: usize| (std::f32::consts::TAU * hz * i as f32 / rate as f32).sin();
+        let mut recording: Vec<f32> = (0..rate * 2).map(|i| 0.5 * tone(200.0, i as usize) + 0.1 * tone(1000.0, i as usize)).collect();
+        let message: Vec<f32> = (0..rate).map(|i| tone(1000.0, i as usize)).collect();
+        let before = rms_db(&recording[rate as usize..]);
+        hide(&mut recording, &message, 0.5, rate, 30.0, Some(1000.0)).unwrap();
+
+        // The music is untouched off the band, the old 1 kHz gone, and the
+        // message is 30 dB under what is left
+        let second = &recording[rate as usize..];
+        let power = |samples: &[f32], hz| 10.0 * (crate::decode::goertzel(samples, rate, hz) * 2.0 / (samples.len() as f64).powi(2)).log10();
+        assert!((power(second, 200.0) - 10.0 * 0.125f64.log10()).abs() < 0.5);
+        assert!(before - rms_db(second) < 0.5);
+        let message_part = &recording[rate as usize..rate as usize * 3 / 2];
+        let expected = rms_db(message_part) - 30.0;
+        assert!((power(message_part, 1000.0) - expected).abs() < 1.0);
+        assert!(power(&recording[rate as usize * 3 / 2..], 1000.0) < expected - 20.0);
+        assert_eq!(parse_margin("30dB"), Ok(30.0));
+    }
+}
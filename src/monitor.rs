@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use chrono::SecondsFormat;
+use regex::Regex;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use crate::extract::CALLSIGN_PATTERN;
+use crate::live::{Input, ReceiverArgs, Transmission, Transmissions};
+use crate::udp::Broadcast;
+
+/// The callsign matcher for `--calls`: the built-in pattern, or a custom one.
+pub fn callsign_regex(custom: Option<&str>) -> Result<Regex> {
+    let pattern = custom.unwrap_or(CALLSIGN_PATTERN);
+    Regex::new(pattern).with_context(|| format!("invalid callsign pattern {}", pattern))
+}
+
+// ---------- Skimmer ---------------------------------------------------------
+/// Decode the input device until interrupted, appending one timestamped line
+/// per transmission to `log` (and echoing it to stdout).
+pub fn monitor(receiver: &ReceiverArgs, log: &Path, calls: Option<&Regex>, mut udp: Option<Broadcast>) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .with_context(|| format!("opening {}", log.display()))?;
+    let input = Input::open(receiver)?;
+    let mut decoder = receiver.decoder(input.sample_rate);
+    eprintln!("Logging to {}", log.display());
+
+    let mut transmissions = Transmissions::default();
+    for samples in input.blocks() {
+        for event in decoder.feed(&samples) {
+            if let Some(udp) = &mut udp {
+                udp.event(&event);
+            }
+            if let Some(transmission) = transmissions.update(&event) {
+                let line = log_line(&transmission, calls);
+                println!("{}", line);
+                writeln!(file, "{}", line)?;
+                file.flush()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// "2026-10-17T14:03:22Z   700 Hz  22 WPM  CQ TEST DE W1AW K  [W1AW]"
+fn log_line(transmission: &Transmission, calls: Option<&Regex>) -> String {
+    let mut line = format!(
+        "{}  {:4.0} Hz  {:2.0} WPM  {}",
+        transmission.start.to_rfc3339_opts(SecondsFormat::Secs, true),
+        transmission.tone_hz.unwrap_or_default(),
+        transmission.wpm.unwrap_or_default(),
+        transmission.text,
+    );
+    if let Some(calls) = calls {
+        let mut found: Vec<&str> = Vec::new();
+        for call in calls.find_iter(&transmission.text).map(|m| m.as_str()) {
+            if !found.contains(&call) {
+                found.push(call);
+            }
+        }
+        if !found.is_empty() {
+            line.push_str(&format!("  [{}]", found.join(" ")));
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_log_line_callsigns() {
+        let time = Utc.with_ymd_and_hms(2026, 10, 17, 14, 3, 22).unwrap();
+        let transmission = Transmission {
+            start: time,
+            end: time,
+            text: "K1ABC/P DE 9A1AA 5NN TEST K1ABC/P".into(),
+            tone_hz: Some(700.0),
+            wpm: Some(22.0),
+        };
+        let calls = callsign_regex(None).unwrap();
+        let line = log_line(&transmission, Some(&calls));
+        assert_eq!(line, "2026-10-17T14:03:22Z   700 Hz  22 WPM  K1ABC/P DE 9A1AA 5NN TEST K1ABC/P  [K1ABC/P 9A1AA]");
+    }
+
+    #[test]
+    fn test_bad_callsign_regex() {
+        assert!(callsign_regex(Some("([A-Z")).is_err());
+    }
+}
@@ -1,9 +1,16 @@
 use lazy_static::lazy_static;
 use phf::phf_map;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
 use std::time::Duration;
 use thiserror::Error;
 
+use crate::generate;
+use crate::pileup;
+use crate::procedure;
+use crate::spaced::Weights;
+
 // ---------- Error types ----------------------------------------------------
 #[derive(Error, Debug)]
 pub enum MorseError {
@@ -17,6 +24,16 @@ pub enum MorseError {
     InvalidFarnsworth(u32, u32),
     #[error("Audio device error: {0}")]
     AudioDeviceError(String),
+    #[error("Invalid scenario: {0}")]
+    InvalidScenario(String),
+    #[error("Invalid automation: {0}")]
+    InvalidAutomation(String),
+    #[error("Invalid morse: '{0}' (only dots, dashes and / between words)")]
+    InvalidCode(String),
+    #[error("Keying aborted: {0}")]
+    KeyingAborted(String),
+    #[error("Interrupted")]
+    Interrupted,
 }
 
 // ---------- Morse table -----------------------------------------------------
@@ -40,6 +57,123 @@ pub const MORSE: phf::Map<char, &'static str> = phf_map! {
     '\r' => "",     // Handle carriage returns as empty
 };
 
+// ---------- Character sets --------------------------------------------------
+// American (railroad) Morse, still heard on landline telegraph circuits and
+// MorseKOB wires, has letters with a space inside them (C is .. .), a long
+// dash for L and a longer one for 0, and a dash of two units rather than
+// three. In its table `_` is the long dash (four units), `=` the longer one
+// (five) and a space the gap inside a character (two units).
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum Charset {
+    #[default]
+    International,
+    /// American (railroad) Morse
+    American,
+}
+
+pub const AMERICAN: phf::Map<char, &'static str> = phf_map! {
+    'A' => ".-",    'B' => "-...",  'C' => ".. .",  'D' => "-..",
+    'E' => ".",     'F' => ".-.",   'G' => "--.",   'H' => "....",
+    'I' => "..",    'J' => "-.-.",  'K' => "-.-",   'L' => "_",
+    'M' => "--",    'N' => "-.",    'O' => ". .",   'P' => ".....",
+    'Q' => "..-.",  'R' => ". ..",  'S' => "...",   'T' => "-",
+    'U' => "..-",   'V' => "...-",  'W' => ".--",   'X' => ".-..",
+    'Y' => ".. ..", 'Z' => "... .",
+    '0' => "=",     '1' => ".--.",  '2' => "..-..", '3' => "...-.",
+    '4' => "....-", '5' => "---",   '6' => "......", '7' => "--..",
+    '8' => "-....", '9' => "-..-",
+    '.' => "..--..", ',' => ".-.-", '?' => "-..-.", '!' => "---.",
+    '&' => ". ...",
+    ' ' => "/",
+    '\n' => "",
+    '\r' => "",
+};
+
+impl Charset {
+    /// The code for `ch` (in upper case), if the set has one.
+    pub fn code(self, ch: char) -> Option<&'static str> {
+        match self {
+            Charset::International => MORSE.get(&ch).copied(),
+            Charset::American => AMERICAN.get(&ch).copied(),
+        }
+    }
+}
+
+// ---------- Keying envelope -------------------------------------------------
+// How each element's edges rise and fall, which is most of what makes a note
+// sound hard or soft. `hard` ramps straight up and down, the way cwgen always
+// has; `raised-cosine` eases in and out along half a cosine, as most rigs
+// shape their keying; `gaussian` follows the integral of a bell curve, the
+// softest of the three for the same edge time. Unless `--rise` and `--fall`
+// say otherwise, the edges take a share of the dot, as they always have.
+// Two imperfections can go on top: `click`, part of each edge stepping
+// straight up or down unshaped, and `hum`, mains ripple on the note the way
+// a tired power supply puts it there.
+const RISE_SHARE: f32 = 0.15;
+const FALL_SHARE: f32 = 0.25;
+// Half the width of the gaussian edge, in standard deviations
+const GAUSSIAN_SPAN: f64 = 2.5;
+// Full-wave rectified 50 Hz mains
+const HUM_HZ: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum EnvelopeShape {
+    /// Straight ramps
+    #[default]
+    Hard,
+    /// Half a cosine on each edge
+    RaisedCosine,
+    /// The integral of a bell curve on each edge
+    Gaussian,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Envelope {
+    pub shape: EnvelopeShape,
+    pub rise: Option<Duration>,
+    pub fall: Option<Duration>,
+    /// Share of each edge that steps instead of following the shape, 0 to 1
+    pub click: f32,
+    /// Depth of the ripple on the note, 0 to 1
+    pub hum: f32,
+}
+
+impl Envelope {
+    /// The rise and fall, in samples, for elements a dot of `dot` long.
+    pub fn edges(&self, dot: Duration, sample_rate: u32) -> (usize, usize) {
+        let samples = |edge: Option<Duration>, share: f32| {
+            let edge = edge.unwrap_or_else(|| dot.mul_f32(share));
+            (sample_rate as f64 * edge.as_secs_f64()) as usize
+        };
+        (samples(self.rise, RISE_SHARE), samples(self.fall, FALL_SHARE))
+    }
+
+    /// The gain `x` of the way (0 to 1) up an edge.
+    pub fn gain(&self, x: f32) -> f32 {
+        let shaped = match self.shape {
+            EnvelopeShape::Hard => x,
+            EnvelopeShape::RaisedCosine => 0.5 - 0.5 * (std::f32::consts::PI * x).cos(),
+            EnvelopeShape::Gaussian => {
+                let z = GAUSSIAN_SPAN / std::f64::consts::SQRT_2;
+                ((erf(z * (2.0 * x as f64 - 1.0)) / erf(z) + 1.0) / 2.0) as f32
+            }
+        };
+        self.click + (1.0 - self.click) * shaped
+    }
+
+    /// The ripple's gain `time` seconds in.
+    pub fn ripple(&self, time: f64) -> f32 {
+        1.0 - self.hum * 0.5 * (1.0 + (std::f64::consts::TAU * HUM_HZ * time).sin() as f32)
+    }
+}
+
+// The error function, to within 1.5e-7 (Abramowitz and Stegun 7.1.26)
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    (1.0 - poly * (-x * x).exp()).copysign(x)
+}
+
 // ---------- Timing ---------------------------------------------------------
 #[derive(Clone, Copy, Debug)]
 pub struct Timing {
@@ -48,6 +182,10 @@ pub struct Timing {
     pub sym: Duration,
     pub chr: Duration,
     pub wrd: Duration,
+    pub charset: Charset,
+    pub envelope: Envelope,
+    /// How many Hz apart the tone plays between the ears, if binaurally.
+    pub binaural: Option<u32>,
 }
 
 impl Timing {
@@ -60,6 +198,9 @@ impl Timing {
             sym: unit,
             chr: unit * 3 + extra,
             wrd: unit * 7 + extra,
+            charset: Charset::International,
+            envelope: Envelope::default(),
+            binaural: None,
         }
     }
 
@@ -77,11 +218,58 @@ impl Timing {
             sym: char_unit,
             chr: char_unit * 3 + extended_gap + extra,
             wrd: char_unit * 7 + extended_gap * 2 + extra,
+            charset: Charset::International,
+            envelope: Envelope::default(),
+            binaural: None,
+        }
+    }
+
+    /// The same speed in `charset`.
+    pub fn with_charset(mut self, charset: Charset) -> Self {
+        if let Charset::American = charset {
+            self.dash = self.dot * 2;
+        }
+        self.charset = charset;
+        self
+    }
+
+    /// The same speed, keyed with `envelope`.
+    pub fn with_envelope(mut self, envelope: Envelope) -> Self {
+        self.envelope = envelope;
+        self
+    }
+
+    /// The same speed, played `offset_hz` apart between the ears.
+    pub fn with_binaural(mut self, offset_hz: Option<u32>) -> Self {
+        self.binaural = offset_hz;
+        self
+    }
+
+    /// Whether the key is down for a symbol of a code, and for how long.
+    pub fn element(&self, sym: char) -> Option<(bool, Duration)> {
+        match sym {
+            '.' => Some((true, self.dot)),
+            '-' => Some((true, self.dash)),
+            '_' => Some((true, self.dot * 4)),
+            '=' => Some((true, self.dot * 5)),
+            // On top of the usual gap after the element before
+            ' ' => Some((false, self.dot)),
+            _ => None,
         }
     }
 }
 
 lazy_static! {
+    /// Reverse of `MORSE`: element string to character.
+    pub static ref REVERSE_MORSE: HashMap<&'static str, char> = {
+        MORSE
+            .entries()
+            // '-' shares '=''s code in the table; decode it as '='
+            .filter(|(ch, code)| !code.is_empty() && **ch != ' ' && **ch != '-')
+            .map(|(ch, code)| (*code, *ch))
+            .collect()
+    };
+
     pub static ref COMMON_TIMINGS: HashMap<u32, Timing> = {
         let mut m = HashMap::new();
         for wpm in 5..=50 {
@@ -93,39 +281,281 @@ lazy_static! {
 
 // ---------- Morse Conversion ------------------------------------------------
 pub fn text_to_morse(text: &str) -> Result<String, MorseError> {
+    text_to_code(text, Charset::International)
+}
+
+/// What `text` sends, one sign at a time: each character, or a prosign
+/// written in angle brackets such as `<SK>`, whose letters run together with
+/// no gap between them. With each is its code in `charset`, None for a
+/// character the set has no code for. A `<` that doesn't open a prosign is
+/// just a character.
+pub fn signs(text: &str, charset: Charset) -> Vec<(&str, Option<String>)> {
+    let mut signs = Vec::new();
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        let (len, code) = prosign(rest, charset)
+            .map_or_else(|| (ch.len_utf8(), charset.code(ch.to_ascii_uppercase()).map(String::from)), |(len, code)| (len, Some(code)));
+        signs.push((&rest[..len], code));
+        rest = &rest[len..];
+    }
+    signs
+}
+
+// The length and code of the prosign `text` starts with, if it does
+fn prosign(text: &str, charset: Charset) -> Option<(usize, String)> {
+    let inner = text.strip_prefix('<')?;
+    let letters = &inner[..inner.find('>')?];
+    if letters.is_empty() {
+        return None;
+    }
+    let code: Option<String> = letters
+        .chars()
+        .map(|c| charset.code(c.to_ascii_uppercase()).filter(|code| !code.is_empty() && !c.is_whitespace()))
+        .collect();
+    Some((letters.len() + 2, code?))
+}
+
+/// Dots and dashes for `text` in `charset`. American characters can have a
+/// space inside them, so they're two spaces apart.
+pub fn text_to_code(text: &str, charset: Charset) -> Result<String, MorseError> {
+    let separator = match charset {
+        Charset::International => " ",
+        Charset::American => "  ",
+    };
     let mut morse_string = String::new();
     
-    for ch in text.chars() {
-        let up = ch.to_ascii_uppercase();
-        
-        // Handle regular characters
-        if let Some(code) = MORSE.get(&up) {
-            if !code.is_empty() {  // Skip empty codes (like newlines)
-                morse_string.push_str(code);
-                morse_string.push(' ');
+    for (sign, code) in signs(text, charset) {
+        match code {
+            // Skip empty codes (like newlines)
+            Some(code) if code.is_empty() => {}
+            Some(code) => {
+                morse_string.push_str(&code);
+                morse_string.push_str(separator);
             }
-        } else {
-            return Err(MorseError::InvalidCharacter(ch));
+            None => return Err(MorseError::InvalidCharacter(sign.chars().next().unwrap_or_default())),
         }
     }
     
     Ok(morse_string.trim().to_string())
 }
 
+/// Shown in decoded text for a sequence that isn't a character
+pub const UNKNOWN_CHAR: char = '*';
+
+/// Prosigns that decode as themselves, in brackets. AR, AS, BT and KN are
+/// the same as +, &, = and ( and decode as those.
+pub const PROSIGNS: [&str; 4] = ["SK", "SOS", "AA", "BK"];
+
+/// Text for dots and dashes written as `text_to_morse` writes them:
+/// characters apart, `/` between words. `_` and the typographic dot and
+/// dashes are read too. A sequence that isn't a character decodes as one of
+/// PROSIGNS, such as `<SK>`, or UNKNOWN_CHAR; anything else that isn't code
+/// is an error.
+pub fn morse_to_text(code: &str) -> Result<String, MorseError> {
+    let mut text = String::new();
+    for word in code.split('/') {
+        let mut letters = String::new();
+        for sequence in word.split_whitespace() {
+            let elements: Option<String> = sequence
+                .chars()
+                .map(|c| match c {
+                    '.' | '·' | '•' => Some('.'),
+                    '-' | '_' | '−' | '–' => Some('-'),
+                    _ => None,
+                })
+                .collect();
+            let elements = elements.ok_or_else(|| MorseError::InvalidCode(sequence.to_string()))?;
+            match REVERSE_MORSE.get(elements.as_str()) {
+                Some(ch) => letters.push(*ch),
+                None => match PROSIGNS.iter().find(|prosign| text_to_morse(prosign).is_ok_and(|code| code.replace(' ', "") == elements)) {
+                    Some(prosign) => letters.push_str(&format!("<{}>", prosign)),
+                    None => letters.push(UNKNOWN_CHAR),
+                },
+            }
+        }
+        if !letters.is_empty() {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(&letters);
+        }
+    }
+    Ok(text)
+}
+
+// ---------- Keying timeline -------------------------------------------------
+/// One key-down or key-up interval of a keyed transmission.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Element {
+    pub key_down: bool,
+    pub duration: Duration,
+}
+
+/// Flatten text into the on/off intervals a key would produce. Characters
+/// without a morse equivalent are skipped; consecutive key-up intervals are
+/// merged so every element alternates state.
+pub fn key_timeline(text: &str, timing: &Timing) -> Vec<Element> {
+    let mut elements: Vec<Element> = Vec::new();
+    let mut push = |key_down: bool, duration: Duration| {
+        match elements.last_mut() {
+            Some(last) if !key_down && !last.key_down => last.duration += duration,
+            _ => elements.push(Element { key_down, duration }),
+        }
+    };
+
+    for (sign, code) in signs(text, timing.charset) {
+        if sign == " " {
+            push(false, timing.wrd - timing.chr);
+            continue;
+        }
+        let Some(code) = code else { continue };
+        if code.is_empty() {
+            continue;
+        }
+        for (key_down, duration) in code.chars().filter_map(|sym| timing.element(sym)) {
+            push(key_down, duration);
+            if key_down {
+                push(false, timing.sym);
+            }
+        }
+        push(false, timing.chr - timing.sym);
+    }
+    elements
+}
+
+/// When each sign of `text` (see `signs`) starts, from the start of the
+/// text.
+pub fn sign_starts<'a>(text: &'a str, timing: &Timing) -> Vec<(&'a str, Duration)> {
+    let mut at = Duration::ZERO;
+    let mut starts = Vec::new();
+    for (sign, _) in signs(text, timing.charset) {
+        starts.push((sign, at));
+        at += key_timeline(sign, timing).iter().map(|e| e.duration).sum::<Duration>();
+    }
+    starts
+}
+
+/// When each character of `text` starts, from the start of the text. The
+/// characters of a prosign all start with it.
+pub fn char_starts(text: &str, timing: &Timing) -> Vec<(char, Duration)> {
+    sign_starts(text, timing)
+        .into_iter()
+        .flat_map(|(sign, at)| sign.chars().map(move |ch| (ch, at)))
+        .collect()
+}
+
 // ---------- Practice Mode Content -------------------------------------------
-#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
 pub enum PracticeMode {
     RandomWords,
     Callsigns,
     QCodes,
     Numbers,
     Custom,
+    Exam,
+    PseudoWords,
+    Markov,
+    Ngrams,
+    /// Distress, urgency, safety and naval message procedure
+    Procedure,
+    /// Q-codes and abbreviations, answered with what they mean
+    Meanings,
+    /// UTC times such as 1432Z
+    Times,
+    /// Frequencies in the CW end of the HF bands, such as 14025
+    Frequencies,
+    /// Signal reports, with cut numbers and contest serials (5NN TT7)
+    Reports,
+    /// Maidenhead grid squares (FN31, JO62QM)
+    Grids,
+    /// Callsigns converted between CW and NATO phonetics
+    Phonetics,
+    /// Five-character groups from the characters learned so far, in Koch
+    /// order (see --lesson)
+    Koch,
+    /// A contest run: call CQ, pull calls out of a pileup and log their
+    /// serials, scored in QSOs an hour
+    Pileup,
 }
 
 const HAM_WORDS: &str = include_str!("words.txt");
 
+const CALL_PREFIXES: &[&str] = &[
+    "W", "K", "N", "AA", "KB", "WA", "VE", "G", "M", "DL", "F", "EA", "I",
+    "JA", "VK", "ZL", "PY", "LU", "CX", "OH", "SM", "UA", "ON", "PA", "OK",
+    "SP", "HA", "YU", "9A", "S5", "LZ",
+];
+
+/// A plausible amateur callsign: prefix, call-area digit and a 1-3 letter suffix.
+pub fn random_callsign(rng: &mut impl Rng) -> String {
+    let mut call = CALL_PREFIXES[rng.random_range(0..CALL_PREFIXES.len())].to_string();
+    call.push(char::from(b'0' + rng.random_range(0..10)));
+    for _ in 0..rng.random_range(1..=3) {
+        call.push(char::from(b'A' + rng.random_range(0..26)));
+    }
+    call
+}
+
+/// Random numbers for practice material, from `seed` (`--seed`) if given:
+/// runs with the same seed get the same items in the same order.
+pub fn practice_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(&mut rand::rng()),
+    }
+}
+
+// A session's worth of one kind of numeric traffic
+fn numeric<R: Rng>(rng: &mut R, item: fn(&mut R) -> String) -> Vec<String> {
+    (0..generate::COUNT).map(|_| item(rng)).collect()
+}
+
+// ---------- Koch method -----------------------------------------------------
+// The Koch method sends at full character speed from the first lesson, so
+// characters are learned as sounds rather than counted out: two characters
+// to begin with and one more a lesson, each lesson being random groups of
+// just the characters learned so far. A lesson is passed at 90% copy. The
+// order is LCWO's, which most courses follow.
+pub const KOCH_ORDER: [char; 41] = [
+    'K', 'M', 'U', 'R', 'E', 'S', 'N', 'A', 'P', 'T', 'L', 'W', 'I', '.', 'J', 'Z', '=', 'F', 'O', 'Y', ',',
+    'V', 'G', '5', '/', 'Q', '9', '2', 'H', '3', '8', 'B', '?', '4', '7', 'C', '1', 'D', '6', '0', 'X',
+];
+pub const KOCH_LESSONS: usize = KOCH_ORDER.len() - 1;
+pub const KOCH_PASS: f64 = 0.9;
+const KOCH_GROUP: usize = 5;
+// How often a character is the lesson's new one rather than any learned one
+const KOCH_NEW_SHARE: f64 = 0.25;
+
+/// The characters learned by `lesson`: two in lesson 1, then one more each.
+pub fn koch_characters(lesson: usize) -> &'static [char] {
+    &KOCH_ORDER[..lesson.clamp(1, KOCH_LESSONS) + 1]
+}
+
+/// `count` groups of the characters learned by `lesson`, the lesson's new
+/// character turning up more often than the rest, and with `weights`
+/// (`--focus-weak`) the ones missed lately more often too.
+pub fn koch_groups(rng: &mut impl Rng, lesson: usize, count: usize, weights: Option<&Weights>) -> Vec<String> {
+    let learned = koch_characters(lesson);
+    let newest = learned[learned.len() - 1];
+    (0..count)
+        .map(|_| {
+            (0..KOCH_GROUP)
+                .map(|_| match (rng.random_bool(KOCH_NEW_SHARE), weights) {
+                    (true, _) => newest,
+                    (false, Some(weights)) => weights.pick(rng, learned),
+                    (false, None) => learned[rng.random_range(0..learned.len())],
+                })
+                .collect()
+        })
+        .collect()
+}
+
 impl PracticeMode {
-    pub fn get_content(&self, custom_text: Option<&str>) -> Vec<String> {
+    /// Items for a session, drawn with `rng`. `custom_text` is the text for
+    /// `custom`, or the corpus `markov` learns from (the built-in word list
+    /// without one), `lesson` the Koch lesson `koch` groups are drawn from
+    /// and `weights` how heavily to draw each of its characters.
+    pub fn get_content(&self, rng: &mut impl Rng, custom_text: Option<&str>, lesson: usize, weights: Option<&Weights>) -> Vec<String> {
         match self {
             PracticeMode::RandomWords => HAM_WORDS
                 .lines()
@@ -133,15 +563,15 @@ impl PracticeMode {
                 .filter(|l| !l.is_empty())
                 .map(str::to_string)
                 .collect(),
-            PracticeMode::Callsigns => vec![
+            PracticeMode::Callsigns => [
                 "W1AW", "K2ABC", "N3XYZ", "W4DEF", "K5GHI", "N6JKL", 
                 "W7MNO", "K8PQR", "N9STU", "VE3ABC", "G4HAM",
             ].iter().map(|s| s.to_string()).collect(),
-            PracticeMode::QCodes => vec![
+            PracticeMode::QCodes => [
                 "QTH", "QRZ", "QSL", "QRM", "QRN", "QRP", "QRQ", "QRS", 
                 "QRT", "QRU", "QRV", "QSB", "QSY", "QSO",
             ].iter().map(|s| s.to_string()).collect(),
-            PracticeMode::Numbers => vec![
+            PracticeMode::Numbers => [
                 "123", "456", "789", "012", "345", "678", "901", "234", 
                 "567", "890", "73", "88", "55",
             ].iter().map(|s| s.to_string()).collect(),
@@ -149,9 +579,37 @@ impl PracticeMode {
                 if let Some(text) = custom_text {
                     text.split_whitespace().map(|s| s.to_string()).collect()
                 } else {
-                    vec!["CQ", "DE", "TEST"].iter().map(|s| s.to_string()).collect()
+                    ["CQ", "DE", "TEST"].iter().map(|s| s.to_string()).collect()
                 }
             }
+            // The exam is its own session (see exam.rs); as drill content, a
+            // QSO's words
+            PracticeMode::Exam => crate::exam::Exam::generate(rng, &Timing::new(20, 0))
+                .qso
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            PracticeMode::PseudoWords => generate::pseudo_words(rng, generate::COUNT),
+            PracticeMode::Markov => {
+                let markov = generate::Markov::train(custom_text.unwrap_or(HAM_WORDS));
+                markov.words(rng, generate::COUNT)
+            }
+            PracticeMode::Ngrams => generate::letter_groups(rng, generate::COUNT),
+            PracticeMode::Procedure => procedure::messages(rng, procedure::COUNT),
+            // The quiz is its own session (see quiz.rs); as drill content,
+            // the codes it asks about
+            PracticeMode::Meanings => crate::quiz::table().into_iter().map(|(code, _)| code.to_string()).collect(),
+            PracticeMode::Times => numeric(rng, generate::utc_time),
+            PracticeMode::Frequencies => numeric(rng, generate::frequency),
+            PracticeMode::Reports => numeric(rng, generate::report),
+            PracticeMode::Grids => numeric(rng, generate::grid),
+            // The drill is its own session (see phonetics.rs); as drill
+            // content, made-up callsigns
+            PracticeMode::Phonetics => (0..generate::COUNT).map(|_| random_callsign(rng)).collect(),
+            PracticeMode::Koch => koch_groups(rng, lesson, generate::COUNT, weights),
+            // The run is its own session (see pileup.rs); as drill content,
+            // calls and exchanges as a station in it would send them
+            PracticeMode::Pileup => pileup::exchanges(rng, generate::COUNT),
         }
     }
 }
@@ -173,6 +631,54 @@ mod tests {
         assert_eq!(text_to_morse("AB").unwrap(), ".- -...");
     }
 
+    #[test]
+    fn test_morse_to_text() {
+        assert_eq!(morse_to_text(".- -... / -.-.").unwrap(), "AB C");
+        assert_eq!(morse_to_text(&text_to_morse("CQ DE W1AW").unwrap()).unwrap(), "CQ DE W1AW");
+        // Slashes without spaces, repeated, or at the ends
+        assert_eq!(morse_to_text("/ ...//--- / / ... /").unwrap(), "S O S");
+        assert_eq!(morse_to_text("·− −··· ..--.. ........").unwrap(), "AB?*");
+        assert!(matches!(morse_to_text(".- x-"), Err(MorseError::InvalidCode(s)) if s == "x-"));
+    }
+
+    #[test]
+    fn test_prosigns() {
+        assert_eq!(text_to_morse("73 <sk>").unwrap(), "--... ...-- / ...-.-");
+        assert_eq!(text_to_morse("<BT> <SOS>").unwrap(), "-...- / ...---...");
+        assert_eq!(morse_to_text(&text_to_morse("TU <SK> <KN>").unwrap()).unwrap(), "TU <SK> (");
+        // Not prosigns
+        assert!(text_to_morse("<SK").is_err());
+        assert!(text_to_morse("<>").is_err());
+        assert!(text_to_morse("<S K>").is_err());
+    }
+
+    #[test]
+    fn test_prosign_timing() {
+        // The letters run together: SK has no character space inside it
+        let timing = Timing::new(20, 0);
+        let length = |text| key_timeline(text, &timing).iter().map(|e| e.duration).sum::<Duration>();
+        assert_eq!(length("<SK>") + timing.chr - timing.sym, length("SK"));
+        let starts: Vec<(&str, Duration)> = sign_starts("<AR>K", &timing);
+        assert_eq!(starts, [("<AR>", Duration::ZERO), ("K", length("<AR>"))]);
+        assert_eq!(char_starts("<AR>", &timing).len(), 4);
+    }
+
+    #[test]
+    fn test_koch_characters() {
+        assert_eq!(koch_characters(1), ['K', 'M']);
+        assert_eq!(koch_characters(KOCH_LESSONS).len(), KOCH_ORDER.len());
+        assert!(KOCH_ORDER.iter().all(|c| text_to_morse(&c.to_string()).is_ok()));
+    }
+
+    #[test]
+    fn test_koch_groups() {
+        let groups = koch_groups(&mut StdRng::seed_from_u64(1), 3, 50, None);
+        assert!(groups.iter().all(|group| group.len() == 5 && group.chars().all(|c| "KMUR".contains(c))));
+        // The new character, R, more often than the others
+        let count = |c| groups.concat().matches(c).count();
+        assert!(count('R') > count('K') && count('R') > count('U'));
+    }
+
     #[test]
     fn test_invalid_character() {
         assert!(text_to_morse("SÖS").is_err());
@@ -182,5 +688,56 @@ mod tests {
     fn test_newline_handling() {
         assert_eq!(text_to_morse("A\nB").unwrap(), ".- -...");
     }
-}
 
+    #[test]
+    fn test_key_timeline() {
+        let timing = Timing::new(20, 0);
+        let elements = key_timeline("A E", &timing);
+        let states: Vec<bool> = elements.iter().map(|e| e.key_down).collect();
+        assert_eq!(states, vec![true, false, true, false, true, false]);
+        assert_eq!(elements[2].duration, timing.dash);
+        // Character space after A plus the word space
+        assert_eq!(elements[3].duration, timing.wrd);
+    }
+
+    #[test]
+    fn test_american_morse() {
+        let timing = Timing::new(20, 0).with_charset(Charset::American);
+        let unit = timing.dot;
+        // C is two dots, a space inside the character, and a dot
+        let durations: Vec<Duration> = key_timeline("C", &timing).iter().map(|e| e.duration).collect();
+        assert_eq!(durations, [unit, unit, unit, unit * 2, unit, timing.chr]);
+        // Short dash for T, long for L, longer for 0
+        let downs = |text| key_timeline(text, &timing)[0].duration;
+        assert_eq!([downs("T"), downs("L"), downs("0")], [unit * 2, unit * 4, unit * 5]);
+        assert_eq!(text_to_code("CO", Charset::American).unwrap(), ".. .  . .");
+        assert!(text_to_code("@", Charset::American).is_err());
+    }
+
+    #[test]
+    fn test_envelope_shapes() {
+        for shape in [EnvelopeShape::Hard, EnvelopeShape::RaisedCosine, EnvelopeShape::Gaussian] {
+            let envelope = Envelope { shape, ..Envelope::default() };
+            assert!(envelope.gain(0.0).abs() < 1e-6 && (envelope.gain(1.0) - 1.0).abs() < 1e-6, "{:?}", shape);
+            assert!((envelope.gain(0.5) - 0.5).abs() < 1e-6, "{:?}", shape);
+            let gains: Vec<f32> = (0..=100).map(|i| envelope.gain(i as f32 / 100.0)).collect();
+            assert!(gains.windows(2).all(|pair| pair[1] >= pair[0]), "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn test_softer_envelopes_start_gently() {
+        let at = |shape| Envelope { shape, ..Envelope::default() }.gain(0.1);
+        assert!(at(EnvelopeShape::Gaussian) < at(EnvelopeShape::RaisedCosine));
+        assert!(at(EnvelopeShape::RaisedCosine) < at(EnvelopeShape::Hard));
+    }
+
+    #[test]
+    fn test_envelope_edges() {
+        // A share of the dot unless given
+        let dot = Duration::from_millis(60);
+        assert_eq!(Envelope::default().edges(dot, 8000), (72, 120));
+        let quick = Envelope { rise: Some(Duration::from_millis(1)), fall: Some(Duration::from_millis(8)), ..Envelope::default() };
+        assert_eq!(quick.edges(dot, 8000), (8, 64));
+    }
+}
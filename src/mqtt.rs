@@ -0,0 +1,182 @@
+use anyhow::{bail, Context, Result};
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+// ---------- MQTT subscriber -------------------------------------------------
+// Just enough MQTT 3.1.1 to subscribe and receive: connect with a clean
+// session, subscribe at QoS 0, and read PUBLISH packets (acknowledging any
+// sent at QoS 1), pinging the broker when the line goes quiet so it keeps
+// the connection open. No TLS or authentication; run a local broker or a
+// bridge for those.
+const KEEP_ALIVE_SECS: u16 = 60;
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const SUBSCRIBE: u8 = 8;
+const PINGREQ: u8 = 12;
+
+pub struct Subscriber {
+    stream: TcpStream,
+}
+
+/// A message as published.
+pub struct Publish {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+impl Subscriber {
+    /// Connect to `broker` (HOST or HOST:PORT) and subscribe to `filters`.
+    pub fn connect(broker: &str, client_id: &str, filters: &[&str]) -> Result<Self> {
+        let address = if broker.contains(':') { broker.to_string() } else { format!("{}:1883", broker) };
+        let stream = TcpStream::connect(&address).with_context(|| format!("connecting to MQTT broker {}", address))?;
+        // Half the keep-alive: a read timing out is the cue to ping
+        stream.set_read_timeout(Some(Duration::from_secs(KEEP_ALIVE_SECS as u64 / 2)))?;
+        let mut subscriber = Subscriber { stream };
+
+        let mut body = Vec::new();
+        put_str(&mut body, "MQTT");
+        body.push(4); // protocol level 3.1.1
+        body.push(0x02); // clean session
+        body.extend(KEEP_ALIVE_SECS.to_be_bytes());
+        put_str(&mut body, client_id);
+        subscriber.send(CONNECT << 4, &body)?;
+        let (kind, reply) = subscriber.read_packet()?;
+        if kind >> 4 != CONNACK || reply.get(1) != Some(&0) {
+            bail!("MQTT broker {} refused the connection (code {:?})", address, reply.get(1));
+        }
+
+        let mut body = vec![0, 1]; // packet identifier
+        for filter in filters {
+            put_str(&mut body, filter);
+            body.push(0); // QoS 0
+        }
+        subscriber.send(SUBSCRIBE << 4 | 0x02, &body)?;
+        Ok(subscriber)
+    }
+
+    /// Wait for the next message published to a subscribed topic.
+    pub fn next(&mut self) -> Result<Publish> {
+        loop {
+            let (kind, body) = match self.read_packet() {
+                Ok(packet) => packet,
+                Err(e) if is_timeout(&e) => {
+                    self.send(PINGREQ << 4, &[])?;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if kind >> 4 != PUBLISH {
+                continue;
+            }
+            let qos = (kind >> 1) & 0x03;
+            let topic_length = u16::from_be_bytes([body[0], body[1]]) as usize;
+            if body.len() < 2 + topic_length + if qos > 0 { 2 } else { 0 } {
+                bail!("malformed MQTT PUBLISH");
+            }
+            let topic = String::from_utf8_lossy(&body[2..2 + topic_length]).into_owned();
+            let mut payload = &body[2 + topic_length..];
+            if qos > 0 {
+                let id = [payload[0], payload[1]];
+                payload = &payload[2..];
+                self.send(PUBACK << 4, &id)?;
+            }
+            return Ok(Publish { topic, payload: payload.to_vec() });
+        }
+    }
+
+    fn send(&mut self, header: u8, body: &[u8]) -> Result<()> {
+        let mut packet = vec![header];
+        put_length(&mut packet, body.len());
+        packet.extend(body);
+        self.stream.write_all(&packet)?;
+        Ok(())
+    }
+
+    fn read_packet(&mut self) -> Result<(u8, Vec<u8>)> {
+        let mut byte = [0u8];
+        match self.stream.read_exact(&mut byte) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => bail!("the broker closed the connection"),
+            result => result?,
+        }
+        let kind = byte[0];
+        let mut length = 0usize;
+        for shift in (0..28).step_by(7) {
+            self.stream.read_exact(&mut byte)?;
+            length |= ((byte[0] & 0x7f) as usize) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0u8; length];
+        self.stream.read_exact(&mut body)?;
+        if kind >> 4 == PUBLISH && length < 2 {
+            bail!("malformed MQTT PUBLISH");
+        }
+        Ok((kind, body))
+    }
+}
+
+fn is_timeout(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<std::io::Error>()
+        .is_some_and(|e| matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut))
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend((s.len() as u16).to_be_bytes());
+    buf.extend(s.as_bytes());
+}
+
+// Variable-length "remaining length": seven bits a byte, low bits first
+fn put_length(buf: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        buf.push(byte);
+        if length == 0 {
+            return;
+        }
+    }
+}
+
+/// Whether `topic` matches a subscription `filter`, with `+` standing for
+/// one level and a final `#` for any number.
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split('/');
+    for level in filter.split('/') {
+        match (level, topic_levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (level, Some(topic_level)) if level == topic_level => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remaining_length() {
+        let mut buf = Vec::new();
+        put_length(&mut buf, 321);
+        assert_eq!(buf, [0xc1, 0x02]);
+    }
+
+    #[test]
+    fn test_topic_matches() {
+        assert!(topic_matches("home/+/door", "home/front/door"));
+        assert!(topic_matches("home/#", "home/front/door"));
+        assert!(topic_matches("home/#", "home"));
+        assert!(!topic_matches("home/+", "home/front/door"));
+        assert!(!topic_matches("home/front", "home/back"));
+    }
+}
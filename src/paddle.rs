@@ -0,0 +1,148 @@
+// ---------- Iambic keyer ----------------------------------------------------
+// Two keyboard keys stand in for a paddle's dit and dah levers. Holding one
+// sends a string of its elements; squeezing both alternates them, starting
+// with the one not sent last. Iambic A and B differ only in what happens when
+// a squeeze is let go: A finishes the element being sent and stops, while B
+// has noticed the other lever closed during it and sends one more, the
+// opposite element, which is what most modern keyers do and what B-trained
+// operators' timing expects.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum IambicMode {
+    /// Stop after the element being sent when a squeeze is let go
+    A,
+    /// Send one more, opposite, element when a squeeze is let go
+    #[default]
+    B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    Dit,
+    Dah,
+}
+
+impl Element {
+    pub fn symbol(self) -> char {
+        match self {
+            Element::Dit => '.',
+            Element::Dah => '-',
+        }
+    }
+}
+
+/// Which element comes next from the paddles, asked each time the one
+/// before (and the space after it) is over.
+#[derive(Debug, Clone)]
+pub struct Iambic {
+    mode: IambicMode,
+    dit: bool,
+    dah: bool,
+    dit_memory: bool,
+    dah_memory: bool,
+    sending: Option<Element>,
+}
+
+impl Iambic {
+    pub fn new(mode: IambicMode) -> Self {
+        Iambic { mode, dit: false, dah: false, dit_memory: false, dah_memory: false, sending: None }
+    }
+
+    /// The levers are now as given.
+    pub fn paddles(&mut self, dit: bool, dah: bool) {
+        self.dit = dit;
+        self.dah = dah;
+        self.remember();
+    }
+
+    /// One lever tapped, where the terminal doesn't say when keys are let
+    /// go: its element is sent once, after the one being sent.
+    pub fn tap(&mut self, element: Element) {
+        match element {
+            Element::Dit => self.dit_memory = true,
+            Element::Dah => self.dah_memory = true,
+        }
+    }
+
+    /// The element just sent is over: the one to send next, if any.
+    pub fn next(&mut self) -> Option<Element> {
+        let dit = self.dit || self.dit_memory;
+        let dah = self.dah || self.dah_memory;
+        self.sending = match (dit, dah) {
+            (true, true) => Some(match self.sending {
+                Some(Element::Dit) => Element::Dah,
+                _ => Element::Dit,
+            }),
+            (true, false) => Some(Element::Dit),
+            (false, true) => Some(Element::Dah),
+            (false, false) => None,
+        };
+        match self.sending {
+            Some(Element::Dit) => self.dit_memory = false,
+            Some(Element::Dah) => self.dah_memory = false,
+            None => {}
+        }
+        self.remember();
+        self.sending
+    }
+
+    // In mode B, the other lever closing at any time during an element
+    // (already closed as it starts included) is remembered
+    fn remember(&mut self) {
+        if self.mode != IambicMode::B {
+            return;
+        }
+        match self.sending {
+            Some(Element::Dit) if self.dah => self.dah_memory = true,
+            Some(Element::Dah) if self.dit => self.dit_memory = true,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Squeeze from the first element, let go during the `release`th, then
+    // send until the keyer stops
+    fn squeeze(mode: IambicMode, release: usize) -> String {
+        let mut keyer = Iambic::new(mode);
+        keyer.paddles(true, true);
+        let mut sent = String::new();
+        while let Some(element) = keyer.next() {
+            sent.push(element.symbol());
+            if sent.len() == release {
+                keyer.paddles(false, false);
+            }
+        }
+        sent
+    }
+
+    #[test]
+    fn test_iambic_squeeze() {
+        assert_eq!(squeeze(IambicMode::A, 3), ".-.");
+        assert_eq!(squeeze(IambicMode::B, 3), ".-.-");
+    }
+
+    #[test]
+    fn test_one_lever() {
+        // Holding one lever repeats it; the other alone takes over
+        let mut keyer = Iambic::new(IambicMode::A);
+        keyer.paddles(false, true);
+        assert_eq!([keyer.next(), keyer.next()], [Some(Element::Dah); 2]);
+        keyer.paddles(true, false);
+        assert_eq!(keyer.next(), Some(Element::Dit));
+        keyer.paddles(false, false);
+        assert_eq!(keyer.next(), None);
+    }
+
+    #[test]
+    fn test_taps() {
+        // Taps without releases: each is sent once
+        let mut keyer = Iambic::new(IambicMode::A);
+        keyer.tap(Element::Dah);
+        keyer.tap(Element::Dit);
+        assert_eq!([keyer.next(), keyer.next(), keyer.next()], [Some(Element::Dit), Some(Element::Dah), None]);
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::morse::Timing;
+
+// Sent with every character; characters from a partner on a newer
+// protocol than this end knows are dropped (see parse)
+const PROTOCOL_VERSION: u32 = 1;
+
+// ---------- Practice partner ------------------------------------------------
+// Two interactive keyers joined over TCP: each character typed on one end is
+// sent as soon as it is keyed, and sounds on the other at the sender's speed
+// and tone, alongside (and mixed with) whatever is being keyed locally. One
+// end waits with `--partner-listen PORT`, the other connects with
+// `--partner HOST:PORT`.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct PartnerArgs {
+    /// In interactive mode, connect to a partner's cwgen (HOST:PORT) and
+    /// hear each other's keying
+    #[arg(long, value_name = "HOST:PORT", requires = "interactive", conflicts_with = "partner_listen")]
+    pub partner: Option<String>,
+
+    /// In interactive mode, wait for a partner to connect on PORT
+    #[arg(long, value_name = "PORT", requires = "interactive")]
+    pub partner_listen: Option<u16>,
+}
+
+// ---------- Wire format -----------------------------------------------------
+// One JSON object per line:
+//
+//     {"source":"cwgen","version":1,"ch":"K","wpm":20,"tone_hz":600}
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Line {
+    source: String,
+    version: u32,
+    ch: char,
+    wpm: u32,
+    tone_hz: u32,
+}
+
+/// A character the partner keyed.
+#[derive(Debug, Clone, Copy)]
+pub struct Keyed {
+    pub ch: char,
+    pub timing: Timing,
+    pub tone: u32,
+}
+
+/// What arrives from the partner.
+pub enum Remote {
+    Keyed(Keyed),
+    Gone(String),
+}
+
+pub struct Partner {
+    stream: TcpStream,
+    pub incoming: Receiver<Remote>,
+}
+
+impl Partner {
+    /// Connect or wait for a connection, as `args` say, if either is given.
+    pub fn connect(args: &PartnerArgs) -> Result<Option<Self>> {
+        let stream = match (&args.partner, args.partner_listen) {
+            (Some(address), _) => TcpStream::connect(address).with_context(|| format!("connecting to {}", address))?,
+            (None, Some(port)) => {
+                let listener = TcpListener::bind(("0.0.0.0", port)).with_context(|| format!("listening on port {}", port))?;
+                println!("Waiting for a partner on port {}...", port);
+                listener.accept()?.0
+            }
+            (None, None) => return Ok(None),
+        };
+        // Characters are tiny and should go out as soon as they are typed
+        stream.set_nodelay(true)?;
+        println!("Partner connected: {}", stream.peer_addr()?);
+
+        let (sender, incoming) = mpsc::channel();
+        let reader = BufReader::new(stream.try_clone()?);
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let remote = match line {
+                    Ok(line) => match parse(&line) {
+                        Some(keyed) => Remote::Keyed(keyed),
+                        None => continue,
+                    },
+                    Err(e) => Remote::Gone(e.to_string()),
+                };
+                let gone = matches!(remote, Remote::Gone(_));
+                if sender.send(remote).is_err() || gone {
+                    return;
+                }
+            }
+            let _ = sender.send(Remote::Gone("disconnected".into()));
+        });
+        Ok(Some(Partner { stream, incoming }))
+    }
+
+    /// Send a character keyed here.
+    pub fn send(&mut self, ch: char, timing: &Timing, tone: u32) -> Result<()> {
+        let line = Line { source: "cwgen".into(), version: PROTOCOL_VERSION, ch, wpm: wpm(timing), tone_hz: tone };
+        writeln!(self.stream, "{}", serde_json::to_string(&line)?)?;
+        Ok(())
+    }
+}
+
+// Lines from a newer version, or that don't parse, are skipped
+fn parse(line: &str) -> Option<Keyed> {
+    let line: Line = serde_json::from_str(line).ok()?;
+    if line.version > PROTOCOL_VERSION || !(1..=100).contains(&line.wpm) || !(100..=3000).contains(&line.tone_hz) {
+        return None;
+    }
+    Some(Keyed { ch: line.ch, timing: Timing::new(line.wpm, 0), tone: line.tone_hz })
+}
+
+// Character speed from the dot length
+fn wpm(timing: &Timing) -> u32 {
+    (1200 / timing.dot.as_millis().max(1)).clamp(1, 100) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partner_lines() {
+        let line = Line { source: "cwgen".into(), version: PROTOCOL_VERSION, ch: 'K', wpm: wpm(&Timing::new(25, 0)), tone_hz: 650 };
+        let keyed = parse(&serde_json::to_string(&line).unwrap()).unwrap();
+        assert_eq!(keyed.ch, 'K');
+        assert_eq!(keyed.tone, 650);
+        assert_eq!(keyed.timing.dot, Timing::new(25, 0).dot);
+    }
+
+    #[test]
+    fn test_unknown_lines_ignored() {
+        assert!(parse("{\"source\":\"cwgen\",\"version\":99,\"ch\":\"K\",\"wpm\":20,\"tone_hz\":600}").is_none());
+        assert!(parse("not json").is_none());
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::io::Read;
+use std::sync::mpsc;
+
+// ---------- Raw PCM input ---------------------------------------------------
+// Headerless audio from pipes: `rtl_fm`, GQRX's UDP output through `nc`, or
+// `ffmpeg -f s16le -`. Multi-channel input is interleaved and downmixed.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PcmFormat {
+    /// Signed 16-bit little-endian
+    S16le,
+    /// 32-bit float little-endian
+    F32le,
+    /// Unsigned 8-bit
+    U8,
+}
+
+impl PcmFormat {
+    fn bytes(self) -> usize {
+        match self {
+            PcmFormat::S16le => 2,
+            PcmFormat::F32le => 4,
+            PcmFormat::U8 => 1,
+        }
+    }
+
+    fn sample(self, bytes: &[u8]) -> f32 {
+        match self {
+            PcmFormat::S16le => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            PcmFormat::F32le => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            PcmFormat::U8 => (bytes[0] as f32 - 128.0) / 128.0,
+        }
+    }
+}
+
+/// Where raw PCM comes from and how it's laid out.
+#[derive(clap::Args, Debug, Clone)]
+pub struct RawInput {
+    /// Read headerless PCM in this format (from stdin, or `-`) instead of
+    /// the soundcard or WAV files
+    #[arg(long, value_enum)]
+    pub input_format: Option<PcmFormat>,
+
+    /// Sample rate of raw input in Hz
+    #[arg(long, default_value_t = 48000, requires = "input_format")]
+    pub rate: u32,
+
+    /// Interleaved channels in raw input
+    #[arg(long, default_value_t = 1, requires = "input_format")]
+    pub channels: u16,
+}
+
+impl RawInput {
+    fn frame_bytes(&self, format: PcmFormat) -> usize {
+        format.bytes() * self.channels.max(1) as usize
+    }
+
+    // Mono samples from whole frames of raw bytes
+    fn to_mono(&self, format: PcmFormat, bytes: &[u8]) -> Vec<f32> {
+        let width = format.bytes();
+        let channels = self.channels.max(1) as usize;
+        bytes
+            .chunks_exact(self.frame_bytes(format))
+            .map(|frame| frame.chunks_exact(width).map(|s| format.sample(s)).sum::<f32>() / channels as f32)
+            .collect()
+    }
+
+    /// Read all of `path` ("-" for stdin) as raw PCM.
+    pub fn read(&self, format: PcmFormat, path: &str) -> Result<Vec<f32>> {
+        let mut bytes = Vec::new();
+        if path == "-" {
+            std::io::stdin().lock().read_to_end(&mut bytes).context("reading stdin")?;
+        } else {
+            bytes = std::fs::read(path).with_context(|| format!("reading {}", path))?;
+        }
+        Ok(self.to_mono(format, &bytes))
+    }
+
+    /// Stream stdin as mono blocks until it closes.
+    pub fn stream_stdin(&self, format: PcmFormat, blocks: mpsc::Sender<Vec<f32>>) {
+        let raw = self.clone();
+        std::thread::spawn(move || {
+            let frame = raw.frame_bytes(format);
+            let mut stdin = std::io::stdin().lock();
+            let mut buf = vec![0u8; frame * 1024];
+            // Bytes of a frame split across reads wait at the front of `buf`
+            let mut carried = 0;
+            loop {
+                let read = match stdin.read(&mut buf[carried..]) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => carried + n,
+                };
+                let whole = read - read % frame;
+                if blocks.send(raw.to_mono(format, &buf[..whole])).is_err() {
+                    break;
+                }
+                buf.copy_within(whole..read, 0);
+                carried = read - whole;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_stereo_s16le() {
+        let raw = RawInput { input_format: Some(PcmFormat::S16le), rate: 8000, channels: 2 };
+        let mut bytes = Vec::new();
+        for (left, right) in [(16384i16, 0i16), (-32768, -32768)] {
+            bytes.extend(left.to_le_bytes());
+            bytes.extend(right.to_le_bytes());
+        }
+        // A trailing partial frame is ignored
+        bytes.push(0);
+        assert_eq!(raw.to_mono(PcmFormat::S16le, &bytes), [0.25, -1.0]);
+    }
+}
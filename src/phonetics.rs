@@ -0,0 +1,133 @@
+use anyhow::Result;
+use rand::Rng;
+
+use crate::audio::{play_audio, Speaker, ToneShape};
+use crate::conditions::Conditions;
+use crate::events::{self, Event};
+use crate::morse::{random_callsign, Timing};
+use crate::quiz::prompt;
+use crate::record::Recorder;
+
+// ---------- NATO phonetics --------------------------------------------------
+// Operators who work mixed phone and CW contests hear the same callsign as
+// "Whiskey One Alpha Whiskey" one minute and as code the next. `--phonetics`
+// spells answers out in the NATO alphabet wherever plain text is shown, and
+// `--practice phonetics` drills the conversion both ways: a callsign in CW to
+// be answered in phonetics, then one in phonetics to be answered as the
+// characters, which are then sent so the code goes with the words.
+const ALPHABET: [(char, &str); 37] = [
+    ('A', "Alpha"), ('B', "Bravo"), ('C', "Charlie"), ('D', "Delta"), ('E', "Echo"), ('F', "Foxtrot"),
+    ('G', "Golf"), ('H', "Hotel"), ('I', "India"), ('J', "Juliett"), ('K', "Kilo"), ('L', "Lima"),
+    ('M', "Mike"), ('N', "November"), ('O', "Oscar"), ('P', "Papa"), ('Q', "Quebec"), ('R', "Romeo"),
+    ('S', "Sierra"), ('T', "Tango"), ('U', "Uniform"), ('V', "Victor"), ('W', "Whiskey"), ('X', "X-ray"),
+    ('Y', "Yankee"), ('Z', "Zulu"),
+    ('0', "Zero"), ('1', "One"), ('2', "Two"), ('3', "Three"), ('4', "Four"), ('5', "Five"), ('6', "Six"),
+    ('7', "Seven"), ('8', "Eight"), ('9', "Nine"), ('/', "Stroke"),
+];
+// Other spellings heard on the air
+const VARIANTS: [(&str, char); 6] = [
+    ("alfa", 'A'), ("juliet", 'J'), ("whisky", 'W'), ("xray", 'X'), ("niner", '9'), ("slash", '/'),
+];
+pub const QUESTIONS: usize = 20;
+
+/// `text` spelled out: "W1AW" is "Whiskey One Alpha Whiskey". Characters
+/// without a phonetic are left as they are.
+pub fn spell(text: &str) -> String {
+    let words: Vec<String> = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| {
+            let c = c.to_ascii_uppercase();
+            ALPHABET.iter().find(|(letter, _)| *letter == c).map_or_else(|| c.to_string(), |(_, word)| word.to_string())
+        })
+        .collect();
+    words.join(" ")
+}
+
+/// The text `words` spell out, or None if one isn't a phonetic.
+pub fn read(words: &str) -> Option<String> {
+    words
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let word = word.to_lowercase();
+            ALPHABET
+                .iter()
+                .find(|(_, phonetic)| phonetic.eq_ignore_ascii_case(&word))
+                .map(|(c, _)| *c)
+                .or_else(|| VARIANTS.iter().find(|(variant, _)| *variant == word).map(|(_, c)| *c))
+        })
+        .collect()
+}
+
+/// "W1AW = Whiskey One Alpha Whiskey", for showing an answer.
+pub fn annotate(text: &str) -> String {
+    format!("{} = {}", text, spell(text))
+}
+
+/// Drill `QUESTIONS` callsigns, alternating CW to phonetics and phonetics
+/// to characters, recording what is played to `record` if given. Returns
+/// how many were asked, and how many right.
+pub fn drill(rng: &mut impl Rng, timing: Timing, tone: u32, conditions: Conditions, tone_shape: ToneShape, record: Option<&str>, speaker: &Speaker) -> Result<(usize, usize)> {
+    let recorder = record.map(|_| Recorder::new(crate::audio::playback_rate()));
+    println!("Phonetics drill – {} callsigns. Enter alone repeats, q quits.\n", QUESTIONS);
+    let (mut asked, mut correct) = (0, 0);
+    while asked < QUESTIONS {
+        let call = random_callsign(rng);
+        let from_cw = asked % 2 == 0;
+        match from_cw {
+            true => println!("{:2}. Say it in phonetics:", asked + 1),
+            false => println!("{:2}. Copy as characters: {}", asked + 1, spell(&call)),
+        }
+        let answer = loop {
+            if from_cw {
+                play_audio(&call, timing, tone, conditions, tone_shape, None, recorder.as_ref(), speaker)?;
+            }
+            match prompt("    > ")? {
+                Some(answer) if answer.is_empty() => continue,
+                answer => break answer,
+            }
+        };
+        let Some(answer) = answer else { break };
+        let right = match from_cw {
+            true => read(&answer).is_some_and(|copy| copy == call),
+            false => answer.split_whitespace().collect::<String>().eq_ignore_ascii_case(&call),
+        };
+        asked += 1;
+        correct += right as usize;
+        println!("    {} {}\n", if right { "✓" } else { "✗" }, annotate(&call));
+        events::emit(Event::ItemScored { item: asked, expected: &call, answer: &answer, correct: right });
+        if !from_cw {
+            play_audio(&call, timing, tone, conditions, tone_shape, None, recorder.as_ref(), speaker)?;
+        }
+    }
+    println!("{}/{} right", correct, asked);
+    if let (Some(recorder), Some(path)) = (&recorder, record) {
+        recorder.save(path)?;
+        println!("Session recorded to: {}", path);
+    }
+    Ok((asked, correct))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spell() {
+        assert_eq!(spell("W1AW"), "Whiskey One Alpha Whiskey");
+    }
+
+    #[test]
+    fn test_annotate() {
+        assert_eq!(annotate("k9x/p"), "k9x/p = Kilo Nine X-ray Stroke Papa");
+    }
+
+    #[test]
+    fn test_read() {
+        assert_eq!(read("whiskey one ALPHA, Whiskey").as_deref(), Some("W1AW"));
+        assert_eq!(read("Kilo Niner Xray Slash Papa").as_deref(), Some("K9X/P"));
+        assert_eq!(read("Whiskey Won"), None);
+        assert_eq!(read(&spell("DL0ABC/9")).as_deref(), Some("DL0ABC/9"));
+    }
+}
@@ -0,0 +1,382 @@
+use anyhow::Result;
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+use rodio::Source;
+
+use crate::audio::{play_mix, playback_rate, MorseSynth, NoiseSource, Speaker, ToneShape};
+use crate::conditions::Conditions;
+use crate::events::{self, Event};
+use crate::exam::copied_as;
+use crate::morse::{random_callsign, Timing};
+use crate::quiz::prompt;
+use crate::record::Recorder;
+
+// ---------- Contest pileup --------------------------------------------------
+// `--practice pileup` runs a contest the way Morse Runner does: you call CQ
+// and several stations answer at once, each at its own speed, pitch and
+// strength over the band noise. Type the call you pulled out and that
+// station sends its exchange (5NN and a serial, sometimes in cut numbers);
+// type the serial and the QSO is logged. A call with a ? in it gets a repeat
+// from the stations it fits, and one a character or two out gets the nearest
+// station's call again. Stations not worked stay on and keep calling, and
+// some give up; new ones arrive. The score is the QSOs logged right, and
+// the rate they were made at.
+pub const CALLERS: u8 = 3;
+/// Most characters a typed call may be out for its station to answer
+const NEAR: usize = 2;
+/// Callers start within this long of the end of your CQ
+const MAX_DELAY: f64 = 1.2;
+/// A caller not worked leaves after a QSO this often
+const GIVE_UP: f64 = 0.2;
+
+/// A station calling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caller {
+    pub call: String,
+    pub wpm: u32,
+    pub tone: u32,
+    /// Signal strength, 1 the loudest
+    pub strength: f32,
+    pub serial: u32,
+    /// Sends its serial with T for 0 and N for 9
+    pub cut: bool,
+    /// Seconds after your CQ that it starts calling
+    pub delay: f64,
+}
+
+impl Caller {
+    /// A station calling at about your `wpm`, near your `tone`.
+    pub fn new(rng: &mut impl Rng, wpm: u32, tone: u32) -> Caller {
+        Caller {
+            call: random_callsign(rng),
+            wpm: (wpm as i64 + rng.random_range(-4..=6)).clamp(5, 60) as u32,
+            tone: (tone as i64 + rng.random_range(-6..=6) * 50).clamp(300, 1500) as u32,
+            strength: rng.random_range(0.2..=1.0),
+            serial: rng.random_range(1..=999),
+            cut: rng.random_bool(0.5),
+            delay: rng.random_range(0.0..MAX_DELAY),
+        }
+    }
+
+    /// "5NN 123", or "5NN 1T3" in cut numbers.
+    pub fn exchange(&self) -> String {
+        exchange(self.serial, self.cut)
+    }
+}
+
+fn exchange(serial: u32, cut: bool) -> String {
+    let serial = format!("{:03}", serial);
+    match cut {
+        true => format!("5NN {}", serial.replace('0', "T").replace('9', "N")),
+        false => format!("5NN {}", serial),
+    }
+}
+
+/// `count` calls with their exchanges, as stations in a run send them.
+pub fn exchanges(rng: &mut impl Rng, count: usize) -> Vec<String> {
+    (0..count)
+        .map(|_| {
+            let call = random_callsign(rng);
+            format!("{} {}", call, exchange(rng.random_range(1..=999), rng.random_bool(0.5)))
+        })
+        .collect()
+}
+
+/// Who answers a call typed into the log.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Answer {
+    /// The station with that call sends its exchange
+    Worked(usize),
+    /// The station with a call a little different sends it again
+    Near(usize),
+    /// "W1?": the stations it fits call again
+    Partial(Vec<usize>),
+    /// Nobody's call; they all call again
+    Nobody,
+}
+
+/// Which of `callers` answers `typed`.
+pub fn answer(callers: &[Caller], typed: &str) -> Answer {
+    let typed = typed.trim().to_ascii_uppercase();
+    if let Some(index) = callers.iter().position(|caller| caller.call == typed) {
+        return Answer::Worked(index);
+    }
+    if typed.contains('?') {
+        let fits: Vec<usize> = callers
+            .iter()
+            .enumerate()
+            .filter(|(_, caller)| typed.split('?').filter(|part| !part.is_empty()).all(|part| caller.call.contains(part)))
+            .map(|(index, _)| index)
+            .collect();
+        return match fits.is_empty() {
+            true => Answer::Nobody,
+            false => Answer::Partial(fits),
+        };
+    }
+    callers
+        .iter()
+        .enumerate()
+        .map(|(index, caller)| (index, off_by(&caller.call, &typed)))
+        .filter(|&(_, off)| off <= NEAR)
+        .min_by_key(|&(_, off)| off)
+        .map_or(Answer::Nobody, |(index, _)| Answer::Near(index))
+}
+
+// Characters of `call` copied wrong or dropped, and any extra
+fn off_by(call: &str, typed: &str) -> usize {
+    let missed = copied_as(call, typed).iter().filter(|(sent, copied)| Some(*sent) != *copied).count();
+    missed + typed.chars().count().saturating_sub(call.chars().count())
+}
+
+/// The serial in a typed exchange: its last word, with cut numbers read as
+/// digits ("5nn 1t3" is 103). A report on its own ("5nn") has no serial.
+pub fn serial(typed: &str) -> Option<u32> {
+    let words: Vec<&str> = typed.split_whitespace().collect();
+    let last = match words[..] {
+        [only] if only.eq_ignore_ascii_case("5NN") || only == "599" => return None,
+        [.., last] => last,
+        [] => return None,
+    };
+    let digits: String = last
+        .chars()
+        .map(|c| match c.to_ascii_uppercase() {
+            'T' | 'O' => '0',
+            'A' => '1',
+            'E' => '5',
+            'N' => '9',
+            c => c,
+        })
+        .collect();
+    digits.parse().ok()
+}
+
+/// QSOs an hour, at `qsos` in `elapsed`.
+pub fn rate(qsos: usize, elapsed: Duration) -> f64 {
+    match elapsed.as_secs_f64() {
+        secs if secs > 0.0 => qsos as f64 * 3600.0 / secs,
+        _ => 0.0,
+    }
+}
+
+/// How a run went.
+#[derive(Debug, Default)]
+pub struct Run {
+    /// QSOs logged
+    pub logged: usize,
+    /// Logged with the right serial
+    pub good: usize,
+    /// Each serial's characters as logged, for the profile
+    pub copied_as: Vec<(char, Option<char>)>,
+}
+
+/// The sounds of a run: each transmission mixed over band noise.
+struct Band<'a> {
+    timing: Timing,
+    conditions: Conditions,
+    tone: u32,
+    tone_shape: ToneShape,
+    sample_rate: u32,
+    started: Instant,
+    recorder: Option<Recorder>,
+    speaker: &'a Speaker,
+}
+
+impl Band<'_> {
+    /// Play `sending` at once, each caller's text from its delay on.
+    fn play(&self, sending: &[(&Caller, String, f64)]) -> Result<()> {
+        let conditions = self.conditions.at(self.started.elapsed().as_secs_f64());
+        let rate = self.sample_rate as f64;
+        let mut mix: Vec<f32> = Vec::new();
+        // Two interleaved when binaural
+        let mut channels = 1;
+        for (caller, text, delay) in sending {
+            let timing = Timing::new(caller.wpm, 0)
+                .with_charset(self.timing.charset)
+                .with_envelope(self.timing.envelope)
+                .with_binaural(self.timing.binaural);
+            let signal = MorseSynth::signal_only(self.sample_rate, text, timing, caller.tone, conditions, self.tone_shape, None)
+                .binaural(timing.binaural);
+            channels = signal.channels();
+            let offset = (delay * rate) as usize * usize::from(channels);
+            for (i, sample) in signal.enumerate() {
+                if mix.len() <= offset + i {
+                    mix.resize(offset + i + 1, 0.0);
+                }
+                mix[offset + i] += sample * caller.strength;
+            }
+        }
+        // A little noise either side, as between overs
+        let tail = (0.3 * rate) as usize * usize::from(channels);
+        mix.splice(0..0, std::iter::repeat_n(0.0, tail));
+        mix.resize(mix.len() + tail, 0.0);
+        for (frame, noise) in mix.chunks_mut(channels.into()).zip(NoiseSource::new(conditions, self.tone, self.sample_rate)) {
+            frame.iter_mut().for_each(|sample| *sample += noise);
+        }
+        play_mix(mix, channels, self.sample_rate, self.recorder.as_ref(), self.speaker)
+    }
+
+    fn calling(&self, callers: &[&Caller]) -> Result<()> {
+        self.play(&callers.iter().map(|caller| (*caller, caller.call.clone(), caller.delay)).collect::<Vec<_>>())
+    }
+}
+
+/// Run a pileup of up to `most` callers until q. Returns how it went.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    rng: &mut impl Rng,
+    timing: Timing,
+    wpm: u32,
+    most: u8,
+    tone: u32,
+    conditions: Conditions,
+    tone_shape: ToneShape,
+    record: Option<&str>,
+    speaker: &Speaker,
+) -> Result<Run> {
+    let sample_rate = playback_rate();
+    let recorder = record.map(|_| Recorder::new(sample_rate));
+    let band = Band { timing, conditions, tone, tone_shape, sample_rate, started: Instant::now(), recorder, speaker };
+    println!("Contest pileup – type the call you copy, then its serial.");
+    println!("Enter alone calls CQ again or asks for a repeat, W1? asks the stations it fits, q ends the run.\n");
+
+    let mut run = Run::default();
+    let mut callers: Vec<Caller> = Vec::new();
+    let started = Instant::now();
+    'run: loop {
+        // Some give up, new ones arrive
+        callers.retain(|_| !rng.random_bool(GIVE_UP));
+        let arriving = rng.random_range(usize::from(callers.is_empty())..=2);
+        for _ in 0..arriving.min((most as usize).saturating_sub(callers.len())) {
+            callers.push(Caller::new(rng, wpm, tone));
+        }
+        println!("» CQ TEST");
+        band.calling(&callers.iter().collect::<Vec<_>>())?;
+
+        // Pull a call out of the pileup
+        let worked = loop {
+            let Some(typed) = prompt("Call> ")? else { break 'run };
+            match answer(&callers, &typed) {
+                Answer::Worked(index) => break index,
+                Answer::Near(index) => band.calling(&[&callers[index]])?,
+                Answer::Partial(fits) => band.calling(&fits.iter().map(|&index| &callers[index]).collect::<Vec<_>>())?,
+                Answer::Nobody => band.calling(&callers.iter().collect::<Vec<_>>())?,
+            }
+        };
+        let caller = callers.remove(worked);
+        println!("» {} 5NN {:03}", caller.call, run.logged + 1);
+        let exchange = caller.exchange();
+        let logged = loop {
+            band.play(&[(&caller, exchange.clone(), 0.0)])?;
+            let Some(typed) = prompt("Exch> ")? else { break 'run };
+            if !typed.is_empty() && typed != "?" {
+                break typed;
+            }
+        };
+
+        let good = serial(&logged) == Some(caller.serial);
+        run.logged += 1;
+        run.good += good as usize;
+        run.copied_as.extend(copied_as(&format!("{:03}", caller.serial), &serial(&logged).map_or(logged.clone(), |n| format!("{:03}", n))));
+        println!("» TU");
+        println!(
+            "  {} {} {}   {} QSOs, {:.0} an hour\n",
+            if good { "✓" } else { "✗" },
+            caller.call,
+            exchange,
+            run.good,
+            rate(run.good, started.elapsed()),
+        );
+        let expected = format!("{} {}", caller.call, exchange);
+        let answer = format!("{} {}", caller.call, logged);
+        events::emit(Event::ItemScored { item: run.logged, expected: &expected, answer: &answer, correct: good });
+    }
+
+    let elapsed = started.elapsed();
+    println!(
+        "\n{} QSOs in {}:{:02}, {:.0} an hour; {} of {} logged with a busted serial",
+        run.good,
+        elapsed.as_secs() / 60,
+        elapsed.as_secs() % 60,
+        rate(run.good, elapsed),
+        run.logged - run.good,
+        run.logged,
+    );
+    if let (Some(recorder), Some(path)) = (&band.recorder, record) {
+        recorder.save(path)?;
+        println!("Session recorded to: {}", path);
+    }
+    Ok(run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn caller(call: &str) -> Caller {
+        Caller { call: call.into(), wpm: 25, tone: 600, strength: 1.0, serial: 90, cut: true, delay: 0.0 }
+    }
+
+    fn callers() -> [Caller; 3] {
+        [caller("W1ABC"), caller("DL2XY"), caller("W1AW")]
+    }
+
+    #[test]
+    fn test_answer_worked() {
+        assert_eq!(answer(&callers(), "dl2xy"), Answer::Worked(1));
+    }
+
+    #[test]
+    fn test_answer_partial() {
+        assert_eq!(answer(&callers(), "W1?"), Answer::Partial(vec![0, 2]));
+        assert_eq!(answer(&callers(), "?XY"), Answer::Partial(vec![1]));
+        assert_eq!(answer(&callers(), "VK?"), Answer::Nobody);
+    }
+
+    #[test]
+    fn test_answer_near() {
+        assert_eq!(answer(&callers(), "DL2XV"), Answer::Near(1));
+        assert_eq!(answer(&callers(), "W1AB"), Answer::Near(0));
+        assert_eq!(answer(&callers(), "JA1ZZZ"), Answer::Nobody);
+    }
+
+    #[test]
+    fn test_cut_numbers() {
+        assert_eq!(caller("W1ABC").exchange(), "5NN TNT");
+        assert_eq!(serial("5nn tnt"), Some(90));
+    }
+
+    #[test]
+    fn test_serial() {
+        assert_eq!(serial("90"), Some(90));
+        assert_eq!(serial("5NN 599"), Some(599));
+        assert_eq!(serial("5NN 1X3"), None);
+        assert_eq!(serial("5nn"), None);
+        assert_eq!(serial("599"), None);
+    }
+
+    #[test]
+    fn test_rate() {
+        assert_eq!(rate(12, Duration::from_secs(600)), 72.0);
+    }
+
+    #[test]
+    fn test_caller_near_your_speed_and_tone() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        for _ in 0..50 {
+            let caller = Caller::new(&mut rng, 28, 600);
+            assert!((24..=34).contains(&caller.wpm) && (300..=900).contains(&caller.tone), "{:?}", caller);
+            assert_eq!(serial(&caller.exchange()), Some(caller.serial));
+        }
+    }
+
+    #[test]
+    fn test_exchanges() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        for sent in exchanges(&mut rng, 20) {
+            assert_eq!(sent.split_whitespace().count(), 3, "{}", sent);
+            assert!(serial(&sent).is_some_and(|serial| (1..=999).contains(&serial)), "{}", sent);
+        }
+    }
+}
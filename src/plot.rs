@@ -0,0 +1,225 @@
+use anyhow::Result;
+use std::fmt::Write as _;
+
+use crate::decode::goertzel;
+use crate::markup;
+
+// ---------- Waveform and spectrogram plots ----------------------------------
+// `cwgen render --plot FILE` draws the rendered audio as a picture: the
+// waveform above (the envelope of each column, so keying shape, fading and
+// crashes show) and a spectrogram below (so drift, chirp and the spread of the
+// noise show). The spectrogram is a Goertzel bank, like the decoder's signal
+// search, over Hann-windowed frames, from 0 Hz to twice the tone, coloured
+// over the top DYNAMIC_RANGE_DB. A .png file gets the picture alone; anything
+// else gets SVG, with the spectrogram embedded as a PNG and axes labelled.
+const COLUMNS: usize = 960;
+const BINS: usize = 128;
+const WAVE_HEIGHT: usize = 120;
+// Pixels per spectrogram bin
+const BIN_HEIGHT: usize = 2;
+const DYNAMIC_RANGE_DB: f64 = 60.0;
+// Space around the panels for the SVG's labels
+const MARGIN: usize = 48;
+
+pub struct Plot {
+    /// Lowest and highest sample in each column
+    waveform: Vec<(f32, f32)>,
+    /// Per column, per bin from the bottom up: 0 (DYNAMIC_RANGE_DB down or
+    /// less) to 1 (the loudest anywhere)
+    spectrogram: Vec<Vec<f64>>,
+    top_hz: f64,
+    seconds: f64,
+}
+
+impl Plot {
+    pub fn new(samples: &[f32], sample_rate: u32, tone: u32) -> Self {
+        let seconds = samples.len() as f64 / sample_rate as f64;
+        let top_hz = (2.0 * tone as f64).min(sample_rate as f64 / 2.0);
+        let waveform = (0..COLUMNS)
+            .map(|column| {
+                let (from, to) = (column * samples.len() / COLUMNS, (column + 1) * samples.len() / COLUMNS);
+                samples[from..to].iter().fold((0f32, 0f32), |(low, high), &s| (low.min(s), high.max(s)))
+            })
+            .collect();
+
+        // Frames long enough to tell neighbouring bins apart, centred on each
+        // column
+        let frame = ((sample_rate as f64 / (top_hz / BINS as f64)) as usize).clamp(64, samples.len().max(64));
+        let window: Vec<f32> = (0..frame)
+            .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / frame as f32).cos())
+            .collect();
+        let mut power: Vec<Vec<f64>> = (0..COLUMNS)
+            .map(|column| {
+                let centre = column * samples.len() / COLUMNS;
+                let from = centre.saturating_sub(frame / 2);
+                let windowed: Vec<f32> = (0..frame)
+                    .map(|i| samples.get(from + i).copied().unwrap_or_default() * window[i])
+                    .collect();
+                (0..BINS)
+                    .map(|bin| 10.0 * (goertzel(&windowed, sample_rate, (bin as f64 + 0.5) * top_hz / BINS as f64) + 1e-12).log10())
+                    .collect()
+            })
+            .collect();
+        let loudest = power.iter().flatten().copied().fold(f64::MIN, f64::max);
+        for db in power.iter_mut().flatten() {
+            *db = ((*db - loudest + DYNAMIC_RANGE_DB) / DYNAMIC_RANGE_DB).clamp(0.0, 1.0);
+        }
+        Plot { waveform, spectrogram: power, top_hz, seconds }
+    }
+
+    /// Write the plot to `path`: PNG for a .png file, SVG otherwise.
+    pub fn write(&self, path: &str) -> Result<()> {
+        markup::write_picture(path, || self.png(), || self.svg())
+    }
+
+    // The waveform over the spectrogram
+    fn png(&self) -> Result<Vec<u8>> {
+        let mut pixels = self.waveform_pixels();
+        pixels.extend(self.spectrogram_pixels());
+        markup::png(&pixels, COLUMNS, WAVE_HEIGHT + BINS * BIN_HEIGHT, png::ColorType::Rgb)
+    }
+
+    // RGB rows, top down
+    fn waveform_pixels(&self) -> Vec<u8> {
+        let mut pixels = vec![0xff; COLUMNS * WAVE_HEIGHT * 3];
+        let row = |s: f32| ((1.0 - s.clamp(-1.0, 1.0)) / 2.0 * (WAVE_HEIGHT - 1) as f32) as usize;
+        for (x, &(low, high)) in self.waveform.iter().enumerate() {
+            for y in row(high)..=row(low) {
+                let at = (y * COLUMNS + x) * 3;
+                pixels[at..at + 3].copy_from_slice(&[0x1b, 0x2a, 0x4a]);
+            }
+        }
+        pixels
+    }
+
+    fn spectrogram_pixels(&self) -> Vec<u8> {
+        let mut pixels = vec![0; COLUMNS * BINS * BIN_HEIGHT * 3];
+        for (x, column) in self.spectrogram.iter().enumerate() {
+            for (bin, &level) in column.iter().enumerate() {
+                for y in 0..BIN_HEIGHT {
+                    let at = (((BINS - 1 - bin) * BIN_HEIGHT + y) * COLUMNS + x) * 3;
+                    pixels[at..at + 3].copy_from_slice(&heat(level));
+                }
+            }
+        }
+        pixels
+    }
+
+    fn svg(&self) -> Result<String> {
+        let spectrum_top = MARGIN + WAVE_HEIGHT + MARGIN / 2;
+        let spectrum_height = BINS * BIN_HEIGHT;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n\
+             <style>text{{font:11px sans-serif;fill:#333}} .axis{{stroke:#999;stroke-width:1}} \
+             .wave{{fill:#1b2a4a}}</style>\n",
+            COLUMNS + 2 * MARGIN,
+            spectrum_top + spectrum_height + MARGIN,
+        );
+
+        // One filled shape through the column highs and back along the lows
+        let y = |s: f32| MARGIN as f64 + (1.0 - s.clamp(-1.0, 1.0) as f64) / 2.0 * WAVE_HEIGHT as f64;
+        let mut wave = String::new();
+        for (x, (_, high)) in self.waveform.iter().enumerate() {
+            let _ = write!(wave, "{}{},{:.1}", if x == 0 { "M" } else { "L" }, MARGIN + x, y(*high));
+        }
+        for (x, (low, _)) in self.waveform.iter().enumerate().rev() {
+            let _ = write!(wave, "L{},{:.1}", MARGIN + x + 1, y(*low));
+        }
+        let _ = writeln!(svg, "<path class=\"wave\" d=\"{}Z\"/>", wave);
+        let _ = writeln!(
+            svg,
+            "<line class=\"axis\" x1=\"{m}\" y1=\"{mid}\" x2=\"{r}\" y2=\"{mid}\"/>",
+            m = MARGIN, mid = MARGIN + WAVE_HEIGHT / 2, r = MARGIN + COLUMNS,
+        );
+
+        let _ = writeln!(
+            svg,
+            "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" href=\"data:image/png;base64,{}\"/>",
+            MARGIN, spectrum_top, COLUMNS, spectrum_height, base64(&markup::png(&self.spectrogram_pixels(), COLUMNS, spectrum_height, png::ColorType::Rgb)?),
+        );
+        for hz in [0.0, self.top_hz / 2.0, self.top_hz] {
+            let y = spectrum_top as f64 + (1.0 - hz / self.top_hz) * spectrum_height as f64;
+            let _ = writeln!(svg, "<text x=\"{}\" y=\"{:.0}\" text-anchor=\"end\">{:.0} Hz</text>", MARGIN - 4, y + 4.0, hz);
+        }
+        let bottom = spectrum_top + spectrum_height;
+        for tick in 0..=5 {
+            let x = MARGIN + tick * COLUMNS / 5;
+            let _ = writeln!(svg, "<line class=\"axis\" x1=\"{x}\" y1=\"{bottom}\" x2=\"{x}\" y2=\"{}\"/>", bottom + 4);
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{:.1} s</text>",
+                x, bottom + 16, self.seconds * tick as f64 / 5.0,
+            );
+        }
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+}
+
+// Black through blue, red and yellow to white
+fn heat(level: f64) -> [u8; 3] {
+    const STOPS: [[f64; 3]; 5] = [[0.0, 0.0, 0.0], [0.1, 0.1, 0.6], [0.8, 0.1, 0.2], [1.0, 0.8, 0.1], [1.0, 1.0, 1.0]];
+    let at = level.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let i = (at as usize).min(STOPS.len() - 2);
+    let t = at - i as f64;
+    std::array::from_fn(|c| ((STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * t) * 255.0) as u8)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            text.push(match i <= chunk.len() {
+                true => ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char,
+                false => '=',
+            });
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Half a second of silence, then half a second of 700 Hz
+    fn plot() -> Plot {
+        let rate = 8000;
+        let samples: Vec<f32> = (0..rate)
+            .map(|i| if i < rate / 2 { 0.0 } else { (std::f32::consts::TAU * 700.0 * i as f32 / rate as f32).sin() * 0.5 })
+            .collect();
+        Plot::new(&samples, rate as u32, 700)
+    }
+
+    #[test]
+    fn test_waveform() {
+        let plot = plot();
+        assert_eq!(plot.waveform.len(), COLUMNS);
+        assert_eq!(plot.waveform[0], (0.0, 0.0));
+        assert!(plot.waveform[COLUMNS - 1].1 > 0.45);
+    }
+
+    #[test]
+    fn test_spectrogram() {
+        // The tone is the middle of the spectrum, and only in the second half
+        let plot = plot();
+        let column = &plot.spectrogram[COLUMNS * 3 / 4];
+        let loudest = (0..BINS).max_by(|a, b| column[*a].total_cmp(&column[*b])).unwrap();
+        assert!(loudest.abs_diff(BINS / 2) <= 1);
+        assert!(plot.spectrogram[COLUMNS / 8][loudest] < 0.1);
+    }
+
+    #[test]
+    fn test_plot_images() {
+        let plot = plot();
+        assert!(plot.png().unwrap().starts_with(b"\x89PNG"));
+        assert!(plot.svg().unwrap().contains("data:image/png;base64,iVBOR"));
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b"CQ DE"), "Q1EgREU=");
+    }
+}
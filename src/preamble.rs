@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+// ---------- Preamble --------------------------------------------------------
+// A few seconds of something steady before the message: on the air, VVV
+// while the other station tunes in, or a carrier to set the level by. It
+// lets listeners set their volume and decoders lock on to the speed and
+// pitch before anything that matters is sent. `--preamble vvv:5` sends VVV
+// groups at the message's speed for about five seconds; `carrier:3` holds
+// the key down for three. A pause separates it from the message, and
+// whatever comes after the message (an answer trailer) is unaffected.
+const DEFAULT_SECONDS: f64 = 5.0;
+const MAX_SECONDS: f64 = 60.0;
+/// Between the preamble and the message
+pub const GAP: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreambleKind {
+    /// VVV groups at the message's speed
+    Vvv,
+    /// A steady key-down tone
+    Carrier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preamble {
+    pub kind: PreambleKind,
+    pub length: Duration,
+}
+
+/// "vvv", "vvv:5", "carrier:3" or "carrier:2.5s".
+pub fn parse_preamble(s: &str) -> Result<Preamble, String> {
+    let (kind, seconds) = s.split_once(':').unwrap_or((s, ""));
+    let kind = match kind.trim().to_ascii_lowercase().as_str() {
+        "vvv" => PreambleKind::Vvv,
+        "carrier" => PreambleKind::Carrier,
+        _ => return Err(format!("unknown preamble {} (expected vvv or carrier, e.g. vvv:5)", kind)),
+    };
+    let seconds = match seconds.trim().trim_end_matches('s') {
+        "" => DEFAULT_SECONDS,
+        seconds => match seconds.parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 && seconds <= MAX_SECONDS => seconds,
+            _ => return Err(format!("invalid preamble length: {} (expected up to {} seconds)", seconds, MAX_SECONDS)),
+        },
+    };
+    Ok(Preamble { kind, length: Duration::from_secs_f64(seconds) })
+}
+
+impl Preamble {
+    /// How many VVV groups fill the length, each `group` long with its word
+    /// space: at least one, and never stopping short.
+    pub fn groups(&self, group: Duration) -> usize {
+        (self.length.as_secs_f64() / group.as_secs_f64().max(1e-3)).ceil().max(1.0) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_preamble() {
+        assert_eq!(parse_preamble("vvv:5"), Ok(Preamble { kind: PreambleKind::Vvv, length: Duration::from_secs(5) }));
+        assert_eq!(parse_preamble("Carrier:2.5s"), Ok(Preamble { kind: PreambleKind::Carrier, length: Duration::from_millis(2500) }));
+        assert_eq!(parse_preamble("vvv").unwrap().length, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_invalid_preamble() {
+        assert!(parse_preamble("qrl:5").is_err());
+        assert!(parse_preamble("vvv:0").is_err());
+        assert!(parse_preamble("carrier:90").is_err());
+    }
+
+    #[test]
+    fn test_preamble_groups() {
+        // VVV at 20 WPM is 2.4 s a group with its word space
+        let preamble = parse_preamble("vvv:5").unwrap();
+        assert_eq!(preamble.groups(Duration::from_millis(2400)), 3);
+        assert_eq!(preamble.groups(Duration::from_secs(10)), 1);
+    }
+}
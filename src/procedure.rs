@@ -0,0 +1,125 @@
+use rand::seq::IndexedRandom;
+use rand::Rng;
+
+// ---------- Procedure traffic -----------------------------------------------
+// Messages in the form maritime, aeronautical and naval stations used on CW,
+// for museum ships, reenactments and anyone who wants to hear how it was
+// done: distress (SOS, and the older CQD), urgency (XXX) and safety (TTT)
+// calls from ships, aircraft working a ground station with the aeronautical
+// Q-codes, and formatted naval messages with a header, groups and an ending.
+// Procedure signals are sent as their letters.
+
+// Messages generated per practice session
+pub const COUNT: usize = 60;
+
+const COAST_STATIONS: &[&str] = &["GKA", "WCC", "NMC", "DAN", "FFL", "PCH", "SVA", "VCS", "KPH", "OXZ"];
+const SHIP_PREFIXES: &[&str] = &["G", "W", "K", "D", "F", "P", "S", "L", "O", "V"];
+const SHIPS: &[&str] = &["ARGO", "CELTIC STAR", "NORDLAND", "MARY ROSE", "ATLANTIC", "OCEAN PRIDE", "TERN", "SEAGULL"];
+const DISTRESS: &[&str] = &[
+    "SINKING", "ON FIRE", "TAKING WATER", "AGROUND", "ENGINE FAILURE DRIFTING", "COLLISION HOLED", "LISTING BADLY",
+];
+const URGENCY: &[&str] = &["MAN OVERBOARD", "MEDICAL ASSISTANCE REQUIRED", "RUDDER DAMAGED", "CREW MEMBER INJURED"];
+const SAFETY: &[&str] = &[
+    "DERELICT ADRIFT", "LIGHT EXTINGUISHED", "ICEBERG SIGHTED", "GALE WARNING", "MINE SIGHTED", "BUOY OFF STATION",
+];
+const GROUND_STATIONS: &[&str] = &["EGLL", "EGPX", "KJFK", "LFPG", "EDDF", "EINN", "CYQX"];
+const PRECEDENCE: &[&str] = &["R", "P", "O", "Z"];
+const COMMANDS: &[&str] = &["CINCLANT", "COMNAVEUR", "NAVSTA NORFOLK", "COMSUBLANT", "NAVCOMMSTA"];
+
+fn ship_call(rng: &mut impl Rng) -> String {
+    let mut call = SHIP_PREFIXES.choose(rng).unwrap().to_string();
+    for _ in 0..3 {
+        call.push(char::from(b'A' + rng.random_range(0..26)));
+    }
+    call
+}
+
+fn position(rng: &mut impl Rng) -> String {
+    format!(
+        "{:02}{:02}{} {:03}{:02}{}",
+        rng.random_range(0..70),
+        rng.random_range(0..60),
+        if rng.random_bool(0.7) { 'N' } else { 'S' },
+        rng.random_range(0..180),
+        rng.random_range(0..60),
+        if rng.random_bool(0.5) { 'W' } else { 'E' },
+    )
+}
+
+// Day of month, hours and minutes with Z: a date-time group
+fn date_time_group(rng: &mut impl Rng) -> String {
+    format!("{:02}{:02}{:02}Z", rng.random_range(1..=28), rng.random_range(0..24), rng.random_range(0..60))
+}
+
+// Five to ten groups of five random letters, as an enciphered text
+fn five_letter_groups(rng: &mut impl Rng) -> Vec<String> {
+    let count = rng.random_range(5..=10);
+    (0..count).map(|_| (0..5).map(|_| char::from(b'A' + rng.random_range(0..26))).collect()).collect()
+}
+
+/// One message of a random kind.
+pub fn message(rng: &mut impl Rng) -> String {
+    let pick = |rng: &mut _, list: &[&str]| list.choose(rng).unwrap().to_string();
+    let call = ship_call(rng);
+    match rng.random_range(0..6) {
+        0 => format!(
+            "SOS SOS SOS DE {call} {call} {call} SOS {ship} {call} POSITION {position} {nature} NEED IMMEDIATE ASSISTANCE {souls} PERSONS ON BOARD K",
+            ship = pick(rng, SHIPS),
+            position = position(rng),
+            nature = pick(rng, DISTRESS),
+            souls = rng.random_range(2..=40),
+        ),
+        1 => format!(
+            "CQD CQD CQD DE {call} {call} POSITION {position} {nature} COME AT ONCE K",
+            position = position(rng),
+            nature = pick(rng, DISTRESS),
+        ),
+        2 => format!(
+            "XXX XXX XXX CQ DE {call} {call} {call} POSITION {position} {nature} K",
+            position = position(rng),
+            nature = pick(rng, URGENCY),
+        ),
+        3 => format!(
+            "TTT TTT TTT CQ DE {coast} {coast} {coast} NAVIGATIONAL WARNING {hazard} POSITION {position} AR",
+            coast = pick(rng, COAST_STATIONS),
+            hazard = pick(rng, SAFETY),
+            position = position(rng),
+        ),
+        4 => format!(
+            "{ground} DE {call} QDM? K {call} DE {ground} QDM {heading:03} QNH {qnh} K",
+            ground = pick(rng, GROUND_STATIONS),
+            heading = rng.random_range(1..=360),
+            qnh = rng.random_range(985..=1035),
+        ),
+        _ => format!(
+            "NR {number} {precedence} {dtg} FM {from} TO {to} BT {groups} BT AR",
+            number = rng.random_range(1..=999),
+            precedence = pick(rng, PRECEDENCE),
+            dtg = date_time_group(rng),
+            from = pick(rng, COMMANDS),
+            to = pick(rng, COMMANDS),
+            groups = five_letter_groups(rng).join(" "),
+        ),
+    }
+}
+
+pub fn messages(rng: &mut impl Rng, count: usize) -> Vec<String> {
+    (0..count).map(|_| message(rng)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::morse::text_to_morse;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_procedure_messages() {
+        let messages = messages(&mut rand::rngs::StdRng::seed_from_u64(7), 200);
+        assert!(messages.iter().all(|message| text_to_morse(message).is_ok()));
+        let opened = |signal: &str| messages.iter().any(|message| message.starts_with(signal));
+        assert!(["SOS SOS SOS", "CQD", "XXX XXX XXX", "TTT TTT TTT", "NR "].into_iter().all(opened));
+        let naval = messages.iter().find(|message| message.starts_with("NR ")).unwrap();
+        assert!(naval.ends_with("BT AR"));
+    }
+}
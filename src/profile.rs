@@ -0,0 +1,507 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::exam::{Graded, PASS_MARK};
+use crate::goals::Goals;
+
+// ---------- Profiles --------------------------------------------------------
+// Everything cwgen keeps between runs lives in one directory per user:
+//
+//     ~/.config/cwgen/               the default profile
+//     ~/.config/cwgen/users/alice/   --user alice
+//
+// each holding `config.toml` (default settings) and `stats.json` (practice
+// and exam history), so people sharing a computer keep their progress apart.
+// CWGEN_HOME overrides the base directory. Besides running totals, every
+// session is logged as it ends (when, what, how fast, how much QRM and how
+// it went) so `cwgen stats` can show progress week by week.
+pub struct Profile {
+    pub user: Option<String>,
+    dir: PathBuf,
+}
+
+/// Defaults from `config.toml`; flags given on the command line win.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub wpm: Option<u32>,
+    pub tone: Option<u32>,
+    pub farnsworth: Option<u32>,
+    pub gap_ms: Option<u64>,
+    /// Output buffer and latency for the interactive keyer
+    pub buffer_size: Option<u32>,
+    pub latency_ms: Option<u64>,
+    pub goals: Goals,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    /// Per practice mode
+    pub practice: BTreeMap<String, PracticeStats>,
+    pub exams: Vec<ExamResult>,
+    /// Per local date (YYYY-MM-DD), for streaks and speed trends
+    pub days: BTreeMap<String, DayStats>,
+    /// Per character, from the answers in MISSES_FROM
+    pub characters: BTreeMap<String, CharacterStats>,
+    /// Per character sent, what else it was copied as and how often
+    pub confusions: BTreeMap<String, BTreeMap<String, u64>>,
+    /// Per character, the share missed lately: each answer moves it
+    /// RECENT_STEP of the way to 1 (missed) or 0 (copied)
+    pub recent: BTreeMap<String, f64>,
+    /// Per word, from copy quiz and copying behind answers
+    pub words: BTreeMap<String, CharacterStats>,
+    /// Every session, oldest first
+    pub attempts: Vec<Attempt>,
+}
+
+/// One practice session or exam.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attempt {
+    /// RFC 3339, in local time so sessions fall in the week they were done
+    pub date: String,
+    pub mode: String,
+    pub wpm: u32,
+    pub qrm: u8,
+    pub items: usize,
+    /// For scored modes, how many were copied
+    pub correct: Option<usize>,
+    pub seconds: f64,
+}
+
+/// A week of attempts, for `cwgen stats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeekStats {
+    /// The Monday it starts on
+    pub start: NaiveDate,
+    pub sessions: usize,
+    pub seconds: f64,
+    /// Fastest speed practiced
+    pub wpm: u32,
+    /// Heaviest QRM practiced under
+    pub qrm: u8,
+    /// Share of scored items copied, if any were scored
+    pub accuracy: Option<f64>,
+}
+
+// How many weeks `cwgen stats` shows
+const WEEKS_SHOWN: usize = 8;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CharacterStats {
+    pub sent: u64,
+    pub missed: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PracticeStats {
+    pub sessions: u32,
+    /// Items sent, repeats included
+    pub items: u64,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DayStats {
+    pub seconds: f64,
+    /// Fastest speed practiced that day
+    pub wpm: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExamResult {
+    /// RFC 3339
+    pub date: String,
+    pub wpm: u32,
+    pub score: usize,
+    pub passed: bool,
+}
+
+/// Everything in a profile as one portable file, for `cwgen stats export`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    /// RFC 3339
+    pub exported: String,
+    /// `config.toml` verbatim, comments and all
+    pub config: Option<String>,
+    pub stats: Stats,
+}
+
+const ARCHIVE_VERSION: u32 = 1;
+const RECENT_STEP: f64 = 0.1;
+/// Where missed characters are counted, for messages when there are none.
+pub const MISSES_FROM: &str = "exams, quizzes, Koch lessons, copying behind and pileups";
+
+fn base_dir() -> Result<PathBuf> {
+    if let Some(home) = std::env::var_os("CWGEN_HOME") {
+        return Ok(PathBuf::from(home));
+    }
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    match config {
+        Some(config) => Ok(config.join("cwgen")),
+        None => bail!("can't find a home directory for profiles; set CWGEN_HOME"),
+    }
+}
+
+impl Profile {
+    pub fn open(user: Option<&str>) -> Result<Profile> {
+        Profile::in_dir(&base_dir()?, user)
+    }
+
+    pub fn in_dir(base: &Path, user: Option<&str>) -> Result<Profile> {
+        let dir = match user {
+            None => base.to_path_buf(),
+            Some(user) => {
+                let valid = !user.is_empty()
+                    && user.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                if !valid {
+                    bail!("user names may only use letters, digits, - and _ (got {:?})", user);
+                }
+                base.join("users").join(user)
+            }
+        };
+        Ok(Profile { user: user.map(String::from), dir })
+    }
+
+    pub fn settings(&self) -> Result<Settings> {
+        match self.config_text()? {
+            Some(text) => toml::from_str(&text)
+                .with_context(|| format!("reading {}", self.dir.join("config.toml").display())),
+            None => Ok(Settings::default()),
+        }
+    }
+
+    fn config_text(&self) -> Result<Option<String>> {
+        let path = self.dir.join("config.toml");
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Ok(Some(text)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    pub fn stats(&self) -> Result<Stats> {
+        let path = self.dir.join("stats.json");
+        match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).with_context(|| format!("reading {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Stats::default()),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    // Written to a temporary file first, so an interrupted run can't leave
+    // half a stats file behind
+    fn save_stats(&self, stats: &Stats) -> Result<()> {
+        std::fs::create_dir_all(&self.dir).with_context(|| format!("creating {}", self.dir.display()))?;
+        let path = self.dir.join("stats.json");
+        let temp = self.dir.join("stats.json.tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(stats)?)
+            .with_context(|| format!("writing {}", temp.display()))?;
+        std::fs::rename(&temp, &path).with_context(|| format!("writing {}", path.display()))
+    }
+
+    pub fn export(&self) -> Result<Archive> {
+        Ok(Archive {
+            version: ARCHIVE_VERSION,
+            exported: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            config: self.config_text()?,
+            stats: self.stats()?,
+        })
+    }
+
+    /// Replace this profile's settings and statistics with an archive's.
+    /// Unless `force` is set, a profile that already has either is left
+    /// alone, so an import can't quietly wipe out progress.
+    pub fn import(&self, archive: &Archive, force: bool) -> Result<()> {
+        if archive.version > ARCHIVE_VERSION {
+            bail!("archive version {} is newer than this cwgen understands ({})", archive.version, ARCHIVE_VERSION);
+        }
+        if let Some(config) = &archive.config {
+            toml::from_str::<Settings>(config).context("the archive's config.toml")?;
+        }
+        let has_data = self.config_text()?.is_some() || self.stats()? != Stats::default();
+        if has_data && !force {
+            bail!("{} already has settings or statistics; use --force to replace them", self.dir.display());
+        }
+        self.save_stats(&archive.stats)?;
+        let config = self.dir.join("config.toml");
+        match &archive.config {
+            Some(text) => std::fs::write(&config, text).with_context(|| format!("writing {}", config.display())),
+            None if config.exists() => std::fs::remove_file(&config).with_context(|| format!("removing {}", config.display())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn record_exam(&self, wpm: u32, qrm: u8, seconds: f64, graded: &Graded) -> Result<()> {
+        self.update(|stats| {
+            stats.tally(&graded.characters);
+            let date = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+            stats.exams.push(ExamResult { date, wpm, score: graded.score, passed: graded.score >= PASS_MARK });
+            stats.attempts.push(Attempt::now("exam", wpm, qrm, graded.questions, Some(graded.score), seconds));
+        })
+    }
+
+    /// Change the statistics with `change` and save them once.
+    pub fn update(&self, change: impl FnOnce(&mut Stats)) -> Result<()> {
+        let mut stats = self.stats()?;
+        change(&mut stats);
+        self.save_stats(&stats)
+    }
+}
+
+impl Attempt {
+    fn now(mode: &str, wpm: u32, qrm: u8, items: usize, correct: Option<usize>, seconds: f64) -> Attempt {
+        Attempt {
+            date: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            mode: mode.to_string(),
+            wpm,
+            qrm,
+            items,
+            correct,
+            seconds,
+        }
+    }
+
+    /// The local date it was done on.
+    pub fn day(&self) -> Option<NaiveDate> {
+        chrono::DateTime::parse_from_rfc3339(&self.date).ok().map(|date| date.date_naive())
+    }
+}
+
+impl Stats {
+    /// Count a practice session. `correct` is how many of the `items` were
+    /// copied, for scored modes.
+    pub fn practiced(&mut self, mode: &str, items: usize, correct: Option<usize>, seconds: f64, wpm: u32, qrm: u8) {
+        let entry = self.practice.entry(mode.to_string()).or_default();
+        entry.sessions += 1;
+        entry.items += items as u64;
+        entry.seconds += seconds;
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let day = self.days.entry(today).or_default();
+        day.seconds += seconds;
+        day.wpm = day.wpm.max(wpm);
+        self.attempts.push(Attempt::now(mode, wpm, qrm, items, correct, seconds));
+    }
+
+    /// Count what was copied: `characters` are the characters sent and what
+    /// was copied in the place of each (see `exam::copied_as`); `words`, the
+    /// words sent and whether each was copied whole.
+    pub fn copied(&mut self, characters: &[(char, Option<char>)], words: &[(String, bool)]) {
+        self.tally(characters);
+        for (word, copied) in words {
+            let entry = self.words.entry(word.to_uppercase()).or_default();
+            entry.sent += 1;
+            entry.missed += u64::from(!copied);
+        }
+    }
+
+    fn tally(&mut self, characters: &[(char, Option<char>)]) {
+        for &(c, copied) in characters {
+            let entry = self.characters.entry(c.to_string()).or_default();
+            // Statistics from before it was kept start from the overall rate
+            let recent = self.recent.entry(c.to_string()).or_insert(entry.missed as f64 / entry.sent.max(1) as f64);
+            *recent += RECENT_STEP * (f64::from(u8::from(copied != Some(c))) - *recent);
+            entry.sent += 1;
+            entry.missed += u64::from(copied != Some(c));
+            if let Some(copied) = copied.filter(|&copied| copied != c) {
+                *self.confusions.entry(c.to_string()).or_default().entry(copied.to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    /// A few lines for `cwgen stats`.
+    pub fn summary(&self) -> String {
+        if self.practice.is_empty() && self.exams.is_empty() && self.attempts.is_empty() {
+            return "No practice recorded yet.".into();
+        }
+        let mut lines = Vec::new();
+        for (mode, stats) in &self.practice {
+            lines.push(format!(
+                "{:<14} {:>4} sessions  {:>6} items  {:>6.1} min",
+                mode, stats.sessions, stats.items, stats.seconds / 60.0,
+            ));
+        }
+        for exam in &self.exams {
+            lines.push(format!(
+                "exam {} at {} WPM: {}/10 {}",
+                &exam.date[..10.min(exam.date.len())],
+                exam.wpm,
+                exam.score,
+                if exam.passed { "PASS" } else { "FAIL" },
+            ));
+        }
+        let weeks = self.weeks();
+        if !weeks.is_empty() {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!("{:<12}{:>9}{:>9}{:>9}{:>6}{:>8}", "Week of", "sessions", "minutes", "top WPM", "QRM", "copied"));
+            for week in weeks.iter().rev().take(WEEKS_SHOWN).rev() {
+                let copied = week.accuracy.map_or("-".into(), |accuracy| format!("{:.0}%", accuracy * 100.0));
+                lines.push(format!(
+                    "{:<12}{:>9}{:>9.1}{:>9}{:>6}{:>8}",
+                    week.start.to_string(),
+                    week.sessions,
+                    week.seconds / 60.0,
+                    week.wpm,
+                    week.qrm,
+                    copied,
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// The logged attempts week by week (Monday to Sunday), oldest first.
+    pub fn weeks(&self) -> Vec<WeekStats> {
+        let mut weeks: BTreeMap<NaiveDate, (WeekStats, usize, usize)> = BTreeMap::new();
+        for attempt in &self.attempts {
+            let Some(day) = attempt.day() else { continue };
+            let start = day - chrono::Days::new(day.weekday().num_days_from_monday() as u64);
+            let (week, items, correct) = weeks.entry(start).or_insert_with(|| {
+                (WeekStats { start, sessions: 0, seconds: 0.0, wpm: 0, qrm: 0, accuracy: None }, 0, 0)
+            });
+            week.sessions += 1;
+            week.seconds += attempt.seconds;
+            week.wpm = week.wpm.max(attempt.wpm);
+            week.qrm = week.qrm.max(attempt.qrm);
+            if let Some(copied) = attempt.correct {
+                *items += attempt.items;
+                *correct += copied;
+            }
+        }
+        weeks
+            .into_values()
+            .map(|(week, items, correct)| WeekStats { accuracy: (items > 0).then(|| correct as f64 / items as f64), ..week })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_practice_tallied() {
+        let mut stats = Stats::default();
+        stats.practiced("callsigns", 20, None, 90.0, 15, 0);
+        stats.practiced("callsigns", 5, None, 30.0, 18, 3);
+        assert_eq!(stats.practice["callsigns"], PracticeStats { sessions: 2, items: 25, seconds: 120.0 });
+        assert_eq!(stats.attempts.iter().map(|attempt| (attempt.mode.as_str(), attempt.qrm)).collect::<Vec<_>>(), [("callsigns", 0), ("callsigns", 3)]);
+        assert_eq!(stats.days.values().map(|day| (day.seconds, day.wpm)).collect::<Vec<_>>(), vec![(120.0, 18)]);
+    }
+
+    #[test]
+    fn test_copy_tallied() {
+        let mut stats = Stats::default();
+        stats.copied(&[('U', Some('V')), ('U', Some('U')), ('W', Some('V')), ('W', Some('V'))], &[("uw".into(), false)]);
+        assert_eq!(stats.characters["W"], CharacterStats { sent: 2, missed: 2 });
+        assert_eq!(stats.characters["U"], CharacterStats { sent: 2, missed: 1 });
+        assert_eq!(stats.confusions["W"]["V"], 2);
+        assert_eq!(stats.confusions["U"].len(), 1);
+        assert!((stats.recent["U"] - 0.09).abs() < 1e-9);
+        assert_eq!(stats.words["UW"], CharacterStats { sent: 1, missed: 1 });
+    }
+
+    #[test]
+    fn test_exam_recorded() {
+        let base = std::env::temp_dir().join(format!("cwgen-profile-exam-{}", std::process::id()));
+        let alice = Profile::in_dir(&base, Some("alice")).unwrap();
+        alice
+            .record_exam(13, 0, 600.0, &Graded { score: 8, questions: 10, characters: vec![('W', Some('W')), ('1', None), ('W', Some('V'))] }).unwrap();
+        let stats = alice.stats().unwrap();
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(stats.exams[0].score, 8);
+        assert_eq!(stats.attempts[0].mode, "exam");
+        assert_eq!(stats.characters["W"], CharacterStats { sent: 2, missed: 1 });
+        assert!((stats.recent["1"] - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_profiles_keep_stats_apart() {
+        let base = std::env::temp_dir().join(format!("cwgen-profile-{}", std::process::id()));
+        let alice = Profile::in_dir(&base, Some("alice")).unwrap();
+        let default = Profile::in_dir(&base, None).unwrap();
+        alice.update(|stats| stats.practiced("callsigns", 20, None, 90.0, 15, 0)).unwrap();
+        std::fs::write(base.join("config.toml"), "wpm = 15\ntone = 600\n").unwrap();
+
+        let stats = alice.stats().unwrap();
+        let settings = (default.settings().unwrap(), alice.settings().unwrap());
+        let default_stats = default.stats().unwrap();
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(stats.practice["callsigns"].items, 20);
+        assert_eq!(default_stats, Stats::default());
+        assert_eq!(settings.0.wpm, Some(15));
+        assert_eq!(settings.1, Settings::default());
+    }
+
+    #[test]
+    fn test_profile_name_refused() {
+        let base = std::env::temp_dir().join(format!("cwgen-profile-name-{}", std::process::id()));
+        assert!(Profile::in_dir(&base, Some("../bob")).is_err());
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let base = std::env::temp_dir().join(format!("cwgen-archive-{}", std::process::id()));
+        let (old, new) = (Profile::in_dir(&base, Some("old")).unwrap(), Profile::in_dir(&base, Some("new")).unwrap());
+        old.update(|stats| stats.practiced("qcodes", 14, Some(12), 60.0, 20, 0)).unwrap();
+        std::fs::write(base.join("users/old/config.toml"), "# slow for now\nwpm = 12\n").unwrap();
+
+        let archive: Archive = serde_json::from_str(&serde_json::to_string(&old.export().unwrap()).unwrap()).unwrap();
+        new.import(&archive, false).unwrap();
+        let refused = new.import(&archive, false);
+        let imported = (new.stats().unwrap(), new.config_text().unwrap());
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(imported.0, archive.stats);
+        assert_eq!(imported.1.as_deref(), Some("# slow for now\nwpm = 12\n"));
+        assert!(refused.is_err());
+    }
+
+    #[test]
+    fn test_weekly_progress() {
+        let attempt = |date: &str, wpm, qrm, items, correct| Attempt {
+            date: date.into(),
+            mode: "words".into(),
+            wpm,
+            qrm,
+            items,
+            correct,
+            seconds: 600.0,
+        };
+        let stats = Stats {
+            attempts: vec![
+                // Sunday night local time is still the week of the 5th
+                attempt("2026-10-11T23:30:00-07:00", 15, 2, 20, Some(15)),
+                attempt("2026-10-06T08:00:00+01:00", 13, 0, 40, None),
+                attempt("2026-10-12T19:00:00+01:00", 18, 5, 10, Some(9)),
+            ],
+            ..Stats::default()
+        };
+        let weeks = stats.weeks();
+        let date = |s: &str| s.parse::<NaiveDate>().unwrap();
+        assert_eq!(
+            weeks,
+            vec![
+                WeekStats { start: date("2026-10-05"), sessions: 2, seconds: 1200.0, wpm: 15, qrm: 2, accuracy: Some(0.75) },
+                WeekStats { start: date("2026-10-12"), sessions: 1, seconds: 600.0, wpm: 18, qrm: 5, accuracy: Some(0.9) },
+            ]
+        );
+        let summary = stats.summary();
+        assert!(summary.contains("Week of      sessions  minutes  top WPM   QRM  copied"), "{}", summary);
+        assert!(summary.contains("2026-10-05          2     20.0       15     2     75%"), "{}", summary);
+    }
+}
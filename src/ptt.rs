@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use crate::morse::Element;
+
+// ---------- PTT -------------------------------------------------------------
+// A transmitter has to be switched to transmit before it is keyed, and a
+// relay chattering in every word gap wears out, so PTT goes down `--ptt-lead`
+// before the first element and stays down until the key has been up for
+// `--ptt-hang`. The schedule is worked out here, from the key timeline, so
+// every keying output gets the same PTT. The VOX output carries it as a tone
+// on the right channel (the keying on the left) for two-channel interfaces;
+// serial, GPIO and rigctl backends don't exist yet, and will take their PTT
+// from the same schedule.
+#[derive(clap::Args, Debug, Clone)]
+pub struct PttArgs {
+    /// With --output vox, assert PTT around each transmission, as a tone on
+    /// the right channel (the keying stays on the left)
+    #[arg(long, conflicts_with = "interactive")]
+    pub ptt: bool,
+
+    /// How long PTT is asserted before the first element, in ms
+    #[arg(long, value_name = "MS", default_value_t = 50)]
+    pub ptt_lead: u64,
+
+    /// How long PTT is held after the last element, in ms; a longer gap
+    /// between elements ends the transmission
+    #[arg(long, value_name = "MS", default_value_t = 200)]
+    pub ptt_hang: u64,
+}
+
+/// The keying and the PTT line over the same span of time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    /// The key timeline, delayed by the lead and padded out by the hang
+    pub key: Vec<Element>,
+    /// PTT asserted (`key_down`) or released
+    pub ptt: Vec<Element>,
+}
+
+impl PttArgs {
+    pub fn lead(&self) -> Duration {
+        Duration::from_millis(self.ptt_lead)
+    }
+
+    pub fn hang(&self) -> Duration {
+        Duration::from_millis(self.ptt_hang)
+    }
+}
+
+/// Wrap `key` in PTT: asserted `lead` before each transmission and released
+/// `hang` after it. A key-up too short for PTT to be released and asserted
+/// again stays within the transmission.
+pub fn schedule(key: &[Element], lead: Duration, hang: Duration) -> Schedule {
+    // Key-down spans, from the start of the (delayed) timeline
+    let mut spans: Vec<(Duration, Duration)> = Vec::new();
+    let mut at = lead;
+    for element in key {
+        if element.key_down {
+            spans.push((at, at + element.duration));
+        }
+        at += element.duration;
+    }
+    let mut transmissions: Vec<(Duration, Duration)> = Vec::new();
+    for (start, end) in spans {
+        match transmissions.last_mut() {
+            Some(last) if start.saturating_sub(lead) <= last.1 + hang => last.1 = end,
+            _ => transmissions.push((start, end)),
+        }
+    }
+    let total = transmissions.last().map_or(at, |last| at.max(last.1 + hang));
+
+    let mut ptt = Vec::new();
+    let mut released = Duration::ZERO;
+    for (start, end) in transmissions {
+        let asserted = start - lead;
+        push(&mut ptt, false, asserted - released);
+        released = end + hang;
+        push(&mut ptt, true, released - asserted);
+    }
+    push(&mut ptt, false, total - released);
+
+    let mut delayed = Vec::new();
+    push(&mut delayed, false, lead);
+    key.iter().for_each(|element| push(&mut delayed, element.key_down, element.duration));
+    push(&mut delayed, false, total - at);
+    Schedule { key: delayed, ptt }
+}
+
+// Appends, merging with a last element in the same state
+fn push(elements: &mut Vec<Element>, key_down: bool, duration: Duration) {
+    if duration.is_zero() {
+        return;
+    }
+    match elements.last_mut() {
+        Some(last) if last.key_down == key_down => last.duration += duration,
+        _ => elements.push(Element { key_down, duration }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::morse::{key_timeline, Timing};
+
+    fn total(elements: &[Element]) -> Duration {
+        elements.iter().map(|e| e.duration).sum()
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    // 20 WPM: 60 ms dots, 180 ms letter gaps, 420 ms word gaps
+    fn wrapped() -> Schedule {
+        schedule(&key_timeline("EE E", &Timing::new(20, 0)), ms(50), ms(200))
+    }
+
+    #[test]
+    fn test_ptt_schedule() {
+        let wrapped = wrapped();
+        assert_eq!(total(&wrapped.key), total(&wrapped.ptt));
+        assert_eq!(wrapped.key[0], Element { key_down: false, duration: ms(50) });
+        assert!(wrapped.ptt[0].key_down);
+        assert_eq!(*wrapped.ptt.last().unwrap(), Element { key_down: true, duration: ms(310) });
+    }
+
+    #[test]
+    fn test_ptt_held_over_letter_gaps() {
+        // The word gap (420 ms, more than the hang plus the lead) releases
+        // PTT in between
+        let asserted: Vec<Duration> = wrapped().ptt.iter().filter(|e| e.key_down).map(|e| e.duration).collect();
+        assert_eq!(asserted, vec![ms(50 + 60 + 180 + 60 + 200), ms(50 + 60 + 200)]);
+    }
+
+    #[test]
+    fn test_nothing_keyed() {
+        assert!(schedule(&key_timeline(" ", &Timing::new(20, 0)), ms(50), ms(200)).ptt.iter().all(|e| !e.key_down));
+    }
+}
@@ -0,0 +1,365 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::audio::{MorseAudio, ToneShape};
+use crate::conditions::{Conditions, ConditionsPreset};
+use crate::morse::{text_to_code, Charset, Envelope, MorseError, Timing};
+use crate::resample::resample;
+
+// ---------- Puzzle sets -----------------------------------------------------
+// Fox hunts, escape rooms and club treasure hunts want a handful of clues,
+// each a little different to copy, and a sheet saying what each one says.
+// `cwgen puzzle hunt.toml` renders every clue in the manifest to its own
+// numbered file and writes the answer key next to them:
+//
+//     title = "Field day fox hunt"
+//     [defaults]
+//     wpm = 15
+//     conditions = "typical"
+//     [[clue]]
+//     name = "start"
+//     text = "LOOK UNDER THE BENCH"
+//     [[clue]]
+//     text = "NEXT CLUE AT THE FLAGPOLE"
+//     answer = "the flagpole"
+//     wpm = 25
+//     tone = 450
+//     reversed = true
+//     hint = "Play it backwards"
+//
+// Any setting can go in [defaults] or on a clue: wpm, farnsworth, tone,
+// conditions (a preset) and qrm, qrn, qsb and jitter over it, reversed, and
+// speed, which plays the finished clue faster or slower (0.5 is half speed
+// and an octave down) for solvers to undo. What isn't set comes from the
+// command line.
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 4.0;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub title: Option<String>,
+    #[serde(default)]
+    pub defaults: Settings,
+    #[serde(rename = "clue", default)]
+    pub clues: Vec<Clue>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Settings {
+    pub wpm: Option<u32>,
+    /// Character speed, faster than `wpm`
+    pub farnsworth: Option<u32>,
+    pub tone: Option<u32>,
+    pub conditions: Option<String>,
+    pub qrm: Option<u8>,
+    pub qrn: Option<u8>,
+    pub qsb: Option<u8>,
+    pub jitter: Option<u8>,
+    pub reversed: Option<bool>,
+    pub speed: Option<f64>,
+    /// Anything else, refused on loading: settings are flattened into a
+    /// clue, where serde can't deny unknown fields itself
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Settings {
+    // These settings, with `defaults` filling the gaps
+    fn or(&self, defaults: &Settings) -> Settings {
+        Settings {
+            wpm: self.wpm.or(defaults.wpm),
+            farnsworth: self.farnsworth.or(defaults.farnsworth),
+            tone: self.tone.or(defaults.tone),
+            conditions: self.conditions.clone().or_else(|| defaults.conditions.clone()),
+            qrm: self.qrm.or(defaults.qrm),
+            qrn: self.qrn.or(defaults.qrn),
+            qsb: self.qsb.or(defaults.qsb),
+            jitter: self.jitter.or(defaults.jitter),
+            reversed: self.reversed.or(defaults.reversed),
+            speed: self.speed.or(defaults.speed),
+            unknown: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Clue {
+    pub name: Option<String>,
+    pub text: String,
+    /// What solvers should come up with, if not the text itself
+    pub answer: Option<String>,
+    pub hint: Option<String>,
+    #[serde(flatten)]
+    pub settings: Settings,
+}
+
+/// What isn't set in the manifest: the command line's settings.
+#[derive(Debug, Clone, Copy)]
+pub struct Base {
+    pub wpm: u32,
+    pub farnsworth: Option<u32>,
+    pub gap_ms: u64,
+    pub tone: u32,
+    pub conditions: Conditions,
+    pub tone_shape: ToneShape,
+    pub drift: Option<u8>,
+    pub charset: Charset,
+    pub envelope: Envelope,
+}
+
+/// A clue as rendered.
+#[derive(Debug)]
+pub struct Rendered {
+    pub file_name: String,
+    pub samples: Vec<f32>,
+    /// How it was made, for the answer key
+    pub description: String,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Manifest> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let manifest: Manifest = toml::from_str(&text).with_context(|| format!("in {}", path.display()))?;
+        if manifest.clues.is_empty() {
+            bail!("{} has no [[clue]]s", path.display());
+        }
+        let places = std::iter::once(("[defaults]".to_string(), &manifest.defaults))
+            .chain(manifest.clues.iter().enumerate().map(|(i, clue)| (format!("clue {}", i + 1), &clue.settings)));
+        for (place, settings) in places {
+            if let Some(key) = settings.unknown.keys().next() {
+                bail!("{}: {} has an unknown setting {:?}", path.display(), place, key);
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Every clue, rendered at `sample_rate`.
+    pub fn render(&self, base: &Base, sample_rate: u32) -> Result<Vec<Rendered>> {
+        self.clues
+            .iter()
+            .enumerate()
+            .map(|(i, clue)| {
+                let settings = clue.settings.or(&self.defaults);
+                let (samples, description) =
+                    render(clue, &settings, base, sample_rate).with_context(|| format!("clue {}", i + 1))?;
+                Ok(Rendered { file_name: file_name(i + 1, clue.name.as_deref()), samples, description })
+            })
+            .collect()
+    }
+
+    /// The answer key, as Markdown.
+    pub fn answer_key(&self, rendered: &[Rendered]) -> String {
+        let mut key = format!("# {} – answer key\n\n", self.title.as_deref().unwrap_or("Puzzle"));
+        key.push_str("| # | File | Answer | Sent | How it was made |\n|---|------|--------|------|-----------------|\n");
+        for (i, (clue, rendered)) in self.clues.iter().zip(rendered).enumerate() {
+            let _ = writeln!(
+                key,
+                "| {} | {} | {} | {} | {} |",
+                i + 1,
+                rendered.file_name,
+                cell(clue.answer.as_deref().unwrap_or(&clue.text)),
+                cell(&clue.text.to_uppercase()),
+                cell(&rendered.description),
+            );
+        }
+        let hints: Vec<String> = self
+            .clues
+            .iter()
+            .enumerate()
+            .filter_map(|(i, clue)| clue.hint.as_ref().map(|hint| format!("{}. {}", i + 1, hint)))
+            .collect();
+        if !hints.is_empty() {
+            let _ = write!(key, "\n## Hints\n\n{}\n", hints.join("\n"));
+        }
+        key
+    }
+}
+
+// The clue's samples and how they were made
+fn render(clue: &Clue, settings: &Settings, base: &Base, sample_rate: u32) -> Result<(Vec<f32>, String)> {
+    text_to_code(&clue.text, base.charset)?;
+    let wpm = settings.wpm.unwrap_or(base.wpm);
+    let farnsworth = settings.farnsworth.or(base.farnsworth).filter(|&speed| speed > wpm);
+    let tone = settings.tone.unwrap_or(base.tone);
+    if !(1..=100).contains(&wpm) {
+        return Err(MorseError::InvalidSpeed(wpm).into());
+    }
+    if !(100..=3000).contains(&tone) {
+        return Err(MorseError::InvalidTone(tone).into());
+    }
+    let speed = settings.speed.unwrap_or(1.0);
+    if !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+        bail!("speed must be between {} and {}, got {}", MIN_SPEED, MAX_SPEED, speed);
+    }
+    let preset = settings
+        .conditions
+        .as_deref()
+        .map(|name| ConditionsPreset::from_str(name, true).map_err(|_| anyhow::anyhow!("unknown conditions {:?} (easy, typical, rough or brutal)", name)))
+        .transpose()?;
+    let base_conditions = preset.map_or(base.conditions, ConditionsPreset::conditions);
+    let conditions = Conditions {
+        qrm: settings.qrm.unwrap_or(base_conditions.qrm),
+        qrn: settings.qrn.unwrap_or(base_conditions.qrn),
+        qsb: settings.qsb.unwrap_or(base_conditions.qsb),
+        jitter: settings.jitter.unwrap_or(base_conditions.jitter),
+        ..base_conditions
+    };
+    let timing = match farnsworth {
+        Some(char_speed) => Timing::new_farnsworth(char_speed, wpm, base.gap_ms),
+        None => Timing::new(wpm, base.gap_ms),
+    }
+    .with_charset(base.charset)
+    .with_envelope(base.envelope);
+
+    let mut samples = MorseAudio::new_with_sample_rate(sample_rate, &clue.text, timing, tone, conditions, base.tone_shape, base.drift)
+        .get_samples()
+        .to_vec();
+    let mut description = match farnsworth {
+        Some(char_speed) => format!("{} WPM ({} character speed), {} Hz", wpm, char_speed, tone),
+        None => format!("{} WPM, {} Hz", wpm, tone),
+    };
+    match (&settings.conditions, conditions == Conditions::default()) {
+        (Some(name), _) => description.push_str(&format!(", {} conditions", name.to_lowercase())),
+        (None, false) => description.push_str(&format!(", QRM {} QRN {} QSB {}", conditions.qrm, conditions.qrn, conditions.qsb)),
+        (None, true) => {}
+    }
+    if speed != 1.0 {
+        samples = change_speed(&samples, sample_rate, speed)?;
+        description.push_str(&format!(", played at {}× speed", speed));
+    }
+    if settings.reversed.unwrap_or(false) {
+        samples.reverse();
+        description.push_str(", reversed");
+    }
+    Ok((samples, description))
+}
+
+// `samples` played `speed` times as fast, pitch and all: resampled to a rate
+// (a round 100 Hz, to keep the resampler's blocks small) that plays back at
+// `sample_rate`
+fn change_speed(samples: &[f32], sample_rate: u32, speed: f64) -> Result<Vec<f32>> {
+    let rate = ((sample_rate as f64 / speed / 100.0).round() as u32 * 100).max(100);
+    resample(samples, sample_rate, rate)
+}
+
+// "03-flagpole.wav", or "03.wav" for a clue without a name
+fn file_name(number: usize, name: Option<&str>) -> String {
+    let slug: String = name
+        .unwrap_or_default()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug = slug.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-");
+    match slug.is_empty() {
+        true => format!("{:02}.wav", number),
+        false => format!("{:02}-{}.wav", number, slug),
+    }
+}
+
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> Manifest {
+        toml::from_str(
+            r#"
+            title = "Fox hunt"
+            [defaults]
+            wpm = 20
+            [[clue]]
+            name = "The Start!"
+            text = "EE"
+            [[clue]]
+            text = "EE"
+            answer = "two e's"
+            reversed = true
+            speed = 0.5
+            tone = 500
+            hint = "Backwards | slow"
+            [[clue]]
+            name = "/"
+            text = "EE"
+            reversed = true
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn base() -> Base {
+        Base {
+            wpm: 10,
+            farnsworth: None,
+            gap_ms: 0,
+            tone: 700,
+            conditions: Conditions::default(),
+            tone_shape: ToneShape::Sine,
+            drift: None,
+            charset: Charset::International,
+            envelope: Envelope::default(),
+        }
+    }
+
+    // A manifest file with `text`, loaded
+    fn load(name: &str, text: &str) -> Result<Manifest> {
+        let path = std::env::temp_dir().join(format!("cwgen-puzzle-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let manifest = Manifest::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        manifest
+    }
+
+    #[test]
+    fn test_puzzle_file_names() {
+        let rendered = manifest().render(&base(), 8000).unwrap();
+        assert_eq!(rendered[0].file_name, "01-the-start.wav");
+        assert_eq!(rendered[1].file_name, "02.wav");
+        assert_eq!(rendered[2].file_name, "03.wav");
+    }
+
+    #[test]
+    fn test_puzzle_speed_and_reversed() {
+        // Twice as long at half speed; backwards is just that, give or take
+        // the faint noise floor
+        let rendered = manifest().render(&base(), 8000).unwrap();
+        let (plain, slow, backwards) = (&rendered[0].samples, &rendered[1].samples, &rendered[2].samples);
+        assert_eq!(slow.len(), plain.len() * 2);
+        assert_eq!(backwards.len(), plain.len());
+        assert!(plain.iter().rev().zip(backwards).all(|(a, b)| (a - b).abs() < 0.05));
+    }
+
+    #[test]
+    fn test_puzzle_answer_key() {
+        let manifest = manifest();
+        let key = manifest.answer_key(&manifest.render(&base(), 8000).unwrap());
+        assert!(key.starts_with("# Fox hunt – answer key"));
+        assert!(key.contains("| 1 | 01-the-start.wav | EE | EE | 20 WPM, 700 Hz |"));
+        assert!(key.contains("| 2 | 02.wav | two e's | EE | 20 WPM, 500 Hz, played at 0.5× speed, reversed |"));
+        assert!(key.contains("2. Backwards | slow"));
+    }
+
+    #[test]
+    fn test_puzzle_bad_conditions() {
+        let bad: Manifest = toml::from_str("[[clue]]\ntext = \"EE\"\nconditions = \"awful\"").unwrap();
+        assert!(bad.render(&base(), 8000).is_err());
+    }
+
+    #[test]
+    fn test_puzzle_unknown_settings() {
+        // A misspelt setting, on a clue or in the defaults
+        let clue = load("clue", "[[clue]]\ntext = \"EE\"\nwpm = 12\ntones = 500");
+        assert!(clue.unwrap_err().to_string().contains("clue 1 has an unknown setting \"tones\""));
+        assert!(load("defaults", "[defaults]\nqrn = 2\nqrx = 1\n[[clue]]\ntext = \"EE\"").is_err());
+        let good = load("good", "[defaults]\nqrn = 2\n[[clue]]\ntext = \"EE\"\nwpm = 12").unwrap();
+        assert_eq!(good.clues[0].settings.wpm, Some(12));
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::Result;
+use rand::seq::{IndexedRandom, SliceRandom};
+use rand::Rng;
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+
+use crate::audio::{play_audio, Speaker, ToneShape};
+use crate::conditions::Conditions;
+use crate::events::{self, Event};
+use crate::morse::Timing;
+use crate::record::Recorder;
+
+// ---------- Meanings quiz ---------------------------------------------------
+// Copying QSB or TU is only half of it; the other half is knowing what they
+// mean. `--practice meanings` sends a Q-code or abbreviation from the table in
+// meanings.txt and asks for its meaning, picked from four (or typed, with
+// `--free-text`). A typed meaning is right when it has at least half of the
+// table's meaning words in it, roughly stemmed (an ending off, then the first
+// STEM letters) so "interference" passes for QRM and "slow down" for QRS.
+// Enter on its own plays the code again; q ends the quiz.
+const MEANINGS: &str = include_str!("meanings.txt");
+pub const QUESTIONS: usize = 20;
+const CHOICES: usize = 4;
+const STEM: usize = 5;
+// Words too common to count towards a typed meaning, unless a meaning has
+// nothing else
+const FILLER: &[&str] = &[
+    "a", "all", "and", "another", "are", "can", "for", "from", "is", "me", "of", "other", "send", "signals", "the",
+    "to", "will", "with", "you",
+];
+const ENDINGS: &[&str] = &["ing", "ed", "er", "es", "s", "e"];
+
+/// Every code in the table, with its meaning.
+pub fn table() -> Vec<(&'static str, &'static str)> {
+    MEANINGS.lines().filter_map(|line| line.split_once('\t')).collect()
+}
+
+// The meaning words that count, shortened to their stems
+fn stems(text: &str) -> HashSet<String> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut meaningful: Vec<&String> = words.iter().filter(|word| !FILLER.contains(&word.as_str())).collect();
+    if meaningful.is_empty() {
+        meaningful = words.iter().collect();
+    }
+    meaningful.into_iter().map(|word| stem(word)).collect()
+}
+
+fn stem(word: &str) -> String {
+    let word = ENDINGS
+        .iter()
+        .find_map(|ending| word.strip_suffix(ending).filter(|rest| rest.len() >= 3))
+        .unwrap_or(word);
+    word.chars().take(STEM).collect()
+}
+
+/// Whether a typed `answer` gives `meaning`.
+pub fn matches(answer: &str, meaning: &str) -> bool {
+    let wanted = stems(meaning);
+    let given = stems(answer);
+    let found = wanted.iter().filter(|stem| given.contains(*stem)).count();
+    !wanted.is_empty() && found * 2 >= wanted.len()
+}
+
+/// Ask `QUESTIONS` codes, recording what is played to `record` if given.
+/// Returns how many were asked, and how many right.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    rng: &mut impl Rng,
+    timing: Timing,
+    tone: u32,
+    conditions: Conditions,
+    tone_shape: ToneShape,
+    free_text: bool,
+    record: Option<&str>,
+    speaker: &Speaker,
+) -> Result<(usize, usize)> {
+    let table = table();
+    let recorder = record.map(|_| Recorder::new(crate::audio::playback_rate()));
+    let mut codes = table.clone();
+    codes.shuffle(rng);
+    // Each meaning once, for the wrong choices (TNX and TKS share one)
+    let mut meanings: Vec<&str> = table.iter().map(|(_, meaning)| *meaning).collect();
+    meanings.sort_unstable();
+    meanings.dedup();
+
+    println!(
+        "Meanings quiz – {} codes; {}. Enter alone repeats, q quits.\n",
+        QUESTIONS,
+        if free_text { "type what each means" } else { "pick what each means" },
+    );
+    let (mut asked, mut correct) = (0, 0);
+    for &(code, meaning) in codes.iter().take(QUESTIONS) {
+        let mut choices: Vec<&str> = meanings
+            .iter()
+            .filter(|other| **other != meaning)
+            .copied()
+            .collect::<Vec<_>>()
+            .choose_multiple(rng, CHOICES - 1)
+            .copied()
+            .collect();
+        choices.push(meaning);
+        choices.shuffle(rng);
+
+        println!("{:2}.", asked + 1);
+        if !free_text {
+            for (i, choice) in choices.iter().enumerate() {
+                println!("    {}) {}", i + 1, choice);
+            }
+        }
+        let answer = loop {
+            play_audio(code, timing, tone, conditions, tone_shape, None, recorder.as_ref(), speaker)?;
+            match prompt("    > ")? {
+                Some(answer) if answer.is_empty() => continue,
+                answer => break answer,
+            }
+        };
+        let Some(answer) = answer else { break };
+        let right = match free_text {
+            true => matches(&answer, meaning),
+            false => answer.parse::<usize>().ok().and_then(|n| choices.get(n.wrapping_sub(1))) == Some(&meaning),
+        };
+        asked += 1;
+        correct += right as usize;
+        println!("    {} {} – {}\n", if right { "✓" } else { "✗" }, code, meaning);
+        events::emit(Event::ItemScored { item: asked, expected: code, answer: &answer, correct: right });
+    }
+    println!("{}/{} right", correct, asked);
+    if let (Some(recorder), Some(path)) = (&recorder, record) {
+        recorder.save(path)?;
+        println!("Session recorded to: {}", path);
+    }
+    Ok((asked, correct))
+}
+
+/// Show `prompt` and read a line, trimmed; `None` for q or the end of input.
+/// The typed drills all answer this way, with Enter alone ("") left to each.
+pub fn prompt(prompt: &str) -> Result<Option<String>> {
+    print!("{}", prompt);
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    if std::io::stdin().lock().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    match line.trim() {
+        "q" | "Q" => Ok(None),
+        line => Ok(Some(line.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meanings_table() {
+        let table = table();
+        assert!(table.len() > QUESTIONS);
+        assert!(table.contains(&("QRM", "Interference from other stations")));
+        let codes: HashSet<&str> = table.iter().map(|(code, _)| *code).collect();
+        assert_eq!(codes.len(), table.len());
+        assert!(table.iter().all(|(code, _)| crate::morse::text_to_morse(code).is_ok()));
+    }
+
+    #[test]
+    fn test_free_text_matches() {
+        assert!(matches("interference", "Interference from other stations"));
+        assert!(matches("the signals fade", "Signals are fading"));
+        assert!(matches("send slower please", "Send slower"));
+        assert!(matches("slow down", "Send slower"));
+        assert!(matches("signal", "Signal"));
+    }
+
+    #[test]
+    fn test_free_text_misses() {
+        assert!(!matches("send faster", "Send slower"));
+        assert!(!matches("", "Location"));
+        assert!(!matches("fading", "Strength of signals"));
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::Result;
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::audio::write_wav;
+
+// ---------- Session recorder -----------------------------------------------
+// Captures everything a session actually plays. Each played source is wrapped
+// in a `Mixdown`, which mixes its samples into a shared timeline at the
+// wall-clock position where playback of that source began. Overlapping
+// sources (the practice noise bed under each word) therefore land on top of
+// each other, and pauses between keystrokes are preserved as silence. Stereo
+// sources are mixed down to the one channel. A mixdown gathers a chunk of its
+// own before taking the timeline's lock, rather than locking for every
+// sample. The replay history (see replay.rs) is fed the same way.
+const CHUNK: usize = 1024;
+
+/// One channel that played sources are mixed onto.
+pub trait Timeline {
+    /// The position of the present on the timeline
+    fn now(&self) -> usize;
+    /// Add `samples` to what is there from `cursor` on.
+    fn mix(&mut self, cursor: usize, samples: &[f32]);
+}
+
+#[derive(Clone)]
+pub struct Recorder {
+    inner: Arc<Mutex<Recording>>,
+}
+
+struct Recording {
+    start: Instant,
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+impl Timeline for Recording {
+    fn now(&self) -> usize {
+        (self.start.elapsed().as_secs_f64() * self.sample_rate as f64) as usize
+    }
+
+    fn mix(&mut self, cursor: usize, samples: &[f32]) {
+        let end = cursor + samples.len();
+        if self.samples.len() < end {
+            self.samples.resize(end, 0.0);
+        }
+        for (mixed, sample) in self.samples[cursor..end].iter_mut().zip(samples) {
+            *mixed += sample;
+        }
+    }
+}
+
+impl Recorder {
+    pub fn new(sample_rate: u32) -> Self {
+        Recorder {
+            inner: Arc::new(Mutex::new(Recording {
+                start: Instant::now(),
+                sample_rate,
+                samples: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn tap<S: Source<Item = f32>>(&self, source: S) -> Mixdown<S, impl Timeline> {
+        Mixdown::new(source, Some(self.inner.clone()))
+    }
+
+    pub fn save(&self, filename: &str) -> Result<()> {
+        let recording = self.inner.lock().unwrap();
+        write_wav(filename, recording.sample_rate, &recording.samples)
+    }
+}
+
+/// `source` as it plays, also mixed onto a timeline if there is one.
+pub struct Mixdown<S, T: Timeline> {
+    source: S,
+    timeline: Option<Arc<Mutex<T>>>,
+    cursor: Option<usize>,
+    channel: u16,
+    // Frames mixed to one channel, not yet on the timeline
+    pending: Vec<f32>,
+}
+
+impl<S, T: Timeline> Mixdown<S, T> {
+    pub fn new(source: S, timeline: Option<Arc<Mutex<T>>>) -> Self {
+        Mixdown { source, timeline, cursor: None, channel: 0, pending: Vec::with_capacity(CHUNK) }
+    }
+
+    fn flush(&mut self) {
+        let (Some(timeline), Some(cursor)) = (&self.timeline, self.cursor) else { return };
+        if self.pending.is_empty() {
+            return;
+        }
+        timeline.lock().unwrap().mix(cursor, &self.pending);
+        self.cursor = Some(cursor + self.pending.len());
+        self.pending.clear();
+    }
+}
+
+impl<S: Source<Item = f32>, T: Timeline> Iterator for Mixdown<S, T> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let Some(sample) = self.source.next() else {
+            self.flush();
+            return None;
+        };
+        let Some(timeline) = &self.timeline else { return Some(sample) };
+        if self.cursor.is_none() {
+            self.cursor = Some(timeline.lock().unwrap().now());
+        }
+        let channels = self.source.channels().max(1);
+        if self.channel == 0 {
+            self.pending.push(0.0);
+        }
+        if let Some(frame) = self.pending.last_mut() {
+            *frame += sample / channels as f32;
+        }
+        self.channel = (self.channel + 1) % channels;
+        if self.channel == 0 && self.pending.len() >= CHUNK {
+            self.flush();
+        }
+        Some(sample)
+    }
+}
+
+impl<S, T: Timeline> Drop for Mixdown<S, T> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl<S: Source<Item = f32>, T: Timeline> Source for Mixdown<S, T> {
+    fn current_frame_len(&self) -> Option<usize> { self.source.current_frame_len() }
+    fn channels(&self) -> u16 { self.source.channels() }
+    fn sample_rate(&self) -> u32 { self.source.sample_rate() }
+    fn total_duration(&self) -> Option<Duration> { self.source.total_duration() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn test_tap_mixes_down_in_chunks() {
+        // Three chunks and a bit of stereo, played the moment recording
+        // starts: all of it lands on the timeline, one channel, once played
+        let recorder = Recorder::new(8000);
+        let frames = 3 * CHUNK + 100;
+        let stereo: Vec<f32> = (0..frames).flat_map(|_| [0.2, 0.4]).collect();
+        let tap = recorder.tap(SamplesBuffer::new(2, 8000, stereo));
+        assert_eq!(tap.count(), 2 * frames);
+        let recording = recorder.inner.lock().unwrap();
+        let start = recording.samples.len() - frames;
+        assert!(start < 80, "{}", start);
+        assert!(recording.samples[start..].iter().all(|s| (s - 0.3).abs() < 1e-6));
+    }
+}
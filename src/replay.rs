@@ -0,0 +1,240 @@
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use rodio::buffer::SamplesBuffer;
+use rodio::{OutputStreamHandle, Sink, Source};
+use std::collections::VecDeque;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::audio::Speaker;
+use crate::eq::equalize;
+use crate::morse::MorseError;
+use crate::record::{Mixdown, Timeline};
+
+// ---------- Instant replay --------------------------------------------------
+// "Wait, what was that word?" With `--replay SECONDS`, everything played is
+// also kept in a rolling history of the last SECONDS, mixed to one channel
+// the way the session recorder mixes (see record.rs), and Tab replays it:
+// what was playing pauses, the history plays, and it carries on where it
+// left off. Time spent replaying is held out of the history's clock, so
+// pressing Tab again goes back over the same stretch rather than the
+// replay's own silence, and out of anything following playback by the
+// clock (captions, events).
+pub const KEY: KeyCode = KeyCode::Tab;
+const KEY_POLL: Duration = Duration::from_millis(20);
+
+/// The rolling history: one channel on a timeline that stops while a replay
+/// plays. Made once for the run and handed to whatever plays along with the
+/// listening EQ (see audio.rs `Speaker`); clones share the one history.
+#[derive(Clone)]
+pub struct History {
+    inner: Arc<Mutex<Ring>>,
+    // Set by Ctrl+C, so anything following playback by the clock stops too
+    interrupted: Arc<AtomicBool>,
+}
+
+impl std::fmt::Debug for History {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("History").finish_non_exhaustive()
+    }
+}
+
+struct Ring {
+    start: Instant,
+    sample_rate: u32,
+    length: usize,
+    /// Time spent replaying so far
+    held: Duration,
+    /// Where on the timeline `samples` starts
+    first: usize,
+    samples: VecDeque<f32>,
+}
+
+impl History {
+    pub fn new(sample_rate: u32, length: Duration) -> History {
+        History {
+            inner: Arc::new(Mutex::new(Ring {
+                start: Instant::now(),
+                sample_rate,
+                length: (length.as_secs_f64() * sample_rate as f64) as usize,
+                held: Duration::ZERO,
+                first: 0,
+                samples: VecDeque::new(),
+            })),
+            interrupted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// The history up to now, at most its length.
+    pub fn last(&self) -> Vec<f32> {
+        let ring = self.inner.lock().unwrap();
+        let end = ring.now().saturating_sub(ring.first).min(ring.samples.len());
+        let start = end.saturating_sub(ring.length);
+        ring.samples.range(start..end).copied().collect()
+    }
+
+    fn hold(&self, held: Duration) {
+        self.inner.lock().unwrap().held += held;
+    }
+
+    fn held(&self) -> Duration {
+        self.inner.lock().unwrap().held
+    }
+}
+
+impl Timeline for Ring {
+    fn now(&self) -> usize {
+        (self.start.elapsed().saturating_sub(self.held).as_secs_f64() * self.sample_rate as f64) as usize
+    }
+
+    fn mix(&mut self, cursor: usize, samples: &[f32]) {
+        // Anything before the start of the ring is already past replaying
+        let skip = self.first.saturating_sub(cursor).min(samples.len());
+        let (cursor, samples) = (cursor + skip, &samples[skip..]);
+        if samples.is_empty() {
+            return;
+        }
+        let (from, to) = (cursor - self.first, cursor - self.first + samples.len());
+        if self.samples.len() < to {
+            self.samples.resize(to, 0.0);
+        }
+        for (mixed, sample) in self.samples.range_mut(from..to).zip(samples) {
+            *mixed += sample;
+        }
+        // What is being written is at or a little ahead of the present (as
+        // far as rodio reads ahead), so a length and a second behind it is
+        // further back than a replay reaches
+        let keep_from = (cursor + samples.len() - 1).saturating_sub(self.length + self.sample_rate as usize);
+        if keep_from > self.first {
+            self.samples.drain(..(keep_from - self.first).min(self.samples.len()));
+            self.first = keep_from;
+        }
+    }
+}
+
+/// `source`, also kept in `history` if there is one.
+pub fn keep<S: Source<Item = f32>>(source: S, history: Option<&History>) -> Mixdown<S, impl Timeline> {
+    Mixdown::new(source, history.map(|history| history.inner.clone()))
+}
+
+/// Pause `playing`, play `speaker`'s history through it, then carry on.
+pub fn replay(handle: &OutputStreamHandle, playing: &[&Sink], speaker: &Speaker) -> Result<()> {
+    let Some(history) = &speaker.history else { return Ok(()) };
+    let samples = history.last();
+    if samples.is_empty() {
+        return Ok(());
+    }
+    let held = Instant::now();
+    for sink in playing {
+        sink.pause();
+    }
+    let sink = Sink::try_new(handle).map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+    sink.append(equalize(SamplesBuffer::new(1, history.inner.lock().unwrap().sample_rate, samples), &speaker.eq));
+    sink.sleep_until_end();
+    for sink in playing {
+        sink.play();
+    }
+    history.hold(held.elapsed());
+    Ok(())
+}
+
+/// Wait for the first of `sinks` to finish, replaying `speaker`'s history
+/// whenever Tab is pressed. In a session that already reads keys, the first other key ends
+/// the listening and is handed back for the session to act on; otherwise
+/// the terminal is put in raw mode only for the wait, other keys are
+/// ignored and Ctrl+C still quits.
+pub fn wait(handle: &OutputStreamHandle, sinks: &[&Sink], speaker: &Speaker) -> Result<Option<Event>> {
+    let Some(until) = sinks.first() else { return Ok(None) };
+    let Some(history) = speaker.history.as_ref().filter(|_| std::io::stdin().is_terminal()) else {
+        until.sleep_until_end();
+        return Ok(None);
+    };
+    let session = terminal::is_raw_mode_enabled()?;
+    if !session {
+        terminal::enable_raw_mode()?;
+    }
+    let result = (|| {
+        while !until.empty() {
+            if !event::poll(KEY_POLL)? {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Release => {}
+                Event::Key(key) if key.code == KEY => replay(handle, sinks, speaker)?,
+                Event::Key(key) if !session && key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    history.interrupted.store(true, Ordering::Relaxed);
+                    sinks.iter().for_each(|sink| sink.stop());
+                    return Err(MorseError::Interrupted.into());
+                }
+                Event::Key(key) if session => {
+                    until.sleep_until_end();
+                    return Ok(Some(Event::Key(key)));
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    })();
+    if !session {
+        terminal::disable_raw_mode()?;
+    }
+    result
+}
+
+/// Follows playback by the clock, with the time spent replaying `history`
+/// since it was made held out.
+pub struct Clock {
+    history: Option<History>,
+    base: Duration,
+}
+
+impl Clock {
+    pub fn start(history: Option<&History>) -> Clock {
+        Clock { history: history.cloned(), base: history.map_or(Duration::ZERO, History::held) }
+    }
+
+    /// Sleep until `at`, later by any replays meanwhile. False if playback
+    /// was interrupted instead.
+    pub fn sleep_until(&self, at: Instant) -> bool {
+        loop {
+            if self.history.as_ref().is_some_and(|history| history.interrupted.load(Ordering::Relaxed)) {
+                return false;
+            }
+            let held = self.history.as_ref().map_or(Duration::ZERO, History::held) - self.base;
+            let wait = (at + held).saturating_duration_since(Instant::now());
+            if wait.is_zero() {
+                return true;
+            }
+            std::thread::sleep(wait.min(KEY_POLL * 5));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_rolls() {
+        // A second's history at 1 kHz, 2.5 s in, with a little written
+        // ahead of the present
+        let history = History::new(1000, Duration::from_secs(1));
+        let mut ring = history.inner.lock().unwrap();
+        ring.start -= Duration::from_millis(2500);
+        for i in 0..2600 {
+            ring.mix(i, &[i as f32]);
+        }
+        assert_eq!((ring.first, ring.samples.len()), (599, 2001));
+        drop(ring);
+        let last = history.last();
+        assert_eq!(last.len(), 1000);
+        assert!((last[0] - 1500.0).abs() < 10.0 && (last[999] - 2499.0).abs() < 10.0, "{} {}", last[0], last[999]);
+
+        // A replay's time is held out of the clock
+        history.hold(Duration::from_millis(500));
+        assert!((history.last()[999] - 1999.0).abs() < 10.0);
+    }
+}
@@ -0,0 +1,334 @@
+use clap::ValueEnum;
+use std::fmt::Write as _;
+
+use crate::markup::escape;
+use crate::profile::{CharacterStats, Stats, MISSES_FROM};
+
+// ---------- Statistics reports ----------------------------------------------
+// `cwgen stats report` turns a profile's statistics into something to send an
+// instructor: a single HTML file with inline SVG charts (no scripts, nothing
+// fetched), or CSV for a spreadsheet. `cwgen stats characters` is the
+// character part of it on the terminal: which are missed most, and what
+// each is mistaken for.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    Html,
+    Csv,
+}
+
+/// Which table `--format csv` writes.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ReportTable {
+    /// Minutes and fastest speed per day
+    Days,
+    /// Exam date, speed and score
+    Exams,
+    /// Sent and missed counts per character
+    Characters,
+    /// Every session: when, mode, speed, QRM and how it went
+    Sessions,
+    /// How often each character was copied as each other one
+    Confusions,
+}
+
+pub fn csv(stats: &Stats, table: ReportTable) -> String {
+    let mut csv = String::new();
+    match table {
+        ReportTable::Days => {
+            csv.push_str("date,minutes,wpm\n");
+            for (date, day) in &stats.days {
+                let _ = writeln!(csv, "{},{:.1},{}", date, day.seconds / 60.0, day.wpm);
+            }
+        }
+        ReportTable::Exams => {
+            csv.push_str("date,wpm,score,passed\n");
+            for exam in &stats.exams {
+                let _ = writeln!(csv, "{},{},{},{}", exam.date, exam.wpm, exam.score, exam.passed);
+            }
+        }
+        ReportTable::Characters => {
+            csv.push_str("character,sent,missed,error_rate\n");
+            for (c, counts) in &stats.characters {
+                let _ = writeln!(csv, "{},{},{},{:.3}", field(c), counts.sent, counts.missed, error_rate(counts.sent, counts.missed));
+            }
+        }
+        ReportTable::Confusions => {
+            csv.push_str("sent,copied_as,count\n");
+            for (sent, copied, count) in confusions(stats) {
+                let _ = writeln!(csv, "{},{},{}", field(&sent), field(&copied), count);
+            }
+        }
+        ReportTable::Sessions => {
+            csv.push_str("date,mode,wpm,qrm,items,correct,minutes\n");
+            for attempt in &stats.attempts {
+                let correct = attempt.correct.map_or(String::new(), |correct| correct.to_string());
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{:.1}",
+                    attempt.date, attempt.mode, attempt.wpm, attempt.qrm, attempt.items, correct, attempt.seconds / 60.0,
+                );
+            }
+        }
+    }
+    csv
+}
+
+// A character as a CSV field: quoted if it is a quote or a comma
+fn field(c: &str) -> String {
+    if c == "\"" || c == "," { format!("\"{}\"", c.replace('"', "\"\"")) } else { c.to_string() }
+}
+
+fn error_rate(sent: u64, missed: u64) -> f64 {
+    missed as f64 / sent.max(1) as f64
+}
+
+/// Each character sent, what it was copied as instead and how often, the
+/// most frequent first.
+pub fn confusions(stats: &Stats) -> Vec<(String, String, u64)> {
+    let mut confusions: Vec<(String, String, u64)> = stats
+        .confusions
+        .iter()
+        .flat_map(|(sent, copied)| copied.iter().map(move |(copied, &count)| (sent.clone(), copied.clone(), count)))
+        .collect();
+    confusions.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)).then(a.1.cmp(&b.1)));
+    confusions
+}
+
+/// The characters missed, the worst first, each with what it was copied
+/// as, for `cwgen stats characters`.
+pub fn characters(stats: &Stats) -> String {
+    let mut missed: Vec<(&String, &CharacterStats)> = stats.characters.iter().filter(|(_, counts)| counts.missed > 0).collect();
+    if missed.is_empty() {
+        return format!("No missed characters recorded yet (they come from {}).\n", MISSES_FROM);
+    }
+    missed.sort_by(|a, b| {
+        error_rate(b.1.sent, b.1.missed).total_cmp(&error_rate(a.1.sent, a.1.missed)).then(a.0.cmp(b.0))
+    });
+    let confusions = confusions(stats);
+    let mut text = format!("{:<6}{:>6}{:>8}{:>7}  copied as\n", "", "sent", "missed", "rate");
+    for (c, counts) in missed {
+        let copied_as: Vec<String> = confusions
+            .iter()
+            .filter(|(sent, _, _)| sent == c)
+            .map(|(_, copied, count)| format!("{} ×{}", copied, count))
+            .collect();
+        let line = format!(
+            "{:<6}{:>6}{:>8}{:>6.0}%  {}",
+            c,
+            counts.sent,
+            counts.missed,
+            100.0 * error_rate(counts.sent, counts.missed),
+            copied_as.join(", "),
+        );
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+// ---------- HTML ------------------------------------------------------------
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 220.0;
+const MARGIN: f64 = 40.0;
+
+pub fn html(stats: &Stats, user: &str) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\n");
+    let _ = writeln!(html, "<title>cwgen progress – {}</title>", escape(user));
+    html.push_str(
+        "<style>body{font-family:sans-serif;max-width:700px;margin:2em auto;color:#222}\
+         svg{background:#fafafa;border:1px solid #ddd}td,th{padding:2px 10px;text-align:right}\
+         .axis{stroke:#999}.label{font-size:11px;fill:#555}</style>\n</head><body>\n",
+    );
+    let _ = writeln!(html, "<h1>CW progress – {}</h1>", escape(user));
+
+    html.push_str("<h2>Speed practiced</h2>\n");
+    let speeds: Vec<(String, f64)> = stats.days.iter().map(|(date, day)| (date.clone(), day.wpm as f64)).collect();
+    html.push_str(&chart(&speeds, "WPM", true));
+
+    html.push_str("<h2>Exam accuracy</h2>\n");
+    let exams: Vec<(String, f64)> = stats
+        .exams
+        .iter()
+        .map(|exam| (format!("{} @ {} WPM", &exam.date[..10.min(exam.date.len())], exam.wpm), exam.score as f64 * 10.0))
+        .collect();
+    html.push_str(&chart(&exams, "% right", false));
+
+    html.push_str("<h2>Errors by character</h2>\n");
+    let mut characters: Vec<(String, f64)> = stats
+        .characters
+        .iter()
+        .map(|(c, counts)| (c.clone(), 100.0 * error_rate(counts.sent, counts.missed)))
+        .collect();
+    characters.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    html.push_str(&bars(&characters));
+
+    html.push_str("<h2>Characters confused</h2>\n");
+    html.push_str(&matrix(stats));
+
+    html.push_str("<h2>Practice</h2>\n<table><tr><th>Mode</th><th>Sessions</th><th>Items</th><th>Minutes</th></tr>\n");
+    for (mode, practice) in &stats.practice {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.0}</td></tr>",
+            escape(mode), practice.sessions, practice.items, practice.seconds / 60.0,
+        );
+    }
+    html.push_str("</table>\n</body></html>\n");
+    html
+}
+
+fn svg_open(height: f64) -> String {
+    format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n", WIDTH, height)
+}
+
+// Points (or a line, with `line`) over evenly spaced labels, with the y
+// axis from 0 to the largest value
+fn chart(points: &[(String, f64)], unit: &str, line: bool) -> String {
+    if points.is_empty() {
+        return "<p>Nothing recorded yet.</p>\n".into();
+    }
+    let top = points.iter().map(|p| p.1).fold(1.0, f64::max);
+    let step = (WIDTH - 2.0 * MARGIN) / (points.len().max(2) - 1) as f64;
+    let xy = |i: usize, value: f64| (MARGIN + i as f64 * step, HEIGHT - MARGIN - value / top * (HEIGHT - 2.0 * MARGIN));
+
+    let mut svg = svg_open(HEIGHT);
+    let _ = writeln!(
+        svg,
+        "<line class=\"axis\" x1=\"{m}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\"/><line class=\"axis\" x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{b}\"/>",
+        m = MARGIN, b = HEIGHT - MARGIN, r = WIDTH - MARGIN,
+    );
+    let _ = writeln!(svg, "<text class=\"label\" x=\"4\" y=\"{}\">{:.0} {}</text>", MARGIN - 6.0, top, escape(unit));
+    if line {
+        let path: Vec<String> = points.iter().enumerate().map(|(i, p)| {
+            let (x, y) = xy(i, p.1);
+            format!("{:.1},{:.1}", x, y)
+        }).collect();
+        let _ = writeln!(svg, "<polyline fill=\"none\" stroke=\"#2a6fdb\" stroke-width=\"2\" points=\"{}\"/>", path.join(" "));
+    }
+    for (i, (label, value)) in points.iter().enumerate() {
+        let (x, y) = xy(i, *value);
+        let _ = writeln!(
+            svg,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"#2a6fdb\"><title>{}: {:.0}</title></circle>",
+            x, y, escape(label), value,
+        );
+    }
+    // First and last labels, so the time span is clear
+    let (first, last) = (&points[0].0, &points[points.len() - 1].0);
+    let _ = writeln!(svg, "<text class=\"label\" x=\"{}\" y=\"{}\">{}</text>", MARGIN, HEIGHT - MARGIN + 16.0, escape(first));
+    if points.len() > 1 {
+        let _ = writeln!(
+            svg,
+            "<text class=\"label\" x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+            WIDTH - MARGIN, HEIGHT - MARGIN + 16.0, escape(last),
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Horizontal bars of percentages, one row per label
+fn bars(rows: &[(String, f64)]) -> String {
+    if rows.is_empty() {
+        return format!("<p>Nothing recorded yet (character errors come from {}).</p>\n", MISSES_FROM);
+    }
+    let row_height = 16.0;
+    let mut svg = svg_open(rows.len() as f64 * row_height + 10.0);
+    for (i, (label, percent)) in rows.iter().enumerate() {
+        let y = 5.0 + i as f64 * row_height;
+        let width = percent / 100.0 * (WIDTH - 2.0 * MARGIN - 50.0);
+        let _ = writeln!(
+            svg,
+            "<text class=\"label\" x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\
+             <rect x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"#db5a2a\"/>\
+             <text class=\"label\" x=\"{:.1}\" y=\"{}\">{:.0}%</text>",
+            MARGIN - 8.0, y + 11.0, escape(label),
+            MARGIN, y + 2.0, width, row_height - 4.0,
+            MARGIN + width + 4.0, y + 11.0, percent,
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Rows for the characters sent, columns for what they were copied as
+// instead, with the counts between
+fn matrix(stats: &Stats) -> String {
+    if stats.confusions.is_empty() {
+        return "<p>Nothing recorded yet.</p>\n".into();
+    }
+    let mut columns: Vec<&String> = stats.confusions.values().flat_map(|copied| copied.keys()).collect();
+    columns.sort();
+    columns.dedup();
+    let mut table = String::from("<table><tr><th>Sent \\ copied</th>");
+    for column in &columns {
+        let _ = write!(table, "<th>{}</th>", escape(column));
+    }
+    table.push_str("</tr>\n");
+    for (sent, copied) in &stats.confusions {
+        let _ = write!(table, "<tr><th>{}</th>", escape(sent));
+        for column in &columns {
+            let count = copied.get(*column).map_or(String::new(), u64::to_string);
+            let _ = write!(table, "<td>{}</td>", count);
+        }
+        table.push_str("</tr>\n");
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::{Attempt, DayStats, ExamResult};
+
+    fn stats() -> Stats {
+        let mut stats = Stats::default();
+        stats.days.insert("2026-10-01".into(), DayStats { seconds: 900.0, wpm: 13 });
+        stats.days.insert("2026-10-02".into(), DayStats { seconds: 600.0, wpm: 15 });
+        stats.exams.push(ExamResult { date: "2026-10-02T18:00:00Z".into(), wpm: 13, score: 8, passed: true });
+        stats.characters.insert("Q".into(), CharacterStats { sent: 4, missed: 1 });
+        stats.characters.insert(",".into(), CharacterStats { sent: 2, missed: 2 });
+        stats.confusions.insert("U".into(), [("V".into(), 3), ("A".into(), 1)].into());
+        stats.confusions.insert("Q".into(), [("Y".into(), 1)].into());
+        stats.attempts.push(Attempt {
+            date: "2026-10-02T18:00:00+01:00".into(),
+            mode: "koch 5".into(),
+            wpm: 15,
+            qrm: 2,
+            items: 20,
+            correct: Some(17),
+            seconds: 300.0,
+        });
+        stats
+    }
+
+    #[test]
+    fn test_csv() {
+        let stats = stats();
+        assert_eq!(csv(&stats, ReportTable::Days), "date,minutes,wpm\n2026-10-01,15.0,13\n2026-10-02,10.0,15\n");
+        assert_eq!(csv(&stats, ReportTable::Characters), "character,sent,missed,error_rate\n\",\",2,2,1.000\nQ,4,1,0.250\n");
+        assert_eq!(csv(&stats, ReportTable::Confusions), "sent,copied_as,count\nU,V,3\nQ,Y,1\nU,A,1\n");
+        assert_eq!(csv(&stats, ReportTable::Sessions), "date,mode,wpm,qrm,items,correct,minutes\n2026-10-02T18:00:00+01:00,koch 5,15,2,20,17,5.0\n");
+    }
+
+    #[test]
+    fn test_html_report() {
+        let html = html(&stats(), "<alice>");
+        assert!(html.contains("CW progress – &lt;alice&gt;"));
+        assert!(html.contains("<polyline"));
+        assert!(html.contains("2026-10-02 @ 13 WPM: 80"));
+        // Worst character first
+        assert!(html.find(">100%<").unwrap() < html.find(">25%<").unwrap());
+        assert!(!html.contains("<script"));
+        assert!(html.contains("<tr><th>U</th><td>1</td><td>3</td><td></td></tr>"), "{}", html);
+    }
+
+    #[test]
+    fn test_characters_report() {
+        let text = characters(&stats());
+        assert!(text.starts_with("        sent  missed   rate  copied as\n,          2       2   100%\n"), "{}", text);
+        assert!(text.contains("Q          4       1    25%  Y ×1\n"), "{}", text);
+    }
+}
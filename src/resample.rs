@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use rubato::{FftFixedInOut, Resampler};
+
+// Input frames per FFT pass; rubato rounds this to suit the rate ratio
+const CHUNK: usize = 1024;
+
+// ---------- Sample-rate conversion ------------------------------------------
+/// Convert mono `samples` from `from` Hz to `to` Hz. The result is trimmed to
+/// the exact converted length, with the resampler's delay removed, so it
+/// lines up with the input sample for sample.
+pub fn resample(samples: &[f32], from: u32, to: u32) -> Result<Vec<f32>> {
+    if from == to || samples.is_empty() {
+        return Ok(samples.to_vec());
+    }
+    let mut resampler = FftFixedInOut::<f32>::new(from as usize, to as usize, CHUNK, 1)
+        .with_context(|| format!("cannot resample {} Hz to {} Hz", from, to))?;
+    let delay = resampler.output_delay();
+    let length = (samples.len() as f64 * to as f64 / from as f64).round() as usize;
+
+    let mut out = Vec::with_capacity(length + delay + CHUNK);
+    let mut position = 0;
+    while position + resampler.input_frames_next() <= samples.len() {
+        let next = position + resampler.input_frames_next();
+        out.extend(&resampler.process(&[&samples[position..next]], None)?[0]);
+        position = next;
+    }
+    if position < samples.len() {
+        out.extend(&resampler.process_partial(Some(&[&samples[position..]]), None)?[0]);
+    }
+    // Push the delayed tail out of the filter with silence
+    while out.len() < length + delay {
+        let silence = vec![0.0; resampler.input_frames_next()];
+        out.extend(&resampler.process(&[silence], None)?[0]);
+    }
+    out.drain(..delay);
+    out.truncate(length);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resample_keeps_tone_and_timing() {
+        // 0.5 s of 700 Hz at 44.1 kHz, then silence
+        let tone: Vec<f32> = (0..44100)
+            .map(|i| if i < 22050 { (std::f64::consts::TAU * 700.0 * i as f64 / 44100.0).sin() as f32 } else { 0.0 })
+            .collect();
+        let out = resample(&tone, 44100, 8000).unwrap();
+        assert_eq!(out.len(), 8000);
+        // Still a full-scale 700 Hz sine where the tone was...
+        let expected = |i: usize| (std::f64::consts::TAU * 700.0 * i as f64 / 8000.0).sin() as f32;
+        assert!((500..3500).all(|i| (out[i] - expected(i)).abs() < 0.1));
+        // ...and silence where it wasn't
+        assert!(out[4500..].iter().all(|s| s.abs() < 0.02));
+    }
+
+    #[test]
+    fn test_resample_whole_chunks() {
+        // Input that ends exactly on a chunk boundary
+        assert_eq!(resample(&[0.5; 2048], 8000, 16000).unwrap().len(), 4096);
+    }
+}
@@ -0,0 +1,110 @@
+use clap::ValueEnum;
+use std::time::Duration;
+
+use crate::audio::ToneShape;
+use crate::morse::{Envelope, EnvelopeShape};
+
+// ---------- Rig sidetones ---------------------------------------------------
+// `--rig` makes practice audio sound like a particular kind of radio by
+// setting the knobs that shape the note together: tone shape, keying
+// envelope and edge times, click, hum and chirp (`--drift`). Each is only a
+// starting point: any of those options given on the command line wins over
+// the preset.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Rig {
+    /// Elecraft K3: soft, evenly shaped keying
+    K3,
+    /// Icom IC-7300: raised-cosine edges at its 4 ms default rise time
+    #[value(name = "ic-7300")]
+    Ic7300,
+    /// A simple QRP kit: quick edges with a little click and chirp
+    QrpKit,
+    /// A vintage tube rig: slow, rounded edges, hum on the note and chirp
+    VintageTube,
+}
+
+/// The knobs a rig sets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sound {
+    pub tone_shape: ToneShape,
+    pub envelope: Envelope,
+    /// As for `--drift`: the share of the tone a long element sags to
+    pub drift: Option<u8>,
+}
+
+impl Rig {
+    pub fn sound(self) -> Sound {
+        let envelope = |shape, rise: u64, fall: u64, click, hum| Envelope {
+            shape,
+            rise: Some(Duration::from_millis(rise)),
+            fall: Some(Duration::from_millis(fall)),
+            click,
+            hum,
+        };
+        match self {
+            Rig::K3 => Sound {
+                tone_shape: ToneShape::Sine,
+                envelope: envelope(EnvelopeShape::Gaussian, 5, 5, 0.0, 0.0),
+                drift: None,
+            },
+            Rig::Ic7300 => Sound {
+                tone_shape: ToneShape::Sine,
+                envelope: envelope(EnvelopeShape::RaisedCosine, 4, 4, 0.0, 0.0),
+                drift: None,
+            },
+            Rig::QrpKit => Sound {
+                tone_shape: ToneShape::Sine,
+                envelope: envelope(EnvelopeShape::Hard, 1, 2, 0.1, 0.0),
+                drift: Some(99),
+            },
+            Rig::VintageTube => Sound {
+                tone_shape: ToneShape::Sine,
+                envelope: envelope(EnvelopeShape::Gaussian, 8, 10, 0.0, 0.15),
+                drift: Some(98),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::MorseSynth;
+    use crate::conditions::Conditions;
+    use crate::morse::Timing;
+
+    // The highest and lowest peak, cycle by cycle, through the middle of a
+    // dah as `rig` sends it
+    fn peaks(rig: Rig) -> (f32, f32) {
+        let sound = rig.sound();
+        let timing = Timing::new(20, 0).with_envelope(sound.envelope);
+        let samples: Vec<f32> = MorseSynth::signal_only(8000, "T", timing, 800, Conditions::default(), sound.tone_shape, sound.drift).collect();
+        let peaks: Vec<f32> = samples[400..1000]
+            .chunks(10)
+            .map(|cycle| cycle.iter().fold(0.0f32, |peak, s| peak.max(s.abs())))
+            .collect();
+        (peaks.iter().copied().fold(0.0, f32::max), peaks.iter().copied().fold(1.0, f32::min))
+    }
+
+    #[test]
+    fn test_rig_sounds() {
+        // A clean rig's note holds steady; a tube rig's ripples with hum
+        let (high, low) = peaks(Rig::Ic7300);
+        assert!(high - low < 0.02, "{} {}", high, low);
+        let (high, low) = peaks(Rig::VintageTube);
+        assert!(high - low > 0.03, "{} {}", high, low);
+    }
+
+    #[test]
+    fn test_clicky_kit() {
+        // Steps a tenth of the way at each edge
+        let kit = Rig::QrpKit.sound().envelope;
+        assert!((kit.gain(0.0) - 0.1).abs() < 1e-6 && (kit.gain(1.0) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rig_names() {
+        assert_eq!(Rig::from_str("ic-7300", true), Ok(Rig::Ic7300));
+    }
+}
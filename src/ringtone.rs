@@ -0,0 +1,122 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+
+use crate::audio::{MorseAudio, ToneShape};
+use crate::conditions::Conditions;
+use crate::loudness::normalize_peak;
+use crate::morse::Timing;
+
+// ---------- Presets ---------------------------------------------------------
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum RingtonePreset {
+    /// Incoming-call tone: up to 30 s, with a pause before it repeats
+    Ringtone,
+    /// Message or notification sound: up to 5 s
+    Notification,
+    /// Alarm: up to 40 s, loud
+    Alarm,
+}
+
+struct Preset {
+    max_secs: f64,
+    // Silence after the code, so a looping ringtone doesn't run together
+    tail_secs: f64,
+    peak_db: f64,
+}
+
+impl RingtonePreset {
+    pub fn name(self) -> &'static str {
+        match self {
+            RingtonePreset::Ringtone => "ringtone",
+            RingtonePreset::Notification => "notification",
+            RingtonePreset::Alarm => "alarm",
+        }
+    }
+
+    fn settings(self) -> Preset {
+        match self {
+            RingtonePreset::Ringtone => Preset { max_secs: 30.0, tail_secs: 1.0, peak_db: -1.0 },
+            RingtonePreset::Notification => Preset { max_secs: 5.0, tail_secs: 0.0, peak_db: -3.0 },
+            RingtonePreset::Alarm => Preset { max_secs: 40.0, tail_secs: 0.5, peak_db: -0.3 },
+        }
+    }
+}
+
+// Phones play 44.1 kHz 16-bit WAV everywhere
+pub const RINGTONE_SAMPLE_RATE: u32 = 44100;
+// Fade at each end, so the clip starts and stops without a click
+const FADE_SECS: f64 = 0.02;
+
+// ---------- Rendering -------------------------------------------------------
+/// Render `text` as a clean, normalized clip for `preset`, or fail if it
+/// won't fit the preset's length limit at this speed.
+pub fn render(text: &str, timing: Timing, tone: u32, tone_shape: ToneShape, preset: RingtonePreset) -> Result<Vec<f32>> {
+    let settings = preset.settings();
+    let audio = MorseAudio::new_with_sample_rate(
+        RINGTONE_SAMPLE_RATE, text, timing, tone, Conditions::default(), tone_shape, None,
+    );
+    let mut samples = audio.get_samples().to_vec();
+    samples.resize(samples.len() + (settings.tail_secs * RINGTONE_SAMPLE_RATE as f64) as usize, 0.0);
+
+    let secs = samples.len() as f64 / RINGTONE_SAMPLE_RATE as f64;
+    if secs > settings.max_secs {
+        bail!(
+            "\"{}\" takes {:.1} s; a {} can be at most {:.0} s, so shorten the text or raise --wpm",
+            text.trim(), secs, preset.name(), settings.max_secs,
+        );
+    }
+    fade(&mut samples, (FADE_SECS * RINGTONE_SAMPLE_RATE as f64) as usize);
+    normalize_peak(&mut samples, settings.peak_db);
+    Ok(samples)
+}
+
+// Raised-cosine fade in and out over `len` samples
+fn fade(samples: &mut [f32], len: usize) {
+    let len = len.min(samples.len() / 2);
+    let total = samples.len();
+    for i in 0..len {
+        let gain = (0.5 - 0.5 * (std::f64::consts::PI * i as f64 / len as f64).cos()) as f32;
+        samples[i] *= gain;
+        samples[total - 1 - i] *= gain;
+    }
+}
+
+/// "CALL FROM MOM" → "call-from-mom.wav"
+pub fn default_file_name(text: &str) -> String {
+    let slug: Vec<String> = text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    if slug.is_empty() {
+        "ringtone.wav".into()
+    } else {
+        format!("{}.wav", slug.join("-"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loudness::peak_db;
+
+    #[test]
+    fn test_ringtone_presets() {
+        let timing = Timing::new(20, 0);
+        let samples = render("CALL FROM MOM", timing, 800, ToneShape::Sine, RingtonePreset::Ringtone).unwrap();
+        assert!((peak_db(&samples) + 1.0).abs() < 1e-3);
+        assert_eq!(samples[0], 0.0);
+        assert!(samples[samples.len() - RINGTONE_SAMPLE_RATE as usize..].iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn test_notification_too_long() {
+        let too_long = render("CALL FROM MOM", Timing::new(20, 0), 800, ToneShape::Sine, RingtonePreset::Notification);
+        assert!(too_long.unwrap_err().to_string().contains("at most 5 s"));
+    }
+
+    #[test]
+    fn test_default_file_name() {
+        assert_eq!(default_file_name("Call from Mom!"), "call-from-mom.wav");
+    }
+}
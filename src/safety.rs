@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use crate::morse::{Element, MorseError};
+
+// ---------- Keying limits ---------------------------------------------------
+// A transmitter keyed for seconds on end, or for an hour of text, can cook
+// its finals. Timelines meant for a rig (the VOX output, with or without
+// PTT) are checked against these limits before anything is keyed, so QRSS
+// settings or a runaway text file sent to a rig by mistake are refused
+// instead of transmitted. The interactive keyer checks each character as it
+// is queued and stops the queue when a transmission runs too long.
+#[derive(clap::Args, Debug, Clone)]
+pub struct SafetyArgs {
+    /// Longest the key may stay down when keying a transmitter (--output
+    /// vox), in ms
+    #[arg(long, value_name = "MS", default_value_t = 2000)]
+    pub max_key_down: u64,
+
+    /// Longest a transmission may run when keying a transmitter, in seconds
+    #[arg(long, value_name = "SECS", default_value_t = 300)]
+    pub max_transmission: u64,
+}
+
+impl SafetyArgs {
+    pub fn max_key_down(&self) -> Duration {
+        Duration::from_millis(self.max_key_down)
+    }
+
+    pub fn max_transmission(&self) -> Duration {
+        Duration::from_secs(self.max_transmission)
+    }
+
+    /// Check `elements` against both limits. A key-up longer than `gap` ends
+    /// a transmission.
+    pub fn check(&self, elements: &[Element], gap: Duration) -> Result<(), MorseError> {
+        let mut at = Duration::ZERO;
+        let mut started: Option<Duration> = None;
+        for element in elements {
+            if element.key_down {
+                if element.duration > self.max_key_down() {
+                    return Err(MorseError::KeyingAborted(format!(
+                        "key down for {:.1} s at {:.1} s in, over the {} ms limit (--max-key-down)",
+                        element.duration.as_secs_f64(),
+                        at.as_secs_f64(),
+                        self.max_key_down
+                    )));
+                }
+                let start = *started.get_or_insert(at);
+                self.check_transmission(at + element.duration - start)?;
+            } else if element.duration > gap {
+                started = None;
+            }
+            at += element.duration;
+        }
+        Ok(())
+    }
+
+    /// Check the length of a transmission so far.
+    pub fn check_transmission(&self, length: Duration) -> Result<(), MorseError> {
+        if length > self.max_transmission() {
+            return Err(MorseError::KeyingAborted(format!(
+                "transmission running over the {} s limit (--max-transmission)",
+                self.max_transmission
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::morse::{key_timeline, Timing};
+
+    const LIMITS: SafetyArgs = SafetyArgs { max_key_down: 2000, max_transmission: 30 };
+    const GAP: Duration = Duration::from_millis(200);
+
+    #[test]
+    fn test_keying_within_limits() {
+        assert!(LIMITS.check(&key_timeline("CQ CQ DE W1AW K", &Timing::new(20, 0)), GAP).is_ok());
+    }
+
+    #[test]
+    fn test_key_down_too_long() {
+        // A 3.6 s dash at 1 WPM
+        let slow = key_timeline("T", &Timing::new(1, 0));
+        assert!(matches!(LIMITS.check(&slow, GAP), Err(MorseError::KeyingAborted(_))));
+    }
+
+    #[test]
+    fn test_transmission_too_long() {
+        // At 20 WPM 36 s of Ts, in one transmission (the 180 ms letter gaps
+        // are shorter than the gap) or in short ones
+        let ts = key_timeline(&"T".repeat(100), &Timing::new(20, 0));
+        assert!(LIMITS.check(&ts, GAP).is_err());
+        assert!(LIMITS.check(&ts, Duration::from_millis(100)).is_ok());
+    }
+}
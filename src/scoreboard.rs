@@ -0,0 +1,333 @@
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::markup::escape;
+
+// ---------- Club scoreboard -------------------------------------------------
+// For a club night everyone runs the same session, fixed by `--seed`, and
+// each result is posted to a scoreboard as the session ends:
+//
+//     cwgen scoreboard --club-key "$KEY"
+//     cwgen --seed 42 --practice exam --scoreboard http://club-pc:8750 --club-key "$KEY"
+//
+// Results are signed with HMAC-SHA256 under the club key, and the server
+// keeps only those it can verify, so knowing its address isn't enough to
+// post to it. Each signed result is taken once, so one seen going by can't
+// be posted again to fill the board. The server speaks just enough HTTP/1.1 for this: POST
+// /results adds one, GET /results gives them all as JSON and GET / shows
+// the board, a table per seed, mode and speed, best first. Anything that
+// takes the same POST can stand in for it. No TLS; put a proxy in front to
+// reach it from outside the club.
+pub const DEFAULT_PORT: u16 = 8750;
+const MAX_REQUEST: usize = 64 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One finished session.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub seed: Option<u64>,
+    pub mode: String,
+    pub wpm: u32,
+    pub items: usize,
+    /// For sessions that are scored
+    pub correct: Option<usize>,
+    pub seconds: f64,
+    pub time: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+}
+
+impl Entry {
+    fn mac(&self, key: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes any key length");
+        // The entry as JSON without its signature
+        let unsigned = Entry { signature: String::new(), ..self.clone() };
+        mac.update(&serde_json::to_vec(&unsigned).unwrap_or_default());
+        mac
+    }
+
+    pub fn sign(&mut self, key: &str) {
+        let signature = self.mac(key).finalize().into_bytes();
+        self.signature = signature.iter().map(|byte| format!("{:02x}", byte)).collect();
+    }
+
+    pub fn verify(&self, key: &str) -> bool {
+        let bytes: Option<Vec<u8>> = (0..self.signature.len())
+            .step_by(2)
+            .map(|i| self.signature.get(i..i + 2).and_then(|hex| u8::from_str_radix(hex, 16).ok()))
+            .collect();
+        bytes.is_some_and(|bytes| self.mac(key).verify_slice(&bytes).is_ok())
+    }
+}
+
+/// Sign `entry` and post it to the scoreboard at `url`
+/// (http://HOST[:PORT][/PATH]).
+pub fn post(url: &str, key: &str, mut entry: Entry) -> Result<()> {
+    let Some(rest) = url.strip_prefix("http://") else {
+        bail!("--scoreboard must be an http:// address (put a proxy in front for https)");
+    };
+    let (host, path) = rest.split_once('/').map_or((rest, ""), |(host, path)| (host, path));
+    let address = if host.contains(':') { host.to_string() } else { format!("{}:{}", host, DEFAULT_PORT) };
+    entry.sign(key);
+    let body = serde_json::to_string(&entry)?;
+
+    let mut stream = TcpStream::connect(&address).with_context(|| format!("connecting to scoreboard {}", address))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    write!(
+        stream,
+        "POST /{}results HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        if path.is_empty() { String::new() } else { format!("{}/", path.trim_end_matches('/')) },
+        host,
+        body.len(),
+        body,
+    )?;
+    let mut response = String::new();
+    stream.take(MAX_REQUEST as u64).read_to_string(&mut response)?;
+    let status = response.split_whitespace().nth(1).unwrap_or_default();
+    if !status.starts_with('2') {
+        let reason = response.split_once("\r\n\r\n").map_or("", |(_, body)| body.trim());
+        bail!("the scoreboard turned the result down ({}): {}", status, reason);
+    }
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+// The request on `stream`, or `None` when its body would be over
+// MAX_REQUEST, which is then left unread
+fn read_request(stream: &TcpStream) -> Result<Option<Request>> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST as u64));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else { bail!("bad request line") };
+    let (method, path) = (method.to_string(), path.to_string());
+    let mut length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().context("bad Content-Length")?;
+            }
+        }
+    }
+    if length > MAX_REQUEST {
+        return Ok(None);
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(Request { method, path, body }))
+}
+
+fn respond(mut stream: &TcpStream, status: &str, content_type: &str, body: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body,
+    )?;
+    Ok(())
+}
+
+/// The results in tables, one per seed, mode and speed, best first: most
+/// right (or most items, unscored), then quickest.
+pub fn tables(entries: &[Entry]) -> BTreeMap<(Option<u64>, String, u32), Vec<&Entry>> {
+    let mut tables: BTreeMap<_, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        tables.entry((entry.seed, entry.mode.clone(), entry.wpm)).or_default().push(entry);
+    }
+    for table in tables.values_mut() {
+        table.sort_by(|a, b| {
+            (b.correct, b.items).cmp(&(a.correct, a.items)).then(a.seconds.total_cmp(&b.seconds))
+        });
+    }
+    tables
+}
+
+fn html(entries: &[Entry]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>cwgen scoreboard</title>\n\
+         <style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:2em}\
+         td,th{padding:.3em 1em;text-align:left;border-bottom:1px solid #ccc}</style></head><body>\n\
+         <h1>Scoreboard</h1>\n",
+    );
+    if entries.is_empty() {
+        html.push_str("<p>No results yet.</p>\n");
+    }
+    for ((seed, mode, wpm), table) in tables(entries) {
+        let seed = seed.map_or_else(|| "no seed".to_string(), |seed| format!("seed {}", seed));
+        let _ = writeln!(html, "<h2>{} at {} WPM, {}</h2>", escape(&mode), wpm, seed);
+        html.push_str("<table><tr><th></th><th>Name</th><th>Right</th><th>Items</th><th>Time</th><th>When</th></tr>\n");
+        for (place, entry) in table.iter().enumerate() {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}:{:02}</td><td>{}</td></tr>",
+                place + 1,
+                escape(&entry.name),
+                entry.correct.map_or_else(|| "–".to_string(), |correct| correct.to_string()),
+                entry.items,
+                entry.seconds as u64 / 60,
+                entry.seconds as u64 % 60,
+                escape(&entry.time),
+            );
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+/// Serve the scoreboard on `port`, keeping results in `data` (JSON lines).
+pub fn serve(port: u16, key: &str, data: &Path) -> Result<()> {
+    let mut entries: Vec<Entry> = match std::fs::read_to_string(data) {
+        Ok(text) => text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", data.display())),
+    };
+    let listener = TcpListener::bind(("0.0.0.0", port)).with_context(|| format!("listening on port {}", port))?;
+    println!("Scoreboard on http://0.0.0.0:{}/ with {} results from {}", port, entries.len(), data.display());
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Scoreboard: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = handle(&stream, key, data, &mut entries) {
+            eprintln!("Scoreboard: {}", e);
+            let _ = respond(&stream, "400 Bad Request", "text/plain", &e.to_string());
+        }
+    }
+    Ok(())
+}
+
+fn handle(stream: &TcpStream, key: &str, data: &Path, entries: &mut Vec<Entry>) -> Result<()> {
+    let Some(request) = read_request(stream)? else {
+        return respond(stream, "413 Content Too Large", "text/plain", "too large for a result");
+    };
+    match (request.method.as_str(), request.path.trim_end_matches('/')) {
+        ("POST", "/results") => {
+            let entry: Entry = serde_json::from_slice(&request.body).context("reading the result")?;
+            if !entry.verify(key) {
+                return respond(stream, "401 Unauthorized", "text/plain", "not signed with the club key");
+            }
+            if posted_before(entries, &entry) {
+                return respond(stream, "409 Conflict", "text/plain", "already stored");
+            }
+            let mut file = std::fs::OpenOptions::new().create(true).append(true).open(data)?;
+            writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+            println!("{}: {} {} at {} WPM", entry.name, entry.mode, entry.correct.unwrap_or(entry.items), entry.wpm);
+            entries.push(entry);
+            respond(stream, "201 Created", "text/plain", "stored")
+        }
+        ("GET", "/results") => respond(stream, "200 OK", "application/json", &serde_json::to_string(entries)?),
+        ("GET", "") => respond(stream, "200 OK", "text/html; charset=utf-8", &html(entries)),
+        _ => respond(stream, "404 Not Found", "text/plain", "not found"),
+    }
+}
+
+// Whether `entry` has been stored already, signature and all
+fn posted_before(entries: &[Entry], entry: &Entry) -> bool {
+    entries.iter().any(|stored| stored.signature.eq_ignore_ascii_case(&entry.signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, correct: usize, seconds: f64) -> Entry {
+        Entry {
+            name: name.into(),
+            seed: Some(42),
+            mode: "exam".into(),
+            wpm: 13,
+            items: 10,
+            correct: Some(correct),
+            seconds,
+            time: "2026-10-17T19:00:00Z".into(),
+            signature: String::new(),
+        }
+    }
+
+    fn signed(name: &str, correct: usize, seconds: f64) -> Entry {
+        let mut entry = entry(name, correct, seconds);
+        entry.sign("club");
+        entry
+    }
+
+    #[test]
+    fn test_signature() {
+        let signed = signed("CX4CC", 9, 301.5);
+        assert!(signed.verify("club"));
+        assert!(!signed.verify("other club"));
+        assert!(!entry("CX4CC", 9, 301.5).verify("club"));
+    }
+
+    #[test]
+    fn test_signature_survives_json() {
+        let signed = signed("CX4CC", 9, 301.5);
+        let posted: Entry = serde_json::from_str(&serde_json::to_string(&signed).unwrap()).unwrap();
+        assert!(posted.verify("club"));
+    }
+
+    #[test]
+    fn test_changed_entry_fails() {
+        let signed = signed("CX4CC", 9, 301.5);
+        assert!(!Entry { correct: Some(10), ..signed }.verify("club"));
+    }
+
+    #[test]
+    fn test_tables_ranked() {
+        let entries = [entry("A", 7, 200.0), entry("B", 9, 400.0), entry("C", 9, 300.0), Entry { wpm: 20, ..entry("D", 1, 1.0) }];
+        let tables = tables(&entries);
+        assert_eq!(tables.len(), 2);
+        let names: Vec<&str> = tables[&(Some(42), "exam".into(), 13)].iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["C", "B", "A"]);
+    }
+
+    #[test]
+    fn test_html() {
+        let entries = [entry("A", 7, 200.0), entry("C", 9, 300.0)];
+        assert!(html(&entries).contains("<td>1</td><td>C</td><td>9</td>"));
+    }
+
+    #[test]
+    fn test_oversized_body_refused() {
+        // Turned down without reading it
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        write!(client, "POST /results HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n").unwrap();
+        let (server, _) = listener.accept().unwrap();
+        assert!(read_request(&server).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_reposted_entry_refused() {
+        let entry = signed("CX4CC", 9, 301.5);
+        assert!(!posted_before(&[], &entry));
+        let stored = [entry.clone()];
+        assert!(posted_before(&stored, &entry));
+        assert!(posted_before(&stored, &Entry { signature: entry.signature.to_uppercase(), ..entry.clone() }));
+        assert!(!posted_before(&stored, &signed("CX4CC", 9, 290.0)));
+    }
+}
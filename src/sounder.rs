@@ -0,0 +1,81 @@
+use rand::{Rng, SeedableRng};
+
+use crate::morse::{key_timeline, Element, Timing};
+
+// ---------- Sounder ---------------------------------------------------------
+// Landline telegraphy wasn't copied from a tone but from a sounder: an
+// electromagnet pulls the armature down onto its stop with a sharp click as
+// the key closes, and a spring throws it back up against the other stop with
+// a duller clack as it opens. Operators read the time between the two. This
+// renders keying that way, as a short knock at each key-down and key-up and
+// silence in between; it suits American Morse best (`--charset american`).
+
+// Each knock: where it rings, how fast it dies away and how loud it is
+const DOWN: Knock = Knock { ring_hz: 1800.0, decay_secs: 0.004, level: 0.8 };
+const UP: Knock = Knock { ring_hz: 950.0, decay_secs: 0.007, level: 0.5 };
+// Long enough for either knock to have died away
+const KNOCK_SECS: f64 = 0.05;
+
+struct Knock {
+    ring_hz: f64,
+    decay_secs: f64,
+    level: f32,
+}
+
+impl Knock {
+    // A damped ring with a burst of noise at the strike
+    fn render(&self, sample_rate: u32) -> Vec<f32> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let length = (KNOCK_SECS * sample_rate as f64) as usize;
+        (0..length)
+            .map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                let envelope = (-t / self.decay_secs).exp();
+                let ring = (2.0 * std::f64::consts::PI * self.ring_hz * t).sin();
+                let noise = rng.random_range(-1.0..1.0) * (-t / (self.decay_secs / 3.0)).exp();
+                self.level * (envelope * (0.7 * ring + 0.3 * noise)) as f32
+            })
+            .collect()
+    }
+}
+
+/// Sounder clicks for `text`.
+pub fn render(text: &str, timing: &Timing, sample_rate: u32) -> Vec<f32> {
+    render_elements(&key_timeline(text, timing), sample_rate)
+}
+
+/// Sounder clicks for any on/off timeline.
+pub fn render_elements(elements: &[Element], sample_rate: u32) -> Vec<f32> {
+    let (down, up) = (DOWN.render(sample_rate), UP.render(sample_rate));
+    let mut samples = Vec::new();
+    let mut at = 0;
+    for element in elements {
+        let knock = if element.key_down { &down } else { &up };
+        let end = at + (element.duration.as_secs_f64() * sample_rate as f64) as usize;
+        samples.resize(samples.len().max(end).max(at + knock.len()), 0.0);
+        for (sample, k) in samples[at..].iter_mut().zip(knock) {
+            *sample += k;
+        }
+        at = end;
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sounder_clicks() {
+        // 20 WPM E: down for 60 ms, then up
+        let rate = 8000;
+        let samples = render("E", &Timing::new(20, 0), rate);
+        let peak = |from_ms: usize, to_ms: usize| {
+            samples[from_ms * 8..to_ms * 8].iter().fold(0f32, |peak, s| peak.max(s.abs()))
+        };
+        assert!(peak(0, 5) > 0.3);
+        assert!(peak(40, 59) < 0.01);
+        assert!(peak(60, 65) > 0.2);
+        assert!(peak(0, 5) > peak(60, 65));
+    }
+}
@@ -0,0 +1,100 @@
+use rand::Rng;
+use std::collections::BTreeMap;
+
+use crate::profile::Stats;
+
+// ---------- Weak characters -------------------------------------------------
+// With `--focus-weak`, the characters in Koch groups are drawn
+// by how often each has been missed lately, rather than evenly, so the weak
+// ones come round again and again until copy of them recovers. "Lately" is
+// the profile's running miss rate per character (see Stats::tally), which
+// each answer moves a tenth of the way, so a character copied well for a
+// while drops back to its fair share. A character never missed, or never
+// answered, keeps the weight of 1; one missed every time comes up BOOST + 1
+// times as often.
+const BOOST: f64 = 4.0;
+/// At or above this weight a character is named as weak
+const WEAK: f64 = 1.5;
+
+/// How heavily to draw each character.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Weights(BTreeMap<char, f64>);
+
+impl Weights {
+    pub fn from_stats(stats: &Stats) -> Weights {
+        let weights = stats
+            .characters
+            .iter()
+            .filter_map(|(c, counts)| {
+                let c = c.chars().next()?;
+                let rate = stats.recent.get(&c.to_string()).copied().unwrap_or(counts.missed as f64 / counts.sent.max(1) as f64);
+                Some((c, 1.0 + BOOST * rate.clamp(0.0, 1.0)))
+            })
+            .collect();
+        Weights(weights)
+    }
+
+    pub fn weight(&self, c: char) -> f64 {
+        self.0.get(&c.to_ascii_uppercase()).copied().unwrap_or(1.0)
+    }
+
+    /// One of `chars`, drawn by weight.
+    pub fn pick(&self, rng: &mut impl Rng, chars: &[char]) -> char {
+        let total: f64 = chars.iter().map(|&c| self.weight(c)).sum();
+        let mut at = rng.random_range(0.0..total);
+        for &c in chars {
+            at -= self.weight(c);
+            if at < 0.0 {
+                return c;
+            }
+        }
+        chars[chars.len() - 1]
+    }
+
+    /// Those of `chars` drawn noticeably more often, the weakest first.
+    pub fn weak(&self, chars: &[char]) -> Vec<char> {
+        let mut weak: Vec<char> = chars.iter().copied().filter(|&c| self.weight(c) >= WEAK).collect();
+        weak.sort_by(|a, b| self.weight(*b).total_cmp(&self.weight(*a)));
+        weak
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::CharacterStats;
+    use rand::SeedableRng;
+
+    fn weights() -> Weights {
+        let mut stats = Stats::default();
+        stats.characters.insert("Q".into(), CharacterStats { sent: 20, missed: 10 });
+        stats.characters.insert("K".into(), CharacterStats { sent: 20, missed: 0 });
+        // Lately Q has been missed every time, and Y not since it was learned
+        stats.characters.insert("Y".into(), CharacterStats { sent: 20, missed: 15 });
+        stats.recent.insert("Q".into(), 1.0);
+        stats.recent.insert("Y".into(), 0.0);
+        Weights::from_stats(&stats)
+    }
+
+    #[test]
+    fn test_weight_follows_recent_misses() {
+        let weights = weights();
+        assert_eq!((weights.weight('Q'), weights.weight('y'), weights.weight('K'), weights.weight('M')), (5.0, 1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_weak() {
+        assert_eq!(weights().weak(&['K', 'Y', 'Q']), ['Q']);
+    }
+
+    #[test]
+    fn test_weak_characters_come_up_more() {
+        let weights = weights();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let drawn: Vec<char> = (0..7000).map(|_| weights.pick(&mut rng, &['K', 'M', 'Q'])).collect();
+        let count = |c| drawn.iter().filter(|&&d| d == c).count();
+        // Q five times as often as K or M: 5000 of 7000
+        assert!((4800..5200).contains(&count('Q')), "{}", count('Q'));
+        assert!((800..1200).contains(&count('K')), "{}", count('K'));
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+
+// ---------- Spoken answers --------------------------------------------------
+// Practice sessions can announce each answer out loud. The engine comes from
+// the optional `tts` feature (speech-dispatcher on Linux, AVFoundation on
+// macOS, SAPI/WinRT on Windows); without it, asking for speech is an error.
+pub struct Speech {
+    #[cfg(feature = "tts")]
+    tts: tts::Tts,
+}
+
+/// "paris. P A R I S.": the item as a word, then spelled out, so callsigns
+/// and Q-codes come across as clearly as plain words.
+pub fn announcement(item: &str) -> String {
+    let spelled: Vec<String> = item.chars().filter(|c| !c.is_whitespace()).map(String::from).collect();
+    format!("{}. {}.", item.to_lowercase(), spelled.join(" "))
+}
+
+/// "w1aw. Whiskey One Alpha Whiskey.": the item spelled in the NATO
+/// alphabet, with `--phonetics`.
+pub fn phonetic_announcement(item: &str) -> String {
+    format!("{}. {}.", item.to_lowercase(), crate::phonetics::spell(item))
+}
+
+#[cfg(feature = "tts")]
+impl Speech {
+    pub fn new() -> Result<Self> {
+        let tts = tts::Tts::default().map_err(|e| anyhow::anyhow!("text-to-speech unavailable: {}", e))?;
+        Ok(Speech { tts })
+    }
+
+    /// Speak `text` and wait until it has been said.
+    pub fn say(&mut self, text: &str) -> Result<()> {
+        self.tts.speak(text, true).map_err(|e| anyhow::anyhow!("text-to-speech failed: {}", e))?;
+        // Engines that can't report progress get a rough reading-speed wait
+        match self.tts.is_speaking() {
+            Ok(_) => {
+                while self.tts.is_speaking().unwrap_or(false) {
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                }
+            }
+            Err(_) => std::thread::sleep(std::time::Duration::from_millis(80 * text.len() as u64)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "tts"))]
+impl Speech {
+    pub fn new() -> Result<Self> {
+        anyhow::bail!("this cwgen was built without text-to-speech; rebuild with `cargo build --release --features tts`")
+    }
+
+    pub fn say(&mut self, _text: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_announcement_spells_item() {
+        assert_eq!(announcement("PARIS"), "paris. P A R I S.");
+        assert_eq!(announcement("W1AW/P"), "w1aw/p. W 1 A W / P.");
+        assert_eq!(phonetic_announcement("W1AW"), "w1aw. Whiskey One Alpha Whiskey.");
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+use id3::{Tag, TagLike, Version};
+use std::path::Path;
+
+// ---------- Tags ------------------------------------------------------------
+// Title/artist/album/track for written files, so a set of lessons sorts
+// itself out in a music or podcast app. Text tags are templates:
+//
+//     --album "Koch Course" --title "Koch Lesson {track} – {wpm} WPM"
+//
+// {wpm}, {farnsworth}, {tone} and {track} come from the render settings,
+// {name} from the output file name. Tags go in an ID3v2.4 chunk inside the
+// WAV, which iTunes/Music, VLC and most podcast apps read.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct Tags {
+    /// Title tag for written files ({wpm}, {tone}, {track}, {name} are filled in)
+    #[arg(long)]
+    pub title: Option<String>,
+
+    /// Artist tag for written files
+    #[arg(long)]
+    pub artist: Option<String>,
+
+    /// Album tag for written files
+    #[arg(long)]
+    pub album: Option<String>,
+
+    /// Track number tag for written files
+    #[arg(long)]
+    pub track: Option<u32>,
+}
+
+impl Tags {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.artist.is_none() && self.album.is_none() && self.track.is_none()
+    }
+
+    /// Fill `{key}` placeholders in the text tags. Unknown ones are left for
+    /// a later pass.
+    pub fn expand(&self, vars: &[(&str, String)]) -> Tags {
+        let fill = |template: &Option<String>| {
+            template.as_ref().map(|template| {
+                let mut text = template.clone();
+                for (key, value) in vars {
+                    text = text.replace(&format!("{{{}}}", key), value);
+                }
+                text
+            })
+        };
+        Tags { title: fill(&self.title), artist: fill(&self.artist), album: fill(&self.album), track: self.track }
+    }
+
+    /// The tags with `{name}` filled in from `path`.
+    pub fn for_file(&self, path: &str) -> Tags {
+        let name = Path::new(path).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        self.expand(&[("name", name)])
+    }
+
+    /// Add the tags to an already written WAV file.
+    pub fn write_to_wav(&self, path: &str) -> Result<()> {
+        if crate::wav::is_rf64(path) {
+            eprintln!("Warning: {}: tags can't be written to an RF64 file", path);
+            return Ok(());
+        }
+        let tags = self.for_file(path);
+        let mut tag = Tag::new();
+        if let Some(title) = tags.title {
+            tag.set_title(title);
+        }
+        if let Some(artist) = tags.artist {
+            tag.set_artist(artist);
+        }
+        if let Some(album) = tags.album {
+            tag.set_album(album);
+        }
+        if let Some(track) = tags.track {
+            tag.set_track(track);
+        }
+        tag.write_to_path(path, Version::Id3v24).with_context(|| format!("tagging {}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::write_wav;
+
+    #[test]
+    fn test_tag_wav() {
+        let tags = Tags {
+            title: Some("Koch Lesson {track} – {wpm} WPM".into()),
+            album: Some("{name}".into()),
+            track: Some(12),
+            ..Tags::default()
+        };
+        let tags = tags.expand(&[("wpm", "22".into()), ("track", "12".into())]);
+        let path = std::env::temp_dir().join(format!("cwgen-tags-{}.wav", std::process::id()));
+        let path = path.to_string_lossy();
+        write_wav(&path, 8000, &[0.0; 800]).unwrap();
+        tags.write_to_wav(&path).unwrap();
+
+        let tag = Tag::read_from_path(&*path).unwrap();
+        let samples = crate::decode::read_audio(&path).unwrap().0.len();
+        std::fs::remove_file(&*path).unwrap();
+        assert_eq!(tag.title(), Some("Koch Lesson 12 – 22 WPM"));
+        assert_eq!(tag.album(), Some(format!("cwgen-tags-{}", std::process::id()).as_str()));
+        assert_eq!(tag.track(), Some(12));
+        assert_eq!(samples, 800);
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::Result;
+use std::fmt::Write as _;
+
+use crate::markup::{self, escape};
+use crate::morse::{key_timeline, signs, Timing};
+
+// ---------- Inker tape ------------------------------------------------------
+// `--output tape` draws the keying the way a Morse inker printed it on paper
+// tape: a line that steps up while the key is down and back down while it is
+// up, with each character's letter under its start. Everything is measured in
+// dots, so at any speed a dot is the same length on the tape and what stands
+// out is the proportions: dashes against dots, and gaps (a Farnsworth gap, or
+// `--extra-gap`) against the spacing inside characters. The tape is broken
+// into rows, between characters, and written as SVG or, for a file ending in
+// .png, as a PNG (which has the trace and the dot ticks but no letters).
+
+// Tape length per dot, and the length of a row
+const DOT_PX: f64 = 6.0;
+const ROW_PX: f64 = 960.0;
+const MARGIN: f64 = 16.0;
+const ROW_HEIGHT: f64 = 56.0;
+// Heights of the trace within a row, key up and key down, and of the letters
+const KEY_UP_Y: f64 = 30.0;
+const KEY_DOWN_Y: f64 = 12.0;
+const LABEL_Y: f64 = 48.0;
+
+/// One row of tape: where each mark starts and how long it is, and the
+/// characters printed under it, in pixels from the row's start.
+#[derive(Debug, Default)]
+pub struct Row {
+    pub marks: Vec<(f64, f64)>,
+    pub labels: Vec<(f64, char)>,
+    pub length: f64,
+}
+
+/// Lay `text` out on rows of tape.
+pub fn layout(text: &str, timing: &Timing) -> Vec<Row> {
+    let dot_secs = timing.dot.as_secs_f64();
+    let mut rows = vec![Row::default()];
+    for (sign, _) in signs(text, timing.charset) {
+        let elements = key_timeline(sign, timing);
+        let width: f64 = elements.iter().map(|e| e.duration.as_secs_f64() / dot_secs * DOT_PX).sum();
+        if width == 0.0 {
+            continue;
+        }
+        let row = rows.last_mut().unwrap();
+        // A character that won't fit starts the next row; a gap that won't
+        // fit is dropped, since the new row starts with the next character
+        if row.length + width > ROW_PX && row.length > 0.0 {
+            if sign.chars().all(char::is_whitespace) {
+                continue;
+            }
+            rows.push(Row::default());
+        }
+        let row = rows.last_mut().unwrap();
+        // A prosign's letters are spread along it
+        let letters: Vec<char> = match sign.strip_prefix('<').and_then(|sign| sign.strip_suffix('>')) {
+            Some(letters) => letters.chars().collect(),
+            None => sign.chars().filter(|ch| !ch.is_whitespace()).collect(),
+        };
+        for (i, ch) in letters.iter().enumerate() {
+            row.labels.push((row.length + width * i as f64 / letters.len() as f64, ch.to_ascii_uppercase()));
+        }
+        for element in elements {
+            let length = element.duration.as_secs_f64() / dot_secs * DOT_PX;
+            if element.key_down {
+                row.marks.push((row.length, length));
+            }
+            row.length += length;
+        }
+    }
+    rows
+}
+
+/// Write the tape to `path`: PNG for a .png file, SVG otherwise.
+pub fn write(path: &str, rows: &[Row]) -> Result<()> {
+    markup::write_picture(path, || png(rows), || Ok(svg(rows)))
+}
+
+pub fn svg(rows: &[Row]) -> String {
+    let width = ROW_PX + 2.0 * MARGIN;
+    let height = rows.len() as f64 * ROW_HEIGHT + MARGIN;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n\
+         <style>.paper{{fill:#f6efdc}} .trace{{fill:none;stroke:#1b2a4a;stroke-width:2}} \
+         .tick{{stroke:#c9bc9c;stroke-width:1}} text{{font:12px monospace;fill:#1b2a4a;}}</style>\n",
+        width, height,
+    );
+    for (i, row) in rows.iter().enumerate() {
+        let top = MARGIN / 2.0 + i as f64 * ROW_HEIGHT;
+        let _ = writeln!(svg, "<g transform=\"translate({},{})\">", MARGIN, top);
+        let _ = writeln!(svg, "<rect class=\"paper\" x=\"0\" y=\"0\" width=\"{}\" height=\"{}\"/>", row.length, ROW_HEIGHT - 4.0);
+        let ticks: String = (0..=(row.length / DOT_PX) as usize)
+            .map(|dot| format!("M{0},{1}v4", dot as f64 * DOT_PX, KEY_UP_Y + 2.0))
+            .collect();
+        let _ = writeln!(svg, "<path class=\"tick\" d=\"{}\"/>", ticks);
+        let mut trace = format!("M0,{}", KEY_UP_Y);
+        for (start, length) in &row.marks {
+            let _ = write!(trace, "H{0}V{1}H{2}V{3}", start, KEY_DOWN_Y, start + length, KEY_UP_Y);
+        }
+        let _ = writeln!(svg, "<path class=\"trace\" d=\"{}H{}\"/>", trace, row.length);
+        for (x, ch) in &row.labels {
+            let _ = writeln!(svg, "<text x=\"{}\" y=\"{}\">{}</text>", x, LABEL_Y, escape(&ch.to_string()));
+        }
+        svg.push_str("</g>\n");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Greyscale, one byte a pixel
+fn png(rows: &[Row]) -> Result<Vec<u8>> {
+    const PAPER: u8 = 0xf0;
+    const MARGIN_SHADE: u8 = 0xff;
+    const TICK: u8 = 0xc0;
+    const INK: u8 = 0x20;
+    let (width, height) = ((ROW_PX + 2.0 * MARGIN) as usize, (rows.len() as f64 * ROW_HEIGHT + MARGIN) as usize);
+    let mut pixels = vec![MARGIN_SHADE; width * height];
+    let mut fill = |x0: f64, y0: f64, x1: f64, y1: f64, shade: u8| {
+        for y in (y0 as usize)..(y1 as usize).min(height) {
+            for x in (x0 as usize)..(x1 as usize).min(width) {
+                pixels[y * width + x] = shade;
+            }
+        }
+    };
+    for (i, row) in rows.iter().enumerate() {
+        let (left, top) = (MARGIN, MARGIN / 2.0 + i as f64 * ROW_HEIGHT);
+        fill(left, top, left + row.length, top + ROW_HEIGHT - 4.0, PAPER);
+        for dot in 0..=(row.length / DOT_PX) as usize {
+            let x = left + dot as f64 * DOT_PX;
+            fill(x, top + KEY_UP_Y + 2.0, x + 1.0, top + KEY_UP_Y + 6.0, TICK);
+        }
+        let mut at = 0.0;
+        for (start, length) in &row.marks {
+            fill(left + at, top + KEY_UP_Y - 1.0, left + start + 1.0, top + KEY_UP_Y + 1.0, INK);
+            fill(left + start - 1.0, top + KEY_DOWN_Y - 1.0, left + start + 1.0, top + KEY_UP_Y + 1.0, INK);
+            fill(left + start, top + KEY_DOWN_Y - 1.0, left + start + length + 1.0, top + KEY_DOWN_Y + 1.0, INK);
+            fill(left + start + length - 1.0, top + KEY_DOWN_Y - 1.0, left + start + length + 1.0, top + KEY_UP_Y + 1.0, INK);
+            at = start + length;
+        }
+        fill(left + at, top + KEY_UP_Y - 1.0, left + row.length, top + KEY_UP_Y + 1.0, INK);
+    }
+
+    markup::png(&pixels, width, height, png::ColorType::Grayscale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tape_layout() {
+        // A: dot, gap, dash; the character gap runs to the next letter
+        let rows = layout("AN", &Timing::new(20, 0));
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].marks[..2], [(0.0, DOT_PX), (2.0 * DOT_PX, 3.0 * DOT_PX)]);
+        assert_eq!(rows[0].labels, [(0.0, 'A'), (8.0 * DOT_PX, 'N')]);
+    }
+
+    #[test]
+    fn test_farnsworth_tape() {
+        // Gaps are longer on the tape, the marks the same
+        let rows = layout("AN", &Timing::new(20, 0));
+        let spread = layout("AN", &Timing::new_farnsworth(20, 10, 0));
+        assert_eq!(spread[0].marks[..2], rows[0].marks[..2]);
+        assert!(spread[0].labels[1].0 > rows[0].labels[1].0);
+    }
+
+    #[test]
+    fn test_tape_wraps() {
+        // A long text wraps between characters
+        let long = layout(&"PARIS ".repeat(20), &Timing::new(20, 0));
+        assert!(long.len() > 1);
+        assert!(long.iter().all(|row| row.length <= ROW_PX));
+    }
+
+    #[test]
+    fn test_tape_images() {
+        let rows = layout("CQ", &Timing::new(20, 0));
+        assert!(svg(&rows).contains("<text"));
+        assert!(png(&rows).unwrap().starts_with(b"\x89PNG"));
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+
+use crate::audio::{ToneGenerator, ToneShape};
+
+// ---------- Test signals ----------------------------------------------------
+// Steady tones, the two-tone SSB test and sweeps for checking an audio chain
+// or a transmitter's audio input, from the same oscillator the CW uses.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Signal {
+    /// One steady tone at --tone
+    Tone,
+    /// --tone and --tone2 together, each 6 dB under the level (the classic
+    /// SSB linearity test)
+    TwoTone,
+    /// A logarithmic sweep from --from to --to
+    Sweep,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct SignalSpec {
+    #[arg(value_enum, default_value_t = Signal::Tone)]
+    pub signal: Signal,
+
+    /// Frequency in Hz
+    #[arg(long, default_value_t = 700)]
+    pub tone: u32,
+
+    /// Second frequency for two-tone, in Hz
+    #[arg(long, default_value_t = 1900)]
+    pub tone2: u32,
+
+    /// Sweep start in Hz
+    #[arg(long, default_value_t = 100)]
+    pub from: u32,
+
+    /// Sweep end in Hz
+    #[arg(long, default_value_t = 3000)]
+    pub to: u32,
+
+    /// Length, e.g. 10s, 500ms or 2m
+    #[arg(long, default_value = "10s", value_parser = parse_duration)]
+    pub duration: f64,
+
+    /// Peak level in dBFS
+    #[arg(long, default_value_t = -6.0, allow_negative_numbers = true)]
+    pub level: f64,
+}
+
+// Fade at each end, so the signal doesn't start or stop with a click
+const FADE_SECS: f64 = 0.01;
+
+/// "10s", "500ms", "2m" or plain seconds.
+pub fn parse_duration(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let (number, scale) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1.0)
+    } else if let Some(mins) = s.strip_suffix('m') {
+        (mins, 60.0)
+    } else {
+        (s, 1.0)
+    };
+    let value: f64 = number.trim().parse().map_err(|_| format!("invalid duration: {}", s))?;
+    if !(value > 0.0 && value * scale <= 3600.0) {
+        return Err(format!("duration must be over 0 and at most an hour, got {}", s));
+    }
+    Ok(value * scale)
+}
+
+impl SignalSpec {
+    pub fn render(&self, sample_rate: u32) -> Result<Vec<f32>> {
+        let highest = match self.signal {
+            Signal::Tone => self.tone,
+            Signal::TwoTone => self.tone.max(self.tone2),
+            Signal::Sweep => self.from.max(self.to),
+        };
+        if highest == 0 || highest >= sample_rate / 2 {
+            bail!("{} Hz can't be produced at a {} Hz sample rate", highest, sample_rate);
+        }
+        if self.level > 0.0 {
+            bail!("level must be at or below 0 dBFS, got {}", self.level);
+        }
+        let len = (self.duration * sample_rate as f64) as usize;
+        let peak = 10f64.powf(self.level / 20.0) as f32;
+        let mut first = ToneGenerator::new(self.tone, sample_rate, ToneShape::Sine, None);
+        let mut samples: Vec<f32> = match self.signal {
+            Signal::Tone => (0..len).map(|_| peak * first.next_sample(0.0)).collect(),
+            Signal::TwoTone => {
+                let mut second = ToneGenerator::new(self.tone2, sample_rate, ToneShape::Sine, None);
+                (0..len).map(|_| peak * 0.5 * (first.next_sample(0.0) + second.next_sample(0.0))).collect()
+            }
+            Signal::Sweep => {
+                let ratio = self.to as f64 / self.from as f64;
+                (0..len)
+                    .map(|i| {
+                        first.set_frequency(self.from as f64 * ratio.powf(i as f64 / len as f64));
+                        peak * first.next_sample(0.0)
+                    })
+                    .collect()
+            }
+        };
+        let fade = ((FADE_SECS * sample_rate as f64) as usize).min(len / 2);
+        for i in 0..fade {
+            let gain = i as f32 / fade as f32;
+            samples[i] *= gain;
+            samples[len - 1 - i] *= gain;
+        }
+        Ok(samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::find_signals;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        signal: SignalSpec,
+    }
+
+    fn signal(args: &[&str]) -> SignalSpec {
+        Cli::parse_from([&["test"], args].concat()).signal
+    }
+
+    #[test]
+    fn test_tone() {
+        let tone = signal(&["--tone", "1000", "--duration", "500ms"]).render(8000).unwrap();
+        assert_eq!(tone.len(), 4000);
+        assert!((crate::loudness::peak_db(&tone) + 6.0).abs() < 0.01);
+        assert!((find_signals(&tone, 8000, (200.0, 3500.0), 1)[0] - 1000.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn test_two_tone() {
+        let two_tone = signal(&["two-tone", "--level", "0"]).render(8000).unwrap();
+        assert!(crate::loudness::peak_db(&two_tone) > -0.1);
+        let mut found = find_signals(&two_tone, 8000, (200.0, 3500.0), 2);
+        found.sort_by(f64::total_cmp);
+        assert!((found[0] - 700.0).abs() < 10.0 && (found[1] - 1900.0).abs() < 10.0, "{:?}", found);
+    }
+
+    #[test]
+    fn test_tone_out_of_range() {
+        assert!(signal(&["--tone", "5000"]).render(8000).is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("2m"), Ok(120.0));
+        assert!(parse_duration("0s").is_err());
+    }
+}
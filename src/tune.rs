@@ -0,0 +1,454 @@
+use anyhow::Result;
+use crossterm::cursor::MoveToColumn;
+use crossterm::event::{self, Event, KeyCode};
+use crossterm::queue;
+use crossterm::terminal::{self, Clear, ClearType};
+use rand::Rng;
+use rodio::{Sink, Source};
+use std::io::Write;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::audio::{output_stream, playback_rate, Interferer, NoiseSource, Speaker};
+use crate::eq::equalize;
+use crate::conditions::Conditions;
+use crate::morse::MorseError;
+
+// ---------- Tunable receiver ------------------------------------------------
+// `cwgen tune` puts stations on a BAND_HZ wide slice of band, each calling CQ
+// and working others on a frequency of its own, and hands over the VFO: the
+// arrow keys tune across it, and a station is heard at the `--tone` pitch
+// when zero-beat, higher or lower either side of that, and through the
+// receiver filter, which fades it out once it is more than half the filter
+// width off. Finding a station, centring it and picking the filter that
+// keeps its neighbours out is then practice of its own.
+const DIAL_KHZ: f64 = 7025.0;
+const BAND_HZ: u32 = 5000;
+// Closest two stations start
+const SPACING_HZ: f64 = 120.0;
+const STEPS_HZ: [u32; 4] = [10, 50, 100, 500];
+const FILTERS_HZ: [u32; 3] = [250, 500, 2400];
+// Noise is as loud as with `--qrm` through the widest filter
+const WIDEST_HZ: f64 = 2400.0;
+// How often the filter gains follow the VFO, and how fast they get there
+const UPDATE_SECS: f64 = 0.01;
+const GAIN_SMOOTHING: f32 = 0.005;
+const KEY_POLL: Duration = Duration::from_millis(20);
+
+// ---------- Split pileups ---------------------------------------------------
+// With `--split` the band is a DXpedition working split: the DX sends on
+// DX_HZ and listens somewhere in SPLIT_HZ, up where the callers are. Each
+// round most callers call (more of them the denser the pileup), the DX
+// answers whoever is nearest where it is listening, that caller gives a
+// report, and the DX says TU UP and moves up a little, back to the bottom
+// of the split once it runs off the top. Finding where the DX is listening
+// is the listener's part: `l` says it is where the VFO is, and is right
+// within LISTEN_HZ.
+const DX_HZ: f64 = 500.0;
+const SPLIT_HZ: (f64, f64) = (1500.0, 4500.0);
+const LISTEN_HZ: f64 = 150.0;
+// How far the DX moves after each QSO
+const QSY_HZ: (f64, f64) = (50.0, 300.0);
+
+enum Phase {
+    // The DX has sent TU UP, or its CQ
+    Thanked,
+    Calling,
+    Answered(usize),
+    Reported(usize),
+}
+
+struct Split {
+    dx: String,
+    listening: f64,
+    pileup: u8,
+    phase: Phase,
+    calling: Vec<usize>,
+    qsos: usize,
+    sample_rate: u32,
+}
+
+impl Split {
+    fn caller(rng: &mut impl Rng, sample_rate: u32) -> Interferer {
+        let frequency = rng.random_range(SPLIT_HZ.0..SPLIT_HZ.1);
+        Interferer::scripted(frequency, rng.random_range(18..=32), rng.random_range(0.05..0.4), sample_rate)
+    }
+
+    // Next in the QSO, once everyone has finished sending. Station 0 is the
+    // DX, the rest are callers.
+    fn step(&mut self, stations: &mut [Interferer], tuning: &Tuning) {
+        if !stations.iter().all(Interferer::idle) {
+            return;
+        }
+        let mut rng = rand::rng();
+        self.phase = match self.phase {
+            Phase::Thanked => {
+                let chance = 0.5 + self.pileup.min(9) as f64 / 18.0;
+                self.calling = (1..stations.len()).filter(|_| rng.random_bool(chance)).collect();
+                if self.calling.is_empty() {
+                    self.calling.push(rng.random_range(1..stations.len()));
+                }
+                for &i in &self.calling {
+                    let call = stations[i].call().unwrap_or_default().to_string();
+                    stations[i].pause(rng.random_range(0.1..1.2));
+                    stations[i].send(&if rng.random_bool(0.5) { call.clone() } else { format!("{} {}", call, call) });
+                }
+                Phase::Calling
+            }
+            Phase::Calling => {
+                let distance = |i: &usize| (stations[*i].frequency() - self.listening).abs();
+                let worked = *self.calling.iter().min_by(|a, b| distance(a).total_cmp(&distance(b))).unwrap();
+                self.listening = stations[worked].frequency();
+                let call = stations[worked].call().unwrap_or_default().to_string();
+                stations[0].send(&format!("{} 5NN", call));
+                Phase::Answered(worked)
+            }
+            Phase::Answered(worked) => {
+                stations[worked].send(if rng.random_bool(0.5) { "TU 5NN" } else { "R 5NN" });
+                Phase::Reported(worked)
+            }
+            Phase::Reported(worked) => {
+                self.qsos += 1;
+                // Sign every few QSOs
+                stations[0].send(&match self.qsos % 3 {
+                    0 => format!("TU {} UP", self.dx),
+                    _ => "TU UP".to_string(),
+                });
+                stations[worked] = Split::caller(&mut rng, self.sample_rate);
+                self.listening += rng.random_range(QSY_HZ.0..QSY_HZ.1);
+                if self.listening > SPLIT_HZ.1 {
+                    self.listening = SPLIT_HZ.0;
+                }
+                Phase::Thanked
+            }
+        };
+        tuning.listening.store(self.listening.round() as u32, Ordering::Relaxed);
+    }
+}
+
+/// The receiver's response `offset_hz` from the centre of a `width_hz`
+/// filter: a fourth-order Butterworth, 3 dB down at the edges.
+pub fn response(offset_hz: f64, width_hz: f64) -> f64 {
+    1.0 / (1.0 + (2.0 * offset_hz / width_hz).powi(8)).sqrt()
+}
+
+/// The dial, shared between the keyboard and the audio.
+pub struct Tuning {
+    /// Hz from the bottom of the band
+    vfo: AtomicU32,
+    width: AtomicU32,
+    /// Where each station is, its call and its speed, as last heard
+    stations: Mutex<Vec<(f64, String, u32)>>,
+    /// Where the DX is listening, working split (0 when not)
+    listening: AtomicU32,
+}
+
+impl Tuning {
+    pub fn vfo(&self) -> u32 {
+        self.vfo.load(Ordering::Relaxed)
+    }
+
+    pub fn set_vfo(&self, hz: u32) {
+        self.vfo.store(hz.min(BAND_HZ), Ordering::Relaxed);
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width.load(Ordering::Relaxed)
+    }
+
+    pub fn listening(&self) -> Option<u32> {
+        Some(self.listening.load(Ordering::Relaxed)).filter(|hz| *hz > 0)
+    }
+
+    /// The station closest to the VFO, if one is inside the filter: how
+    /// far off it is, its call and its speed.
+    pub fn nearest(&self) -> Option<(f64, String, u32)> {
+        let vfo = self.vfo() as f64;
+        let half = self.width() as f64 / 2.0;
+        self.stations
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(frequency, call, wpm)| (frequency - vfo, call.clone(), *wpm))
+            .filter(|(offset, _, _)| offset.abs() <= half)
+            .min_by(|a, b| a.0.abs().total_cmp(&b.0.abs()))
+    }
+}
+
+pub struct Receiver {
+    stations: Vec<Interferer>,
+    gains: Vec<f32>,
+    targets: Vec<f32>,
+    noise: NoiseSource,
+    noise_gain: f32,
+    tuning: Arc<Tuning>,
+    split: Option<Split>,
+    tone: u32,
+    sample_rate: u32,
+    update_in: usize,
+}
+
+impl Receiver {
+    /// `count` stations scattered over the band, heard with the noise and
+    /// pileup of `conditions`, through a `width` Hz filter.
+    pub fn new(count: usize, width: u32, conditions: Conditions, tone: u32, sample_rate: u32) -> Self {
+        let mut rng = rand::rng();
+        let mut frequencies: Vec<f64> = Vec::with_capacity(count);
+        while frequencies.len() < count {
+            let frequency = rng.random_range(SPACING_HZ..BAND_HZ as f64 - SPACING_HZ);
+            // Give up on the spacing if the band is too crowded for it
+            let crowded = frequencies.len() as f64 * SPACING_HZ > BAND_HZ as f64 / 2.0;
+            if crowded || frequencies.iter().all(|other| (other - frequency).abs() >= SPACING_HZ) {
+                frequencies.push(frequency);
+            }
+        }
+        let stations = frequencies
+            .into_iter()
+            .map(|frequency| Interferer::station(frequency, conditions.pileup, conditions.interferer_drift, sample_rate))
+            .collect();
+        Receiver::with_stations(stations, width, conditions, tone, sample_rate)
+    }
+
+    /// A DX working split, with `callers` calling it, and the VFO on the
+    /// DX.
+    pub fn split(callers: usize, width: u32, conditions: Conditions, tone: u32, sample_rate: u32) -> Self {
+        let mut rng = rand::rng();
+        let dx = Interferer::scripted(DX_HZ, rng.random_range(26..=34), 0.5, sample_rate);
+        let call = dx.call().unwrap_or_default().to_string();
+        let mut stations = vec![dx];
+        stations.extend((0..callers).map(|_| Split::caller(&mut rng, sample_rate)));
+        stations[0].send(&format!("CQ DE {} {} UP", call, call));
+        let mut receiver = Receiver::with_stations(stations, width, conditions, tone, sample_rate);
+        receiver.tuning.set_vfo(DX_HZ as u32);
+        receiver.split = Some(Split {
+            dx: call,
+            listening: rng.random_range(SPLIT_HZ.0..SPLIT_HZ.1),
+            pileup: conditions.pileup,
+            phase: Phase::Thanked,
+            calling: Vec::new(),
+            qsos: 0,
+            sample_rate,
+        });
+        receiver
+    }
+
+    fn with_stations(stations: Vec<Interferer>, width: u32, conditions: Conditions, tone: u32, sample_rate: u32) -> Self {
+        let count = stations.len();
+        let tuning = Tuning {
+            vfo: AtomicU32::new(BAND_HZ / 2),
+            width: AtomicU32::new(width),
+            stations: Mutex::new(Vec::new()),
+            listening: AtomicU32::new(0),
+        };
+        Receiver {
+            stations,
+            gains: vec![0.0; count],
+            targets: vec![0.0; count],
+            // The stations here are the only ones
+            noise: NoiseSource::new(Conditions { interferers: 0, ..conditions }, tone, sample_rate),
+            noise_gain: 1.0,
+            tuning: Arc::new(tuning),
+            split: None,
+            tone,
+            sample_rate,
+            update_in: 0,
+        }
+    }
+
+    pub fn tuning(&self) -> Arc<Tuning> {
+        self.tuning.clone()
+    }
+
+    // Follow the dial: retune every station, and work out how much of each
+    // gets through the filter
+    fn update(&mut self) {
+        if let Some(split) = &mut self.split {
+            split.step(&mut self.stations, &self.tuning);
+        }
+        let shift = self.tone as f64 - self.tuning.vfo() as f64;
+        let width = self.tuning.width() as f64;
+        for (station, target) in self.stations.iter_mut().zip(&mut self.targets) {
+            station.tune(shift);
+            let pitch = station.frequency() + shift;
+            // Too low to hear, or on the other side of zero beat
+            *target = if pitch < 100.0 { 0.0 } else { response(pitch - self.tone as f64, width) as f32 };
+        }
+        self.noise_gain = (width / WIDEST_HZ).sqrt() as f32;
+        if let Ok(mut heard) = self.tuning.stations.try_lock() {
+            *heard = self
+                .stations
+                .iter()
+                .map(|station| (station.frequency(), station.call().unwrap_or_default().to_string(), station.wpm()))
+                .collect();
+        }
+        self.update_in = (UPDATE_SECS * self.sample_rate as f64) as usize;
+    }
+}
+
+impl Iterator for Receiver {
+    type Item = f32;
+    fn next(&mut self) -> Option<f32> {
+        if self.update_in == 0 {
+            self.update();
+        }
+        self.update_in -= 1;
+        let mut sample = 0.0;
+        for ((station, gain), target) in self.stations.iter_mut().zip(&mut self.gains).zip(&self.targets) {
+            *gain += (target - *gain) * GAIN_SMOOTHING;
+            sample += station.next() * *gain;
+        }
+        Some(sample + self.noise.next().unwrap_or_default() * self.noise_gain)
+    }
+}
+
+impl Source for Receiver {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { 1 }
+    fn sample_rate(&self) -> u32 { self.sample_rate }
+    fn total_duration(&self) -> Option<Duration> { None }
+}
+
+/// Tune across `stations` stations (callers, with `split`) until Esc.
+pub fn run(stations: usize, split: bool, width: u32, conditions: Conditions, tone: u32, speaker: &Speaker) -> Result<()> {
+    let receiver = match split {
+        true => Receiver::split(stations, width, conditions, tone, playback_rate()),
+        false => Receiver::new(stations, width, conditions, tone, playback_rate()),
+    };
+    let tuning = receiver.tuning();
+    let (_stream, handle) = output_stream()?;
+    let sink = Sink::try_new(&handle).map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+    sink.append(equalize(receiver, &speaker.eq));
+
+    let dial = |hz: f64| DIAL_KHZ + hz / 1000.0;
+    match split {
+        true => println!(
+            "A DX on {:.3} kHz is working split, listening between {:.1} and {:.1} kHz. \
+             ←/→ tune, ↑/↓ step, w filter, Enter identifies, l when you've found where the DX listens, Esc quits.\n",
+            dial(DX_HZ),
+            dial(SPLIT_HZ.0),
+            dial(SPLIT_HZ.1),
+        ),
+        false => println!(
+            "{} stations between {:.1} and {:.1} kHz. ←/→ tune, ↑/↓ step, w filter, Enter identifies, Esc quits.\n",
+            stations,
+            DIAL_KHZ,
+            dial(BAND_HZ as f64),
+        ),
+    }
+    let mut step = 1;
+    let (mut tries, mut found) = (0, 0);
+    let mut stdout = std::io::stdout();
+    terminal::enable_raw_mode()?;
+    let result = (|| -> Result<()> {
+        loop {
+            queue!(stdout, MoveToColumn(0), Clear(ClearType::CurrentLine))?;
+            write!(
+                stdout,
+                "{:.3} kHz   step {} Hz   filter {} Hz",
+                dial(tuning.vfo() as f64),
+                STEPS_HZ[step],
+                tuning.width(),
+            )?;
+            stdout.flush()?;
+            let key = loop {
+                if event::poll(KEY_POLL)? {
+                    if let Event::Key(key) = event::read()? {
+                        break key;
+                    }
+                }
+            };
+            match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(()),
+                KeyCode::Left => tuning.set_vfo(tuning.vfo().saturating_sub(STEPS_HZ[step])),
+                KeyCode::Right => tuning.set_vfo(tuning.vfo() + STEPS_HZ[step]),
+                KeyCode::Up => step = (step + 1).min(STEPS_HZ.len() - 1),
+                KeyCode::Down => step = step.saturating_sub(1),
+                KeyCode::Char('w') => {
+                    let next = FILTERS_HZ.iter().position(|&w| w == tuning.width()).map_or(0, |i| (i + 1) % FILTERS_HZ.len());
+                    tuning.width.store(FILTERS_HZ[next], Ordering::Relaxed);
+                }
+                KeyCode::Enter => {
+                    let heard = match tuning.nearest() {
+                        Some((offset, call, wpm)) => format!("{} at {} WPM, {:+.0} Hz off", call, wpm, offset),
+                        None => "nobody inside the filter".into(),
+                    };
+                    write!(stdout, "   {}\r\n", heard)?;
+                }
+                KeyCode::Char('l') => {
+                    let Some(listening) = tuning.listening() else { continue };
+                    let off = tuning.vfo() as f64 - listening as f64;
+                    tries += 1;
+                    let verdict = match off.abs() <= LISTEN_HZ {
+                        true => {
+                            found += 1;
+                            "✓".to_string()
+                        }
+                        false => format!("✗ {:+.0} Hz off:", off),
+                    };
+                    write!(stdout, "   {} the DX is listening on {:.3} kHz\r\n", verdict, dial(listening as f64))?;
+                }
+                _ => {}
+            }
+        }
+    })();
+    terminal::disable_raw_mode()?;
+    sink.stop();
+    println!();
+    if tries > 0 {
+        println!("Found where the DX was listening {}/{} times", found, tries);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_response() {
+        assert!((response(0.0, 500.0) - 1.0).abs() < 1e-9);
+        assert!((response(250.0, 500.0) - 0.5f64.sqrt()).abs() < 1e-9);
+        assert!(response(-1000.0, 500.0) < 0.01);
+        assert!(response(1000.0, 2400.0) > 0.7);
+    }
+
+    #[test]
+    fn test_receiver_tuning() {
+        let mut receiver = Receiver::new(6, 250, Conditions::default(), 600, 8000);
+        let tuning = receiver.tuning();
+        receiver.update();
+        let (frequency, call, _) = tuning.stations.lock().unwrap()[2].clone();
+        // Zero beat on the third station: it comes through, and those well
+        // off it don't
+        tuning.set_vfo(frequency.round() as u32);
+        receiver.by_ref().take(2000).for_each(drop);
+        assert!(receiver.gains[2] > 0.95);
+        for (station, gain) in receiver.stations.iter().zip(&receiver.gains) {
+            assert!((station.frequency() - frequency).abs() < 500.0 || *gain < 0.05);
+        }
+        assert_eq!(tuning.nearest().map(|(_, heard, _)| heard), Some(call));
+    }
+
+    #[test]
+    fn test_split_pileup() {
+        let mut receiver = Receiver::split(6, 500, Conditions { pileup: 9, ..Conditions::default() }, 600, 4000);
+        let tuning = receiver.tuning();
+        assert_eq!(tuning.vfo(), DX_HZ as u32);
+        // Run until the DX answers someone: whoever is nearest where it was
+        // listening, which is now where that caller is
+        let mut worked = None;
+        for _ in 0..4000 * 60 {
+            receiver.next();
+            if let Some(Split { phase: Phase::Answered(i), .. }) = receiver.split {
+                worked = Some(i);
+                break;
+            }
+        }
+        let worked = worked.expect("no QSO in a minute");
+        let listening = tuning.listening().unwrap() as f64;
+        assert!((receiver.stations[worked].frequency() - listening).abs() < 1.0);
+        assert!((SPLIT_HZ.0..SPLIT_HZ.1).contains(&listening));
+        assert!(!receiver.stations[0].idle());
+    }
+}
@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::extract::{self, Found, Kind};
+use crate::live::{LiveEvent, Transmission, Transmissions};
+
+// The "version" in each datagram, for listeners to check before reading
+// the fields (which they should treat as open-ended)
+const SCHEMA_VERSION: u32 = 1;
+
+// ---------- Wire format -----------------------------------------------------
+// One JSON object per datagram:
+//
+//     {"source":"cwgen","version":1,"time":"2026-10-17T14:03:22.512Z","type":"char","ch":"K",...}
+//
+// `type` is one of:
+//   locked       tone_hz                      the decoder found a signal
+//   char         ch, confidence, wpm, tone_hz a character as soon as it is read
+//   transmission start, end, text, wpm,       a whole transmission, after the
+//                tone_hz, callsigns, extracted squelch closes
+#[derive(Serialize)]
+struct Datagram<'a> {
+    source: &'static str,
+    version: u32,
+    time: String,
+    #[serde(flatten)]
+    message: Message<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message<'a> {
+    Locked {
+        tone_hz: f64,
+    },
+    Char {
+        ch: char,
+        confidence: f64,
+        wpm: Option<f64>,
+        tone_hz: Option<f64>,
+    },
+    Transmission {
+        start: String,
+        end: String,
+        text: &'a str,
+        wpm: Option<f64>,
+        tone_hz: Option<f64>,
+        callsigns: Vec<&'a str>,
+        extracted: Vec<Found>,
+    },
+}
+
+// ---------- Sender ----------------------------------------------------------
+/// Sends decoder output to a UDP listener (a logger, a dashboard, or a
+/// broadcast address) as it happens.
+pub struct Broadcast {
+    socket: UdpSocket,
+    target: SocketAddr,
+    transmissions: Transmissions,
+}
+
+impl Broadcast {
+    pub fn new(target: &str) -> Result<Self> {
+        let target = target
+            .to_socket_addrs()
+            .with_context(|| format!("resolving {}", target))?
+            .next()
+            .with_context(|| format!("no address for {}", target))?;
+        let bind: SocketAddr = if target.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_broadcast(true)?;
+        Ok(Broadcast { socket, target, transmissions: Transmissions::default() })
+    }
+
+    /// Send whatever `event` has to report. A lost datagram shouldn't stop
+    /// the decoder, so failures are only printed.
+    pub fn event(&mut self, event: &LiveEvent) {
+        let transmission = self.transmissions.update(event);
+        let message = match event {
+            LiveEvent::Locked(tone) => Some(Message::Locked { tone_hz: *tone }),
+            LiveEvent::Char(c) => Some(Message::Char {
+                ch: c.ch,
+                confidence: c.confidence,
+                wpm: c.wpm,
+                tone_hz: c.tone_hz,
+            }),
+            LiveEvent::SignalEnd => transmission.as_ref().map(transmission_message),
+            LiveEvent::SignalStart => None,
+        };
+        if let Some(message) = message {
+            if let Err(e) = self.send(message) {
+                eprintln!("UDP send to {} failed: {}", self.target, e);
+            }
+        }
+    }
+
+    fn send(&self, message: Message) -> Result<()> {
+        let datagram = Datagram {
+            source: "cwgen",
+            version: SCHEMA_VERSION,
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            message,
+        };
+        self.socket.send_to(&serde_json::to_vec(&datagram)?, self.target)?;
+        Ok(())
+    }
+}
+
+fn transmission_message(transmission: &Transmission) -> Message<'_> {
+    let extracted = extract::extract(&transmission.text);
+    let mut callsigns: Vec<&str> = Vec::new();
+    for f in extracted.iter().filter(|f| f.kind == Kind::Callsign) {
+        let call = &transmission.text[f.start..f.end];
+        if !callsigns.contains(&call) {
+            callsigns.push(call);
+        }
+    }
+    Message::Transmission {
+        start: transmission.start.to_rfc3339_opts(SecondsFormat::Millis, true),
+        end: transmission.end.to_rfc3339_opts(SecondsFormat::Millis, true),
+        text: &transmission.text,
+        wpm: transmission.wpm,
+        tone_hz: transmission.tone_hz,
+        callsigns,
+        extracted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::DecodedChar;
+
+    #[test]
+    fn test_transmission_datagram() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener.set_read_timeout(Some(std::time::Duration::from_secs(2))).unwrap();
+        let mut udp = Broadcast::new(&listener.local_addr().unwrap().to_string()).unwrap();
+
+        let char = |ch| LiveEvent::Char(DecodedChar {
+            ch, confidence: 1.0, alternatives: Vec::new(), wpm: Some(20.0), tone_hz: Some(700.0),
+        });
+        udp.event(&LiveEvent::SignalStart);
+        for ch in "CQ W1AW K".chars() {
+            udp.event(&char(ch));
+        }
+        udp.event(&LiveEvent::SignalEnd);
+
+        let mut buf = [0u8; 2048];
+        let mut last = serde_json::Value::Null;
+        for _ in 0..9 {
+            let len = listener.recv(&mut buf).unwrap();
+            last = serde_json::from_slice(&buf[..len]).unwrap();
+        }
+        assert_eq!(last["type"], "char");
+        let len = listener.recv(&mut buf).unwrap();
+        let message: serde_json::Value = serde_json::from_slice(&buf[..len]).unwrap();
+        assert_eq!(message["source"], "cwgen");
+        assert_eq!(message["type"], "transmission");
+        assert_eq!(message["text"], "CQ W1AW K");
+        assert_eq!(message["callsigns"], serde_json::json!(["W1AW"]));
+        assert_eq!(message["extracted"][0]["kind"], "callsign");
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use crate::audio::{ToneGenerator, ToneShape};
+use crate::morse::{key_timeline, Element, Timing};
+
+// ---------- VOX keying ------------------------------------------------------
+// Some rig interfaces key the transmitter from audio: a tone present means
+// key down. This renders the keying as that tone, steady and strong with
+// edges just soft enough not to click, instead of a shaped sidetone. A
+// rig switching over on VOX swallows the start of the first element, so the
+// tone comes up `--vox-lead-in` early at the start of a transmission.
+#[derive(clap::Args, Debug, Clone)]
+pub struct VoxArgs {
+    /// With --output vox, how much earlier the tone comes up at the start of
+    /// a transmission, in ms, for the VOX to switch over
+    #[arg(long, value_name = "MS", default_value_t = 50)]
+    pub vox_lead_in: u64,
+}
+
+// Peak level of the keying tone: strong enough for any detector, with room
+// left before the soundcard clips
+const LEVEL: f32 = 0.8;
+// Rise and fall of each element
+const EDGE_SECS: f64 = 0.001;
+
+/// Keying tone for `text`, with the first element `lead_in` longer.
+pub fn render(text: &str, timing: &Timing, tone: u32, sample_rate: u32, lead_in: Duration) -> Vec<f32> {
+    render_elements(&key_timeline(text, timing), tone, sample_rate, lead_in)
+}
+
+/// Keying tone for any on/off timeline, such as a PTT schedule.
+pub fn render_elements(elements: &[Element], tone: u32, sample_rate: u32, lead_in: Duration) -> Vec<f32> {
+    let mut generator = ToneGenerator::new(tone, sample_rate, ToneShape::Sine, None);
+    let edge = ((EDGE_SECS * sample_rate as f64) as usize).max(1);
+    let mut samples = Vec::new();
+    let mut first = true;
+    for element in elements {
+        let mut length = (element.duration.as_secs_f64() * sample_rate as f64) as usize;
+        if !element.key_down {
+            samples.resize(samples.len() + length, 0.0);
+            continue;
+        }
+        if first {
+            length += (lead_in.as_secs_f64() * sample_rate as f64) as usize;
+            first = false;
+        }
+        for i in 0..length {
+            let gain = (i.min(length - 1 - i) as f32 / edge as f32).min(1.0);
+            samples.push(LEVEL * gain * generator.next_sample(0.0));
+        }
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vox_keying() {
+        // 20 WPM: 60 ms dots, so "E E" is a dot, a word gap and a dot
+        let timing = Timing::new(20, 0);
+        let samples = render("E E", &timing, 1000, 8000, Duration::from_millis(50));
+        let keyed: Vec<bool> = samples.chunks(40).map(|chunk| chunk.iter().any(|s| s.abs() > 0.5)).collect();
+        let first_off = keyed.iter().position(|on| !on).unwrap();
+        // Lead-in plus a dot, in 5 ms chunks
+        assert_eq!(first_off, 22);
+        let second_on = first_off + keyed[first_off..].iter().position(|on| *on).unwrap();
+        assert_eq!(second_on - first_off, 84);
+        assert!(samples.iter().all(|s| s.abs() <= LEVEL));
+    }
+}
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use crossterm::style::{Color, Stylize};
+use rodio::buffer::SamplesBuffer;
+use rodio::Sink;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::audio::{output_stream, Speaker};
+use crate::eq::equalize;
+use crate::decode::goertzel;
+use crate::morse::MorseError;
+
+// ---------- Terminal waterfall ----------------------------------------------
+// `--output waterfall` plays the signal and draws it as it plays, the way an
+// SDR's waterfall shows CW: frequency across the terminal, SPAN_HZ either side
+// of the tone (from 0 Hz up for a low one), and time running down it a row at
+// a time, each row half a dot long, so dots are short blocks and dashes long
+// ones that can be read by eye. The QRM, static and interfering stations of
+// the band conditions show up too, as speckle and as other traces to either
+// side. Each row is worked out as it is due, with the same Goertzel bank as
+// `render --plot`, and shown in shades from blank (DYNAMIC_RANGE_DB down) to a
+// full block (a full-scale tone).
+const SPAN_HZ: f64 = 700.0;
+const DYNAMIC_RANGE_DB: f64 = 50.0;
+const MAX_COLUMNS: usize = 100;
+// Rows are kept between these, whatever the speed
+const MIN_ROW: Duration = Duration::from_millis(15);
+const MAX_ROW: Duration = Duration::from_millis(60);
+const SHADES: [(char, Color); 6] = [
+    (' ', Color::Reset),
+    ('·', Color::DarkBlue),
+    ('░', Color::Blue),
+    ('▒', Color::DarkYellow),
+    ('▓', Color::Yellow),
+    ('█', Color::White),
+];
+
+pub struct Waterfall {
+    pub row_length: Duration,
+    row_samples: usize,
+    window: Vec<f32>,
+    /// A full-scale tone through the window, in dB
+    full_scale: f64,
+    columns: usize,
+    sample_rate: u32,
+    low_hz: f64,
+    high_hz: f64,
+}
+
+impl Waterfall {
+    /// A waterfall `columns` wide around `tone`, with rows of about half a
+    /// `dot`.
+    pub fn new(sample_rate: u32, tone: u32, columns: usize, dot: Duration) -> Self {
+        let row_length = (dot / 2).clamp(MIN_ROW, MAX_ROW);
+        let row_samples = ((row_length.as_secs_f64() * sample_rate as f64) as usize).max(1);
+        let low_hz = (tone as f64 - SPAN_HZ).max(0.0);
+        let high_hz = (low_hz + 2.0 * SPAN_HZ).min(sample_rate as f64 / 2.0);
+        let bin_hz = (high_hz - low_hz) / columns as f64;
+        // Frames long enough to tell neighbouring columns apart, centred on
+        // each row
+        let frame = ((sample_rate as f64 / bin_hz) as usize).max(row_samples);
+        let window: Vec<f32> = (0..frame)
+            .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / frame as f32).cos())
+            .collect();
+        // A Hann window halves a tone's sum, and its power is a quarter of that squared
+        let full_scale = 20.0 * (frame as f64 / 4.0).log10();
+        Waterfall { row_length, row_samples, window, full_scale, columns, sample_rate, low_hz, high_hz }
+    }
+
+    /// How many rows `samples` take.
+    pub fn rows(&self, samples: &[f32]) -> usize {
+        samples.len().div_ceil(self.row_samples)
+    }
+
+    /// Row `row` of `samples`, per column from low to high frequency: 0
+    /// (DYNAMIC_RANGE_DB down or less) to 1 (full scale).
+    pub fn row(&self, samples: &[f32], row: usize) -> Vec<f64> {
+        let frame = self.window.len();
+        let from = (row * self.row_samples + self.row_samples / 2).saturating_sub(frame / 2);
+        let windowed: Vec<f32> = (0..frame)
+            .map(|i| samples.get(from + i).copied().unwrap_or_default() * self.window[i])
+            .collect();
+        let bin_hz = (self.high_hz - self.low_hz) / self.columns as f64;
+        (0..self.columns)
+            .map(|column| {
+                let hz = self.low_hz + (column as f64 + 0.5) * bin_hz;
+                let db = 10.0 * (goertzel(&windowed, self.sample_rate, hz) + 1e-12).log10();
+                ((db - self.full_scale + DYNAMIC_RANGE_DB) / DYNAMIC_RANGE_DB).clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+
+    /// The frequency scale to go above the rows.
+    pub fn scale(&self, columns: usize) -> String {
+        let low = format!("{:.0} Hz", self.low_hz);
+        let middle = format!("{:.0}", (self.low_hz + self.high_hz) / 2.0);
+        let high = format!("{:.0} Hz", self.high_hz);
+        let mut scale = format!("{:<width$}", low, width = columns / 2 - middle.len() / 2);
+        scale.push_str(&middle);
+        let rest = columns.saturating_sub(scale.chars().count());
+        scale.push_str(&format!("{:>width$}", high, width = rest));
+        scale
+    }
+}
+
+/// One row in coloured shades.
+pub fn line(row: &[f64]) -> String {
+    row.iter()
+        .map(|&level| {
+            let (shade, color) = SHADES[((level * SHADES.len() as f64) as usize).min(SHADES.len() - 1)];
+            shade.with(color).to_string()
+        })
+        .collect()
+}
+
+/// Play `samples` through `speaker` and draw their waterfall in step with
+/// them.
+pub fn show(samples: Vec<f32>, sample_rate: u32, tone: u32, dot: Duration, speaker: &Speaker) -> Result<()> {
+    let width = crossterm::terminal::size().map_or(80, |(width, _)| width as usize);
+    let columns = width.saturating_sub(2).clamp(20, MAX_COLUMNS);
+    let waterfall = Waterfall::new(sample_rate, tone, columns, dot);
+    let (_stream, handle) = output_stream()?;
+    let sink = Sink::try_new(&handle).map_err(|e| MorseError::AudioDeviceError(e.to_string()))?;
+    sink.append(equalize(SamplesBuffer::new(1, sample_rate, samples.clone()), &speaker.eq));
+    println!("{}", waterfall.scale(columns));
+    let start = Instant::now();
+    let mut stdout = std::io::stdout();
+    for i in 0..waterfall.rows(&samples) {
+        let row = waterfall.row(&samples, i);
+        if let Some(wait) = (waterfall.row_length * i as u32).checked_sub(start.elapsed()) {
+            std::thread::sleep(wait);
+        }
+        writeln!(stdout, "{}", line(&row))?;
+        stdout.flush()?;
+    }
+    sink.sleep_until_end();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waterfall(tone: u32) -> Waterfall {
+        Waterfall::new(8000, tone, 70, Duration::from_millis(60))
+    }
+
+    #[test]
+    fn test_waterfall() {
+        // A tenth of a second of silence, then 600 Hz
+        let rate = 8000;
+        let samples: Vec<f32> = (0..rate / 5)
+            .map(|i| if i < rate / 10 { 0.0 } else { (std::f32::consts::TAU * 600.0 * i as f32 / rate as f32).sin() * 0.5 })
+            .collect();
+        let waterfall = waterfall(600);
+        assert_eq!(waterfall.row_length, Duration::from_millis(30));
+        assert_eq!(waterfall.rows(&samples), 7);
+        let row = waterfall.row(&samples, 5);
+        let loudest = (0..70).max_by(|a, b| row[*a].total_cmp(&row[*b])).unwrap();
+        assert!(loudest.abs_diff(30) <= 1);
+        assert!(waterfall.row(&samples, 0)[loudest] < 0.1);
+        assert!(line(&row).contains('█'));
+    }
+
+    #[test]
+    fn test_waterfall_scale() {
+        // Starting at 0 Hz below a low tone, and centred on a higher one
+        let scale = waterfall(600).scale(70);
+        assert_eq!(scale.len(), 70);
+        assert!(scale.starts_with("0 Hz") && scale.ends_with("1400 Hz"));
+        assert!(waterfall(1200).scale(70).contains(" 1200 "));
+    }
+}
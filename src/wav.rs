@@ -0,0 +1,206 @@
+use anyhow::{Context, Result};
+use rand::Rng;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+
+use crate::audio::{dither_to_i16, WavFormat};
+
+// ---------- WAV writer ------------------------------------------------------
+// Written files are plain RIFF WAV while they fit in its 4 GB and turn into
+// RF64 (EBU Tech 3306) past that; a book-length render at 44.1 kHz stereo
+// would otherwise wrap its 32-bit lengths and leave a file that plays for a
+// few minutes. Room for RF64's ds64 chunk is kept at the start of every file
+// as a JUNK chunk, which readers skip, so the switch is just a different
+// header. The layout is fixed, so the samples always start at DATA_START.
+const DS64_LEN: u32 = 28;
+const FMT_LEN: u32 = 16;
+const DATA_START: u64 = 12 + 8 + DS64_LEN as u64 + 8 + FMT_LEN as u64 + 8;
+
+pub struct WavFile {
+    file: BufWriter<File>,
+    format: WavFormat,
+    channels: u16,
+    sample_rate: u32,
+    data_bytes: u64,
+}
+
+impl WavFile {
+    pub fn create(path: &str, sample_rate: u32, format: WavFormat, channels: u16) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("writing {}", path))?;
+        let mut wav = WavFile { file: BufWriter::new(file), format, channels, sample_rate, data_bytes: 0 };
+        wav.write_header()?;
+        Ok(wav)
+    }
+
+    /// Open a file this wrote before to carry on after its first
+    /// `data_bytes` bytes of samples, dropping anything after them.
+    pub fn resume(path: &str, sample_rate: u32, format: WavFormat, channels: u16, data_bytes: u64) -> Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path).with_context(|| format!("opening {}", path))?;
+        let mut header = [0u8; DATA_START as usize];
+        file.read_exact(&mut header).with_context(|| format!("reading {}", path))?;
+        let mut wav = WavFile { file: BufWriter::new(file), format, channels, sample_rate, data_bytes };
+        if header[8..12] != *b"WAVE" || header[72..76] != *b"data" || header[56..72] != wav.fmt() {
+            anyhow::bail!("{} wasn't written with the same sample rate, bit depth and channel count", path);
+        }
+        wav.file.get_ref().set_len(DATA_START + data_bytes)?;
+        wav.write_header()?;
+        wav.file.seek(SeekFrom::End(0))?;
+        Ok(wav)
+    }
+
+    /// Write one sample to `repeat` channels. Dithered once per frame, so
+    /// dual-mono channels stay identical.
+    pub fn write(&mut self, sample: f32, repeat: u16, rng: &mut impl Rng) -> Result<()> {
+        let mut bytes = [0u8; 4];
+        let length = match self.format {
+            WavFormat::Int16 => {
+                bytes[..2].copy_from_slice(&dither_to_i16(sample, rng).to_le_bytes());
+                2
+            }
+            WavFormat::Int24 => {
+                bytes = ((sample * 8_388_607.0).round().clamp(-8_388_608.0, 8_388_607.0) as i32).to_le_bytes();
+                3
+            }
+            WavFormat::Float32 => {
+                bytes = sample.to_le_bytes();
+                4
+            }
+        };
+        for _ in 0..repeat {
+            self.file.write_all(&bytes[..length])?;
+        }
+        self.data_bytes += (length * repeat as usize) as u64;
+        Ok(())
+    }
+
+    /// Frames written.
+    pub fn frames(&self) -> u64 {
+        self.data_bytes / self.block_align() as u64
+    }
+
+    pub fn data_bytes(&self) -> u64 {
+        self.data_bytes
+    }
+
+    /// Bring the header up to date, so the file plays as far as it has
+    /// been written.
+    pub fn flush(&mut self) -> Result<()> {
+        self.write_header()?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.flush()
+    }
+
+    fn is_rf64(&self) -> bool {
+        DATA_START - 8 + self.data_bytes > u32::MAX as u64
+    }
+
+    fn block_align(&self) -> u16 {
+        self.channels * self.bytes_per_sample()
+    }
+
+    fn bytes_per_sample(&self) -> u16 {
+        match self.format {
+            WavFormat::Int16 => 2,
+            WavFormat::Int24 => 3,
+            WavFormat::Float32 => 4,
+        }
+    }
+
+    fn fmt(&self) -> Vec<u8> {
+        let tag: u16 = if let WavFormat::Float32 = self.format { 3 } else { 1 };
+        let mut fmt = Vec::with_capacity(FMT_LEN as usize);
+        fmt.extend(tag.to_le_bytes());
+        fmt.extend(self.channels.to_le_bytes());
+        fmt.extend(self.sample_rate.to_le_bytes());
+        fmt.extend((self.sample_rate * self.block_align() as u32).to_le_bytes());
+        fmt.extend(self.block_align().to_le_bytes());
+        fmt.extend((self.bytes_per_sample() * 8).to_le_bytes());
+        fmt
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let riff_size = DATA_START - 8 + self.data_bytes;
+        let rf64 = self.is_rf64();
+        let short = |size: u64| if rf64 { u32::MAX } else { size as u32 };
+        let mut header = Vec::with_capacity(DATA_START as usize);
+        header.extend(if rf64 { b"RF64" } else { b"RIFF" });
+        header.extend(short(riff_size).to_le_bytes());
+        header.extend(b"WAVE");
+        header.extend(if rf64 { b"ds64" } else { b"JUNK" });
+        header.extend(DS64_LEN.to_le_bytes());
+        header.extend(riff_size.to_le_bytes());
+        header.extend(self.data_bytes.to_le_bytes());
+        header.extend(self.frames().to_le_bytes());
+        header.extend(0u32.to_le_bytes());
+        header.extend(b"fmt ");
+        header.extend(FMT_LEN.to_le_bytes());
+        header.extend(self.fmt());
+        header.extend(b"data");
+        header.extend(short(self.data_bytes).to_le_bytes());
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        Ok(())
+    }
+}
+
+/// Whether `path` is an RF64 file, which tag writers don't understand.
+pub fn is_rf64(path: &str) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && magic == *b"RF64"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 100 frames of 24-bit stereo
+    fn write(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("cwgen-wav-{}-{}.wav", name, std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let mut wav = WavFile::create(&path, 8000, WavFormat::Int24, 2).unwrap();
+        let mut rng = rand::rng();
+        for _ in 0..100 {
+            wav.write(0.5, 2, &mut rng).unwrap();
+        }
+        wav.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_wav_header() {
+        let path = write("header");
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!((reader.spec().bits_per_sample, reader.spec().channels, reader.duration()), (24, 2, 100));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_resume_other_format() {
+        let path = write("resume");
+        assert!(WavFile::resume(&path, 8000, WavFormat::Int16, 2, 0).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rf64_header() {
+        let path = write("rf64");
+        let path = path.as_str();
+        // Past 4 GB, without writing 4 GB: the header is all that changes
+        let mut wav = WavFile::resume(path, 8000, WavFormat::Int24, 2, 600).unwrap();
+        wav.data_bytes = 5 << 30;
+        wav.write_header().unwrap();
+        wav.file.flush().unwrap();
+        assert!(is_rf64(path));
+        let header = std::fs::read(path).unwrap();
+        assert_eq!(&header[0..4], b"RF64");
+        assert_eq!(&header[12..16], b"ds64");
+        assert_eq!(u64::from_le_bytes(header[28..36].try_into().unwrap()), 5 << 30);
+        assert_eq!(u32::from_le_bytes(header[76..80].try_into().unwrap()), u32::MAX);
+        std::fs::remove_file(path).unwrap();
+    }
+}